@@ -5,28 +5,132 @@ use std::collections::{BTreeMap, BTreeSet};
 pub(crate) struct Test {
     #[serde(rename = "svn-dump-source", default = "dump_source_uncompressed")]
     pub(crate) svn_dump_source: SvnDumpSource,
+    /// Number of parts that are compressed independently (as blocks, frames
+    /// or streams, depending on the compression).
+    #[serde(rename = "svn-dump-compressed-parts", default = "one")]
+    pub(crate) svn_dump_compressed_parts: usize,
     #[serde(rename = "svn-dump-version", default = "dump_version_2")]
     pub(crate) svn_dump_version: SvnDumpVersion,
+    /// Numbers of SVN revisions (from `svn-revs`) at which the dump is
+    /// split into several incremental dump files.
+    #[serde(rename = "svn-dump-shards", default)]
+    pub(crate) svn_dump_shards: Vec<usize>,
+    /// Whether the dump is passed to the standard input of the converter.
+    #[serde(rename = "svn-dump-stdin", default = "false_")]
+    pub(crate) svn_dump_stdin: bool,
+    /// If set, the SVN revisions are written to a FSFS repository instead
+    /// of a dump.
+    #[serde(rename = "svn-repo")]
+    pub(crate) svn_repo: Option<SvnRepo>,
+    /// If set, the SVN revisions are written to a repository that is dumped
+    /// by a mock `svnadmin`. With `svn-repo`, the repository can also be
+    /// read natively.
+    #[serde(rename = "svnadmin", default = "false_")]
+    pub(crate) svnadmin: bool,
+    /// If set, the SVN revisions are served through the svn:// protocol
+    /// instead of written to a dump.
+    #[serde(rename = "svn-server", default = "false_")]
+    pub(crate) svn_server: bool,
+    /// If set with `svn-server`, the SVN revisions are loaded into a
+    /// repository by the real `svnadmin` and served by the real `svnserve`
+    /// instead of the minimal server of the tests. The test is skipped when
+    /// they are not installed.
+    #[serde(rename = "real-svnserve", default = "false_")]
+    pub(crate) real_svnserve: bool,
+    /// SVN revisions at which the server drops the connection while
+    /// replaying them (once for each revision).
+    #[serde(rename = "svn-server-drops", default)]
+    pub(crate) svn_server_drops: Vec<u32>,
+    /// If set, every run fetches the revisions from the server with a
+    /// shared `--remote-cache`, which is filled by an additional run
+    /// before the final one.
+    #[serde(rename = "remote-cache", default = "false_")]
+    pub(crate) remote_cache: bool,
     #[serde(rename = "svn-uuid")]
     pub(crate) svn_uuid: Option<String>,
-    #[serde(rename = "svn-revs")]
+    #[serde(rename = "svn-revs", default)]
     pub(crate) svn_revs: Vec<SvnRev>,
+    /// Dump file (relative to the test) that is converted as is instead of
+    /// a dump of `svn-revs`, such as one written by old Subversion tools.
+    #[serde(rename = "svn-dump-file")]
+    pub(crate) svn_dump_file: Option<String>,
+    /// Additional source repositories, whose dumps are passed with
+    /// `--src` after the main one.
+    #[serde(rename = "svn-sources", default)]
+    pub(crate) svn_sources: Vec<SvnSource>,
     #[serde(rename = "conv-params")]
     pub(crate) conv_params: String,
     #[serde(rename = "user-map")]
     pub(crate) user_map: Option<String>,
     #[serde(rename = "git-repack", default = "false_")]
     pub(crate) git_repack: bool,
+    /// Numbers of SVN revisions (from `svn-revs`) to convert in successive
+    /// incremental runs before the final run with all revisions.
+    #[serde(rename = "incremental-steps", default)]
+    pub(crate) incremental_steps: Vec<usize>,
+    /// Numbers of SVN revisions (from `svn-revs`) to convert in successive
+    /// runs that fail and are resumed from their last checkpoint, before
+    /// the final run with all revisions.
+    #[serde(rename = "resume-steps", default)]
+    pub(crate) resume_steps: Vec<usize>,
+    /// Conversion parameters of a previous run whose stage 1 output is
+    /// saved and reused by the final run.
+    #[serde(rename = "stage1-conv-params")]
+    pub(crate) stage1_conv_params: Option<String>,
+    /// If set, the dump is first exported by the converter as a dump of
+    /// this version, with `export-conv-params`, and the conversion reads
+    /// the exported dump.
+    #[serde(rename = "export-dump")]
+    pub(crate) export_dump: Option<SvnDumpVersion>,
+    /// Conversion parameters of the export run (`conv-params` by default).
+    #[serde(rename = "export-conv-params")]
+    pub(crate) export_conv_params: Option<String>,
+    /// Additional command line arguments for every run of the converter.
+    #[serde(rename = "args", default)]
+    pub(crate) args: Vec<String>,
     #[serde(rename = "failed", default = "false_")]
     pub(crate) failed: bool,
     #[serde(rename = "logs")]
     pub(crate) logs: Option<String>,
+    /// Expected content of the file written with `--rename-log`, which is
+    /// passed to every run of the converter if set.
+    #[serde(rename = "rename-log")]
+    pub(crate) rename_log: Option<String>,
     #[serde(rename = "git-tags", default)]
     pub(crate) git_tags: Vec<GitTag>,
     #[serde(rename = "git-refs")]
     pub(crate) git_refs: Option<BTreeSet<String>>,
     #[serde(rename = "git-revs", default)]
     pub(crate) git_revs: Vec<GitRev>,
+    /// Expected output repositories of a split conversion, by their path
+    /// relative to `--dest`. The top-level `git-*` fields are not checked
+    /// if set.
+    #[serde(rename = "outputs", default)]
+    pub(crate) outputs: BTreeMap<String, Output>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Output {
+    /// Expected number of objects in the repository, to check that it does
+    /// not get the objects of the other outputs.
+    #[serde(rename = "git-num-objects")]
+    pub(crate) git_num_objects: Option<u64>,
+    #[serde(rename = "git-tags", default)]
+    pub(crate) git_tags: Vec<GitTag>,
+    #[serde(rename = "git-refs")]
+    pub(crate) git_refs: Option<BTreeSet<String>>,
+    #[serde(rename = "git-revs", default)]
+    pub(crate) git_revs: Vec<GitRev>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SvnSource {
+    #[serde(rename = "svn-uuid")]
+    pub(crate) svn_uuid: Option<String>,
+    #[serde(rename = "svn-revs")]
+    pub(crate) svn_revs: Vec<SvnRev>,
 }
 
 #[derive(serde::Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -52,6 +156,8 @@ fn dump_source_uncompressed() -> SvnDumpSource {
 
 #[derive(serde::Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) enum SvnDumpVersion {
+    #[serde(rename = "1")]
+    One,
     #[serde(rename = "2")]
     Two,
     #[serde(rename = "3")]
@@ -63,6 +169,28 @@ fn dump_version_2() -> SvnDumpVersion {
     SvnDumpVersion::Two
 }
 
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SvnRepo {
+    pub(crate) format: u32,
+    /// Number of revisions per shard, linear layout if not set.
+    #[serde(rename = "shard-size")]
+    pub(crate) shard_size: Option<u32>,
+    /// Whether complete shards are packed.
+    #[serde(rename = "packed", default = "false_")]
+    pub(crate) packed: bool,
+    /// If set, the repository is created by the real `svnadmin` instead of
+    /// written by the test. The test is skipped when `svnadmin` is not
+    /// installed.
+    #[serde(rename = "real-svnadmin", default = "false_")]
+    pub(crate) real_svnadmin: bool,
+    /// Directory (relative to the test) with a copy of the repository
+    /// created by the real `svnadmin`, which is converted instead, so the
+    /// test does not need Subversion. When it does not exist, the test
+    /// writes it if `SVN2GIT_WRITE_FSFS_FIXTURES` is set.
+    pub(crate) fixture: Option<String>,
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SvnRev {
@@ -77,7 +205,7 @@ pub(crate) struct SvnRev {
 #[serde(deny_unknown_fields)]
 pub(crate) struct SvnNode {
     pub(crate) path: String,
-    pub(crate) kind: SvnNodeKind,
+    pub(crate) kind: Option<SvnNodeKind>,
     pub(crate) action: SvnNodeAction,
     #[serde(rename = "copy-from-path")]
     pub(crate) copy_from_path: Option<String>,
@@ -89,6 +217,14 @@ pub(crate) struct SvnNode {
     pub(crate) text_delta: Option<bool>,
     pub(crate) props: Option<BTreeMap<String, Option<String>>>,
     pub(crate) text: Option<Bytes>,
+    #[serde(rename = "text-content-md5")]
+    pub(crate) text_content_md5: Option<String>,
+    #[serde(rename = "text-content-sha1")]
+    pub(crate) text_content_sha1: Option<String>,
+    #[serde(rename = "text-delta-base-md5")]
+    pub(crate) text_delta_base_md5: Option<String>,
+    #[serde(rename = "text-copy-source-md5")]
+    pub(crate) text_copy_source_md5: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -238,3 +374,8 @@ impl<'de> serde::de::Visitor<'de> for BytesVisitor {
 fn false_() -> bool {
     false
 }
+
+#[inline(always)]
+fn one() -> usize {
+    1
+}
@@ -0,0 +1,791 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+
+use md5::Digest as _;
+
+use crate::defs;
+
+/// Number of entries per page of the log-to-phys indices, small to have
+/// revisions that span several pages.
+const L2P_PAGE_SIZE: usize = 2;
+
+#[derive(Clone)]
+struct RepRef {
+    rev: u32,
+    item: u64,
+    len: usize,
+    expanded_len: usize,
+    md5: [u8; 16],
+    sha1: [u8; 20],
+}
+
+#[derive(Clone)]
+struct NodeState {
+    id: String,
+    text: Option<RepRef>,
+    /// Expanded text, to make deltas against it.
+    text_data: Vec<u8>,
+    props: Option<RepRef>,
+}
+
+/// Contents of a revision file, before its indices (with logical
+/// addressing) or trailer (with physical addressing).
+struct RevData {
+    data: Vec<u8>,
+    /// Offset of each item with logical addressing.
+    item_offsets: Vec<Option<u64>>,
+    root_item: u64,
+    changes_item: u64,
+}
+
+struct RevWriter {
+    rev: u32,
+    logical: bool,
+    data: Vec<u8>,
+    item_offsets: Vec<Option<u64>>,
+}
+
+impl RevWriter {
+    fn new(rev: u32, logical: bool) -> Self {
+        Self {
+            rev,
+            logical,
+            data: Vec::new(),
+            // 0 is unused, 1 is the changed paths list and 2 is the root
+            // node.
+            item_offsets: vec![None; 3],
+        }
+    }
+
+    /// Returns the reference (item index or offset) of the next item.
+    fn next_item(&self) -> u64 {
+        if self.logical {
+            self.item_offsets.len() as u64
+        } else {
+            self.data.len() as u64
+        }
+    }
+
+    fn add_item(&mut self, index: Option<u64>, data: &[u8]) -> u64 {
+        let offset = self.data.len() as u64;
+        self.data.extend(data);
+        if self.logical {
+            let index = index.unwrap_or(self.item_offsets.len() as u64);
+            let i = index as usize;
+            if i >= self.item_offsets.len() {
+                self.item_offsets.resize(i + 1, None);
+            }
+            self.item_offsets[i] = Some(offset);
+            index
+        } else {
+            offset
+        }
+    }
+
+    /// Writes a text or properties representation, as a delta against
+    /// `base` if provided.
+    fn add_rep(
+        &mut self,
+        data: &[u8],
+        base: Option<(&RepRef, &[u8])>,
+        reps: &mut BTreeMap<[u8; 16], RepRef>,
+    ) -> RepRef {
+        let md5: [u8; 16] = md5::Md5::digest(data).into();
+        if let Some(shared) = reps.get(&md5) {
+            // Representation sharing
+            return shared.clone();
+        }
+
+        let mut raw = Vec::new();
+        let len = if let Some((base_rep, base_data)) = base {
+            let delta = make_delta(base_data, data);
+            writeln!(
+                raw,
+                "DELTA {} {} {}",
+                base_rep.rev, base_rep.item, base_rep.len,
+            )
+            .unwrap();
+            raw.extend(&delta);
+            delta.len()
+        } else {
+            raw.extend(b"PLAIN\n");
+            raw.extend(data);
+            data.len()
+        };
+        raw.extend(b"ENDREP\n");
+
+        let item = self.add_item(None, &raw);
+        let rep = RepRef {
+            rev: self.rev,
+            item,
+            len,
+            expanded_len: data.len(),
+            md5,
+            sha1: sha1::Sha1::digest(data).into(),
+        };
+        reps.insert(md5, rep.clone());
+        rep
+    }
+
+    fn finish(mut self, changes: &[u8]) -> RevData {
+        let root_item = self.next_item();
+        let root_node = format!(
+            "id: 0.0.r{0}/{root_item}\ntype: dir\ncount: {0}\ncpath: /\n\n",
+            self.rev,
+        );
+        let root_item = self.add_item(Some(2), root_node.as_bytes());
+        let changes_item = self.add_item(Some(1), changes);
+        RevData {
+            data: self.data,
+            item_offsets: self.item_offsets,
+            root_item,
+            changes_item,
+        }
+    }
+}
+
+/// Writes the first `num_revs` revisions of `svn-revs` to a FSFS
+/// repository at `path`, with only the data that svn2git reads (for
+/// example, directory entries are not written).
+pub(crate) fn write_repo(
+    test_def: &defs::Test,
+    repo_def: &defs::SvnRepo,
+    num_revs: usize,
+    path: &Path,
+) -> Result<(), String> {
+    let format = repo_def.format;
+    let logical = format >= 7;
+
+    let rev0_changes: &[u8] = if logical { b"\n" } else { b"" };
+    let mut revs_data = vec![RevWriter::new(0, logical).finish(rev0_changes)];
+    let mut revs_props = vec![BTreeMap::new()];
+    let mut states = vec![BTreeMap::<String, NodeState>::new()];
+    let mut reps = BTreeMap::new();
+
+    for (i, svn_rev) in test_def.svn_revs[..num_revs].iter().enumerate() {
+        let rev = i as u32 + 1;
+        if svn_rev.no.is_some_and(|no| no != rev) {
+            return Err("repository revisions must be consecutive".into());
+        }
+
+        let mut writer = RevWriter::new(rev, logical);
+        let mut state = states.last().unwrap().clone();
+        let mut changes = Vec::new();
+
+        for svn_node in svn_rev.nodes.iter() {
+            if svn_node.prop_delta.is_some() || svn_node.text_delta.is_some() {
+                return Err("repository nodes cannot have deltas".into());
+            }
+
+            let node_path = &svn_node.path;
+            let is_dir = matches!(svn_node.kind, Some(defs::SvnNodeKind::Dir));
+            let kind = if is_dir { "dir" } else { "file" };
+
+            if matches!(
+                svn_node.action,
+                defs::SvnNodeAction::Delete | defs::SvnNodeAction::Replace,
+            ) {
+                let old_id = state
+                    .get(node_path)
+                    .ok_or_else(|| format!("{node_path:?} does not exist"))?
+                    .id
+                    .clone();
+                state.retain(|path, _| !is_same_or_child(path, node_path));
+                if matches!(svn_node.action, defs::SvnNodeAction::Delete) {
+                    write!(changes, "{old_id} delete-{kind} false false ").unwrap();
+                    if logical {
+                        changes.extend(b"false ");
+                    }
+                    writeln!(changes, "/{node_path}\n").unwrap();
+                    continue;
+                }
+            }
+
+            let copy_from = svn_node.copy_from_path.as_ref().map(|copy_from_path| {
+                (
+                    svn_node.copy_from_rev.unwrap_or(rev - 1),
+                    copy_from_path.as_str(),
+                )
+            });
+
+            let mut node = match svn_node.action {
+                defs::SvnNodeAction::Change => state
+                    .get(node_path)
+                    .cloned()
+                    .ok_or_else(|| format!("{node_path:?} does not exist"))?,
+                _ => {
+                    if let Some((copy_from_rev, copy_from_path)) = copy_from {
+                        let src_state = states
+                            .get(copy_from_rev as usize)
+                            .ok_or_else(|| format!("revision {copy_from_rev} does not exist"))?;
+                        for (src_path, src_node) in src_state.iter() {
+                            if is_same_or_child(src_path, copy_from_path) {
+                                let dst_path =
+                                    format!("{node_path}{}", &src_path[copy_from_path.len()..]);
+                                state.insert(dst_path, src_node.clone());
+                            }
+                        }
+                        state
+                            .get(node_path)
+                            .cloned()
+                            .ok_or_else(|| format!("{copy_from_path:?} does not exist"))?
+                    } else {
+                        NodeState {
+                            id: String::new(),
+                            text: None,
+                            text_data: Vec::new(),
+                            props: None,
+                        }
+                    }
+                }
+            };
+
+            let is_new =
+                copy_from.is_none() && !matches!(svn_node.action, defs::SvnNodeAction::Change);
+            let text_mod = svn_node.text.is_some() || (is_new && !is_dir);
+            let prop_mod = svn_node.props.is_some() || is_new;
+
+            if let Some(ref props) = svn_node.props {
+                let mut raw_props = Vec::new();
+                for (key, value) in props.iter() {
+                    if let Some(value) = value {
+                        write_hash_entry(key.as_bytes(), value.as_bytes(), &mut raw_props);
+                    }
+                }
+                raw_props.extend(b"END\n");
+                node.props = Some(writer.add_rep(&raw_props, None, &mut reps));
+            }
+
+            if let Some(ref text) = svn_node.text {
+                let base = node
+                    .text
+                    .as_ref()
+                    .map(|base_rep| (base_rep, node.text_data.as_slice()));
+                let rep = writer.add_rep(text.as_slice(), base, &mut reps);
+                node.text = Some(rep);
+                node.text_data = text.to_vec();
+            }
+
+            let node_item = writer.next_item();
+            node.id = format!("0.0.r{rev}/{node_item}");
+            let mut node_rev = format!("id: {}\ntype: {kind}\ncount: 0\n", node.id);
+            for (key, rep) in [("text", &node.text), ("props", &node.props)] {
+                if let Some(rep) = rep {
+                    write!(
+                        node_rev,
+                        "{key}: {} {} {} {} {}",
+                        rep.rev,
+                        rep.item,
+                        rep.len,
+                        rep.expanded_len,
+                        hex(&rep.md5),
+                    )
+                    .unwrap();
+                    if format >= 6 {
+                        write!(node_rev, " {} _1/{}", hex(&rep.sha1), rep.item).unwrap();
+                    }
+                    node_rev.push('\n');
+                }
+            }
+            writeln!(node_rev, "cpath: /{node_path}").unwrap();
+            if let Some((copy_from_rev, copy_from_path)) = copy_from {
+                writeln!(node_rev, "copyfrom: {copy_from_rev} /{copy_from_path}").unwrap();
+            }
+            node_rev.push('\n');
+            writer.add_item(None, node_rev.as_bytes());
+
+            let action = match svn_node.action {
+                defs::SvnNodeAction::Change => "modify",
+                defs::SvnNodeAction::Add => "add",
+                defs::SvnNodeAction::Delete => unreachable!(),
+                defs::SvnNodeAction::Replace => "replace",
+            };
+            write!(
+                changes,
+                "{} {action}-{kind} {text_mod} {prop_mod} ",
+                node.id
+            )
+            .unwrap();
+            if logical {
+                changes.extend(b"false ");
+            }
+            writeln!(changes, "/{node_path}").unwrap();
+            if let Some((copy_from_rev, copy_from_path)) = copy_from {
+                writeln!(changes, "{copy_from_rev} /{copy_from_path}").unwrap();
+            } else {
+                changes.push(b'\n');
+            }
+
+            state.insert(node_path.clone(), node);
+        }
+
+        if logical {
+            changes.push(b'\n');
+        }
+        revs_data.push(writer.finish(&changes));
+        revs_props.push(svn_rev.props.clone());
+        states.push(state);
+    }
+
+    write_repo_files(test_def, repo_def, &revs_data, &revs_props, path)
+        .map_err(|e| format!("failed to write repository {path:?}: {e}"))
+}
+
+/// Creates a FSFS repository at `path` with the real `svnadmin`, loading
+/// the dump of the first `num_revs` revisions of `svn-revs`, so the reader
+/// is also checked against repositories written by Subversion.
+///
+/// The format is chosen with `--compatible-version` (the latest one if
+/// `repo_def` is `None`), which also chooses logical addressing from
+/// format 7. Like the Subversion test suite, the shard size is changed in
+/// `db/format` before the first revision is written.
+pub(crate) fn create_repo_with_svnadmin(
+    test_def: &defs::Test,
+    repo_def: Option<&defs::SvnRepo>,
+    num_revs: usize,
+    path: &Path,
+) -> Result<(), String> {
+    if path.exists() {
+        std::fs::remove_dir_all(path).map_err(|e| format!("failed to remove {path:?}: {e}"))?;
+    }
+
+    let mut create_cmd = std::process::Command::new("svnadmin");
+    create_cmd.arg("create").arg("--fs-type").arg("fsfs");
+    if let Some(repo_def) = repo_def {
+        let compatible_version = match repo_def.format {
+            4 => "1.6",
+            5 => "1.7",
+            6 => "1.8",
+            7 => "1.9",
+            8 => "1.10",
+            format => return Err(format!("svnadmin cannot create format {format}")),
+        };
+        create_cmd
+            .arg("--compatible-version")
+            .arg(compatible_version);
+    }
+    run_svnadmin(create_cmd.arg(path), None)?;
+
+    if let Some(repo_def) = repo_def {
+        let db_path = path.join("db");
+        let format_path = db_path.join("format");
+        let raw_format = std::fs::read_to_string(&format_path)
+            .map_err(|e| format!("failed to read {format_path:?}: {e}"))?;
+        let mut new_format = String::new();
+        for line in raw_format.lines() {
+            if line.starts_with("layout ") {
+                match repo_def.shard_size {
+                    Some(shard_size) => writeln!(new_format, "layout sharded {shard_size}"),
+                    None => writeln!(new_format, "layout linear"),
+                }
+                .unwrap();
+            } else {
+                writeln!(new_format, "{line}").unwrap();
+            }
+        }
+        std::fs::write(&format_path, new_format)
+            .map_err(|e| format!("failed to write {format_path:?}: {e}"))?;
+
+        if repo_def.shard_size.is_none() {
+            // Revision 0 has been written in its shard.
+            for dir in ["revs", "revprops"] {
+                let shard_path = db_path.join(dir).join("0");
+                let tmp_path = db_path.join(dir).join("0.tmp");
+                std::fs::rename(shard_path.join("0"), &tmp_path)
+                    .and_then(|()| std::fs::remove_dir(&shard_path))
+                    .and_then(|()| std::fs::rename(&tmp_path, &shard_path))
+                    .map_err(|e| format!("failed to move revision 0 in {shard_path:?}: {e}"))?;
+            }
+        }
+    }
+
+    let dump = crate::test::make_svn_dump(test_def, 0..num_revs, true, false);
+    run_svnadmin(
+        std::process::Command::new("svnadmin")
+            .arg("load")
+            .arg("--quiet")
+            .arg(path),
+        Some(&dump),
+    )?;
+
+    if repo_def.is_some_and(|repo_def| repo_def.packed) {
+        run_svnadmin(
+            std::process::Command::new("svnadmin")
+                .arg("pack")
+                .arg("--quiet")
+                .arg(path),
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies the repository at `src_path` to `dst_path`, replacing it.
+pub(crate) fn copy_repo(src_path: &Path, dst_path: &Path) -> Result<(), String> {
+    if dst_path.exists() {
+        std::fs::remove_dir_all(dst_path)
+            .map_err(|e| format!("failed to remove {dst_path:?}: {e}"))?;
+    }
+
+    let mut dir_queue = vec![(src_path.to_path_buf(), dst_path.to_path_buf())];
+    while let Some((src_dir, dst_dir)) = dir_queue.pop() {
+        std::fs::create_dir_all(&dst_dir)
+            .map_err(|e| format!("failed to create {dst_dir:?}: {e}"))?;
+        let entries =
+            std::fs::read_dir(&src_dir).map_err(|e| format!("failed to read {src_dir:?}: {e}"))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("failed to read {src_dir:?}: {e}"))?;
+            let src_entry_path = entry.path();
+            let dst_entry_path = dst_dir.join(entry.file_name());
+            let is_dir = entry
+                .file_type()
+                .map_err(|e| format!("failed to read {src_entry_path:?}: {e}"))?
+                .is_dir();
+            if is_dir {
+                dir_queue.push((src_entry_path, dst_entry_path));
+            } else {
+                std::fs::copy(&src_entry_path, &dst_entry_path).map_err(|e| {
+                    format!("failed to copy {src_entry_path:?} to {dst_entry_path:?}: {e}")
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_svnadmin(cmd: &mut std::process::Command, input: Option<&[u8]>) -> Result<(), String> {
+    let mut child = cmd
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run svnadmin: {e}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    if let Some(input) = input {
+        stdin
+            .write_all(input)
+            .map_err(|e| format!("failed to write to svnadmin: {e}"))?;
+    }
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run svnadmin: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "svnadmin failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        ));
+    }
+    Ok(())
+}
+
+fn write_repo_files(
+    test_def: &defs::Test,
+    repo_def: &defs::SvnRepo,
+    revs_data: &[RevData],
+    revs_props: &[BTreeMap<String, String>],
+    path: &Path,
+) -> std::io::Result<()> {
+    let format = repo_def.format;
+    let logical = format >= 7;
+    let youngest_rev = revs_data.len() as u32 - 1;
+
+    if path.exists() {
+        std::fs::remove_dir_all(path)?;
+    }
+    let db_path = path.join("db");
+    std::fs::create_dir_all(&db_path)?;
+    std::fs::write(path.join("format"), "5\n")?;
+    std::fs::write(db_path.join("fs-type"), "fsfs\n")?;
+
+    let mut db_format = format!("{format}\n");
+    match repo_def.shard_size {
+        Some(shard_size) => writeln!(db_format, "layout sharded {shard_size}").unwrap(),
+        None => db_format.push_str("layout linear\n"),
+    }
+    if logical {
+        db_format.push_str("addressing logical\n");
+    }
+    std::fs::write(db_path.join("format"), db_format)?;
+    std::fs::write(db_path.join("current"), format!("{youngest_rev}\n"))?;
+    if let Some(ref uuid) = test_def.svn_uuid {
+        std::fs::write(db_path.join("uuid"), format!("{uuid}\n"))?;
+    }
+
+    let shard_size = repo_def.shard_size.unwrap_or(u32::MAX);
+    let min_unpacked_rev = if repo_def.packed {
+        (youngest_rev + 1) / shard_size * shard_size
+    } else {
+        0
+    };
+    if repo_def.shard_size.is_some() {
+        std::fs::write(
+            db_path.join("min-unpacked-rev"),
+            format!("{min_unpacked_rev}\n"),
+        )?;
+    }
+
+    let shard_dir = |dir: &str, rev: u32| {
+        if repo_def.shard_size.is_some() {
+            db_path.join(dir).join((rev / shard_size).to_string())
+        } else {
+            db_path.join(dir)
+        }
+    };
+
+    for shard_start in (0..min_unpacked_rev).step_by(shard_size as usize) {
+        let shard = shard_start / shard_size;
+        let shard_revs = shard_start..(shard_start + shard_size);
+
+        let pack_dir = db_path.join("revs").join(format!("{shard}.pack"));
+        std::fs::create_dir_all(&pack_dir)?;
+        let revs = &revs_data[(shard_revs.start as usize)..(shard_revs.end as usize)];
+        std::fs::write(
+            pack_dir.join("pack"),
+            make_rev_file(shard_start, revs, logical),
+        )?;
+        if !logical {
+            let mut manifest = String::new();
+            let mut offset = 0;
+            for rev_data in revs {
+                writeln!(manifest, "{offset}").unwrap();
+                offset += rev_data.data.len() + trailer(rev_data).len();
+            }
+            std::fs::write(pack_dir.join("manifest"), manifest)?;
+        }
+
+        if format >= 6 {
+            // Properties of revision 0 are not packed.
+            let first_rev = shard_revs.start.max(1);
+            let mut pack = String::new();
+            writeln!(pack, "{first_rev}\n{}", shard_revs.end - first_rev).unwrap();
+            let mut hashes = Vec::new();
+            for rev in first_rev..shard_revs.end {
+                let rev_props = make_rev_props(&revs_props[rev as usize]);
+                writeln!(pack, "{}", rev_props.len()).unwrap();
+                hashes.extend(rev_props);
+            }
+            pack.push('\n');
+            let mut pack = pack.into_bytes();
+            pack.extend(hashes);
+
+            let pack_name = format!("{first_rev}.0");
+            let pack_dir = db_path.join("revprops").join(format!("{shard}.pack"));
+            std::fs::create_dir_all(&pack_dir)?;
+            std::fs::write(pack_dir.join(&pack_name), compress(&pack))?;
+            let manifest = format!("{pack_name}\n").repeat((shard_revs.end - first_rev) as usize);
+            std::fs::write(pack_dir.join("manifest"), manifest)?;
+        }
+    }
+
+    for (rev, rev_data) in revs_data.iter().enumerate() {
+        let rev = rev as u32;
+        if rev >= min_unpacked_rev {
+            let dir = shard_dir("revs", rev);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(
+                dir.join(rev.to_string()),
+                make_rev_file(rev, std::slice::from_ref(rev_data), logical),
+            )?;
+        }
+        if rev >= min_unpacked_rev || format < 6 || rev == 0 {
+            let dir = shard_dir("revprops", rev);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(
+                dir.join(rev.to_string()),
+                make_rev_props(&revs_props[rev as usize]),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn trailer(rev_data: &RevData) -> String {
+    format!("\n{} {}\n", rev_data.root_item, rev_data.changes_item)
+}
+
+/// Makes a revision or pack file, which contains the revisions of
+/// `revs_data` starting at `first_rev`.
+fn make_rev_file(first_rev: u32, revs_data: &[RevData], logical: bool) -> Vec<u8> {
+    let mut file = Vec::new();
+    if !logical {
+        for rev_data in revs_data {
+            file.extend(&rev_data.data);
+            file.extend(trailer(rev_data).as_bytes());
+        }
+        return file;
+    }
+
+    let mut revs_offsets = Vec::new();
+    for rev_data in revs_data {
+        let base = file.len() as u64;
+        file.extend(&rev_data.data);
+        revs_offsets.push(
+            rev_data
+                .item_offsets
+                .iter()
+                .map(|offset| offset.map(|offset| base + offset))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    let l2p_offset = file.len();
+    file.extend(make_l2p_index(first_rev, &revs_offsets));
+    let l2p_md5 = md5::Md5::digest(&file[l2p_offset..]);
+
+    // The phys-to-log index is not read by svn2git.
+    let p2l_offset = file.len();
+    file.extend(b"P2L-INDEX\n");
+    let p2l_md5 = md5::Md5::digest(&file[p2l_offset..]);
+
+    let footer = format!(
+        "{l2p_offset} {} {p2l_offset} {}",
+        hex(&l2p_md5),
+        hex(&p2l_md5),
+    );
+    file.extend(footer.as_bytes());
+    file.push(footer.len() as u8);
+    file
+}
+
+fn make_l2p_index(first_rev: u32, revs_offsets: &[Vec<Option<u64>>]) -> Vec<u8> {
+    let mut pages = Vec::new();
+    let mut rev_page_counts = Vec::new();
+    for rev_offsets in revs_offsets {
+        let rev_pages = rev_offsets.chunks(L2P_PAGE_SIZE);
+        rev_page_counts.push(rev_pages.len() as u64);
+        for page_offsets in rev_pages {
+            let mut page = Vec::new();
+            let mut last_value = 0i64;
+            for offset in page_offsets {
+                let value = offset.map_or(0, |offset| offset as i64 + 1);
+                let diff = value - last_value;
+                let encoded = if diff >= 0 {
+                    diff as u64 * 2
+                } else {
+                    (-diff * 2 - 1) as u64
+                };
+                write_l2p_number(encoded, &mut page);
+                last_value = value;
+            }
+            pages.push((page_offsets.len(), page));
+        }
+    }
+
+    let mut index = b"L2P-INDEX\n".to_vec();
+    write_l2p_number(first_rev.into(), &mut index);
+    write_l2p_number(L2P_PAGE_SIZE as u64, &mut index);
+    write_l2p_number(revs_offsets.len() as u64, &mut index);
+    write_l2p_number(pages.len() as u64, &mut index);
+    for rev_page_count in rev_page_counts {
+        write_l2p_number(rev_page_count, &mut index);
+    }
+    for (entry_count, page) in pages.iter() {
+        write_l2p_number(page.len() as u64, &mut index);
+        write_l2p_number(*entry_count as u64, &mut index);
+    }
+    for (_, page) in pages {
+        index.extend(page);
+    }
+    index
+}
+
+fn write_l2p_number(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Makes a svndiff0 delta of `target` that copies the prefix it has in
+/// common with `source`.
+fn make_delta(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let common_len = source
+        .iter()
+        .zip(target)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut instructions = Vec::new();
+    if common_len != 0 {
+        push_instruction(0b00, common_len, &mut instructions);
+        push_svndiff_number(0, &mut instructions);
+    }
+    if common_len != target.len() {
+        push_instruction(0b10, target.len() - common_len, &mut instructions);
+    }
+    let new_data = &target[common_len..];
+
+    let mut delta = b"SVN\0".to_vec();
+    push_svndiff_number(0, &mut delta);
+    push_svndiff_number(source.len(), &mut delta);
+    push_svndiff_number(target.len(), &mut delta);
+    push_svndiff_number(instructions.len(), &mut delta);
+    push_svndiff_number(new_data.len(), &mut delta);
+    delta.extend(instructions);
+    delta.extend(new_data);
+    delta
+}
+
+fn push_instruction(op: u8, len: usize, out: &mut Vec<u8>) {
+    if len < 0x40 {
+        out.push((op << 6) | len as u8);
+    } else {
+        out.push(op << 6);
+        push_svndiff_number(len, out);
+    }
+}
+
+fn push_svndiff_number(value: usize, out: &mut Vec<u8>) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rem = value >> 7;
+    while rem != 0 {
+        groups.push((rem & 0x7F) as u8 | 0x80);
+        rem >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+/// Compresses data with zlib in the format used by Subversion.
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    push_svndiff_number(data.len(), &mut compressed);
+    let mut encoder =
+        flate2::write::ZlibEncoder::new(&mut compressed, flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap();
+    compressed
+}
+
+fn make_rev_props(props: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for (key, value) in props.iter() {
+        write_hash_entry(key.as_bytes(), value.as_bytes(), &mut raw);
+    }
+    raw.extend(b"END\n");
+    raw
+}
+
+fn write_hash_entry(key: &[u8], value: &[u8], out: &mut Vec<u8>) {
+    writeln!(out, "K {}", key.len()).unwrap();
+    out.extend(key);
+    writeln!(out, "\nV {}", value.len()).unwrap();
+    out.extend(value);
+    out.push(b'\n');
+}
+
+fn is_same_or_child(path: &str, parent: &str) -> bool {
+    path.strip_prefix(parent)
+        .is_some_and(|rem| rem.is_empty() || rem.starts_with('/'))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
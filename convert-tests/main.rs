@@ -7,15 +7,22 @@
 )]
 #![forbid(unsafe_code)]
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 mod defs;
+mod fsfs;
+mod svnadmin;
+mod svnserve;
 mod test;
 
 fn main() -> ExitCode {
+    if let Some(exit_code) = svnadmin::run_mock() {
+        return exit_code;
+    }
+
     let root_path = Path::new(env!("CARGO_MANIFEST_DIR"))
         .canonicalize()
         .unwrap();
@@ -25,13 +32,24 @@ fn main() -> ExitCode {
 
     let args = libtest_mimic::Arguments::from_args();
 
+    let mut installed_programs = BTreeMap::new();
     let mut tests = Vec::new();
     for test_path in tests_paths {
         let full_test_path = root_path.join(&test_path);
-        tests.push(libtest_mimic::Trial::test(
-            test_path.display().to_string(),
-            move || test::run_test(&full_test_path).map_err(|e| e.into()),
-        ));
+        // Tests that need programs that are not installed are ignored.
+        let ignored = test::required_programs(&full_test_path)
+            .into_iter()
+            .any(|program| {
+                !*installed_programs
+                    .entry(program)
+                    .or_insert_with(|| test::is_installed(program))
+            });
+        tests.push(
+            libtest_mimic::Trial::test(test_path.display().to_string(), move || {
+                test::run_test(&full_test_path).map_err(|e| e.into())
+            })
+            .with_ignored_flag(ignored),
+        );
     }
 
     let conclusion = libtest_mimic::run(&args, tests);
@@ -0,0 +1,157 @@
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::defs;
+
+/// Creates a repository that cannot be read natively, so it is dumped by
+/// the mock `svnadmin`, and the directory with the mock programs in
+/// `bin_path`.
+pub(crate) fn write_repo(
+    test_path: &Path,
+    num_revs: usize,
+    path: &Path,
+    bin_path: &Path,
+) -> Result<(), String> {
+    if path.exists() {
+        std::fs::remove_dir_all(path).map_err(|e| format!("failed to remove {path:?}: {e}"))?;
+    }
+    std::fs::create_dir_all(path.join("db"))
+        .map_err(|e| format!("failed to create {path:?}: {e}"))?;
+    std::fs::write(path.join("format"), "5\n")
+        .map_err(|e| format!("failed to write {path:?}: {e}"))?;
+    write_mock(test_path, num_revs, path, bin_path)
+}
+
+/// Makes the repository in `path` dumpable by the mock `svnadmin`, whose
+/// directory (with the mock programs) is created in `bin_path`.
+pub(crate) fn write_mock(
+    test_path: &Path,
+    num_revs: usize,
+    path: &Path,
+    bin_path: &Path,
+) -> Result<(), String> {
+    if bin_path.exists() {
+        std::fs::remove_dir_all(bin_path)
+            .map_err(|e| format!("failed to remove {bin_path:?}: {e}"))?;
+    }
+    std::fs::write(
+        path.join("mock-test"),
+        format!("{}\n{num_revs}\n", test_path.display()),
+    )
+    .map_err(|e| format!("failed to write {path:?}: {e}"))?;
+
+    std::fs::create_dir_all(bin_path).map_err(|e| format!("failed to create {bin_path:?}: {e}"))?;
+    let exe_path =
+        std::env::current_exe().map_err(|e| format!("failed to get executable path: {e}"))?;
+    for name in ["svnadmin", "svnlook"] {
+        let mock_path = bin_path
+            .join(name)
+            .with_extension(std::env::consts::EXE_EXTENSION);
+        #[cfg(unix)]
+        let r = std::os::unix::fs::symlink(&exe_path, &mock_path);
+        #[cfg(not(unix))]
+        let r = std::fs::copy(&exe_path, &mock_path).map(|_| ());
+        r.map_err(|e| format!("failed to create {mock_path:?}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// If this process has been run as the mock `svnadmin` or `svnlook`, runs
+/// it and returns its exit code.
+pub(crate) fn run_mock() -> Option<ExitCode> {
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let name = Path::new(args.first()?).file_stem()?.to_str()?;
+    let r = match name {
+        "svnadmin" => mock_svnadmin(&args[1..]),
+        "svnlook" => mock_svnlook(&args[1..]),
+        _ => return None,
+    };
+    match r {
+        Ok(()) => Some(ExitCode::SUCCESS),
+        Err(e) => {
+            eprintln!("{name}: {e}");
+            Some(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn load_repo(repo_path: &OsString) -> Result<(defs::Test, u32), String> {
+    let mock_path = Path::new(repo_path).join("mock-test");
+    let mock = std::fs::read_to_string(&mock_path)
+        .map_err(|e| format!("failed to read {mock_path:?}: {e}"))?;
+    let (test_path, num_revs) = mock
+        .trim_end()
+        .split_once('\n')
+        .ok_or("invalid mock repository")?;
+    let test_def_raw =
+        std::fs::read(test_path).map_err(|e| format!("failed to read {test_path:?}: {e}"))?;
+    let test_def = serde_yaml::from_slice(&test_def_raw)
+        .map_err(|e| format!("failed to parse {test_path:?}: {e}"))?;
+    let num_revs = num_revs.parse().map_err(|_| "invalid mock repository")?;
+    Ok((test_def, num_revs))
+}
+
+/// `svnadmin dump REPO -q [-rA:B] [--incremental] [--deltas]`
+///
+/// Deltas are accepted but not used, and the dumped range is reported in the
+/// standard error.
+fn mock_svnadmin(args: &[OsString]) -> Result<(), String> {
+    let [cmd, repo_path, quiet, options @ ..] = args else {
+        return Err("invalid arguments".into());
+    };
+    if cmd != "dump" || quiet != "-q" {
+        return Err("invalid arguments".into());
+    }
+    let (test_def, num_revs) = load_repo(repo_path)?;
+
+    let mut range = None;
+    let mut incremental = false;
+    let mut deltas = false;
+    for option in options {
+        let option = option.to_str().ok_or("invalid arguments")?;
+        if let Some(range_arg) = option.strip_prefix("-r") {
+            let (start, end) = range_arg.split_once(':').ok_or("invalid range")?;
+            let start = start.parse::<u32>().map_err(|_| "invalid range")?;
+            let end = end.parse::<u32>().map_err(|_| "invalid range")?;
+            range = Some((start, end));
+        } else if option == "--incremental" {
+            incremental = true;
+        } else if option == "--deltas" {
+            deltas = true;
+        } else {
+            return Err(format!("unknown option {option:?}"));
+        }
+    }
+
+    let (start, end) = range.unwrap_or((0, num_revs));
+    if start > end || end > num_revs {
+        return Err(format!("invalid range {start}:{end}"));
+    }
+    if start != 0 && !incremental {
+        return Err("only incremental dumps can start after revision 0".into());
+    }
+    eprintln!(
+        "dumping revisions {start}-{end}{}",
+        if deltas { " with deltas" } else { "" },
+    );
+
+    // Revision `i + 1` is `svn-revs[i]`.
+    let revs = start.saturating_sub(1) as usize..end as usize;
+    let dump = crate::test::make_svn_dump(&test_def, revs, start == 0, false);
+    std::io::Write::write_all(&mut std::io::stdout(), &dump)
+        .map_err(|e| format!("failed to write dump: {e}"))
+}
+
+/// `svnlook youngest REPO`
+fn mock_svnlook(args: &[OsString]) -> Result<(), String> {
+    let [cmd, repo_path] = args else {
+        return Err("invalid arguments".into());
+    };
+    if cmd != "youngest" {
+        return Err("invalid arguments".into());
+    }
+    let (_, num_revs) = load_repo(repo_path)?;
+    println!("{num_revs}");
+    Ok(())
+}
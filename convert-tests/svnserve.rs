@@ -0,0 +1,548 @@
+use std::io::Write as _;
+
+use md5::Digest as _;
+
+use crate::defs;
+
+const DEFAULT_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Item of the svn:// protocol.
+enum Item {
+    Number(u64),
+    /// Strings are not needed by the server.
+    String,
+    Word(String),
+    List(Vec<Item>),
+}
+
+impl Item {
+    fn as_list(&self) -> Option<&[Item]> {
+        match *self {
+            Self::List(ref l) => Some(l),
+            _ => None,
+        }
+    }
+
+    fn as_word(&self) -> Option<&str> {
+        match *self {
+            Self::Word(ref w) => Some(w),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<u64> {
+        match *self {
+            Self::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the raw representation of items.
+#[derive(Default)]
+struct ItemWriter(Vec<u8>);
+
+impl ItemWriter {
+    fn open(&mut self) -> &mut Self {
+        self.0.extend(b"( ");
+        self
+    }
+
+    fn close(&mut self) -> &mut Self {
+        self.0.extend(b") ");
+        self
+    }
+
+    fn word(&mut self, w: &str) -> &mut Self {
+        write!(self.0, "{w} ").unwrap();
+        self
+    }
+
+    fn number(&mut self, n: u64) -> &mut Self {
+        write!(self.0, "{n} ").unwrap();
+        self
+    }
+
+    fn string(&mut self, s: &[u8]) -> &mut Self {
+        write!(self.0, "{}:", s.len()).unwrap();
+        self.0.extend(s);
+        self.0.push(b' ');
+        self
+    }
+
+    /// Starts a command or a response: `( <name> ( `
+    fn cmd(&mut self, name: &str) -> &mut Self {
+        self.open().word(name).open()
+    }
+
+    /// Ends a command or a response: `) ) `
+    fn end(&mut self) -> &mut Self {
+        self.close().close()
+    }
+
+    /// `( success ( ( ) 0: ) ) `
+    fn trivial_auth(&mut self) -> &mut Self {
+        self.cmd("success").open().close().string(b"").end()
+    }
+
+    fn prop_list(&mut self, props: &[(&[u8], &[u8])]) -> &mut Self {
+        self.open();
+        for &(name, value) in props {
+            self.open().string(name).string(value).close();
+        }
+        self.close()
+    }
+}
+
+/// Revision served through the svn:// protocol.
+struct Rev {
+    props: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Editor commands of the replay of the revision.
+    drive: Vec<u8>,
+}
+
+/// Starts a minimal `svnserve` that serves the first `num_revs` revisions
+/// of `svn-revs` to any number of connections, and returns its URL.
+pub(crate) fn start_server(test_def: &defs::Test, num_revs: usize) -> Result<String, String> {
+    let uuid = test_def
+        .svn_uuid
+        .as_deref()
+        .unwrap_or(DEFAULT_UUID)
+        .to_owned();
+
+    let mut revs = vec![Rev {
+        props: Vec::new(),
+        drive: Vec::new(),
+    }];
+    for (i, svn_rev) in test_def.svn_revs[..num_revs].iter().enumerate() {
+        let rev = i as u32 + 1;
+        if svn_rev.no.is_some_and(|no| no != rev) {
+            return Err("server revisions must be consecutive".into());
+        }
+        revs.push(Rev {
+            props: svn_rev
+                .props
+                .iter()
+                .map(|(name, value)| (name.clone().into_bytes(), value.clone().into_bytes()))
+                .collect(),
+            drive: make_drive(svn_rev, rev),
+        });
+    }
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("failed to start server: {e}"))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("failed to start server: {e}"))?
+        .port();
+
+    let mut drops = test_def.svn_server_drops.clone();
+
+    // The server runs until the test process finishes.
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            // Errors are reported by the client.
+            let _ = serve(stream, &uuid, port, &revs, &mut drops);
+        }
+    });
+
+    Ok(format!("svn://127.0.0.1:{port}/repo"))
+}
+
+/// Loads the first `num_revs` revisions of `svn-revs` into a repository
+/// created by the real `svnadmin` at `root_path/repo`, starts a listener
+/// that runs the real `svnserve` in inetd mode for each connection, and
+/// returns its URL.
+pub(crate) fn start_real_server(
+    test_def: &defs::Test,
+    num_revs: usize,
+    root_path: &std::path::Path,
+) -> Result<String, String> {
+    if !test_def.svn_server_drops.is_empty() {
+        return Err("the real svnserve cannot drop connections".into());
+    }
+    crate::fsfs::create_repo_with_svnadmin(test_def, None, num_revs, &root_path.join("repo"))?;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("failed to start server: {e}"))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("failed to start server: {e}"))?
+        .port();
+
+    // Each `svnserve` exits when its connection is closed, and the listener
+    // runs until the test process finishes.
+    let root_path = root_path.to_path_buf();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            // Errors are reported by the client.
+            let _ = run_real_server(stream, &root_path);
+        }
+    });
+
+    Ok(format!("svn://127.0.0.1:{port}/repo"))
+}
+
+fn run_real_server(stream: std::net::TcpStream, root_path: &std::path::Path) -> Result<(), String> {
+    let mut child = std::process::Command::new("svnserve")
+        .arg("--inetd")
+        .arg("--root")
+        .arg(root_path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map_err(|e| format!("failed to run svnserve: {e}"))?;
+    let mut child_stdin = child.stdin.take().unwrap();
+    let mut child_stdout = child.stdout.take().unwrap();
+
+    let mut stream_reader = stream.try_clone().map_err(|e| e.to_string())?;
+    let mut stream_writer = stream;
+    let to_server = std::thread::spawn(move || {
+        let _ = std::io::copy(&mut stream_reader, &mut child_stdin);
+    });
+    let _ = std::io::copy(&mut child_stdout, &mut stream_writer);
+    let _ = stream_writer.shutdown(std::net::Shutdown::Both);
+    let _ = to_server.join();
+
+    child
+        .wait()
+        .map_err(|e| format!("failed to run svnserve: {e}"))?;
+    Ok(())
+}
+
+fn make_drive(svn_rev: &defs::SvnRev, rev: u32) -> Vec<u8> {
+    let mut w = ItemWriter::default();
+    w.cmd("open-root")
+        .open()
+        .number((rev - 1).into())
+        .close()
+        .string(b"d0")
+        .end();
+
+    for (i, svn_node) in svn_rev.nodes.iter().enumerate() {
+        let path = svn_node.path.as_bytes();
+        let token = format!("n{i}");
+        let token = token.as_bytes();
+        let is_dir = matches!(svn_node.kind, Some(defs::SvnNodeKind::Dir));
+
+        if matches!(
+            svn_node.action,
+            defs::SvnNodeAction::Delete | defs::SvnNodeAction::Replace,
+        ) {
+            w.cmd("delete-entry")
+                .string(path)
+                .open()
+                .number((rev - 1).into())
+                .close()
+                .string(b"d0")
+                .end();
+            if matches!(svn_node.action, defs::SvnNodeAction::Delete) {
+                continue;
+            }
+        }
+
+        if matches!(svn_node.action, defs::SvnNodeAction::Change) {
+            w.cmd(if is_dir { "open-dir" } else { "open-file" })
+                .string(path)
+                .string(b"d0")
+                .string(token)
+                .open()
+                .number((rev - 1).into())
+                .close()
+                .end();
+        } else {
+            w.cmd(if is_dir { "add-dir" } else { "add-file" })
+                .string(path)
+                .string(b"d0")
+                .string(token)
+                .open();
+            if let Some(ref copy_from_path) = svn_node.copy_from_path {
+                // Copy sources are absolute paths in the repository.
+                w.string(format!("/repo/{copy_from_path}").as_bytes())
+                    .number(svn_node.copy_from_rev.unwrap_or(rev - 1).into());
+            }
+            w.close().end();
+        }
+
+        for (name, value) in svn_node.props.iter().flatten() {
+            w.cmd(if is_dir {
+                "change-dir-prop"
+            } else {
+                "change-file-prop"
+            })
+            .string(token)
+            .string(name.as_bytes())
+            .open();
+            if let Some(value) = value {
+                w.string(value.as_bytes());
+            }
+            w.close().end();
+        }
+
+        if is_dir {
+            w.cmd("close-dir").string(token).end();
+            continue;
+        }
+
+        let mut text_md5 = svn_node.text_content_md5.clone();
+        if let Some(ref text) = svn_node.text {
+            let delta = if svn_node.text_delta == Some(true) {
+                text.to_vec()
+            } else {
+                text_md5.get_or_insert_with(|| hex(&md5::Md5::digest(text.as_slice())));
+                make_full_text_delta(text)
+            };
+            w.cmd("apply-textdelta").string(token).open();
+            if let Some(ref base_md5) = svn_node.text_delta_base_md5 {
+                w.string(base_md5.as_bytes());
+            }
+            w.close().end();
+            // Split in several chunks, as the server does with large deltas.
+            for chunk in delta.chunks(8) {
+                w.cmd("textdelta-chunk").string(token).string(chunk).end();
+            }
+            w.cmd("textdelta-end").string(token).end();
+        }
+        w.cmd("close-file").string(token).open();
+        if let Some(text_md5) = text_md5 {
+            w.string(text_md5.as_bytes());
+        }
+        w.close().end();
+    }
+
+    w.cmd("close-dir").string(b"d0").end();
+    w.cmd("finish-replay").end();
+    w.0
+}
+
+/// Makes a svndiff0 delta that inserts the whole text.
+fn make_full_text_delta(text: &[u8]) -> Vec<u8> {
+    fn push_number(value: usize, out: &mut Vec<u8>) {
+        let mut groups = vec![(value & 0x7F) as u8];
+        let mut rem = value >> 7;
+        while rem != 0 {
+            groups.push((rem & 0x7F) as u8 | 0x80);
+            rem >>= 7;
+        }
+        out.extend(groups.iter().rev());
+    }
+
+    let mut delta = b"SVN\0".to_vec();
+    if !text.is_empty() {
+        let mut instructions = vec![0b1000_0000];
+        push_number(text.len(), &mut instructions);
+        push_number(0, &mut delta);
+        push_number(0, &mut delta);
+        push_number(text.len(), &mut delta);
+        push_number(instructions.len(), &mut delta);
+        push_number(text.len(), &mut delta);
+        delta.extend(instructions);
+        delta.extend(text);
+    }
+    delta
+}
+
+fn serve(
+    stream: std::net::TcpStream,
+    uuid: &str,
+    port: u16,
+    revs: &[Rev],
+    drops: &mut Vec<u32>,
+) -> Result<(), String> {
+    let mut reader = std::io::BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut writer = stream;
+    let mut send = |w: &mut ItemWriter| -> Result<(), String> {
+        let r = writer.write_all(&w.0).map_err(|e| e.to_string());
+        w.0.clear();
+        r
+    };
+    let mut w = ItemWriter::default();
+    let latest_rev = revs.len() as u64 - 1;
+
+    // Greeting
+    w.cmd("success").number(2).number(2).open().close().open();
+    w.word("edit-pipeline").word("svndiff1").close().end();
+    send(&mut w)?;
+
+    let client_greeting = read_item(&mut reader)?;
+    if client_greeting
+        .as_list()
+        .and_then(|items| items.first())
+        .and_then(Item::as_number)
+        != Some(2)
+    {
+        return Err("unexpected client greeting".into());
+    }
+
+    // Authentication
+    w.cmd("success").open().word("ANONYMOUS").close();
+    w.string(uuid.as_bytes()).end();
+    send(&mut w)?;
+    let auth = read_item(&mut reader)?;
+    if auth.as_list().and_then(|items| items.first()?.as_word()) != Some("ANONYMOUS") {
+        return Err("unexpected authentication".into());
+    }
+    w.cmd("success").end();
+
+    // Repository info
+    w.cmd("success").string(uuid.as_bytes());
+    w.string(format!("svn://127.0.0.1:{port}/repo").as_bytes());
+    w.open().word("mergeinfo").close().end();
+    send(&mut w)?;
+
+    loop {
+        let cmd = match read_item(&mut reader) {
+            Ok(cmd) => cmd,
+            // Connection closed
+            Err(_) => return Ok(()),
+        };
+        let Some([name, params]) = cmd.as_list() else {
+            return Err("malformed command".into());
+        };
+        let params = params.as_list().unwrap_or_default();
+        let rev_param = |i: usize| params.get(i).and_then(Item::as_number);
+
+        w.trivial_auth();
+        match name.as_word() {
+            Some("get-latest-rev") => {
+                w.cmd("success").number(latest_rev).end();
+            }
+            Some("rev-proplist") => {
+                let rev = rev_param(0).filter(|&rev| rev <= latest_rev);
+                if let Some(rev) = rev {
+                    let props = revs[rev as usize]
+                        .props
+                        .iter()
+                        .map(|(name, value)| (name.as_slice(), value.as_slice()))
+                        .collect::<Vec<_>>();
+                    w.cmd("success").prop_list(&props).end();
+                } else {
+                    no_such_revision(&mut w, rev_param(0));
+                }
+            }
+            Some("replay-range") => {
+                let start_rev = rev_param(0).unwrap_or(0);
+                let end_rev = rev_param(1).unwrap_or(0);
+                if start_rev == 0 || start_rev > end_rev || end_rev > latest_rev {
+                    no_such_revision(&mut w, Some(end_rev));
+                } else {
+                    for (rev_no, rev) in revs
+                        .iter()
+                        .enumerate()
+                        .take(end_rev as usize + 1)
+                        .skip(start_rev as usize)
+                    {
+                        if let Some(i) = drops.iter().position(|&drop| drop as usize == rev_no) {
+                            // Send the previous revisions and half of
+                            // this one.
+                            drops.remove(i);
+                            w.open().word("revprops");
+                            w.0.extend(&rev.drive[..(rev.drive.len() / 2)]);
+                            send(&mut w)?;
+                            return Err("connection dropped".into());
+                        }
+                        let props = rev
+                            .props
+                            .iter()
+                            .map(|(name, value)| (name.as_slice(), value.as_slice()))
+                            .collect::<Vec<_>>();
+                        w.open().word("revprops").prop_list(&props).close();
+                        w.0.extend(&rev.drive);
+                    }
+                    w.cmd("success").end();
+                }
+            }
+            _ => {
+                w.cmd("failure").open().number(210001);
+                w.string(b"Unknown command")
+                    .string(b"")
+                    .number(0)
+                    .close()
+                    .end();
+            }
+        }
+        send(&mut w)?;
+    }
+}
+
+fn no_such_revision(w: &mut ItemWriter, rev: Option<u64>) {
+    let msg = format!("No such revision {}", rev.unwrap_or(0));
+    w.cmd("failure").open().number(160006);
+    w.string(msg.as_bytes()).string(b"").number(0).close().end();
+}
+
+fn read_item(reader: &mut impl std::io::BufRead) -> Result<Item, String> {
+    let first = loop {
+        let byte = read_byte(reader)?;
+        if !byte.is_ascii_whitespace() {
+            break byte;
+        }
+    };
+    read_item_from(reader, first)
+}
+
+fn read_item_from(reader: &mut impl std::io::BufRead, first: u8) -> Result<Item, String> {
+    match first {
+        b'(' => {
+            let mut items = Vec::new();
+            loop {
+                let next = loop {
+                    let byte = read_byte(reader)?;
+                    if !byte.is_ascii_whitespace() {
+                        break byte;
+                    }
+                };
+                if next == b')' {
+                    return Ok(Item::List(items));
+                }
+                items.push(read_item_from(reader, next)?);
+            }
+        }
+        b'0'..=b'9' => {
+            let mut n = u64::from(first - b'0');
+            loop {
+                let next = read_byte(reader)?;
+                if next.is_ascii_digit() {
+                    n = n * 10 + u64::from(next - b'0');
+                } else if next == b':' {
+                    let mut s = vec![0; n as usize];
+                    reader.read_exact(&mut s).map_err(|e| e.to_string())?;
+                    read_byte(reader)?;
+                    return Ok(Item::String);
+                } else {
+                    return Ok(Item::Number(n));
+                }
+            }
+        }
+        _ => {
+            let mut word = String::from(char::from(first));
+            loop {
+                let next = read_byte(reader)?;
+                if next.is_ascii_whitespace() {
+                    return Ok(Item::Word(word));
+                }
+                word.push(char::from(next));
+            }
+        }
+    }
+}
+
+fn read_byte(reader: &mut impl std::io::BufRead) -> Result<u8, String> {
+    let buf = reader.fill_buf().map_err(|e| e.to_string())?;
+    let Some(&byte) = buf.first() else {
+        return Err("connection closed".into());
+    };
+    reader.consume(1);
+    Ok(byte)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -8,7 +8,6 @@ use crate::defs;
 
 pub(crate) fn run_test(test_path: &Path) -> Result<(), String> {
     let temp_dir = get_tmp_dir()?;
-    let svn2git_bin = Path::new(env!("CARGO_BIN_EXE_svn2git"));
 
     let test_def_raw =
         std::fs::read(test_path).map_err(|e| format!("failed to read {test_path:?}: {e}"))?;
@@ -28,116 +27,307 @@ pub(crate) fn run_test(test_path: &Path) -> Result<(), String> {
         .map_err(|e| format!("failed to write {conv_params_path:?}: {e}"))?;
 
     let svn_dump_path = temp_dir.join("svn-dump");
-    let svn_dump = make_svn_dump(&test_def);
-    let svn_dump = match test_def.svn_dump_source {
-        defs::SvnDumpSource::Uncompressed => svn_dump,
-        defs::SvnDumpSource::CompressedXz => {
-            liblzma::encode_all(&mut svn_dump.as_slice(), 6).unwrap()
-        }
-        defs::SvnDumpSource::CompressedGzip => {
-            let mut compressed = Vec::new();
-            let mut encoder =
-                flate2::write::GzEncoder::new(&mut compressed, flate2::Compression::default());
-            encoder.write_all(&svn_dump).unwrap();
-            encoder.finish().unwrap();
-            compressed
-        }
-        defs::SvnDumpSource::CompressedBzip2 => {
-            let mut compressed = Vec::new();
-            let mut encoder =
-                bzip2::write::BzEncoder::new(&mut compressed, bzip2::Compression::default());
-            encoder.write_all(&svn_dump).unwrap();
-            encoder.finish().unwrap();
-            compressed
-        }
-        defs::SvnDumpSource::CompressedZstd => {
-            zstd::encode_all(&mut svn_dump.as_slice(), 3).unwrap()
+    let git_repo_path = temp_dir.join("converted.git");
+    let conv_log_path = temp_dir.join("conv.log");
+
+    let incremental = !test_def.incremental_steps.is_empty();
+    for (i, &num_revs) in test_def.incremental_steps.iter().enumerate() {
+        write_svn_dump(test_path, &test_def, num_revs, false, &svn_dump_path)?;
+        run_convert(
+            &test_def,
+            &conv_params_path,
+            &svn_dump_path,
+            &git_repo_path,
+            &temp_dir.join(format!("conv-step{i}.log")),
+            &["--incremental".as_ref()],
+            0,
+        )?;
+    }
+
+    let resume = !test_def.resume_steps.is_empty();
+    for (i, &num_revs) in test_def.resume_steps.iter().enumerate() {
+        write_svn_dump(test_path, &test_def, num_revs, true, &svn_dump_path)?;
+        run_convert(
+            &test_def,
+            &conv_params_path,
+            &svn_dump_path,
+            &git_repo_path,
+            &temp_dir.join(format!("conv-resume-step{i}.log")),
+            &[
+                Some("--checkpoint-interval=0".as_ref()),
+                (i != 0).then_some("--resume".as_ref()),
+                incremental.then_some("--incremental".as_ref()),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>(),
+            1,
+        )?;
+    }
+
+    write_svn_dump(
+        test_path,
+        &test_def,
+        test_def.svn_revs.len(),
+        false,
+        &svn_dump_path,
+    )?;
+
+    for (i, svn_source) in test_def.svn_sources.iter().enumerate() {
+        let svn_source_dump = make_svn_dump_of(
+            &test_def.svn_dump_version,
+            svn_source.svn_uuid.as_deref(),
+            &svn_source.svn_revs,
+            0..svn_source.svn_revs.len(),
+            true,
+            false,
+        );
+        let svn_source_path = svn_source_dump_path(&svn_dump_path, i);
+        std::fs::write(&svn_source_path, svn_source_dump)
+            .map_err(|e| format!("failed to write {svn_source_path:?}: {e}"))?;
+    }
+
+    if let Some(ref export_version) = test_def.export_dump {
+        if incremental || resume || test_def.stage1_conv_params.is_some() {
+            return Err("exported dumps cannot be converted in several runs".into());
         }
-        defs::SvnDumpSource::CompressedLz4 => {
-            let mut compressed = Vec::new();
-            let mut encoder = lz4_flex::frame::FrameEncoder::new(&mut compressed);
-            encoder.write_all(&svn_dump).unwrap();
-            encoder.finish().unwrap();
-            compressed
+
+        let export_conv_params_path = temp_dir.join("export-conv-params.toml");
+        let export_conv_params = test_def
+            .export_conv_params
+            .as_ref()
+            .unwrap_or(&test_def.conv_params);
+        std::fs::write(&export_conv_params_path, export_conv_params.as_bytes())
+            .map_err(|e| format!("failed to write {export_conv_params_path:?}: {e}"))?;
+
+        let export_log_path = temp_dir.join("export.log");
+        run_export(
+            &test_def,
+            export_version,
+            &export_conv_params_path,
+            &svn_dump_path,
+            &export_log_path,
+            test_def.failed.into(),
+        )?;
+
+        if test_def.failed {
+            if let Some(ref expected_logs) = test_def.logs {
+                check_log(&export_log_path, expected_logs)?;
+            }
+            std::fs::remove_dir_all(&temp_dir)
+                .map_err(|e| format!("failed to remove {temp_dir:?}: {e}"))?;
+            return Ok(());
         }
-    };
-    std::fs::write(&svn_dump_path, svn_dump)
-        .map_err(|e| format!("failed to write {svn_dump_path:?}: {e}"))?;
+    }
 
-    let git_repo_path = temp_dir.join("converted.git");
-    let conv_log_path = temp_dir.join("conv.log");
+    if test_def.remote_cache {
+        run_convert(
+            &test_def,
+            &conv_params_path,
+            &svn_dump_path,
+            &temp_dir.join("cache-fill.git"),
+            &temp_dir.join("cache-fill-conv.log"),
+            &[],
+            0,
+        )?;
+    }
+
+    let stage1_cache_path = temp_dir.join("stage1-cache");
+    let reuse_stage1 = if let Some(ref stage1_conv_params) = test_def.stage1_conv_params {
+        let stage1_conv_params_path = temp_dir.join("stage1-conv-params.toml");
+        std::fs::write(&stage1_conv_params_path, stage1_conv_params.as_bytes())
+            .map_err(|e| format!("failed to write {stage1_conv_params_path:?}: {e}"))?;
+
+        run_convert(
+            &test_def,
+            &stage1_conv_params_path,
+            &svn_dump_path,
+            &temp_dir.join("stage1.git"),
+            &temp_dir.join("stage1-conv.log"),
+            &["--reuse-stage1".as_ref(), stage1_cache_path.as_os_str()],
+            0,
+        )?;
+        true
+    } else {
+        false
+    };
 
     run_convert(
-        svn2git_bin,
+        &test_def,
         &conv_params_path,
         &svn_dump_path,
         &git_repo_path,
         &conv_log_path,
-        test_def.git_repack,
+        &[
+            test_def.git_repack.then_some("--git-repack".as_ref()),
+            incremental.then_some("--incremental".as_ref()),
+            resume.then_some("--resume".as_ref()),
+            reuse_stage1.then_some("--reuse-stage1".as_ref()),
+            reuse_stage1.then_some(stage1_cache_path.as_os_str()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>(),
         test_def.failed.into(),
     )?;
 
+    if (incremental || resume || reuse_stage1) && !test_def.failed {
+        // The result of an incremental, resumed or stage-1-reusing
+        // conversion must be the same as the one of a full conversion.
+        let full_git_repo_path = temp_dir.join("full.git");
+        run_convert(
+            &test_def,
+            &conv_params_path,
+            &svn_dump_path,
+            &full_git_repo_path,
+            &temp_dir.join("full-conv.log"),
+            &[],
+            0,
+        )?;
+
+        let converted_refs = get_git_refs(&git_repo_path)?;
+        let full_refs = get_git_refs(&full_git_repo_path)?;
+        if converted_refs != full_refs {
+            return Err(format!(
+                "conversion differs from a full conversion:\nconverted: {converted_refs:?}\nfull: {full_refs:?}",
+            ));
+        }
+    }
+
     if let Some(ref expected_logs) = test_def.logs {
         check_log(&conv_log_path, expected_logs)?;
     }
 
-    if !test_def.failed {
-        let fsck_result = std::process::Command::new("git")
-            .current_dir(&git_repo_path)
-            .arg("fsck")
-            .arg("--strict")
-            .arg("--no-progress")
-            .output()
-            .map_err(|e| format!("failed to run git fsck: {e}"))?;
-
-        if !fsck_result.status.success() {
+    if let Some(ref expected_rename_log) = test_def.rename_log {
+        let rename_log_path = temp_dir.join("rename.log");
+        let rename_log = std::fs::read_to_string(&rename_log_path)
+            .map_err(|e| format!("failed to read {rename_log_path:?}: {e}"))?;
+        if rename_log != *expected_rename_log {
             return Err(format!(
-                "git fsck finished with {}\nstdout:\n{}\nstderr:\n{}",
-                fsck_result.status,
-                String::from_utf8_lossy(&fsck_result.stdout),
-                String::from_utf8_lossy(&fsck_result.stderr),
+                "rename log mismatch:\nexpected:\n{expected_rename_log}\nactual:\n{rename_log}",
             ));
         }
+    }
 
-        let git_repo = gix::open(&git_repo_path)
-            .map_err(|e| format!("failed to open git repository {git_repo_path:?}: {e}"))?;
+    if !test_def.failed {
+        if test_def.outputs.is_empty() {
+            check_git_repo(
+                &git_repo_path,
+                test_def.git_refs.as_ref(),
+                &test_def.git_tags,
+                &test_def.git_revs,
+            )?;
+        }
+        for (output_path, output) in test_def.outputs.iter() {
+            check_git_repo(
+                &git_repo_path.join(output_path),
+                output.git_refs.as_ref(),
+                &output.git_tags,
+                &output.git_revs,
+            )
+            .map_err(|e| format!("output {output_path:?}: {e}"))?;
+            if let Some(expected_num_objects) = output.git_num_objects {
+                check_git_num_objects(&git_repo_path.join(output_path), expected_num_objects)
+                    .map_err(|e| format!("output {output_path:?}: {e}"))?;
+            }
+        }
+    }
 
-        if let Some(ref expected_git_refs) = test_def.git_refs {
-            let mut actual_git_refs = BTreeSet::new();
+    std::fs::remove_dir_all(&temp_dir)
+        .map_err(|e| format!("failed to remove {temp_dir:?}: {e}"))?;
 
-            let refs = git_repo
-                .refs
-                .iter()
-                .map_err(|e| format!("failed to get git refs: {e}"))?;
-            let refs_iter = refs
-                .all()
-                .map_err(|e| format!("failed to get git refs: {e}"))?;
-            for ref_ in refs_iter {
-                let ref_ = ref_.map_err(|e| format!("failed to get git refs: {e}"))?;
-                actual_git_refs.insert(ref_.name.to_string());
-            }
+    Ok(())
+}
 
-            if actual_git_refs != *expected_git_refs {
-                return Err(format!(
-                    "unexpected git refs:\nactual: {actual_git_refs:?}\nexpected: {expected_git_refs:?}",
-                ));
-            }
-        }
+fn check_git_repo(
+    git_repo_path: &Path,
+    expected_git_refs: Option<&BTreeSet<String>>,
+    git_tags: &[defs::GitTag],
+    git_revs: &[defs::GitRev],
+) -> Result<(), String> {
+    let fsck_result = std::process::Command::new("git")
+        .current_dir(git_repo_path)
+        .arg("fsck")
+        .arg("--strict")
+        .arg("--no-progress")
+        .output()
+        .map_err(|e| format!("failed to run git fsck: {e}"))?;
+
+    if !fsck_result.status.success() {
+        return Err(format!(
+            "git fsck finished with {}\nstdout:\n{}\nstderr:\n{}",
+            fsck_result.status,
+            String::from_utf8_lossy(&fsck_result.stdout),
+            String::from_utf8_lossy(&fsck_result.stderr),
+        ));
+    }
+
+    let git_repo = gix::open(git_repo_path)
+        .map_err(|e| format!("failed to open git repository {git_repo_path:?}: {e}"))?;
 
-        for git_tag in test_def.git_tags.iter() {
-            check_git_tag(&git_repo, git_tag)
-                .map_err(|e| format!("tag {:?} check failed: {e}", git_tag.tag))?;
+    if let Some(expected_git_refs) = expected_git_refs {
+        let mut actual_git_refs = BTreeSet::new();
+
+        let refs = git_repo
+            .refs
+            .iter()
+            .map_err(|e| format!("failed to get git refs: {e}"))?;
+        let refs_iter = refs
+            .all()
+            .map_err(|e| format!("failed to get git refs: {e}"))?;
+        for ref_ in refs_iter {
+            let ref_ = ref_.map_err(|e| format!("failed to get git refs: {e}"))?;
+            actual_git_refs.insert(ref_.name.to_string());
         }
 
-        for git_rev in test_def.git_revs.iter() {
-            check_git_rev(&git_repo, git_rev)
-                .map_err(|e| format!("revision {:?} check failed: {e}", git_rev.rev))?;
+        if actual_git_refs != *expected_git_refs {
+            return Err(format!(
+                "unexpected git refs:\nactual: {actual_git_refs:?}\nexpected: {expected_git_refs:?}",
+            ));
         }
     }
 
-    std::fs::remove_dir_all(&temp_dir)
-        .map_err(|e| format!("failed to remove {temp_dir:?}: {e}"))?;
+    for git_tag in git_tags.iter() {
+        check_git_tag(&git_repo, git_tag)
+            .map_err(|e| format!("tag {:?} check failed: {e}", git_tag.tag))?;
+    }
+
+    for git_rev in git_revs.iter() {
+        check_git_rev(&git_repo, git_rev)
+            .map_err(|e| format!("revision {:?} check failed: {e}", git_rev.rev))?;
+    }
+
+    Ok(())
+}
+
+fn check_git_num_objects(git_repo_path: &Path, expected_num_objects: u64) -> Result<(), String> {
+    let count_result = std::process::Command::new("git")
+        .current_dir(git_repo_path)
+        .arg("count-objects")
+        .arg("-v")
+        .output()
+        .map_err(|e| format!("failed to run git count-objects: {e}"))?;
+    if !count_result.status.success() {
+        return Err(format!(
+            "git count-objects finished with {}",
+            count_result.status
+        ));
+    }
 
+    let mut num_objects = 0;
+    for line in String::from_utf8_lossy(&count_result.stdout).lines() {
+        if let Some(("count" | "in-pack", value)) = line.split_once(": ") {
+            num_objects += value
+                .parse::<u64>()
+                .map_err(|_| format!("invalid git count-objects output: {line:?}"))?;
+        }
+    }
+
+    if num_objects != expected_num_objects {
+        return Err(format!(
+            "unexpected number of git objects: {num_objects} (expected {expected_num_objects})",
+        ));
+    }
     Ok(())
 }
 
@@ -160,36 +350,300 @@ fn get_tmp_dir() -> Result<PathBuf, String> {
     }
 }
 
-fn make_svn_dump(test_def: &defs::Test) -> Vec<u8> {
+/// Writes the dump of the first `num_revs` revisions to `path`, which is
+/// a directory of dump files if `svn-dump-shards` is set, or a repository
+/// if `svn-repo` or `svnadmin` is set. If `svn-server` is set, the
+/// revisions are served by a new server instead, and its URL is written to
+/// `path`.
+/// Returns the external programs that are needed to run the test at
+/// `test_path`, which is skipped when any of them is not installed.
+pub(crate) fn required_programs(test_path: &Path) -> Vec<&'static str> {
+    // Tests that cannot be read fail when they are run.
+    let Some(test_def) = std::fs::read(test_path)
+        .ok()
+        .and_then(|raw| serde_yaml::from_slice::<defs::Test>(&raw).ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut programs = Vec::new();
+    if test_def.svn_repo.as_ref().is_some_and(|svn_repo| {
+        svn_repo.real_svnadmin
+            && !svn_repo
+                .fixture
+                .as_ref()
+                .is_some_and(|fixture| test_path.with_file_name(fixture).exists())
+    }) {
+        programs.push("svnadmin");
+    }
+    if test_def.real_svnserve {
+        programs.extend(["svnadmin", "svnserve"]);
+    }
+    programs
+}
+
+/// Returns whether `program` can be run.
+pub(crate) fn is_installed(program: &str) -> bool {
+    std::process::Command::new(program)
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn write_svn_dump(
+    test_path: &Path,
+    test_def: &defs::Test,
+    num_revs: usize,
+    interrupted: bool,
+    path: &Path,
+) -> Result<(), String> {
+    if let Some(ref svn_repo) = test_def.svn_repo {
+        if interrupted {
+            return Err("repositories cannot be interrupted".into());
+        }
+        if svn_repo.real_svnadmin {
+            let fixture_path = svn_repo
+                .fixture
+                .as_ref()
+                .filter(|_| num_revs == test_def.svn_revs.len())
+                .map(|fixture| test_path.with_file_name(fixture));
+            if let Some(ref fixture_path) = fixture_path {
+                if fixture_path.exists() {
+                    return crate::fsfs::copy_repo(fixture_path, path);
+                }
+            }
+            crate::fsfs::create_repo_with_svnadmin(test_def, Some(svn_repo), num_revs, path)?;
+            if let Some(ref fixture_path) = fixture_path {
+                if std::env::var_os("SVN2GIT_WRITE_FSFS_FIXTURES").is_some() {
+                    crate::fsfs::copy_repo(path, fixture_path)?;
+                }
+            }
+            return Ok(());
+        }
+        crate::fsfs::write_repo(test_def, svn_repo, num_revs, path)?;
+        if test_def.svnadmin {
+            // The repository can be read natively, but is dumped by the
+            // mock `svnadmin` if the conversion asks for it.
+            crate::svnadmin::write_mock(
+                test_path,
+                num_revs,
+                path,
+                &path.with_file_name("mock-bin"),
+            )?;
+        }
+        return Ok(());
+    }
+    if test_def.svnadmin {
+        if interrupted {
+            return Err("repositories cannot be interrupted".into());
+        }
+        return crate::svnadmin::write_repo(
+            test_path,
+            num_revs,
+            path,
+            &path.with_file_name("mock-bin"),
+        );
+    }
+    if test_def.svn_server {
+        if interrupted {
+            return Err("servers cannot be interrupted".into());
+        }
+        let url = if test_def.real_svnserve {
+            crate::svnserve::start_real_server(
+                test_def,
+                num_revs,
+                &path.with_file_name("svnserve-root"),
+            )?
+        } else {
+            crate::svnserve::start_server(test_def, num_revs)?
+        };
+        return std::fs::write(path, url).map_err(|e| format!("failed to write {path:?}: {e}"));
+    }
+
+    if let Some(ref svn_dump_file) = test_def.svn_dump_file {
+        if interrupted {
+            return Err("dump files cannot be interrupted".into());
+        }
+        let src_path = test_path.with_file_name(svn_dump_file);
+        return std::fs::copy(&src_path, path)
+            .map(|_| ())
+            .map_err(|e| format!("failed to copy {src_path:?} to {path:?}: {e}"));
+    }
+
+    if test_def.svn_dump_shards.is_empty() {
+        return write_svn_dump_file(test_def, 0..num_revs, interrupted, path);
+    }
+
+    if path.exists() {
+        std::fs::remove_dir_all(path).map_err(|e| format!("failed to remove {path:?}: {e}"))?;
+    }
+    std::fs::create_dir(path).map_err(|e| format!("failed to create {path:?}: {e}"))?;
+
+    let mut shard_start = 0;
+    for (i, &shard_end) in test_def
+        .svn_dump_shards
+        .iter()
+        .filter(|&&shard_end| shard_end < num_revs)
+        .chain(std::iter::once(&num_revs))
+        .enumerate()
+    {
+        write_svn_dump_file(
+            test_def,
+            shard_start..shard_end,
+            interrupted && shard_end == num_revs,
+            &path.join(format!("{i}.dump")),
+        )?;
+        shard_start = shard_end;
+    }
+
+    Ok(())
+}
+
+fn write_svn_dump_file(
+    test_def: &defs::Test,
+    revs: std::ops::Range<usize>,
+    interrupted: bool,
+    path: &Path,
+) -> Result<(), String> {
+    let with_rev0 = revs.start == 0;
+    let svn_dump = make_svn_dump(test_def, revs, with_rev0, interrupted);
+    let parts = test_def.svn_dump_compressed_parts.max(1);
+    let part_len = svn_dump.len().div_ceil(parts).max(1);
+    let svn_dump = match test_def.svn_dump_source {
+        defs::SvnDumpSource::Uncompressed => svn_dump,
+        defs::SvnDumpSource::CompressedXz if parts > 1 => {
+            // The multi-threaded encoder writes several blocks, with their
+            // sizes in their headers.
+            let stream = liblzma::stream::MtStreamBuilder::new()
+                .threads(2)
+                .block_size(part_len as u64)
+                .preset(6)
+                .encoder()
+                .unwrap();
+            let mut compressed = Vec::new();
+            let mut encoder = liblzma::write::XzEncoder::new_stream(&mut compressed, stream);
+            encoder.write_all(&svn_dump).unwrap();
+            encoder.finish().unwrap();
+            compressed
+        }
+        defs::SvnDumpSource::CompressedXz => {
+            liblzma::encode_all(&mut svn_dump.as_slice(), 6).unwrap()
+        }
+        defs::SvnDumpSource::CompressedGzip => {
+            if parts > 1 {
+                return Err("gzip dumps cannot have several compressed parts".into());
+            }
+            let mut compressed = Vec::new();
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut compressed, flate2::Compression::default());
+            encoder.write_all(&svn_dump).unwrap();
+            encoder.finish().unwrap();
+            compressed
+        }
+        defs::SvnDumpSource::CompressedBzip2 => {
+            // Concatenated streams, like pbzip2 writes
+            let mut compressed = Vec::new();
+            for part in svn_dump.chunks(part_len) {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(&mut compressed, bzip2::Compression::default());
+                encoder.write_all(part).unwrap();
+                encoder.finish().unwrap();
+            }
+            compressed
+        }
+        defs::SvnDumpSource::CompressedZstd => {
+            let mut compressed = Vec::new();
+            for mut part in svn_dump.chunks(part_len) {
+                compressed.extend(zstd::encode_all(&mut part, 3).unwrap());
+            }
+            compressed
+        }
+        defs::SvnDumpSource::CompressedLz4 => {
+            if parts > 1 {
+                return Err("lz4 dumps cannot have several compressed parts".into());
+            }
+            let mut compressed = Vec::new();
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(&mut compressed);
+            encoder.write_all(&svn_dump).unwrap();
+            encoder.finish().unwrap();
+            compressed
+        }
+    };
+    std::fs::write(path, svn_dump).map_err(|e| format!("failed to write {path:?}: {e}"))
+}
+
+/// Makes an incremental dump of `revs` (indices of `svn-revs`), preceded
+/// by an empty revision 0 if `with_rev0` is set and `svn-revs` does not
+/// define it.
+///
+/// If `interrupted` is true, the dump ends with an invalid revision, which
+/// makes the conversion fail after the checkpoint taken before importing it.
+pub(crate) fn make_svn_dump(
+    test_def: &defs::Test,
+    revs: std::ops::Range<usize>,
+    with_rev0: bool,
+    interrupted: bool,
+) -> Vec<u8> {
+    make_svn_dump_of(
+        &test_def.svn_dump_version,
+        test_def.svn_uuid.as_deref(),
+        &test_def.svn_revs,
+        revs,
+        with_rev0,
+        interrupted,
+    )
+}
+
+fn make_svn_dump_of(
+    svn_dump_version: &defs::SvnDumpVersion,
+    svn_uuid: Option<&str>,
+    svn_revs: &[defs::SvnRev],
+    revs: std::ops::Range<usize>,
+    with_rev0: bool,
+    interrupted: bool,
+) -> Vec<u8> {
     use std::io::Write as _;
 
     let mut dump = Vec::<u8>::new();
 
     dump.extend(b"SVN-fs-dump-format-version: ");
-    dump.extend(match test_def.svn_dump_version {
+    dump.extend(match svn_dump_version {
+        defs::SvnDumpVersion::One => b"1\n\n",
         defs::SvnDumpVersion::Two => b"2\n\n",
         defs::SvnDumpVersion::Three => b"3\n\n",
     });
 
-    if let Some(ref svn_uuid) = test_def.svn_uuid {
+    // Version 1 dumps do not have UUID records, and otherwise have the
+    // same records as version 2 dumps.
+    let is_v1 = *svn_dump_version == defs::SvnDumpVersion::One;
+
+    if let Some(svn_uuid) = svn_uuid.filter(|_| !is_v1) {
         dump.extend(b"UUID: ");
         dump.extend(svn_uuid.as_bytes());
         dump.extend(b"\n\n");
     }
 
-    let mut rev0_props = Vec::<u8>::new();
-    end_svn_props(&mut rev0_props);
+    if with_rev0 && svn_revs.first().is_none_or(|svn_rev| svn_rev.no != Some(0)) {
+        let mut rev0_props = Vec::<u8>::new();
+        end_svn_props(&mut rev0_props);
 
-    dump.extend(b"Revision-number: 0\n");
-    writeln!(dump, "Prop-content-length: {}", rev0_props.len()).unwrap();
-    writeln!(dump, "Content-length: {}", rev0_props.len()).unwrap();
-    dump.extend(b"\n");
-    dump.extend(rev0_props);
-    dump.extend(b"\n");
+        dump.extend(b"Revision-number: 0\n");
+        writeln!(dump, "Prop-content-length: {}", rev0_props.len()).unwrap();
+        writeln!(dump, "Content-length: {}", rev0_props.len()).unwrap();
+        dump.extend(b"\n");
+        dump.extend(rev0_props);
+        dump.extend(b"\n");
+    }
 
     let mut prev_svn_rev_no = 0;
-    for svn_rev in test_def.svn_revs.iter() {
+    for (i, svn_rev) in svn_revs[..revs.end].iter().enumerate() {
         let svn_rev_no = svn_rev.no.unwrap_or(prev_svn_rev_no + 1);
+        if i < revs.start {
+            prev_svn_rev_no = svn_rev_no;
+            continue;
+        }
 
         let mut rev_props = Vec::<u8>::new();
         for (prop_name, prop_value) in svn_rev.props.iter() {
@@ -229,12 +683,14 @@ fn make_svn_dump(test_def: &defs::Test) -> Vec<u8> {
             dump.extend(svn_node.path.as_bytes());
             dump.extend(b"\n");
 
-            dump.extend(b"Node-kind: ");
-            dump.extend(match svn_node.kind {
-                defs::SvnNodeKind::File => b"file".as_slice(),
-                defs::SvnNodeKind::Dir => b"dir".as_slice(),
-            });
-            dump.extend(b"\n");
+            if let Some(ref kind) = svn_node.kind {
+                dump.extend(b"Node-kind: ");
+                dump.extend(match kind {
+                    defs::SvnNodeKind::File => b"file".as_slice(),
+                    defs::SvnNodeKind::Dir => b"dir".as_slice(),
+                });
+                dump.extend(b"\n");
+            }
 
             dump.extend(b"Node-action: ");
             dump.extend(match svn_node.action {
@@ -275,6 +731,17 @@ fn make_svn_dump(test_def: &defs::Test) -> Vec<u8> {
                 .unwrap();
             }
 
+            for (key, value) in [
+                ("Text-content-md5", &svn_node.text_content_md5),
+                ("Text-content-sha1", &svn_node.text_content_sha1),
+                ("Text-delta-base-md5", &svn_node.text_delta_base_md5),
+                ("Text-copy-source-md5", &svn_node.text_copy_source_md5),
+            ] {
+                if let Some(value) = value {
+                    writeln!(dump, "{key}: {value}").unwrap();
+                }
+            }
+
             let props_len = node_props.as_ref().map(Vec::len);
             let text_len = svn_node.text.as_ref().map(defs::Bytes::len);
             if let Some(props_len) = props_len {
@@ -283,12 +750,17 @@ fn make_svn_dump(test_def: &defs::Test) -> Vec<u8> {
             if let Some(text_len) = text_len {
                 writeln!(dump, "Text-content-length: {text_len}").unwrap();
             }
-            writeln!(
-                dump,
-                "Content-length: {}",
-                props_len.unwrap_or(0) + text_len.unwrap_or(0)
-            )
-            .unwrap();
+            // Subversion only writes it for nodes with content, which
+            // matters in version 1 dumps, where it can be the length of a
+            // text without Text-content-length.
+            if !is_v1 || props_len.is_some() || text_len.is_some() {
+                writeln!(
+                    dump,
+                    "Content-length: {}",
+                    props_len.unwrap_or(0) + text_len.unwrap_or(0)
+                )
+                .unwrap();
+            }
             dump.extend(b"\n");
 
             if let Some(ref node_props) = node_props {
@@ -303,6 +775,12 @@ fn make_svn_dump(test_def: &defs::Test) -> Vec<u8> {
         prev_svn_rev_no = svn_rev_no;
     }
 
+    if interrupted {
+        writeln!(dump, "Revision-number: {}", prev_svn_rev_no + 1).unwrap();
+        dump.extend(b"Prop-content-length: 10\nContent-length: 10\n\nPROPS-END\n\n");
+        dump.extend(b"Node-path: interrupted\nNode-action: invalid\n\n");
+    }
+
     dump
 }
 
@@ -325,28 +803,116 @@ fn end_svn_props(out: &mut Vec<u8>) {
     out.extend(b"PROPS-END\n");
 }
 
+/// Exports the dump at `svn_dump_path` to `exported.dump` in the same
+/// directory, which is read by [`run_convert`] instead.
+fn run_export(
+    test_def: &defs::Test,
+    export_version: &defs::SvnDumpVersion,
+    conv_params_path: &Path,
+    svn_dump_path: &Path,
+    export_log_path: &Path,
+    expect_exit_code: i32,
+) -> Result<(), String> {
+    let conv_bin = Path::new(env!("CARGO_BIN_EXE_svn2git"));
+    let mut conv_cmd = std::process::Command::new(conv_bin);
+    conv_cmd.arg("--no-progress").arg("--src");
+    add_src_args(&mut conv_cmd, test_def, svn_dump_path)?;
+    conv_cmd
+        .arg("--export-dump")
+        .arg(svn_dump_path.with_file_name("exported.dump"))
+        .arg("--export-dump-version")
+        .arg(match export_version {
+            defs::SvnDumpVersion::One => "1",
+            defs::SvnDumpVersion::Two => "2",
+            defs::SvnDumpVersion::Three => "3",
+        })
+        .arg("--conv-params")
+        .arg(conv_params_path)
+        .arg("--log-file")
+        .arg(export_log_path)
+        .args(&test_def.args);
+
+    run_cmd(conv_cmd, expect_exit_code, export_log_path)
+}
+
 fn run_convert(
-    conv_bin: &Path,
+    test_def: &defs::Test,
     conv_params_path: &Path,
     svn_dump_path: &Path,
     git_repo_path: &Path,
     conv_log_path: &Path,
-    git_repack: bool,
+    extra_args: &[&std::ffi::OsStr],
     expect_exit_code: i32,
 ) -> Result<(), String> {
+    let conv_bin = Path::new(env!("CARGO_BIN_EXE_svn2git"));
     let mut conv_cmd = std::process::Command::new(conv_bin);
+    conv_cmd.arg("--no-progress").arg("--src");
+    if test_def.export_dump.is_some() {
+        conv_cmd.arg(svn_dump_path.with_file_name("exported.dump"));
+    } else {
+        add_src_args(&mut conv_cmd, test_def, svn_dump_path)?;
+    }
+    conv_cmd.args((0..test_def.svn_sources.len()).map(|i| svn_source_dump_path(svn_dump_path, i)));
     conv_cmd
-        .arg("--no-progress")
-        .arg("--src")
-        .arg(svn_dump_path)
         .arg("--dest")
         .arg(git_repo_path)
         .arg("--conv-params")
         .arg(conv_params_path)
         .arg("--log-file")
         .arg(conv_log_path)
-        .args(git_repack.then_some("--git-repack"));
+        .args(&test_def.args)
+        .args(extra_args);
+    if test_def.rename_log.is_some() {
+        conv_cmd
+            .arg("--rename-log")
+            .arg(conv_log_path.with_file_name("rename.log"));
+    }
+
+    run_cmd(conv_cmd, expect_exit_code, conv_log_path)
+}
+
+fn svn_source_dump_path(svn_dump_path: &Path, i: usize) -> PathBuf {
+    svn_dump_path.with_file_name(format!("svn-source-{i}.dump"))
+}
+
+fn add_src_args(
+    conv_cmd: &mut std::process::Command,
+    test_def: &defs::Test,
+    svn_dump_path: &Path,
+) -> Result<(), String> {
+    if test_def.svn_dump_stdin {
+        let svn_dump_file = std::fs::File::open(svn_dump_path)
+            .map_err(|e| format!("failed to open {svn_dump_path:?}: {e}"))?;
+        conv_cmd.arg("-").stdin(svn_dump_file);
+    } else if test_def.svn_server {
+        let url = std::fs::read_to_string(svn_dump_path)
+            .map_err(|e| format!("failed to read {svn_dump_path:?}: {e}"))?;
+        conv_cmd.arg(url).arg("--remote-svn");
+        if test_def.remote_cache {
+            let remote_cache_path = svn_dump_path.with_file_name("remote-cache");
+            conv_cmd.arg("--remote-cache").arg(remote_cache_path);
+        }
+    } else {
+        conv_cmd.arg(svn_dump_path);
+    }
+    if test_def.svnadmin {
+        let mut paths = vec![svn_dump_path.with_file_name("mock-bin")];
+        paths.extend(std::env::split_paths(
+            &std::env::var_os("PATH").unwrap_or_default(),
+        ));
+        let path_var =
+            std::env::join_paths(paths).map_err(|e| format!("failed to set PATH: {e}"))?;
+        conv_cmd.env("PATH", path_var);
+    }
+    Ok(())
+}
 
+fn run_cmd(
+    mut conv_cmd: std::process::Command,
+    expect_exit_code: i32,
+    conv_log_path: &Path,
+) -> Result<(), String> {
+    let conv_bin = Path::new(env!("CARGO_BIN_EXE_svn2git"));
     let cmd_out = conv_cmd
         .output()
         .map_err(|e| format!("failed to run {conv_bin:?}: {e}"))?;
@@ -365,6 +931,26 @@ fn run_convert(
     Ok(())
 }
 
+fn get_git_refs(git_repo_path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let git_repo = gix::open(git_repo_path)
+        .map_err(|e| format!("failed to open git repository {git_repo_path:?}: {e}"))?;
+
+    let mut git_refs = BTreeMap::new();
+    let refs = git_repo
+        .refs
+        .iter()
+        .map_err(|e| format!("failed to get git refs: {e}"))?;
+    let refs_iter = refs
+        .all()
+        .map_err(|e| format!("failed to get git refs: {e}"))?;
+    for ref_ in refs_iter {
+        let ref_ = ref_.map_err(|e| format!("failed to get git refs: {e}"))?;
+        git_refs.insert(ref_.name.to_string(), ref_.target.to_string());
+    }
+
+    Ok(git_refs)
+}
+
 fn check_log(log_path: &Path, expected_pattern: &str) -> Result<(), String> {
     let log_data =
         std::fs::read(log_path).map_err(|e| format!("failed to read {log_path:?}: {e}"))?;
@@ -1,3 +1,4 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 #[derive(clap::Parser)]
@@ -28,21 +29,89 @@ pub(crate) struct Cli {
         long = "src",
         short = 's',
         value_name = "PATH",
-        help = "Source Subversion repository"
+        num_args = 1..,
+        required = true,
+        help = "Source Subversion repository, or dump files, directories of dump files or file name patterns"
     )]
-    pub(crate) src: PathBuf,
+    pub(crate) src: Vec<PathBuf>,
     #[arg(
         long = "remote-svn",
-        help = "Source Subversion repository is remote (use svnrdump)"
+        help = "Source Subversion repository is remote (svn:// natively, other URLs with svnrdump)"
     )]
     pub(crate) remote_svn: bool,
+    #[arg(
+        long = "remote-chunk-size",
+        value_name = "REVS",
+        requires = "remote_svn",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Fetch the remote repository in chunks of this many revisions, retrying failed chunks"
+    )]
+    pub(crate) remote_chunk_size: Option<u32>,
+    #[arg(
+        long = "remote-retries",
+        value_name = "N",
+        help = "Number of times a failed chunk of the remote repository is fetched again",
+        default_value_t = 5
+    )]
+    pub(crate) remote_retries: u32,
+    #[arg(
+        long = "remote-cache",
+        value_name = "DIR",
+        requires = "remote_chunk_size",
+        help = "Directory where fetched chunks of the remote repository are kept for later runs"
+    )]
+    pub(crate) remote_cache: Option<PathBuf>,
+    #[arg(
+        long = "svnrdump-arg",
+        value_name = "ARG",
+        requires = "remote_svn",
+        allow_hyphen_values = true,
+        help = "Additional argument for svnrdump (can be used several times)"
+    )]
+    pub(crate) svnrdump_args: Vec<OsString>,
+    #[arg(
+        long = "svnadmin",
+        conflicts_with = "remote_svn",
+        help = "Dump the local source repository with svnadmin, even if it can be read natively"
+    )]
+    pub(crate) svnadmin: bool,
+    #[arg(
+        long = "svnadmin-deltas",
+        conflicts_with = "remote_svn",
+        help = "Ask svnadmin to dump file contents as deltas"
+    )]
+    pub(crate) svnadmin_deltas: bool,
+    #[arg(
+        long = "svnadmin-jobs",
+        value_name = "N",
+        conflicts_with = "remote_svn",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of svnadmin processes that dump disjoint revision ranges in parallel (default: 1)"
+    )]
+    pub(crate) svnadmin_jobs: Option<u16>,
     #[arg(
         long = "dest",
         short = 'd',
         value_name = "PATH",
+        required_unless_present = "export_dump",
         help = "Destination where the new Git repository will be created"
     )]
-    pub(crate) dest: PathBuf,
+    pub(crate) dest: Option<PathBuf>,
+    #[arg(
+        long = "export-dump",
+        value_name = "PATH",
+        conflicts_with_all = ["dest", "git_repack", "rename_log", "incremental", "resume", "reuse_stage1", "first_rev"],
+        help = "Write a filtered SVN dump to this file instead of converting to Git"
+    )]
+    pub(crate) export_dump: Option<PathBuf>,
+    #[arg(
+        long = "export-dump-version",
+        value_name = "VERSION",
+        value_enum,
+        requires = "export_dump",
+        help = "Format version of the exported SVN dump (2 by default, 3 is needed to keep deltas)"
+    )]
+    pub(crate) export_dump_version: Option<DumpVersion>,
     #[arg(
         long = "conv-params",
         short = 'P',
@@ -62,6 +131,114 @@ pub(crate) struct Cli {
         help = "run \"git repack\" at the end of conversion"
     )]
     pub(crate) git_repack: bool,
+    #[arg(
+        long = "rename-log",
+        value_name = "PATH",
+        help = "Write the moved SVN branches and tags, with their Git refs, to this file"
+    )]
+    pub(crate) rename_log: Option<PathBuf>,
+    #[arg(
+        long = "incremental",
+        help = "Keep conversion state in the destination, so later runs only import new revisions"
+    )]
+    pub(crate) incremental: bool,
+    #[arg(
+        long = "checkpoint-interval",
+        value_name = "SECONDS",
+        help = "Save checkpoints of an ongoing conversion, with this minimum time between them"
+    )]
+    pub(crate) checkpoint_interval: Option<u64>,
+    #[arg(
+        long = "resume",
+        help = "Resume an interrupted conversion from its last checkpoint"
+    )]
+    pub(crate) resume: bool,
+    #[arg(
+        long = "reuse-stage1",
+        value_name = "DIR",
+        conflicts_with_all = ["incremental", "resume"],
+        help = "Directory where the output of stage 1 is saved and reused in later runs"
+    )]
+    pub(crate) reuse_stage1: Option<PathBuf>,
+    #[arg(
+        long = "first-rev",
+        value_name = "REV",
+        help = "First SVN revision to emit commits for (earlier history is squashed into root commits)"
+    )]
+    pub(crate) first_rev: Option<u32>,
+    #[arg(
+        long = "last-rev",
+        value_name = "REV",
+        help = "Last SVN revision to convert"
+    )]
+    pub(crate) last_rev: Option<u32>,
+    #[arg(
+        long = "allow-checksum-mismatch",
+        help = "Report checksum mismatches in the SVN dump as warnings instead of errors"
+    )]
+    pub(crate) allow_checksum_mismatch: bool,
+    #[arg(
+        long = "lenient",
+        help = "Repair recoverable anomalies in the SVN dump instead of failing, reporting them as warnings"
+    )]
+    pub(crate) lenient: bool,
+    #[arg(
+        long = "max-text-size",
+        value_name = "BYTES",
+        help = "Maximum size of a file text or delta in the SVN dump"
+    )]
+    pub(crate) max_text_size: Option<u64>,
+    #[arg(
+        long = "max-props-size",
+        value_name = "BYTES",
+        help = "Maximum size of the properties of a record in the SVN dump (default: 256 MiB)"
+    )]
+    pub(crate) max_props_size: Option<u64>,
+    #[arg(
+        long = "max-path-length",
+        value_name = "BYTES",
+        help = "Maximum length of a path in the SVN dump (default: 64 KiB)"
+    )]
+    pub(crate) max_path_length: Option<u64>,
+    #[arg(
+        long = "max-path-depth",
+        value_name = "N",
+        help = "Maximum number of components of a path in the SVN dump (default: 4096)"
+    )]
+    pub(crate) max_path_depth: Option<u64>,
+    #[arg(
+        long = "max-nodes-per-rev",
+        value_name = "N",
+        help = "Maximum number of node records in a revision of the SVN dump (default: 16777216)"
+    )]
+    pub(crate) max_nodes_per_rev: Option<u64>,
+    #[arg(
+        long = "max-delta-window",
+        value_name = "BYTES",
+        help = "Maximum size of the views and sections of a window in SVN deltas (default: 64 MiB)"
+    )]
+    pub(crate) max_delta_window: Option<u64>,
+    #[arg(
+        long = "max-delta-target-size",
+        value_name = "BYTES",
+        help = "Maximum size of the text resulting from applying an SVN delta"
+    )]
+    pub(crate) max_delta_target_size: Option<u64>,
+    #[arg(
+        long = "stream-file-size",
+        value_name = "SIZE",
+        help = "size (in MiB) above which file contents are streamed instead of kept in memory",
+        default_value_t = 64
+    )]
+    pub(crate) stream_file_size: u64,
+    #[arg(
+        long = "decompress-threads",
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of threads to decompress multi-block XZ, multi-frame zstd and concatenated bzip2 dumps",
+        default_value_t = 1
+    )]
+    pub(crate) decompress_threads: u16,
 }
 
 #[derive(Copy, Clone, Debug, clap::ValueEnum)]
@@ -78,6 +255,14 @@ pub(crate) enum LogLevel {
     Trace,
 }
 
+#[derive(Copy, Clone, Debug, clap::ValueEnum)]
+pub(crate) enum DumpVersion {
+    #[value(name = "2")]
+    Two,
+    #[value(name = "3")]
+    Three,
+}
+
 impl LogLevel {
     pub(crate) fn to_log_level_filter(self) -> tracing::Level {
         match self {
@@ -1,12 +1,31 @@
+//! Binary serialization of the conversion data. It is also used for the
+//! state that persists across runs, so integers are written with a fixed
+//! width in little-endian order.
+
 #[derive(Debug)]
 pub(super) struct DeserializeError;
 
 #[inline]
 pub(super) fn serialize_byte_slice_into(bytes: &[u8], out: &mut Vec<u8>) {
-    out.extend(bytes.len().to_ne_bytes());
+    serialize_usize_into(bytes.len(), out);
     out.extend(bytes);
 }
 
+#[inline]
+pub(super) fn serialize_bool_into(value: bool, out: &mut Vec<u8>) {
+    out.push(value.into());
+}
+
+#[inline]
+pub(super) fn serialize_u32_into(value: u32, out: &mut Vec<u8>) {
+    out.extend(value.to_le_bytes());
+}
+
+#[inline]
+pub(super) fn serialize_usize_into(value: usize, out: &mut Vec<u8>) {
+    out.extend(u64::try_from(value).unwrap().to_le_bytes());
+}
+
 #[inline]
 pub(super) fn serialize_oid_into(oid: &gix_hash::ObjectId, out: &mut Vec<u8>) {
     match oid {
@@ -47,9 +66,20 @@ pub(super) fn deserialize_byte_array_from<const N: usize>(
     Ok(*array)
 }
 
+#[inline]
+pub(super) fn deserialize_u32_from(src: &mut &[u8]) -> Result<u32, DeserializeError> {
+    Ok(u32::from_le_bytes(deserialize_byte_array_from(src)?))
+}
+
+#[inline]
+pub(super) fn deserialize_usize_from(src: &mut &[u8]) -> Result<usize, DeserializeError> {
+    usize::try_from(u64::from_le_bytes(deserialize_byte_array_from(src)?))
+        .map_err(|_| DeserializeError)
+}
+
 #[inline]
 pub(super) fn deserialize_byte_slice_from(src: &mut &[u8]) -> Result<Vec<u8>, DeserializeError> {
-    let len = usize::from_ne_bytes(deserialize_byte_array_from(src)?);
+    let len = deserialize_usize_from(src)?;
     if src.len() < len {
         return Err(DeserializeError);
     }
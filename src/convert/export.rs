@@ -0,0 +1,330 @@
+use super::{ConvertError, Options};
+use crate::term_out::ProgressPrint;
+use crate::{FHashSet, svn};
+
+/// Writes the SVN dump read from `src_paths` to `dst_path`, leaving out
+/// excluded paths, files matching `delete-files` and revisions after the
+/// last revision to convert.
+///
+/// Revision numbers are kept, so revisions whose nodes are all left out
+/// are written empty.
+pub(crate) fn export(
+    progress_print: &ProgressPrint,
+    options: &Options,
+    src_paths: &[std::path::PathBuf],
+    src_is_remote: bool,
+    dst_path: &std::path::Path,
+    dump_version: svn::dump::DumpVersion,
+) -> Result<(), ConvertError> {
+    tracing::info!("exporting SVN dump to {dst_path:?}");
+
+    let mut svn_dump_src = svn::source::DumpSource::open(
+        src_paths,
+        src_is_remote,
+        &options.remote,
+        &options.svnadmin,
+        options.decompress_threads,
+        &options.dump_limits,
+        &options.delta_limits,
+        None,
+        options.last_rev,
+    )
+    .map_err(|e| {
+        tracing::error!("failed to open SVN dump source: {e}");
+        ConvertError
+    })?;
+
+    let svn_dump_reader =
+        svn::dump::DumpReader::new(svn_dump_src.stream(), options.dump_limits.clone()).map_err(
+            |e| {
+                tracing::error!("failed to read SVN dump: {e}");
+                ConvertError
+            },
+        )?;
+
+    let dst_file = std::fs::File::create(dst_path).map_err(|e| {
+        tracing::error!("failed to create {dst_path:?}: {e}");
+        ConvertError
+    })?;
+    let svn_dump_writer =
+        svn::dump::DumpWriter::new(std::io::BufWriter::new(dst_file), dump_version)
+            .map_err(|e| write_error(dst_path, e))?;
+
+    let mut exporter = Exporter {
+        progress_print,
+        options,
+        dst_path,
+        svn_dump_reader,
+        svn_dump_writer,
+        deleted_file_name_dirs: FHashSet::default(),
+    };
+    exporter.run()?;
+
+    exporter
+        .svn_dump_writer
+        .finish()
+        .map_err(|e| write_error(dst_path, e))?;
+
+    if let Err(e) = svn_dump_src.close() {
+        tracing::error!("SVN dump error: {e}");
+        return Err(ConvertError);
+    }
+
+    Ok(())
+}
+
+fn write_error(dst_path: &std::path::Path, e: std::io::Error) -> ConvertError {
+    tracing::error!("failed to write {dst_path:?}: {e}");
+    ConvertError
+}
+
+struct Exporter<'a> {
+    progress_print: &'a ProgressPrint,
+    options: &'a Options,
+    dst_path: &'a std::path::Path,
+    svn_dump_reader: svn::dump::DumpReader<'a>,
+    svn_dump_writer: svn::dump::DumpWriter<std::io::BufWriter<std::fs::File>>,
+    /// Directories whose names match `delete-files`, which are kept, unlike
+    /// files with such names. Needed to tell which deletions are kept,
+    /// since delete records do not have a node kind.
+    deleted_file_name_dirs: FHashSet<Vec<u8>>,
+}
+
+enum NodeFilter {
+    Keep,
+    Drop,
+    /// The node replaces a path that is not exported, so it is written
+    /// as an add.
+    KeepAsAdd,
+    /// The node replaces a kept path with a file that is not exported, so
+    /// only the deletion of the path is written.
+    DeleteOnly,
+}
+
+impl Exporter<'_> {
+    fn next_record(&mut self) -> Result<Option<svn::dump::Record>, ConvertError> {
+        self.svn_dump_reader.next_record().map_err(|e| {
+            tracing::error!("failed to read SVN dump record: {e}");
+            ConvertError
+        })
+    }
+
+    fn skip_text(&mut self) -> Result<(), ConvertError> {
+        self.svn_dump_reader.skip_text().map_err(|e| {
+            tracing::error!("failed to read SVN dump: {e}");
+            ConvertError
+        })
+    }
+
+    fn run(&mut self) -> Result<(), ConvertError> {
+        let mut uuid = None;
+        let mut last_rev_no = None;
+        let mut new_dump = false;
+        let mut skipping = false;
+        while let Some(record) = self.next_record()? {
+            match record {
+                svn::dump::Record::NewDump => {
+                    new_dump = true;
+                }
+                svn::dump::Record::Uuid(dump_uuid) => {
+                    if let Some(uuid) = uuid {
+                        if uuid != dump_uuid {
+                            tracing::error!(
+                                "SVN dump UUID {dump_uuid} does not match the previous UUID record",
+                            );
+                            return Err(ConvertError);
+                        }
+                    } else {
+                        tracing::info!("SVN repository UUID: {dump_uuid}");
+                        uuid = Some(dump_uuid);
+                        self.svn_dump_writer
+                            .write_uuid(&dump_uuid)
+                            .map_err(|e| write_error(self.dst_path, e))?;
+                    }
+                }
+                svn::dump::Record::Rev(rev_record) => {
+                    super::check_interrupted()?;
+
+                    if new_dump {
+                        if let Some(last_rev_no) = last_rev_no {
+                            if rev_record.rev_no != last_rev_no + 1 {
+                                tracing::error!(
+                                    "SVN dump starting at revision {} does not continue the previous dump, which ends at revision {last_rev_no}",
+                                    rev_record.rev_no,
+                                );
+                                return Err(ConvertError);
+                            }
+                        }
+                        new_dump = false;
+                    }
+                    if last_rev_no.is_some_and(|last_rev_no| rev_record.rev_no <= last_rev_no) {
+                        tracing::error!("non monotonic increasing SVN revision numbers");
+                        return Err(ConvertError);
+                    }
+                    last_rev_no = Some(rev_record.rev_no);
+
+                    skipping = self
+                        .options
+                        .last_rev
+                        .is_some_and(|last_rev| rev_record.rev_no > last_rev);
+                    if skipping {
+                        tracing::debug!(
+                            "skipping SVN revision {} after the last revision to export",
+                            rev_record.rev_no,
+                        );
+                        continue;
+                    }
+
+                    tracing::debug!("exporting SVN revision {}", rev_record.rev_no);
+                    self.progress_print
+                        .set_progress(format!("exporting SVN revision {}", rev_record.rev_no));
+                    self.svn_dump_writer
+                        .write_rev(&rev_record)
+                        .map_err(|e| write_error(self.dst_path, e))?;
+                }
+                svn::dump::Record::Node(mut node_record) => {
+                    let Some(svn_rev) = last_rev_no else {
+                        tracing::error!("SVN dump has a node record before first revision node");
+                        return Err(ConvertError);
+                    };
+                    if skipping {
+                        self.skip_text()?;
+                        continue;
+                    }
+
+                    match self.filter_node(svn_rev, &node_record)? {
+                        NodeFilter::Keep => {}
+                        NodeFilter::Drop => {
+                            tracing::trace!(
+                                "dropping SVN dump node record: path=\"{}\"",
+                                node_record.path.escape_ascii(),
+                            );
+                            self.skip_text()?;
+                            continue;
+                        }
+                        NodeFilter::KeepAsAdd => {
+                            node_record.action = svn::dump::NodeAction::Add;
+                        }
+                        NodeFilter::DeleteOnly => {
+                            self.skip_text()?;
+                            node_record = svn::dump::NodeRecord {
+                                path: node_record.path,
+                                kind: None,
+                                action: svn::dump::NodeAction::Delete,
+                                copy_from: None,
+                                properties: None,
+                                text: None,
+                            };
+                        }
+                    }
+
+                    self.write_node(svn_rev, &node_record)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn filter_node(
+        &mut self,
+        svn_rev: u32,
+        node_record: &svn::dump::NodeRecord,
+    ) -> Result<NodeFilter, ConvertError> {
+        let path = node_record.path.as_slice();
+        if self.options.is_path_excluded(path) {
+            return Ok(NodeFilter::Drop);
+        }
+
+        if let Some(ref copy_from) = node_record.copy_from {
+            if self.options.is_path_excluded(&copy_from.path) {
+                tracing::error!(
+                    "SVN revision {svn_rev}: cannot export copy of excluded path \"{}\" to \"{}\"",
+                    copy_from.path.escape_ascii(),
+                    path.escape_ascii(),
+                );
+                return Err(ConvertError);
+            }
+        }
+
+        let is_deleted_file_name = |path: &[u8]| {
+            let name = path.rsplit(|&c| c == b'/').next().unwrap();
+            self.options.delete_files.is_match(name)
+        };
+
+        let replaces = matches!(
+            node_record.action,
+            svn::dump::NodeAction::Delete | svn::dump::NodeAction::Replace,
+        );
+        let replaced_dir = replaces && self.deleted_file_name_dirs.remove(path);
+        if replaces {
+            self.deleted_file_name_dirs
+                .retain(|dir| !dir.starts_with(path) || dir.get(path.len()) != Some(&b'/'));
+        }
+
+        if !is_deleted_file_name(path) {
+            if node_record.kind == Some(svn::dump::NodeKind::File) {
+                if let Some(ref copy_from) = node_record.copy_from {
+                    if is_deleted_file_name(&copy_from.path) {
+                        tracing::error!(
+                            "SVN revision {svn_rev}: cannot export copy of deleted file \"{}\" to \"{}\"",
+                            copy_from.path.escape_ascii(),
+                            path.escape_ascii(),
+                        );
+                        return Err(ConvertError);
+                    }
+                }
+            }
+            return Ok(NodeFilter::Keep);
+        }
+
+        match node_record.kind {
+            Some(svn::dump::NodeKind::Dir) => {
+                let filter =
+                    if node_record.action == svn::dump::NodeAction::Replace && !replaced_dir {
+                        NodeFilter::KeepAsAdd
+                    } else {
+                        NodeFilter::Keep
+                    };
+                if node_record.action != svn::dump::NodeAction::Change {
+                    self.deleted_file_name_dirs.insert(path.to_vec());
+                }
+                Ok(filter)
+            }
+            Some(svn::dump::NodeKind::File) => {
+                if replaced_dir {
+                    Ok(NodeFilter::DeleteOnly)
+                } else {
+                    Ok(NodeFilter::Drop)
+                }
+            }
+            None if replaced_dir => Ok(NodeFilter::Keep),
+            None => Ok(NodeFilter::Drop),
+        }
+    }
+
+    fn write_node(
+        &mut self,
+        svn_rev: u32,
+        node_record: &svn::dump::NodeRecord,
+    ) -> Result<(), ConvertError> {
+        let has_deltas = node_record.properties.as_ref().is_some_and(|p| p.is_delta)
+            || node_record.text.as_ref().is_some_and(|t| t.is_delta);
+        if has_deltas && !self.svn_dump_writer.version().has_deltas() {
+            tracing::error!(
+                "SVN revision {svn_rev}: node \"{}\" has deltas, which cannot be exported to a version 2 dump",
+                node_record.path.escape_ascii(),
+            );
+            return Err(ConvertError);
+        }
+
+        let text_len = self.svn_dump_reader.remaining_text_len();
+        let mut text_reader = self.svn_dump_reader.text_reader();
+        self.svn_dump_writer
+            .write_node(node_record, text_len, &mut text_reader)
+            .map_err(|e| {
+                tracing::error!("failed to copy SVN dump node: {e}");
+                ConvertError
+            })
+    }
+}
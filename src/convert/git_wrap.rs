@@ -1,5 +1,5 @@
 use super::ConvertError;
-use crate::git;
+use crate::{FHashSet, git};
 
 pub(super) struct Importer {
     importer: git::Importer,
@@ -9,14 +9,40 @@ impl Importer {
     pub(super) fn init(
         path: &std::path::Path,
         obj_cache_size: usize,
+        persist_dir: Option<&std::path::Path>,
     ) -> Result<Self, ConvertError> {
-        let importer = git::Importer::init(path, obj_cache_size).map_err(|e| {
+        let importer = git::Importer::init(path, obj_cache_size, persist_dir).map_err(|e| {
             tracing::error!("failed to initialize git import: {e}");
             ConvertError
         })?;
         Ok(Self { importer })
     }
 
+    pub(super) fn init_with_storage(
+        path: &std::path::Path,
+        obj_cache_size: usize,
+        storage_dir: &std::path::Path,
+    ) -> Result<Self, ConvertError> {
+        let importer = git::Importer::init_with_storage(path, obj_cache_size, storage_dir)
+            .map_err(|e| {
+                tracing::error!("failed to initialize git import: {e}");
+                ConvertError
+            })?;
+        Ok(Self { importer })
+    }
+
+    pub(super) fn open(
+        path: &std::path::Path,
+        obj_cache_size: usize,
+        persist_dir: &std::path::Path,
+    ) -> Result<Self, ConvertError> {
+        let importer = git::Importer::open(path, obj_cache_size, persist_dir).map_err(|e| {
+            tracing::error!("failed to open git import: {e}");
+            ConvertError
+        })?;
+        Ok(Self { importer })
+    }
+
     #[inline]
     pub(super) fn inner(&mut self) -> &mut git::Importer {
         &mut self.importer
@@ -26,6 +52,17 @@ impl Importer {
         self.importer.abort();
     }
 
+    pub(super) fn save_storage(&self) -> Result<(), ConvertError> {
+        self.importer.save_storage().map_err(|e| {
+            tracing::error!("failed to save git import storage: {e}");
+            ConvertError
+        })
+    }
+
+    pub(super) fn freeze_storage(&mut self) {
+        self.importer.freeze_storage();
+    }
+
     pub(super) fn finish(
         self,
         progress_cb: impl FnMut(git::ImportFinishProgress),
@@ -63,6 +100,20 @@ impl Importer {
         })
     }
 
+    pub(super) fn copy_tree_from(
+        &mut self,
+        src: &Self,
+        tree_oid: gix_hash::ObjectId,
+        copied: &mut FHashSet<gix_hash::ObjectId>,
+    ) -> Result<(), ConvertError> {
+        self.importer
+            .copy_tree_from(&src.importer, tree_oid, copied)
+            .map_err(|e| {
+                tracing::error!("failed to copy tree {tree_oid}: {e}");
+                ConvertError
+            })
+    }
+
     pub(crate) fn get_raw(
         &self,
         id: gix_hash::ObjectId,
@@ -73,6 +124,40 @@ impl Importer {
         })
     }
 
+    pub(super) fn blob_writer(&self) -> Result<git::BlobWriter, ConvertError> {
+        self.importer.blob_writer().map_err(|e| {
+            tracing::error!("failed to create blob writer: {e}");
+            ConvertError
+        })
+    }
+
+    pub(super) fn put_blob_stream(
+        &mut self,
+        writer: git::BlobWriter,
+    ) -> Result<gix_hash::ObjectId, ConvertError> {
+        self.importer.put_blob_stream(writer).map_err(|e| {
+            tracing::error!("failed to put object: {e}");
+            ConvertError
+        })
+    }
+
+    pub(super) fn get_blob_size(&self, id: gix_hash::ObjectId) -> Result<u64, ConvertError> {
+        self.importer.get_blob_size(id).map_err(|e| {
+            tracing::error!("failed to get object {id}: {e}");
+            ConvertError
+        })
+    }
+
+    pub(super) fn get_blob_reader(
+        &self,
+        id: gix_hash::ObjectId,
+    ) -> Result<git::BlobReader<'_>, ConvertError> {
+        self.importer.get_blob_reader(id).map_err(|e| {
+            tracing::error!("failed to get object {id}: {e}");
+            ConvertError
+        })
+    }
+
     pub(super) fn get_blob(&self, id: gix_hash::ObjectId) -> Result<Vec<u8>, ConvertError> {
         self.importer.get_blob(id).map_err(|e| {
             tracing::error!("failed to get object {id}: {e}");
@@ -94,6 +179,16 @@ impl Importer {
         })
     }
 
+    pub(super) fn ls_tree(
+        &self,
+        tree_oid: gix_hash::ObjectId,
+    ) -> Result<Vec<(Vec<u8>, gix_object::tree::EntryKind, gix_hash::ObjectId)>, ConvertError> {
+        self.importer.ls_tree(tree_oid).map_err(|e| {
+            tracing::error!("failed to ls tree {tree_oid}: {e}");
+            ConvertError
+        })
+    }
+
     pub(crate) fn set_head(&mut self, head_ref: &str) {
         self.importer.set_head(head_ref);
     }
@@ -101,4 +196,8 @@ impl Importer {
     pub(super) fn set_ref(&mut self, ref_name: &str, commit_oid: gix_hash::ObjectId) {
         self.importer.set_ref(ref_name, commit_oid);
     }
+
+    pub(super) fn remove_ref(&mut self, ref_name: &str) -> Option<gix_hash::ObjectId> {
+        self.importer.remove_ref(ref_name)
+    }
 }
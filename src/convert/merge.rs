@@ -0,0 +1,310 @@
+use std::collections::BTreeSet;
+
+use gix_object::tree::EntryKind;
+
+use super::options::Options;
+use super::stage1::{BranchData, BranchRevData, Head, Output, State, UnbranchedRevData};
+use super::{ConvertError, git_wrap};
+use crate::FHashMap;
+use crate::term_out::ProgressPrint;
+
+/// A source repository of a merged conversion, imported by stage 1 with
+/// its own options.
+pub(super) struct Source<'a> {
+    pub(super) name: &'a str,
+    pub(super) subdir: &'a [u8],
+    pub(super) options: &'a Options,
+    pub(super) output: Output,
+}
+
+/// Origin of the revisions of a merged conversion, recorded in the
+/// metadata of the commits.
+pub(super) struct Origin {
+    /// Name and UUID of each source repository.
+    pub(super) sources: Vec<(String, Option<uuid::Uuid>)>,
+    /// Source repository of each root commit.
+    pub(super) root_rev_sources: Vec<usize>,
+    /// SVN path of each branch revision in its source repository.
+    pub(super) branch_rev_svn_paths: Vec<Vec<u8>>,
+}
+
+/// Merges the outputs of stage 1 of several source repositories into the
+/// output of one repository.
+///
+/// Revisions are interleaved by their `svn:date`, keeping the order of each
+/// source. The trees of each source are placed in its subdirectory. Live
+/// branches of different sources that get the same name with the renames of
+/// their sources become one branch, whose trees hold the last tree of each
+/// of them. Tags and deleted branches are never merged.
+///
+/// Branches of the merged output are named after their Git name, so they
+/// must not be renamed again.
+pub(super) fn merge(
+    progress_print: &ProgressPrint,
+    mut sources: Vec<Source<'_>>,
+    git_import: &mut git_wrap::Importer,
+) -> Result<Output, ConvertError> {
+    tracing::info!("merging {} source repositories", sources.len());
+    progress_print.set_progress("merging source repositories".into());
+
+    let root_order = interleave_root_revs(&sources);
+
+    // Branch revisions keep the order of their root commits, so their
+    // indices in the merged output can be computed beforehand.
+    let mut branch_rev_map: Vec<Vec<usize>> = sources
+        .iter()
+        .map(|source| vec![0; source.output.branch_rev_data.len()])
+        .collect();
+    let mut next_branch_rev = vec![0; sources.len()];
+    let mut num_branch_revs = 0;
+    for &(src_i, root_rev) in root_order.iter() {
+        let branch_rev_data = &sources[src_i].output.branch_rev_data;
+        let next = &mut next_branch_rev[src_i];
+        while branch_rev_data
+            .get(*next)
+            .is_some_and(|rev_data| rev_data.root_rev == root_rev)
+        {
+            branch_rev_map[src_i][*next] = num_branch_revs;
+            num_branch_revs += 1;
+            *next += 1;
+        }
+    }
+
+    let map_revs = |src_i: usize, revs: &BTreeSet<usize>| -> BTreeSet<usize> {
+        revs.iter().map(|&rev| branch_rev_map[src_i][rev]).collect()
+    };
+
+    let mut origin = Origin {
+        sources: sources
+            .iter()
+            .map(|source| (source.name.to_owned(), source.output.svn_uuid))
+            .collect(),
+        root_rev_sources: Vec::with_capacity(root_order.len()),
+        branch_rev_svn_paths: Vec::with_capacity(num_branch_revs),
+    };
+    let mut root_rev_data = Vec::with_capacity(root_order.len());
+    let mut unbranched_rev_data = Vec::<UnbranchedRevData>::new();
+    let mut branch_data = Vec::<BranchData>::new();
+    let mut branch_rev_data = Vec::<BranchRevData>::new();
+
+    // name -> (merged branch, sources merged into it)
+    let mut merged_branches = FHashMap::<Vec<u8>, (usize, Vec<usize>)>::default();
+    let mut branch_map: Vec<Vec<Option<usize>>> = sources
+        .iter()
+        .map(|source| vec![None; source.output.branch_data.len()])
+        .collect();
+
+    // The root commits are moved from their sources, so the SVN revisions
+    // needed to rename branches are kept aside.
+    let src_svn_revs = sources
+        .iter()
+        .map(|source| {
+            source
+                .output
+                .root_rev_data
+                .iter()
+                .map(|rev_data| rev_data.svn_rev)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut src_root_revs = sources
+        .iter_mut()
+        .map(|source| std::mem::take(&mut source.output.root_rev_data).into_iter())
+        .collect::<Vec<_>>();
+    let mut next_unbranched_rev = vec![0; sources.len()];
+    let mut next_branch_rev = vec![0; sources.len()];
+
+    for (i, &(src_i, src_root_rev)) in root_order.iter().enumerate() {
+        progress_print.set_progress(format!(
+            "merging source repositories - {} / {}",
+            i + 1,
+            root_order.len(),
+        ));
+
+        let source = &sources[src_i];
+        let root_rev = root_rev_data.len();
+        root_rev_data.push(src_root_revs[src_i].next().unwrap());
+        origin.root_rev_sources.push(src_i);
+
+        let src_unbranched_rev_data = &source.output.unbranched_rev_data;
+        let next = &mut next_unbranched_rev[src_i];
+        while let Some(src_rev_data) = src_unbranched_rev_data
+            .get(*next)
+            .filter(|rev_data| rev_data.root_rev == src_root_rev)
+        {
+            let tree_oid = place_tree(
+                git_import,
+                unbranched_rev_data.last().map(|rev_data| rev_data.tree_oid),
+                source.subdir,
+                src_rev_data.tree_oid,
+            )?;
+            unbranched_rev_data.push(UnbranchedRevData { root_rev, tree_oid });
+            *next += 1;
+        }
+
+        let src_branch_rev_data = &source.output.branch_rev_data;
+        let next = &mut next_branch_rev[src_i];
+        while let Some(src_rev_data) = src_branch_rev_data
+            .get(*next)
+            .filter(|rev_data| rev_data.root_rev == src_root_rev)
+        {
+            let src_branch_data = &source.output.branch_data[src_rev_data.branch];
+            let branch = match branch_map[src_i][src_rev_data.branch] {
+                Some(branch) => branch,
+                None => {
+                    let renamer = source.options.branch_renamer(
+                        src_branch_data.is_tag,
+                        src_svn_revs[src_i][src_branch_data.svn_path_root_rev()],
+                    );
+                    let git_name = renamer.rename(&src_branch_data.svn_path).into_owned();
+
+                    // Only live branches of different sources are merged.
+                    let mergeable = !src_branch_data.is_tag && !src_branch_data.deleted;
+                    let existing =
+                        merged_branches
+                            .get_mut(&git_name)
+                            .filter(|(_, merged_sources)| {
+                                mergeable && !merged_sources.contains(&src_i)
+                            });
+                    let branch = if let Some((branch, merged_sources)) = existing {
+                        tracing::debug!(
+                            "merging branch \"{}\" of \"{}\" into \"{}\"",
+                            src_branch_data.svn_path.escape_ascii(),
+                            source.name,
+                            git_name.escape_ascii(),
+                        );
+                        merged_sources.push(src_i);
+                        *branch
+                    } else {
+                        branch_data.push(BranchData {
+                            svn_path: git_name.clone(),
+                            is_tag: src_branch_data.is_tag,
+                            partial_sub_path: Vec::new(),
+                            deleted: src_branch_data.deleted,
+                            tip_commit: None,
+                            first_root_rev: root_rev,
+                            last_root_rev: root_rev,
+                            rev_map: Vec::new(),
+                            prev_svn_paths: Vec::new(),
+                        });
+                        let branch = branch_data.len() - 1;
+                        if mergeable {
+                            merged_branches
+                                .entry(git_name)
+                                .or_insert((branch, vec![src_i]));
+                        }
+                        branch
+                    };
+                    branch_map[src_i][src_rev_data.branch] = Some(branch);
+                    branch
+                }
+            };
+
+            // The branch continues from its last revision, which might
+            // come from another source, or starts where its first source
+            // branch was copied from.
+            let branch_rev = branch_rev_data.len();
+            let parent = branch_data[branch]
+                .tip_commit
+                .or_else(|| src_rev_data.parent.map(|p| branch_rev_map[src_i][p]));
+            let tail = parent.map_or(branch_rev, |p| branch_rev_data[p].tail);
+            let tree_oid = place_tree(
+                git_import,
+                parent.map(|p| branch_rev_data[p].tree_oid),
+                source.subdir,
+                src_rev_data.tree_oid,
+            )?;
+
+            let merged_branch_data = &mut branch_data[branch];
+            merged_branch_data.tip_commit = Some(branch_rev);
+            merged_branch_data.last_root_rev = root_rev;
+            merged_branch_data.rev_map.push((root_rev, branch_rev));
+
+            branch_rev_data.push(BranchRevData {
+                branch,
+                parent,
+                tail,
+                root_rev,
+                required_in_mergeinfo: src_rev_data.required_in_mergeinfo,
+                added_svn_merges: map_revs(src_i, &src_rev_data.added_svn_merges),
+                removed_svn_merges: map_revs(src_i, &src_rev_data.removed_svn_merges),
+                ignore_merges: src_rev_data.ignore_merges,
+                fully_reverted_merges_in: map_revs(src_i, &src_rev_data.fully_reverted_merges_in),
+                tree_oid,
+            });
+            origin
+                .branch_rev_svn_paths
+                .push(src_branch_data.svn_path_at(src_root_rev).to_vec());
+            *next += 1;
+        }
+    }
+
+    let head_branch = match sources[0].output.head_branch {
+        Head::Branch(branch) => Head::Branch(branch_map[0][branch].unwrap()),
+        Head::Unbranched => Head::Unbranched,
+    };
+
+    Ok(Output {
+        svn_uuid: None,
+        root_rev_data,
+        unbranched_rev_data,
+        branch_data,
+        branch_rev_data,
+        head_branch,
+        merge_origin: Some(origin),
+        state: State {
+            dump_uuid: None,
+            tree_map: FHashMap::default(),
+            live_branches: FHashMap::default(),
+            path_to_branch: FHashMap::default(),
+            has_partial_branches: false,
+            branch_path_commits: FHashMap::default(),
+        },
+    })
+}
+
+/// Returns the root commits of all the sources, as `(source, root commit)`,
+/// ordered by their `svn:date` without changing the order within each
+/// source.
+fn interleave_root_revs(sources: &[Source<'_>]) -> Vec<(usize, usize)> {
+    // `svn:date` values have a fixed format, so they sort as bytes.
+    let rev_date = |src_i: usize, root_rev: usize| {
+        sources[src_i].output.root_rev_data[root_rev]
+            .svn_rev_props
+            .get(b"svn:date".as_slice())
+            .map_or(b"".as_slice(), Vec::as_slice)
+    };
+
+    let mut next = vec![0; sources.len()];
+    let mut order = Vec::new();
+    while let Some(src_i) = (0..sources.len())
+        .filter(|&src_i| next[src_i] < sources[src_i].output.root_rev_data.len())
+        .min_by_key(|&src_i| rev_date(src_i, next[src_i]))
+    {
+        order.push((src_i, next[src_i]));
+        next[src_i] += 1;
+    }
+    order
+}
+
+/// Returns the tree `base_tree_oid` with `tree_oid` placed at `subdir`.
+fn place_tree(
+    git_import: &mut git_wrap::Importer,
+    base_tree_oid: Option<gix_hash::ObjectId>,
+    subdir: &[u8],
+    tree_oid: gix_hash::ObjectId,
+) -> Result<gix_hash::ObjectId, ConvertError> {
+    let mut change_set = crate::git::ChangeSet::new(base_tree_oid);
+    if tree_oid == git_import.empty_tree_oid() {
+        change_set.remove(subdir);
+    } else {
+        change_set.change(subdir, EntryKind::Tree, tree_oid);
+    }
+    Ok(change_set
+        .apply(git_import.inner())
+        .map_err(|e| {
+            tracing::error!("failed to apply git change set: {e}");
+            ConvertError
+        })?
+        .unwrap_or_else(|| git_import.empty_tree_oid()))
+}
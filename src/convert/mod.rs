@@ -1,19 +1,45 @@
 use crate::term_out::ProgressPrint;
-use crate::{FHashMap, git};
+use crate::{FHashMap, FHashSet, git};
 
 mod bin_ser_de;
+mod export;
 mod git_wrap;
+mod merge;
 mod meta;
 mod options;
+mod path_rewrite;
 mod stage1;
 mod stage2;
+mod state;
 mod svn_tree;
 mod tree_builder;
 
-pub(crate) use options::{InitOptions, Options};
+pub(crate) use export::export;
+pub(crate) use options::{BranchLayout, InitOptions, Options};
 
 pub(crate) struct ConvertError;
 
+static INTERRUPTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Asks the running conversion to stop at the next revision boundary,
+/// saving a checkpoint if enabled.
+pub(crate) fn interrupt() {
+    INTERRUPTED.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn is_interrupted() -> bool {
+    INTERRUPTED.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+fn check_interrupted() -> Result<(), ConvertError> {
+    if is_interrupted() {
+        tracing::error!("conversion interrupted");
+        Err(ConvertError)
+    } else {
+        Ok(())
+    }
+}
+
 pub(crate) struct GitCommitMeta {
     pub(crate) author: gix_actor::Signature,
     pub(crate) committer: gix_actor::Signature,
@@ -29,6 +55,7 @@ pub(crate) trait GitMetaMaker {
     fn make_git_commit_meta(
         &self,
         svn_uuid: Option<&uuid::Uuid>,
+        svn_source: Option<&str>,
         svn_rev_no: u32,
         svn_path: Option<&[u8]>,
         svn_rev_props: &FHashMap<Vec<u8>, Vec<u8>>,
@@ -37,6 +64,7 @@ pub(crate) trait GitMetaMaker {
     fn make_git_tag_meta(
         &self,
         svn_uuid: Option<&uuid::Uuid>,
+        svn_source: Option<&str>,
         svn_rev_no: u32,
         svn_path: &[u8],
         svn_rev_props: &FHashMap<Vec<u8>, Vec<u8>>,
@@ -47,40 +75,418 @@ pub(crate) fn convert(
     progress_print: &ProgressPrint,
     options: &Options,
     makedata_meta: &dyn GitMetaMaker,
-    src_path: &std::path::Path,
+    src_paths: &[std::path::PathBuf],
     src_is_remote: bool,
     dst_path: &std::path::Path,
 ) -> Result<(), ConvertError> {
     progress_print.set_progress("initializing git import".into());
 
-    let mut git_import = git_wrap::Importer::init(dst_path, options.git_obj_cache_size)?;
+    // The state needed to continue or resume the conversion is kept in
+    // a directory inside the git repository.
+    let state_dir = dst_path.join("svn2git");
+    let state_path = state_dir.join("state");
+    let checkpoint_path = state_dir.join("checkpoint");
+    let persist = options.incremental || options.checkpoint_interval.is_some();
 
-    let mut run_stages = || {
-        let stage1_out = stage1::run(
-            progress_print,
-            options,
-            src_path,
-            src_is_remote,
-            &mut git_import,
-        )?;
-        stage2::run(
+    // When reusing the output of stage 1, the object storage is kept in
+    // the cache directory instead.
+    let stage1_cache = options
+        .reuse_stage1
+        .as_deref()
+        .map(|cache_dir| (cache_dir, cache_dir.join("stage1")));
+
+    let existing_stage1_cache = stage1_cache
+        .as_ref()
+        .filter(|(cache_dir, _)| cache_dir.exists());
+
+    let (mut git_import, prev_state, cached_stage1_out) = if let Some((cache_dir, cache_path)) =
+        existing_stage1_cache
+    {
+        if !cache_path.exists() {
+            tracing::error!("{cache_dir:?} does not contain a stage 1 output");
+            return Err(ConvertError);
+        }
+        tracing::info!("reusing saved stage 1 output");
+        let cached = state::load(cache_path)?;
+        let git_import =
+            git_wrap::Importer::init_with_storage(dst_path, options.git_obj_cache_size, cache_dir)?;
+        (git_import, None, Some(cached.stage1))
+    } else if options.resume {
+        if !checkpoint_path.exists() {
+            tracing::error!("no checkpoint found in {dst_path:?}");
+            return Err(ConvertError);
+        }
+        tracing::info!("resuming interrupted conversion");
+        let prev_state = state::load(&checkpoint_path)?;
+        let git_import =
+            git_wrap::Importer::open(dst_path, options.git_obj_cache_size, &state_dir)?;
+        (git_import, Some(prev_state), None)
+    } else if options.incremental && dst_path.exists() {
+        if checkpoint_path.exists() {
+            tracing::error!("previous conversion was interrupted, use --resume to continue it");
+            return Err(ConvertError);
+        }
+        tracing::info!("continuing previous conversion");
+        let prev_state = state::load(&state_path)?;
+        let git_import =
+            git_wrap::Importer::open(dst_path, options.git_obj_cache_size, &state_dir)?;
+        (git_import, Some(prev_state), None)
+    } else {
+        let persist_dir = if let Some((cache_dir, _)) = stage1_cache {
+            Some(cache_dir)
+        } else {
+            persist.then_some(state_dir.as_path())
+        };
+        let git_import =
+            git_wrap::Importer::init(dst_path, options.git_obj_cache_size, persist_dir)?;
+        (git_import, None, None)
+    };
+
+    let run_stages = || {
+        let (prev_stage1_out, prev_stage2_state) = match prev_state {
+            Some(prev_state) => (Some(prev_state.stage1), prev_state.stage2),
+            None => (None, None),
+        };
+
+        let stage1_out = if let Some(stage1_out) = cached_stage1_out {
+            stage1_out
+        } else {
+            let mut save_checkpoint =
+                |git_import: &mut git_wrap::Importer, stage1_out: &stage1::Output| {
+                    git_import.save_storage()?;
+                    state::save(&checkpoint_path, stage1_out, prev_stage2_state.as_ref())
+                };
+
+            let stage1_out = stage1::run(
+                progress_print,
+                options,
+                src_paths,
+                src_is_remote,
+                prev_stage1_out,
+                options
+                    .checkpoint_interval
+                    .map(|interval| stage1::Checkpoints {
+                        interval,
+                        save: &mut save_checkpoint,
+                    }),
+                &mut git_import,
+            )?;
+
+            if options.checkpoint_interval.is_some() {
+                progress_print.set_progress("saving checkpoint".into());
+                tracing::info!("saving checkpoint at the end of stage 1");
+                save_checkpoint(&mut git_import, &stage1_out)?;
+            }
+
+            if let Some((_, ref cache_path)) = stage1_cache {
+                progress_print.set_progress("saving stage 1 output".into());
+                tracing::info!("saving stage 1 output");
+                git_import.save_storage()?;
+                state::save(cache_path, &stage1_out, None)?;
+                git_import.freeze_storage();
+            }
+
+            stage1_out
+        };
+
+        let stage2_state = stage2::run(
             progress_print,
             options,
             makedata_meta,
             &mut git_import,
             &stage1_out,
+            prev_stage2_state,
         )?;
-        Ok(())
+        Ok((stage1_out, stage2_state))
     };
 
-    match run_stages() {
-        Ok(()) => {}
+    let (stage1_out, stage2_state) = match run_stages() {
+        Ok(r) => r,
+        Err(ConvertError) => {
+            git_import.abort();
+            return Err(ConvertError);
+        }
+    };
+
+    finish_git_import(progress_print, git_import)?;
+
+    if options.incremental {
+        progress_print.set_progress("saving conversion state".into());
+        tracing::info!("saving conversion state");
+        state::save(&state_path, &stage1_out, Some(&stage2_state))?;
+        if checkpoint_path.exists() {
+            std::fs::remove_file(&checkpoint_path).map_err(|e| {
+                tracing::error!("failed to remove {checkpoint_path:?}: {e}");
+                ConvertError
+            })?;
+        }
+    } else if persist || options.resume {
+        std::fs::remove_dir_all(&state_dir).map_err(|e| {
+            tracing::error!("failed to remove {state_dir:?}: {e}");
+            ConvertError
+        })?;
+    }
+
+    progress_print.set_progress("finalizing".into());
+    progress_print.freeze_progress();
+
+    if options.git_repack {
+        git_repack(dst_path)?;
+    }
+
+    Ok(())
+}
+
+/// Converts the SVN repository into several Git repositories in one pass
+/// over the dump. Each output has its own destination (relative to
+/// `dst_path`) and options, which only differ in their branch and tag specs
+/// and head.
+///
+/// Stage 1 imports the objects of all the outputs into the importer of the
+/// first one. Every other output has its own importer, into which the trees
+/// of its commits are copied before emitting them.
+pub(crate) fn convert_split(
+    progress_print: &ProgressPrint,
+    outputs: &[(std::path::PathBuf, Options)],
+    makedata_meta: &dyn GitMetaMaker,
+    src_paths: &[std::path::PathBuf],
+    src_is_remote: bool,
+    dst_path: &std::path::Path,
+) -> Result<(), ConvertError> {
+    let outputs = outputs
+        .iter()
+        .map(|(output_path, options)| (dst_path.join(output_path), options))
+        .collect::<Vec<_>>();
+    let (first_output_path, first_options) = outputs.first().expect("no outputs");
+
+    progress_print.set_progress("initializing git import".into());
+
+    for (output_path, _) in outputs.iter() {
+        if let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                tracing::error!("failed to create {parent:?}: {e}");
+                ConvertError
+            })?;
+        }
+    }
+
+    let mut git_import =
+        git_wrap::Importer::init(first_output_path, first_options.git_obj_cache_size, None)?;
+
+    let all_options = outputs
+        .iter()
+        .map(|&(_, options)| options)
+        .collect::<Vec<_>>();
+    let stage1_outs = match stage1::run_split(
+        progress_print,
+        &all_options,
+        src_paths,
+        src_is_remote,
+        &mut git_import,
+    ) {
+        Ok(r) => r,
         Err(ConvertError) => {
             git_import.abort();
             return Err(ConvertError);
         }
+    };
+
+    // The root commits are only in the first output of stage 1, and are
+    // lent to each output in turn. The first output is written last, since
+    // the other ones copy their objects from its importer.
+    let mut stage1_outs = stage1_outs.into_iter();
+    let mut first_stage1_out = stage1_outs.next().unwrap();
+    for ((output_path, options), mut stage1_out) in outputs[1..].iter().zip(stage1_outs) {
+        tracing::info!("writing output {output_path:?}");
+
+        let mut output_import =
+            match git_wrap::Importer::init(output_path, options.git_obj_cache_size, None) {
+                Ok(output_import) => output_import,
+                Err(ConvertError) => {
+                    git_import.abort();
+                    return Err(ConvertError);
+                }
+            };
+
+        stage1_out.root_rev_data = std::mem::take(&mut first_stage1_out.root_rev_data);
+        let r = write_split_output(
+            progress_print,
+            options,
+            makedata_meta,
+            &git_import,
+            &mut output_import,
+            &stage1_out,
+        );
+        first_stage1_out.root_rev_data = std::mem::take(&mut stage1_out.root_rev_data);
+        if r.is_err() {
+            output_import.abort();
+            git_import.abort();
+            return Err(ConvertError);
+        }
+
+        if let Err(ConvertError) = finish_git_import(progress_print, output_import) {
+            git_import.abort();
+            return Err(ConvertError);
+        }
+
+        if options.git_repack {
+            if let Err(ConvertError) = git_repack(output_path) {
+                git_import.abort();
+                return Err(ConvertError);
+            }
+        }
+    }
+
+    tracing::info!("writing output {first_output_path:?}");
+    let r = stage2::run(
+        progress_print,
+        first_options,
+        makedata_meta,
+        &mut git_import,
+        &first_stage1_out,
+        None,
+    );
+    if r.is_err() {
+        git_import.abort();
+        return Err(ConvertError);
+    }
+
+    finish_git_import(progress_print, git_import)?;
+
+    if first_options.git_repack {
+        git_repack(first_output_path)?;
+    }
+
+    progress_print.set_progress("finalizing".into());
+    progress_print.freeze_progress();
+
+    Ok(())
+}
+
+/// Copies the trees of the commits of an output of a split conversion from
+/// `src_import`, where stage 1 imported them, and emits the commits.
+fn write_split_output(
+    progress_print: &ProgressPrint,
+    options: &Options,
+    makedata_meta: &dyn GitMetaMaker,
+    src_import: &git_wrap::Importer,
+    git_import: &mut git_wrap::Importer,
+    stage1_out: &stage1::Output,
+) -> Result<(), ConvertError> {
+    progress_print.set_progress("copying objects".into());
+    // The trees are copied in the order they were imported, so they can
+    // be stored as deltas of the previous ones.
+    let mut copied = FHashSet::default();
+    let mut tree_oids = stage1_out
+        .unbranched_rev_data
+        .iter()
+        .map(|rev_data| (rev_data.root_rev, rev_data.tree_oid))
+        .chain(
+            stage1_out
+                .branch_rev_data
+                .iter()
+                .map(|rev_data| (rev_data.root_rev, rev_data.tree_oid)),
+        )
+        .collect::<Vec<_>>();
+    tree_oids.sort_by_key(|&(root_rev, _)| root_rev);
+    for (_, tree_oid) in tree_oids {
+        git_import.copy_tree_from(src_import, tree_oid, &mut copied)?;
+    }
+
+    stage2::run(
+        progress_print,
+        options,
+        makedata_meta,
+        git_import,
+        stage1_out,
+        None,
+    )?;
+    Ok(())
+}
+
+/// A source repository of a merged conversion.
+pub(crate) struct MergeSource {
+    pub(crate) name: String,
+    /// Directory of the Git trees where the source is placed.
+    pub(crate) subdir: Vec<u8>,
+    pub(crate) src_paths: Vec<std::path::PathBuf>,
+    /// Options used to import the source, with its own branch and tag
+    /// specs and head.
+    pub(crate) options: Options,
+}
+
+/// Converts several SVN repositories into one Git repository, placing
+/// each of them in its own subdirectory and interleaving their revisions
+/// by date.
+///
+/// `options` are used to emit the commits of the merged history, and its
+/// branch and tag specs are not used.
+pub(crate) fn convert_merge(
+    progress_print: &ProgressPrint,
+    options: &Options,
+    sources: &[MergeSource],
+    makedata_meta: &dyn GitMetaMaker,
+    src_is_remote: bool,
+    dst_path: &std::path::Path,
+) -> Result<(), ConvertError> {
+    progress_print.set_progress("initializing git import".into());
+
+    let mut git_import = git_wrap::Importer::init(dst_path, options.git_obj_cache_size, None)?;
+
+    let run_stages = |git_import: &mut git_wrap::Importer| {
+        let mut merge_sources = Vec::with_capacity(sources.len());
+        for source in sources.iter() {
+            tracing::info!("importing source repository \"{}\"", source.name);
+            let output = stage1::run(
+                progress_print,
+                &source.options,
+                &source.src_paths,
+                src_is_remote,
+                None,
+                None,
+                git_import,
+            )?;
+            merge_sources.push(merge::Source {
+                name: &source.name,
+                subdir: &source.subdir,
+                options: &source.options,
+                output,
+            });
+        }
+
+        let stage1_out = merge::merge(progress_print, merge_sources, git_import)?;
+
+        stage2::run(
+            progress_print,
+            options,
+            makedata_meta,
+            git_import,
+            &stage1_out,
+            None,
+        )
+    };
+
+    if run_stages(&mut git_import).is_err() {
+        git_import.abort();
+        return Err(ConvertError);
+    }
+
+    finish_git_import(progress_print, git_import)?;
+
+    progress_print.set_progress("finalizing".into());
+    progress_print.freeze_progress();
+
+    if options.git_repack {
+        git_repack(dst_path)?;
     }
 
+    Ok(())
+}
+
+fn finish_git_import(
+    progress_print: &ProgressPrint,
+    git_import: git_wrap::Importer,
+) -> Result<(), ConvertError> {
     progress_print.set_progress("finalizing git import".into());
 
     tracing::info!("finalizing git import");
@@ -102,36 +508,32 @@ pub(crate) fn convert(
         git::ImportFinishProgress::MakeIndex => {
             progress_print.set_progress("finalizing git import - generating pack index".into());
         }
-    })?;
-
-    progress_print.set_progress("finalizing".into());
-    progress_print.freeze_progress();
+    })
+}
 
-    if options.git_repack {
-        tracing::info!("running git repack");
-        let mut repack_child = std::process::Command::new("git")
-            .arg("repack")
-            .arg("-a") // repack already-packed objects
-            .arg("-d") // delete old packs
-            .arg("-f") // compute deltas from scratch
-            .current_dir(dst_path)
-            .stdin(std::process::Stdio::null())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .spawn()
-            .map_err(|e| {
-                tracing::error!("failed to spawn \"git repack\": {e:?}");
-                ConvertError
-            })?;
-        let repack_exit_code = repack_child.wait().map_err(|e| {
-            tracing::error!("failed to wait for \"git repack\": {e:?}");
+fn git_repack(dst_path: &std::path::Path) -> Result<(), ConvertError> {
+    tracing::info!("running git repack");
+    let mut repack_child = std::process::Command::new("git")
+        .arg("repack")
+        .arg("-a") // repack already-packed objects
+        .arg("-d") // delete old packs
+        .arg("-f") // compute deltas from scratch
+        .current_dir(dst_path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit())
+        .spawn()
+        .map_err(|e| {
+            tracing::error!("failed to spawn \"git repack\": {e:?}");
             ConvertError
         })?;
-        if !repack_exit_code.success() {
-            tracing::error!("git repack exited with code {repack_exit_code}");
-            return Err(ConvertError);
-        }
+    let repack_exit_code = repack_child.wait().map_err(|e| {
+        tracing::error!("failed to wait for \"git repack\": {e:?}");
+        ConvertError
+    })?;
+    if !repack_exit_code.success() {
+        tracing::error!("git repack exited with code {repack_exit_code}");
+        return Err(ConvertError);
     }
-
     Ok(())
 }
@@ -1,12 +1,18 @@
 use std::borrow::Cow;
 
 use super::ConvertError;
+use super::path_rewrite::{PathRewriteAddError, PathRewrites};
 use crate::path_pattern::PathPattern;
-use crate::{FHashMap, FHashSet};
+use crate::svn::source::{RemoteOptions, SvnadminOptions};
+use crate::{FHashMap, FHashSet, svn};
 
+#[derive(Clone)]
 pub(crate) struct InitOptions {
     pub(crate) keep_deleted_branches: bool,
     pub(crate) keep_deleted_tags: bool,
+    pub(crate) detect_branch_renames: bool,
+    pub(crate) rename_aliases: bool,
+    pub(crate) rename_log: Option<std::path::PathBuf>,
     pub(crate) head_path: Vec<u8>,
     pub(crate) unbranched_name: Option<String>,
     pub(crate) enable_merges: bool,
@@ -14,18 +20,44 @@ pub(crate) struct InitOptions {
     pub(crate) avoid_fully_reverted_merges: bool,
     pub(crate) generate_gitignore: bool,
     pub(crate) delete_files: PathPattern,
+    pub(crate) exclude_paths: PathPattern,
+    pub(crate) subdirectory: Vec<u8>,
     pub(crate) git_obj_cache_size: usize,
     pub(crate) git_repack: bool,
+    pub(crate) incremental: bool,
+    pub(crate) checkpoint_interval: Option<std::time::Duration>,
+    pub(crate) resume: bool,
+    pub(crate) reuse_stage1: Option<std::path::PathBuf>,
+    pub(crate) first_rev: Option<u32>,
+    pub(crate) last_rev: Option<u32>,
+    pub(crate) allow_checksum_mismatch: bool,
+    pub(crate) lenient: bool,
+    pub(crate) dump_limits: svn::dump::Limits,
+    pub(crate) delta_limits: svn::diff::Limits,
+    pub(crate) stream_file_size: u64,
+    pub(crate) remote: RemoteOptions,
+    pub(crate) svnadmin: SvnadminOptions,
+    pub(crate) decompress_threads: usize,
 }
 
 pub(crate) struct Options {
-    root_dir_spec: ContainerDirSpecNode,
-    pub(super) rename_branches: BranchRenamer,
+    /// Branch and tag specs used in revisions not covered by `layouts`.
+    default_layout: BranchLayout,
+    /// Branch and tag specs that replace the default ones in a range of
+    /// revisions. Ranges do not overlap.
+    layouts: Vec<BranchLayout>,
     pub(super) keep_deleted_branches: bool,
     pub(super) partial_branches: PartialBranchSet,
-    pub(super) rename_tags: BranchRenamer,
     pub(super) keep_deleted_tags: bool,
     pub(super) partial_tags: PartialBranchSet,
+    /// Whether a branch or tag whose root is moved continues at its new
+    /// path, even if the layout does not change.
+    pub(super) detect_branch_renames: bool,
+    /// Whether to keep a ref with each previous name of a moved branch or
+    /// tag.
+    pub(super) rename_aliases: bool,
+    /// File where the moved branches and tags are listed.
+    pub(super) rename_log: Option<std::path::PathBuf>,
     pub(super) head_path: Vec<u8>,
     pub(super) unbranched_name: Option<String>,
     pub(super) enable_merges: bool,
@@ -34,8 +66,50 @@ pub(crate) struct Options {
     pub(super) ignore_merges_at: FHashMap<u32, FHashSet<Vec<u8>>>,
     pub(super) generate_gitignore: bool,
     pub(super) delete_files: PathPattern,
+    /// SVN paths that are left out, along with everything below them.
+    pub(super) exclude_paths: PathPattern,
+    /// Path inside every branch that becomes the root of its Git trees,
+    /// or empty to keep whole branches.
+    pub(super) subdirectory: Vec<u8>,
+    pub(super) path_rewrites: PathRewrites,
     pub(super) git_obj_cache_size: usize,
     pub(super) git_repack: bool,
+    pub(super) incremental: bool,
+    pub(super) checkpoint_interval: Option<std::time::Duration>,
+    pub(super) resume: bool,
+    pub(super) reuse_stage1: Option<std::path::PathBuf>,
+    /// First SVN revision to emit commits for. History before it is
+    /// squashed into one root commit per branch.
+    pub(super) first_rev: Option<u32>,
+    /// Last SVN revision to import.
+    pub(super) last_rev: Option<u32>,
+    /// Report checksum mismatches in the dump as warnings instead of errors.
+    pub(super) allow_checksum_mismatch: bool,
+    /// Repair recoverable anomalies in the dump instead of failing.
+    pub(super) lenient: bool,
+    /// Limits on the sizes found in the dump.
+    pub(super) dump_limits: svn::dump::Limits,
+    /// Limits on the sizes found in deltas of the dump.
+    pub(super) delta_limits: svn::diff::Limits,
+    /// File texts larger than this (in bytes) are streamed to the storage
+    /// instead of being kept entirely in memory.
+    pub(super) stream_file_size: u64,
+    /// How a remote source repository is fetched.
+    pub(super) remote: RemoteOptions,
+    /// How a local repository is dumped with svnadmin.
+    pub(super) svnadmin: SvnadminOptions,
+    /// Number of threads used to decompress dump files.
+    pub(super) decompress_threads: usize,
+}
+
+/// Branch and tag specs, along with their renames, that apply to a range
+/// of revisions.
+pub(crate) struct BranchLayout {
+    first_rev: u32,
+    last_rev: u32,
+    root_dir_spec: ContainerDirSpecNode,
+    rename_branches: BranchRenamer,
+    rename_tags: BranchRenamer,
 }
 
 enum DirSpecNode {
@@ -43,9 +117,43 @@ enum DirSpecNode {
     Container(ContainerDirSpecNode),
 }
 
+#[derive(Default)]
 struct ContainerDirSpecNode {
-    wildcard: Option<bool>,
+    /// Spec of the directories not found in `subdirs` (`*`).
+    wildcard: Option<Box<DirSpecNode>>,
+    /// Specs that apply after any number of directories (`**`).
+    recursive: Option<Box<ContainerDirSpecNode>>,
     subdirs: FHashMap<Vec<u8>, DirSpecNode>,
+    /// Whether this directory is excluded by a negated spec (`!`), so it
+    /// is not a branch even if another spec matches it.
+    excluded: bool,
+}
+
+impl ContainerDirSpecNode {
+    /// Returns whether there are branch or tag specs below this directory.
+    fn has_specs(&self) -> bool {
+        self.wildcard.as_deref().is_some_and(DirSpecNode::has_specs)
+            || self.recursive.as_deref().is_some_and(Self::has_specs)
+            || self.subdirs.values().any(DirSpecNode::has_specs)
+    }
+}
+
+impl DirSpecNode {
+    fn has_specs(&self) -> bool {
+        match self {
+            Self::Branch(_) => true,
+            Self::Container(container) => container.has_specs(),
+        }
+    }
+}
+
+/// Splits the component of `path` that starts at `start`, returning the
+/// end of the component and the start of the next one.
+fn next_component(path: &[u8], start: usize) -> (usize, usize) {
+    match path[start..].iter().position(|&c| c == b'/') {
+        Some(pos) => (start + pos, start + pos + 1),
+        None => (path.len(), path.len()),
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -59,19 +167,21 @@ pub(crate) struct BranchRenameAddError;
 
 pub(crate) struct PartialBranchAddError;
 
+#[derive(Debug)]
+pub(crate) struct LayoutAddError;
+
 impl Options {
     pub(crate) fn new(init: InitOptions) -> Self {
         Self {
-            root_dir_spec: ContainerDirSpecNode {
-                wildcard: None,
-                subdirs: FHashMap::default(),
-            },
-            rename_branches: BranchRenamer::new(),
+            default_layout: BranchLayout::new(0, u32::MAX),
+            layouts: Vec::new(),
             keep_deleted_branches: init.keep_deleted_branches,
             partial_branches: PartialBranchSet::new(),
-            rename_tags: BranchRenamer::new(),
             keep_deleted_tags: init.keep_deleted_tags,
             partial_tags: PartialBranchSet::new(),
+            detect_branch_renames: init.detect_branch_renames,
+            rename_aliases: init.rename_aliases,
+            rename_log: init.rename_log,
             head_path: init.head_path,
             unbranched_name: init.unbranched_name,
             enable_merges: init.enable_merges,
@@ -80,11 +190,39 @@ impl Options {
             ignore_merges_at: FHashMap::default(),
             generate_gitignore: init.generate_gitignore,
             delete_files: init.delete_files,
+            exclude_paths: init.exclude_paths,
+            subdirectory: init.subdirectory,
+            path_rewrites: PathRewrites::default(),
             git_obj_cache_size: init.git_obj_cache_size,
             git_repack: init.git_repack,
+            incremental: init.incremental,
+            checkpoint_interval: init.checkpoint_interval,
+            resume: init.resume,
+            reuse_stage1: init.reuse_stage1,
+            first_rev: init.first_rev,
+            last_rev: init.last_rev,
+            allow_checksum_mismatch: init.allow_checksum_mismatch,
+            lenient: init.lenient,
+            dump_limits: init.dump_limits,
+            delta_limits: init.delta_limits,
+            stream_file_size: init.stream_file_size,
+            remote: init.remote,
+            svnadmin: init.svnadmin,
+            decompress_threads: init.decompress_threads,
         }
     }
 
+    /// Returns whether `path` or any of its ancestors matches
+    /// `exclude-paths`.
+    pub(super) fn is_path_excluded(&self, path: &[u8]) -> bool {
+        path.iter()
+            .enumerate()
+            .filter(|&(_, &c)| c == b'/')
+            .map(|(i, _)| &path[..i])
+            .chain(std::iter::once(path))
+            .any(|prefix| !prefix.is_empty() && self.exclude_paths.is_match(prefix))
+    }
+
     pub(crate) fn validate(&self) -> Result<(), ConvertError> {
         if self.head_path.is_empty() {
             if self.unbranched_name.is_none() {
@@ -94,62 +232,184 @@ impl Options {
                 Ok(())
             }
         } else {
-            match self.classify_dir(&self.head_path) {
-                DirClass::Branch(_, _, b"") => Ok(()),
-                _ => {
-                    tracing::error!(
-                        "head path \"{}\" is not a possible branch path",
-                        self.head_path.escape_ascii(),
-                    );
-                    Err(ConvertError)
-                }
+            let is_branch = std::iter::once(&self.default_layout)
+                .chain(self.layouts.iter())
+                .any(|layout| {
+                    matches!(
+                        layout.classify_dir(&self.head_path),
+                        DirClass::Branch(_, _, b""),
+                    )
+                });
+            if is_branch {
+                Ok(())
+            } else {
+                tracing::error!(
+                    "head path \"{}\" is not a possible branch path",
+                    self.head_path.escape_ascii(),
+                );
+                Err(ConvertError)
             }
         }
     }
 
+    /// Adds a rule that moves paths inside the trees of branches and tags.
+    pub(crate) fn add_path_rewrite(
+        &mut self,
+        from: &[u8],
+        to: &[u8],
+    ) -> Result<(), PathRewriteAddError> {
+        self.path_rewrites.add(from, to)
+    }
+
+    pub(crate) fn add_partial_branch(&mut self, name: &[u8]) -> Result<(), PartialBranchAddError> {
+        self.partial_branches.add(name)
+    }
+
+    pub(crate) fn add_partial_tag(&mut self, name: &[u8]) -> Result<(), PartialBranchAddError> {
+        self.partial_tags.add(name)
+    }
+
+    /// Returns the layout used for the default branch and tag specs.
+    pub(crate) fn default_layout_mut(&mut self) -> &mut BranchLayout {
+        &mut self.default_layout
+    }
+
+    /// Adds a layout whose specs replace the default ones from `first_rev`
+    /// to `last_rev` (or the last revision if `None`). Fails if the range
+    /// is empty or overlaps with another layout.
+    pub(crate) fn add_layout(
+        &mut self,
+        first_rev: u32,
+        last_rev: Option<u32>,
+    ) -> Result<&mut BranchLayout, LayoutAddError> {
+        let last_rev = last_rev.unwrap_or(u32::MAX);
+        if first_rev > last_rev
+            || self
+                .layouts
+                .iter()
+                .any(|layout| first_rev <= layout.last_rev && last_rev >= layout.first_rev)
+        {
+            return Err(LayoutAddError);
+        }
+        self.layouts.push(BranchLayout::new(first_rev, last_rev));
+        Ok(self.layouts.last_mut().unwrap())
+    }
+
+    fn layout_at(&self, svn_rev: u32) -> &BranchLayout {
+        self.layouts
+            .iter()
+            .find(|layout| (layout.first_rev..=layout.last_rev).contains(&svn_rev))
+            .unwrap_or(&self.default_layout)
+    }
+
+    /// Classifies a directory according to the branch and tag specs that
+    /// apply to `svn_rev`.
+    pub(super) fn classify_dir<'a>(&self, path: &'a [u8], svn_rev: u32) -> DirClass<'a> {
+        self.layout_at(svn_rev).classify_dir(path)
+    }
+
+    /// Returns the branch or tag renames that apply to `svn_rev`.
+    pub(super) fn branch_renamer(&self, is_tag: bool, svn_rev: u32) -> &BranchRenamer {
+        let layout = self.layout_at(svn_rev);
+        if is_tag {
+            &layout.rename_tags
+        } else {
+            &layout.rename_branches
+        }
+    }
+
+    pub(crate) fn check_partial_branch(&self, branch_path: &[u8], is_tag: bool) -> bool {
+        if is_tag {
+            self.partial_tags.check(branch_path)
+        } else {
+            self.partial_branches.check(branch_path)
+        }
+    }
+
+    pub(crate) fn add_ignored_merge_at(&mut self, path: &[u8], rev: u32) {
+        self.ignore_merges_at
+            .entry(rev)
+            .or_default()
+            .insert(path.to_vec());
+    }
+}
+
+impl BranchLayout {
+    fn new(first_rev: u32, last_rev: u32) -> Self {
+        Self {
+            first_rev,
+            last_rev,
+            root_dir_spec: ContainerDirSpecNode::default(),
+            rename_branches: BranchRenamer::new(),
+            rename_tags: BranchRenamer::new(),
+        }
+    }
+
+    /// Adds a branch or tag spec. Each component of `path` is a directory
+    /// name, `*` (any directory) or, except the last one, `**` (any number
+    /// of directories). A `!` prefix makes a negated spec, which excludes
+    /// the matching directories from being branches or tags.
+    ///
+    /// On conflict with a previous spec, returns the path of that spec.
     pub(crate) fn add_branch_dir(
         &mut self,
         path: &[u8],
         is_tag: bool,
     ) -> Result<(), Option<Vec<u8>>> {
+        let (negated, path) = match path.strip_prefix(b"!") {
+            Some(path) => (true, path),
+            None => (false, path),
+        };
         if path == b"" || path.starts_with(b"/") || path.ends_with(b"/") {
             return Err(None);
         }
 
-        let mut current_path_len = 0;
-        let mut current_dir_node = &mut self.root_dir_spec;
         let mut components = path.split(|&c| c == b'/');
         let last_component = components.next_back().unwrap();
-        for component in components {
-            if component == b"*" {
+        if last_component == b"**" || (negated && last_component == b"*") {
+            return Err(None);
+        }
+        let mut prev_component: &[u8] = b"";
+        for component in path.split(|&c| c == b'/') {
+            if component.is_empty()
+                || (component.contains(&b'*') && component != b"*" && component != b"**")
+                || (component == b"**" && prev_component == b"**")
+            {
                 return Err(None);
             }
+            prev_component = component;
+        }
+
+        let mut current_path_len = 0;
+        let mut current_dir_node = &mut self.root_dir_spec;
+        for component in components {
             if current_path_len != 0 {
                 // count '/'
                 current_path_len += 1;
             }
             current_path_len += component.len();
 
-            match current_dir_node.subdirs.entry(component.to_vec()) {
-                std::collections::hash_map::Entry::Occupied(entry) => match entry.into_mut() {
+            let next_node = if component == b"**" {
+                current_dir_node.recursive.get_or_insert_default().as_mut()
+            } else {
+                let node = if component == b"*" {
+                    current_dir_node.wildcard.get_or_insert_with(|| {
+                        Box::new(DirSpecNode::Container(ContainerDirSpecNode::default()))
+                    })
+                } else {
+                    current_dir_node
+                        .subdirs
+                        .entry(component.to_vec())
+                        .or_insert_with(|| DirSpecNode::Container(ContainerDirSpecNode::default()))
+                };
+                match node {
                     DirSpecNode::Branch(_) => {
                         return Err(Some(path[..current_path_len].to_vec()));
                     }
-                    DirSpecNode::Container(container) => {
-                        current_dir_node = container;
-                    }
-                },
-                std::collections::hash_map::Entry::Vacant(entry) => {
-                    let new_node = entry.insert(DirSpecNode::Container(ContainerDirSpecNode {
-                        wildcard: None,
-                        subdirs: FHashMap::default(),
-                    }));
-                    let DirSpecNode::Container(container) = new_node else {
-                        unreachable!();
-                    };
-                    current_dir_node = container;
+                    DirSpecNode::Container(container) => container,
                 }
-            }
+            };
+            current_dir_node = next_node;
         }
 
         if last_component == b"*" {
@@ -157,7 +417,18 @@ impl Options {
                 return Err(Some(path.to_vec()));
             }
 
-            current_dir_node.wildcard = Some(is_tag);
+            current_dir_node.wildcard = Some(Box::new(DirSpecNode::Branch(is_tag)));
+        } else if negated {
+            let node = current_dir_node
+                .subdirs
+                .entry(last_component.to_vec())
+                .or_insert_with(|| DirSpecNode::Container(ContainerDirSpecNode::default()));
+            match node {
+                DirSpecNode::Container(container) if !container.excluded => {
+                    container.excluded = true;
+                }
+                _ => return Err(Some(path.to_vec())),
+            }
         } else {
             match current_dir_node.subdirs.entry(last_component.to_vec()) {
                 std::collections::hash_map::Entry::Occupied(_) => {
@@ -188,77 +459,129 @@ impl Options {
         self.rename_tags.add(from, to)
     }
 
-    pub(crate) fn add_partial_branch(&mut self, name: &[u8]) -> Result<(), PartialBranchAddError> {
-        self.partial_branches.add(name)
+    /// Classifies a directory according to the branch and tag specs.
+    ///
+    /// A directory name in a spec takes precedence over a `*` branch or tag
+    /// at the same level, while the specs below a `*` or `**` also apply to
+    /// the directories named in other specs. Specs are tried in that order
+    /// (name, `*`, `**`) and the first branch found wins over any branch
+    /// parent, so branches cannot be nested.
+    fn classify_dir<'a>(&self, path: &'a [u8]) -> DirClass<'a> {
+        self.classify_dir_in(&self.root_dir_spec, path, 0)
     }
 
-    pub(crate) fn add_partial_tag(&mut self, name: &[u8]) -> Result<(), PartialBranchAddError> {
-        self.partial_tags.add(name)
-    }
+    fn classify_dir_in<'a>(
+        &self,
+        dir_node: &ContainerDirSpecNode,
+        path: &'a [u8],
+        start: usize,
+    ) -> DirClass<'a> {
+        if start == path.len() {
+            return if dir_node.has_specs() {
+                DirClass::BranchParent
+            } else {
+                DirClass::Unbranched
+            };
+        }
 
-    pub(super) fn classify_dir<'a>(&self, path: &'a [u8]) -> DirClass<'a> {
-        let mut current_path_len = 0;
-        let mut current_dir_node = &self.root_dir_spec;
-        if path != b"" {
-            for component in path.split(|&c| c == b'/') {
-                if current_path_len != 0 {
-                    // count '/'
-                    current_path_len += 1;
+        let (end, next_start) = next_component(path, start);
+        let component = &path[start..end];
+        let branch = |is_tag| {
+            if self.is_dir_excluded(&self.root_dir_spec, &path[..end], 0) {
+                DirClass::Unbranched
+            } else {
+                DirClass::Branch(&path[..end], is_tag, &path[next_start..])
+            }
+        };
+
+        let mut shadows_wildcard = false;
+        let mut is_parent = false;
+        let mut check = |class: DirClass<'a>| match class {
+            DirClass::Branch(..) => Some(class),
+            DirClass::BranchParent => {
+                is_parent = true;
+                None
+            }
+            DirClass::Unbranched => None,
+        };
+
+        if let Some(subdir_spec) = dir_node.subdirs.get(component) {
+            let class = match subdir_spec {
+                DirSpecNode::Branch(is_tag) => branch(*is_tag),
+                DirSpecNode::Container(container) => {
+                    shadows_wildcard = true;
+                    self.classify_dir_in(container, path, next_start)
                 }
-                current_path_len += component.len();
-
-                if let Some(subdir_spec) = current_dir_node.subdirs.get(component) {
-                    match subdir_spec {
-                        DirSpecNode::Branch(is_tag) => {
-                            return DirClass::Branch(
-                                &path[..current_path_len],
-                                *is_tag,
-                                if current_path_len == path.len() {
-                                    b""
-                                } else {
-                                    &path[(current_path_len + 1)..]
-                                },
-                            );
-                        }
-                        DirSpecNode::Container(container) => {
-                            current_dir_node = container;
-                        }
-                    }
-                } else if let Some(is_tag) = current_dir_node.wildcard {
-                    return DirClass::Branch(
-                        &path[..current_path_len],
-                        is_tag,
-                        if current_path_len == path.len() {
-                            b""
-                        } else {
-                            &path[(current_path_len + 1)..]
-                        },
-                    );
-                } else {
-                    return DirClass::Unbranched;
+            };
+            if let Some(class) = check(class) {
+                return class;
+            }
+        }
+
+        if let Some(ref wildcard_spec) = dir_node.wildcard {
+            let class = match **wildcard_spec {
+                DirSpecNode::Branch(_) if shadows_wildcard => DirClass::Unbranched,
+                DirSpecNode::Branch(is_tag) => branch(is_tag),
+                DirSpecNode::Container(ref container) => {
+                    self.classify_dir_in(container, path, next_start)
                 }
+            };
+            if let Some(class) = check(class) {
+                return class;
             }
         }
-        if current_dir_node.wildcard.is_some() || !current_dir_node.subdirs.is_empty() {
+
+        if let Some(ref recursive_spec) = dir_node.recursive {
+            let mut skip_start = start;
+            loop {
+                if let Some(class) = check(self.classify_dir_in(recursive_spec, path, skip_start)) {
+                    return class;
+                }
+                if skip_start == path.len() {
+                    break;
+                }
+                skip_start = next_component(path, skip_start).1;
+            }
+        }
+
+        if is_parent {
             DirClass::BranchParent
         } else {
             DirClass::Unbranched
         }
     }
 
-    pub(crate) fn check_partial_branch(&self, branch_path: &[u8], is_tag: bool) -> bool {
-        if is_tag {
-            self.partial_tags.check(branch_path)
-        } else {
-            self.partial_branches.check(branch_path)
+    /// Returns whether `path` is matched by a negated spec.
+    fn is_dir_excluded(&self, dir_node: &ContainerDirSpecNode, path: &[u8], start: usize) -> bool {
+        if start == path.len() {
+            return dir_node.excluded;
         }
-    }
 
-    pub(crate) fn add_ignored_merge_at(&mut self, path: &[u8], rev: u32) {
-        self.ignore_merges_at
-            .entry(rev)
-            .or_default()
-            .insert(path.to_vec());
+        let (end, next_start) = next_component(path, start);
+        let component = &path[start..end];
+        if let Some(DirSpecNode::Container(container)) = dir_node.subdirs.get(component) {
+            if self.is_dir_excluded(container, path, next_start) {
+                return true;
+            }
+        }
+        if let Some(DirSpecNode::Container(container)) = dir_node.wildcard.as_deref() {
+            if self.is_dir_excluded(container, path, next_start) {
+                return true;
+            }
+        }
+        if let Some(ref recursive_spec) = dir_node.recursive {
+            let mut skip_start = start;
+            loop {
+                if self.is_dir_excluded(recursive_spec, path, skip_start) {
+                    return true;
+                }
+                if skip_start == path.len() {
+                    break;
+                }
+                skip_start = next_component(path, skip_start).1;
+            }
+        }
+        false
     }
 }
 
@@ -359,13 +682,18 @@ impl PartialBranchSet {
 
 #[cfg(test)]
 mod tests {
-    use super::{DirClass, InitOptions, Options};
+    use super::{BranchLayout, DirClass, InitOptions, Options};
     use crate::path_pattern::PathPattern;
+    use crate::svn;
+    use crate::svn::source::{RemoteOptions, SvnadminOptions};
 
     fn default_init() -> InitOptions {
         InitOptions {
             keep_deleted_branches: true,
             keep_deleted_tags: true,
+            detect_branch_renames: false,
+            rename_aliases: false,
+            rename_log: None,
             head_path: b"trunk".to_vec(),
             unbranched_name: Some("unbranched".into()),
             enable_merges: false,
@@ -373,88 +701,276 @@ mod tests {
             avoid_fully_reverted_merges: false,
             generate_gitignore: false,
             delete_files: PathPattern::default(),
+            exclude_paths: PathPattern::default(),
+            subdirectory: Vec::new(),
             git_obj_cache_size: 250_000_000,
             git_repack: false,
+            incremental: false,
+            checkpoint_interval: None,
+            resume: false,
+            reuse_stage1: None,
+            first_rev: None,
+            last_rev: None,
+            allow_checksum_mismatch: false,
+            lenient: false,
+            dump_limits: svn::dump::Limits::default(),
+            delta_limits: svn::diff::Limits::default(),
+            stream_file_size: 64 * 1024 * 1024,
+            remote: RemoteOptions::default(),
+            svnadmin: SvnadminOptions::default(),
+            decompress_threads: 1,
         }
     }
 
     #[test]
     fn test_add_branch_dir() {
-        let mut options = Options::new(default_init());
-        options.add_branch_dir(b"*", false).unwrap();
-        options.add_branch_dir(b"a", false).unwrap();
-        options.add_branch_dir(b"b", false).unwrap();
-        options.add_branch_dir(b"c/*", false).unwrap();
-        options.add_branch_dir(b"c/a/*", false).unwrap();
-        options.add_branch_dir(b"c/b", false).unwrap();
-        options.add_branch_dir(b"c/c/a", false).unwrap();
-        assert_eq!(
-            options.add_branch_dir(b"a", false),
-            Err(Some(b"a".to_vec())),
-        );
+        let mut layout = BranchLayout::new(0, u32::MAX);
+        layout.add_branch_dir(b"*", false).unwrap();
+        layout.add_branch_dir(b"a", false).unwrap();
+        layout.add_branch_dir(b"b", false).unwrap();
+        layout.add_branch_dir(b"c/*", false).unwrap();
+        layout.add_branch_dir(b"c/a/*", false).unwrap();
+        layout.add_branch_dir(b"c/b", false).unwrap();
+        layout.add_branch_dir(b"c/c/a", false).unwrap();
+        assert_eq!(layout.add_branch_dir(b"a", false), Err(Some(b"a".to_vec())),);
         assert_eq!(
-            options.add_branch_dir(b"a/*", false),
+            layout.add_branch_dir(b"a/*", false),
             Err(Some(b"a".to_vec())),
         );
         assert_eq!(
-            options.add_branch_dir(b"a/b", false),
+            layout.add_branch_dir(b"a/b", false),
             Err(Some(b"a".to_vec())),
         );
+        assert_eq!(layout.add_branch_dir(b"c", false), Err(Some(b"c".to_vec())),);
         assert_eq!(
-            options.add_branch_dir(b"c", false),
-            Err(Some(b"c".to_vec())),
-        );
-        assert_eq!(
-            options.add_branch_dir(b"c/a", false),
+            layout.add_branch_dir(b"c/a", false),
             Err(Some(b"c/a".to_vec())),
         );
         assert_eq!(
-            options.add_branch_dir(b"c/a/*", false),
+            layout.add_branch_dir(b"c/a/*", false),
             Err(Some(b"c/a/*".to_vec())),
         );
     }
 
     #[test]
     fn test_classify_dir() {
-        let mut options = Options::new(default_init());
-        options.add_branch_dir(b"a", false).unwrap();
-        options.add_branch_dir(b"b/*", false).unwrap();
-        options.add_branch_dir(b"b/a/*", false).unwrap();
-        options.add_branch_dir(b"b/b", false).unwrap();
-        options.add_branch_dir(b"b/c/a", false).unwrap();
+        let mut layout = BranchLayout::new(0, u32::MAX);
+        layout.add_branch_dir(b"a", false).unwrap();
+        layout.add_branch_dir(b"b/*", false).unwrap();
+        layout.add_branch_dir(b"b/a/*", false).unwrap();
+        layout.add_branch_dir(b"b/b", false).unwrap();
+        layout.add_branch_dir(b"b/c/a", false).unwrap();
 
         assert_eq!(
-            options.classify_dir(b"a"),
+            layout.classify_dir(b"a"),
             DirClass::Branch(b"a", false, b""),
         );
         assert_eq!(
-            options.classify_dir(b"a/1"),
+            layout.classify_dir(b"a/1"),
             DirClass::Branch(b"a", false, b"1"),
         );
         assert_eq!(
-            options.classify_dir(b"a/1/2"),
+            layout.classify_dir(b"a/1/2"),
             DirClass::Branch(b"a", false, b"1/2"),
         );
-        assert_eq!(options.classify_dir(b"b"), DirClass::BranchParent);
-        assert_eq!(options.classify_dir(b"b/a"), DirClass::BranchParent);
+        assert_eq!(layout.classify_dir(b"b"), DirClass::BranchParent);
+        assert_eq!(layout.classify_dir(b"b/a"), DirClass::BranchParent);
         assert_eq!(
-            options.classify_dir(b"b/a/a"),
+            layout.classify_dir(b"b/a/a"),
             DirClass::Branch(b"b/a/a", false, b""),
         );
         assert_eq!(
-            options.classify_dir(b"b/a/a/1"),
+            layout.classify_dir(b"b/a/a/1"),
             DirClass::Branch(b"b/a/a", false, b"1"),
         );
         assert_eq!(
-            options.classify_dir(b"b/a/a/1/2"),
+            layout.classify_dir(b"b/a/a/1/2"),
             DirClass::Branch(b"b/a/a", false, b"1/2"),
         );
-        assert_eq!(options.classify_dir(b"b/c"), DirClass::BranchParent);
+        assert_eq!(layout.classify_dir(b"b/c"), DirClass::BranchParent);
         assert_eq!(
-            options.classify_dir(b"b/c/a"),
+            layout.classify_dir(b"b/c/a"),
             DirClass::Branch(b"b/c/a", false, b""),
         );
-        assert_eq!(options.classify_dir(b"b/c/b"), DirClass::Unbranched);
-        assert_eq!(options.classify_dir(b"c"), DirClass::Unbranched);
+        assert_eq!(layout.classify_dir(b"b/c/b"), DirClass::Unbranched);
+        assert_eq!(layout.classify_dir(b"c"), DirClass::Unbranched);
+    }
+
+    #[test]
+    fn test_add_branch_dir_wildcards() {
+        let mut layout = BranchLayout::new(0, u32::MAX);
+        layout.add_branch_dir(b"*/trunk", false).unwrap();
+        layout.add_branch_dir(b"*/branches/*", false).unwrap();
+        layout.add_branch_dir(b"projects/*/tags/*", true).unwrap();
+        layout.add_branch_dir(b"**/releases/*", true).unwrap();
+        layout
+            .add_branch_dir(b"!*/branches/sandbox", false)
+            .unwrap();
+        layout.add_branch_dir(b"!old/trunk", false).unwrap();
+        assert_eq!(
+            layout.add_branch_dir(b"*/trunk", false),
+            Err(Some(b"*/trunk".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"*/trunk/*", false),
+            Err(Some(b"*/trunk".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"*/branches/*", true),
+            Err(Some(b"*/branches/*".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"*/branches/*/x", false),
+            Err(Some(b"*/branches/*".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"!*/trunk", false),
+            Err(Some(b"*/trunk".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"!*/branches/sandbox", false),
+            Err(Some(b"*/branches/sandbox".to_vec())),
+        );
+        assert_eq!(
+            layout.add_branch_dir(b"*/branches/sandbox", false),
+            Err(Some(b"*/branches/sandbox".to_vec())),
+        );
+        assert_eq!(layout.add_branch_dir(b"a/**", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"**/**/a", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"a/b*", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"a*/b", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"a//b", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"!a/*", false), Err(None));
+        assert_eq!(layout.add_branch_dir(b"!", false), Err(None));
+    }
+
+    #[test]
+    fn test_classify_dir_wildcards() {
+        let mut layout = BranchLayout::new(0, u32::MAX);
+        layout.add_branch_dir(b"*/trunk", false).unwrap();
+        layout.add_branch_dir(b"*/branches/*", false).unwrap();
+        layout.add_branch_dir(b"projects/*/tags/*", true).unwrap();
+        layout
+            .add_branch_dir(b"!*/branches/sandbox", false)
+            .unwrap();
+        layout.add_branch_dir(b"!old/trunk", false).unwrap();
+
+        assert_eq!(layout.classify_dir(b""), DirClass::BranchParent);
+        assert_eq!(layout.classify_dir(b"a"), DirClass::BranchParent);
+        assert_eq!(
+            layout.classify_dir(b"a/trunk"),
+            DirClass::Branch(b"a/trunk", false, b""),
+        );
+        assert_eq!(
+            layout.classify_dir(b"a/trunk/x/y"),
+            DirClass::Branch(b"a/trunk", false, b"x/y"),
+        );
+        assert_eq!(layout.classify_dir(b"a/branches"), DirClass::BranchParent);
+        assert_eq!(
+            layout.classify_dir(b"a/branches/b1/x"),
+            DirClass::Branch(b"a/branches/b1", false, b"x"),
+        );
+        assert_eq!(
+            layout.classify_dir(b"a/branches/sandbox"),
+            DirClass::Unbranched,
+        );
+        assert_eq!(
+            layout.classify_dir(b"a/branches/sandbox/x"),
+            DirClass::Unbranched,
+        );
+        assert_eq!(layout.classify_dir(b"a/other"), DirClass::Unbranched);
+
+        // The specs below "*" also apply to "projects".
+        assert_eq!(
+            layout.classify_dir(b"projects/trunk"),
+            DirClass::Branch(b"projects/trunk", false, b""),
+        );
+        assert_eq!(
+            layout.classify_dir(b"projects/p/tags/v1"),
+            DirClass::Branch(b"projects/p/tags/v1", true, b""),
+        );
+        assert_eq!(layout.classify_dir(b"a/p/tags/v1"), DirClass::Unbranched);
+
+        assert_eq!(layout.classify_dir(b"old/trunk"), DirClass::Unbranched);
+        assert_eq!(layout.classify_dir(b"old/trunk/x"), DirClass::Unbranched);
+        assert_eq!(
+            layout.classify_dir(b"old/branches/b1"),
+            DirClass::Branch(b"old/branches/b1", false, b""),
+        );
+
+        let mut layout = BranchLayout::new(0, u32::MAX);
+        layout.add_branch_dir(b"*/trunk", false).unwrap();
+        layout.add_branch_dir(b"**/releases/*", true).unwrap();
+
+        assert_eq!(layout.classify_dir(b"a/b/c"), DirClass::BranchParent);
+        assert_eq!(
+            layout.classify_dir(b"releases/r1"),
+            DirClass::Branch(b"releases/r1", true, b""),
+        );
+        assert_eq!(
+            layout.classify_dir(b"a/b/c/releases/r1/x"),
+            DirClass::Branch(b"a/b/c/releases/r1", true, b"x"),
+        );
+        // Branches cannot be nested.
+        assert_eq!(
+            layout.classify_dir(b"a/trunk/releases/r1"),
+            DirClass::Branch(b"a/trunk", false, b"releases/r1"),
+        );
+    }
+
+    #[test]
+    fn test_classify_dir_layouts() {
+        let mut options = Options::new(default_init());
+        let layout = options.default_layout_mut();
+        layout.add_branch_dir(b"trunk", false).unwrap();
+        layout.add_branch_dir(b"branches/*", false).unwrap();
+        let layout = options.add_layout(10, Some(19)).unwrap();
+        layout.add_branch_dir(b"product/trunk", false).unwrap();
+        let layout = options.add_layout(20, None).unwrap();
+        layout.add_branch_dir(b"product/branches/*", false).unwrap();
+        assert!(options.add_layout(15, Some(25)).is_err());
+        assert!(options.add_layout(5, Some(10)).is_err());
+        assert!(options.add_layout(9, Some(8)).is_err());
+        options.add_layout(0, Some(0)).unwrap();
+
+        assert_eq!(options.classify_dir(b"trunk", 0), DirClass::Unbranched);
+        assert_eq!(
+            options.classify_dir(b"trunk", 9),
+            DirClass::Branch(b"trunk", false, b""),
+        );
+        assert_eq!(
+            options.classify_dir(b"branches/b1", 9),
+            DirClass::Branch(b"branches/b1", false, b""),
+        );
+        assert_eq!(options.classify_dir(b"trunk", 10), DirClass::Unbranched);
+        assert_eq!(options.classify_dir(b"product", 10), DirClass::BranchParent);
+        assert_eq!(
+            options.classify_dir(b"product/trunk", 19),
+            DirClass::Branch(b"product/trunk", false, b""),
+        );
+        assert_eq!(
+            options.classify_dir(b"product/trunk", 20),
+            DirClass::Unbranched,
+        );
+        assert_eq!(
+            options.classify_dir(b"product/branches/b1", 1000),
+            DirClass::Branch(b"product/branches/b1", false, b""),
+        );
+    }
+
+    #[test]
+    fn test_is_path_excluded() {
+        let mut init = default_init();
+        init.exclude_paths = PathPattern::new(["trunk/big", "branches/*/vendor"], true).unwrap();
+        let options = Options::new(init);
+
+        assert!(options.is_path_excluded(b"trunk/big"));
+        assert!(options.is_path_excluded(b"trunk/big/a"));
+        assert!(options.is_path_excluded(b"branches/b1/vendor/a/b"));
+        assert!(!options.is_path_excluded(b""));
+        assert!(!options.is_path_excluded(b"trunk"));
+        assert!(!options.is_path_excluded(b"trunk/bigger"));
+        assert!(!options.is_path_excluded(b"branches/b1"));
+        assert!(!options.is_path_excluded(b"branches/b1/vendors"));
     }
 }
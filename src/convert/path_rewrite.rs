@@ -0,0 +1,290 @@
+use gix_object::tree::EntryKind;
+
+use super::{ConvertError, git_wrap};
+use crate::FHashMap;
+
+/// Rules that move paths inside the Git trees of branches and tags.
+///
+/// Each rule moves the paths that match its `from` pattern, where `*`
+/// matches any part of a path component, to its `to` template, where each
+/// `*` is replaced by what the corresponding `*` of `from` matched. Rules
+/// are applied in order, each one to the tree produced by the previous one.
+#[derive(Clone, Default)]
+pub(super) struct PathRewrites {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+struct Rule {
+    from: Vec<u8>,
+    from_components: Vec<Vec<u8>>,
+    to: Vec<u8>,
+}
+
+pub(crate) struct PathRewriteAddError;
+
+struct Match {
+    path: Vec<u8>,
+    kind: EntryKind,
+    oid: gix_hash::ObjectId,
+    target: Vec<u8>,
+}
+
+impl PathRewrites {
+    pub(super) fn add(&mut self, from: &[u8], to: &[u8]) -> Result<(), PathRewriteAddError> {
+        let is_valid_path = |path: &[u8]| {
+            !path.is_empty()
+                && path
+                    .split(|&c| c == b'/')
+                    .all(|component| !matches!(component, b"" | b"." | b".."))
+        };
+        let num_wildcards = |path: &[u8]| path.iter().filter(|&&c| c == b'*').count();
+
+        if !is_valid_path(from)
+            || !is_valid_path(to)
+            || from.windows(2).any(|w| w == b"**")
+            || num_wildcards(from) != num_wildcards(to)
+        {
+            return Err(PathRewriteAddError);
+        }
+
+        self.rules.push(Rule {
+            from: from.to_vec(),
+            from_components: from.split(|&c| c == b'/').map(<[u8]>::to_vec).collect(),
+            to: to.to_vec(),
+        });
+        Ok(())
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the rules to the tree `tree_oid` of `branch_path`.
+    ///
+    /// Moving a path to one that already exists, or moving two paths to the
+    /// same one, is an error.
+    pub(super) fn apply(
+        &self,
+        git_import: &mut git_wrap::Importer,
+        mut tree_oid: gix_hash::ObjectId,
+        branch_path: &[u8],
+        svn_rev: u32,
+    ) -> Result<gix_hash::ObjectId, ConvertError> {
+        for rule in self.rules.iter() {
+            let mut matches = Vec::new();
+            rule.collect_matches(
+                git_import,
+                tree_oid,
+                0,
+                &mut Vec::new(),
+                &mut Vec::new(),
+                &mut matches,
+            )?;
+            if matches.is_empty() {
+                continue;
+            }
+
+            let mut change_set = crate::git::ChangeSet::new(Some(tree_oid));
+            for m in matches.iter() {
+                change_set.remove(&m.path);
+            }
+            let removed_tree_oid = apply_change_set(git_import, &change_set)?;
+
+            let collision = |m: &Match, existing: &[u8]| {
+                tracing::error!(
+                    "SVN revision {svn_rev}: rewriting \"{}\" to \"{}\" in \"{}\" (rule \"{}\") collides with \"{}\"",
+                    m.path.escape_ascii(),
+                    m.target.escape_ascii(),
+                    branch_path.escape_ascii(),
+                    rule.from.escape_ascii(),
+                    existing.escape_ascii(),
+                );
+                ConvertError
+            };
+
+            let mut targets = FHashMap::<&[u8], &[u8]>::default();
+            let mut change_set = crate::git::ChangeSet::new(Some(removed_tree_oid));
+            for m in matches.iter() {
+                for (target, path) in targets.iter() {
+                    if is_same_or_nested(target, &m.target) {
+                        return Err(collision(m, path));
+                    }
+                }
+                if let Some(existing) = find_existing(git_import, removed_tree_oid, &m.target)? {
+                    return Err(collision(m, &existing));
+                }
+                targets.insert(&m.target, &m.path);
+                change_set.change(&m.target, m.kind, m.oid);
+            }
+            tree_oid = apply_change_set(git_import, &change_set)?;
+        }
+
+        Ok(tree_oid)
+    }
+}
+
+impl Rule {
+    fn collect_matches(
+        &self,
+        git_import: &git_wrap::Importer,
+        tree_oid: gix_hash::ObjectId,
+        component_i: usize,
+        path: &mut Vec<u8>,
+        captures: &mut Vec<Vec<u8>>,
+        matches: &mut Vec<Match>,
+    ) -> Result<(), ConvertError> {
+        let is_last = component_i == self.from_components.len() - 1;
+        for (name, kind, oid) in git_import.ls_tree(tree_oid)? {
+            let num_captures = captures.len();
+            if !match_component(&self.from_components[component_i], &name, captures) {
+                continue;
+            }
+
+            let path_len = path.len();
+            if !path.is_empty() {
+                path.push(b'/');
+            }
+            path.extend(&name);
+
+            if is_last {
+                matches.push(Match {
+                    path: path.clone(),
+                    kind,
+                    oid,
+                    target: expand_template(&self.to, captures),
+                });
+            } else if kind == EntryKind::Tree {
+                self.collect_matches(git_import, oid, component_i + 1, path, captures, matches)?;
+            }
+
+            path.truncate(path_len);
+            captures.truncate(num_captures);
+        }
+        Ok(())
+    }
+}
+
+fn apply_change_set(
+    git_import: &mut git_wrap::Importer,
+    change_set: &crate::git::ChangeSet,
+) -> Result<gix_hash::ObjectId, ConvertError> {
+    Ok(change_set
+        .apply(git_import.inner())
+        .map_err(|e| {
+            tracing::error!("failed to apply git change set: {e}");
+            ConvertError
+        })?
+        .unwrap_or_else(|| git_import.empty_tree_oid()))
+}
+
+/// Returns the path that prevents `path` from being added to `tree_oid`,
+/// which is either `path` itself or a parent of it that is not a directory.
+fn find_existing(
+    git_import: &git_wrap::Importer,
+    tree_oid: gix_hash::ObjectId,
+    path: &[u8],
+) -> Result<Option<Vec<u8>>, ConvertError> {
+    let mut end = 0;
+    while end < path.len() {
+        end = path[end + 1..]
+            .iter()
+            .position(|&c| c == b'/')
+            .map_or(path.len(), |pos| end + 1 + pos);
+        match git_import.ls(tree_oid, &path[..end])? {
+            None => return Ok(None),
+            Some((EntryKind::Tree, _)) if end != path.len() => {}
+            Some(_) => return Ok(Some(path[..end].to_vec())),
+        }
+    }
+    Ok(None)
+}
+
+fn is_same_or_nested(a: &[u8], b: &[u8]) -> bool {
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    longer
+        .strip_prefix(shorter)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"))
+}
+
+/// Matches `name` against a path component `pattern`, pushing what each `*`
+/// matched to `captures`. The first match found is used.
+fn match_component(pattern: &[u8], name: &[u8], captures: &mut Vec<Vec<u8>>) -> bool {
+    let Some(star) = pattern.iter().position(|&c| c == b'*') else {
+        return pattern == name;
+    };
+    let (prefix, rest_pattern) = (&pattern[..star], &pattern[star + 1..]);
+    let Some(rest_name) = name.strip_prefix(prefix) else {
+        return false;
+    };
+
+    let num_captures = captures.len();
+    for capture_len in 0..=rest_name.len() {
+        captures.push(rest_name[..capture_len].to_vec());
+        if match_component(rest_pattern, &rest_name[capture_len..], captures) {
+            return true;
+        }
+        captures.truncate(num_captures);
+    }
+    false
+}
+
+fn expand_template(template: &[u8], captures: &[Vec<u8>]) -> Vec<u8> {
+    let mut captures = captures.iter();
+    let mut result = Vec::with_capacity(template.len());
+    for &c in template.iter() {
+        if c == b'*' {
+            result.extend(captures.next().unwrap());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_template, is_same_or_nested, match_component};
+
+    fn captures(pattern: &str, name: &str) -> Option<Vec<String>> {
+        let mut captures = Vec::new();
+        match_component(pattern.as_bytes(), name.as_bytes(), &mut captures).then(|| {
+            captures
+                .into_iter()
+                .map(|c| String::from_utf8(c).unwrap())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_match_component() {
+        assert_eq!(captures("src", "src"), Some(vec![]));
+        assert_eq!(captures("src", "srcs"), None);
+        assert_eq!(captures("*", "java"), Some(vec!["java".into()]));
+        assert_eq!(captures("*.c", "main.c"), Some(vec!["main".into()]));
+        assert_eq!(captures("*.c", "main.h"), None);
+        assert_eq!(
+            captures("lib*-*", "libfoo-bar-1"),
+            Some(vec!["foo".into(), "bar-1".into()]),
+        );
+        assert_eq!(captures("lib*", "lib"), Some(vec!["".into()]));
+    }
+
+    #[test]
+    fn test_expand_template() {
+        assert_eq!(
+            expand_template(b"src/*/*.c", &[b"foo".to_vec(), b"main".to_vec()]),
+            b"src/foo/main.c",
+        );
+        assert_eq!(expand_template(b"java", &[]), b"java");
+    }
+
+    #[test]
+    fn test_is_same_or_nested() {
+        assert!(is_same_or_nested(b"a/b", b"a/b"));
+        assert!(is_same_or_nested(b"a", b"a/b"));
+        assert!(is_same_or_nested(b"a/b/c", b"a/b"));
+        assert!(!is_same_or_nested(b"a/b", b"a/bc"));
+        assert!(!is_same_or_nested(b"a", b"b"));
+    }
+}
@@ -2,12 +2,14 @@ use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use gix_object::tree::EntryKind;
+use sha1::Digest as _;
 
 use super::options::{DirClass, Options};
-use super::{ConvertError, git_wrap, meta, svn_tree, tree_builder};
+use super::{ConvertError, git_wrap, merge, meta, svn_tree, tree_builder};
 use crate::term_out::ProgressPrint;
 use crate::{FHashMap, FHashSet, svn};
 
+#[derive(Copy, Clone)]
 pub(super) enum Head {
     Branch(usize),
     Unbranched,
@@ -20,36 +22,146 @@ pub(super) struct Output {
     pub(super) branch_data: Vec<BranchData>,
     pub(super) branch_rev_data: Vec<BranchRevData>,
     pub(super) head_branch: Head,
+    /// Origin of the revisions when the output merges several source
+    /// repositories. Merged outputs are never saved.
+    pub(super) merge_origin: Option<merge::Origin>,
+    pub(super) state: State,
 }
 
+/// Internal state of the stage, needed to continue importing revisions
+/// on top of a previous [`Output`].
+pub(super) struct State {
+    /// UUID found in the SVN dump, which might differ from
+    /// [`Output::svn_uuid`] if the repository is a mirror.
+    pub(super) dump_uuid: Option<uuid::Uuid>,
+    pub(super) tree_map: FHashMap<gix_hash::ObjectId, Option<gix_hash::ObjectId>>,
+    pub(super) live_branches: FHashMap<Vec<u8>, usize>,
+    pub(super) path_to_branch: FHashMap<Vec<u8>, FHashMap<Vec<u8>, Vec<usize>>>,
+    pub(super) has_partial_branches: bool,
+    pub(super) branch_path_commits: FHashMap<Vec<u8>, Vec<(usize, usize)>>,
+}
+
+/// Periodic saving of the stage output, so an interrupted conversion
+/// can be resumed.
+pub(super) struct Checkpoints<'a> {
+    pub(super) interval: std::time::Duration,
+    pub(super) save:
+        &'a mut dyn FnMut(&mut git_wrap::Importer, &Output) -> Result<(), ConvertError>,
+}
+
+/// Runs the stage.
+///
+/// When `prev_output` is provided, revisions up to the last revision
+/// imported in it are skipped and the new revisions are imported on top of it.
+///
+/// Checkpoints are only taken between revisions, once the head branch has
+/// been found.
 pub(super) fn run(
     progress_print: &ProgressPrint,
     options: &Options,
-    src_path: &std::path::Path,
+    src_paths: &[std::path::PathBuf],
     src_is_remote: bool,
+    prev_output: Option<Output>,
+    checkpoints: Option<Checkpoints<'_>>,
     git_import: &mut git_wrap::Importer,
 ) -> Result<Output, ConvertError> {
+    let mut outputs = run_outputs(
+        progress_print,
+        &[options],
+        src_paths,
+        src_is_remote,
+        prev_output,
+        checkpoints,
+        git_import,
+    )?;
+    Ok(outputs.pop().unwrap())
+}
+
+/// Runs the stage once for several outputs of a split conversion, which
+/// differ in their branch and tag specs and head. The objects of all the
+/// outputs are imported into `git_import`.
+///
+/// The outputs are returned in the order of `options`. Only the first one
+/// has the root commits and the tree map, since they are the same for all
+/// of them.
+pub(super) fn run_split(
+    progress_print: &ProgressPrint,
+    options: &[&Options],
+    src_paths: &[std::path::PathBuf],
+    src_is_remote: bool,
+    git_import: &mut git_wrap::Importer,
+) -> Result<Vec<Output>, ConvertError> {
+    run_outputs(
+        progress_print,
+        options,
+        src_paths,
+        src_is_remote,
+        None,
+        None,
+        git_import,
+    )
+}
+
+fn run_outputs(
+    progress_print: &ProgressPrint,
+    options: &[&Options],
+    src_paths: &[std::path::PathBuf],
+    src_is_remote: bool,
+    prev_output: Option<Output>,
+    checkpoints: Option<Checkpoints<'_>>,
+    git_import: &mut git_wrap::Importer,
+) -> Result<Vec<Output>, ConvertError> {
     tracing::info!("Stage 1: import SVN repository");
 
-    let mut svn_dump_src = svn::source::DumpSource::open(src_path, src_is_remote).map_err(|e| {
+    let (&options, split_options) = options.split_first().expect("no output options");
+    assert!(split_options.is_empty() || (prev_output.is_none() && checkpoints.is_none()));
+
+    let resume_rev = prev_output
+        .as_ref()
+        .and_then(|prev| prev.root_rev_data.last())
+        .map(|rev_data| rev_data.svn_rev);
+    if let Some(resume_rev) = resume_rev {
+        tracing::info!("continuing after SVN revision {resume_rev}");
+    }
+
+    let mut svn_dump_src = svn::source::DumpSource::open(
+        src_paths,
+        src_is_remote,
+        &options.remote,
+        &options.svnadmin,
+        options.decompress_threads,
+        &options.dump_limits,
+        &options.delta_limits,
+        resume_rev,
+        options.last_rev,
+    )
+    .map_err(|e| {
         tracing::error!("failed to open SVN dump source: {e}");
         ConvertError
     })?;
 
-    let svn_dump_reader = svn::dump::DumpReader::new(svn_dump_src.stream()).map_err(|e| {
-        tracing::error!("failed to read SVN dump: {e}");
-        ConvertError
-    })?;
+    let svn_dump_reader =
+        svn::dump::DumpReader::new(svn_dump_src.stream(), options.dump_limits.clone()).map_err(
+            |e| {
+                tracing::error!("failed to read SVN dump: {e}");
+                ConvertError
+            },
+        )?;
 
-    let r = Stage {
+    let mut stage = Stage {
         progress_print,
         options,
         svn_dump_reader,
         git_import,
+        checkpoints,
+        last_checkpoint: (std::time::Instant::now(), 0),
+        resume_rev,
+        dump_uuid: None,
         svn_uuid: None,
         root_rev_data: Vec::new(),
         svn_rev_map: FHashMap::default(),
         tree_map: FHashMap::default(),
+        rewritten_trees: FHashMap::default(),
         unbranched_rev_data: Vec::new(),
         branch_data: Vec::new(),
         branch_rev_data: Vec::new(),
@@ -58,8 +170,16 @@ pub(super) fn run(
         path_to_branch: FHashMap::default(),
         has_partial_branches: false,
         branch_path_commits: FHashMap::default(),
+        split_outputs: split_options
+            .iter()
+            .map(|&options| OutputBranches::new(options))
+            .collect(),
+        repairs: Vec::new(),
+    };
+    if let Some(prev_output) = prev_output {
+        stage.load_prev_output(prev_output);
     }
-    .run()?;
+    let r = stage.run()?;
 
     if let Err(e) = svn_dump_src.close() {
         tracing::error!("SVN dump error: {e}");
@@ -130,15 +250,22 @@ enum UnbranchedNodeAction {
     ModDir(bool),
 }
 
-struct Stage<'a> {
+struct Stage<'a, 'c> {
     progress_print: &'a ProgressPrint,
     options: &'a Options,
     svn_dump_reader: svn::dump::DumpReader<'a>,
     git_import: &'a mut git_wrap::Importer,
+    checkpoints: Option<Checkpoints<'c>>,
+    // (time, number of imported revisions)
+    last_checkpoint: (std::time::Instant, usize),
+    resume_rev: Option<u32>,
+    dump_uuid: Option<uuid::Uuid>,
     svn_uuid: Option<uuid::Uuid>,
     root_rev_data: Vec<RootCommitData>,
     svn_rev_map: FHashMap<u32, usize>,
     tree_map: FHashMap<gix_hash::ObjectId, Option<gix_hash::ObjectId>>,
+    /// Branch trees with the path rewrites applied, by the original tree.
+    rewritten_trees: FHashMap<gix_hash::ObjectId, gix_hash::ObjectId>,
     unbranched_rev_data: Vec<UnbranchedRevData>,
     branch_data: Vec<BranchData>,
     branch_rev_data: Vec<BranchRevData>,
@@ -148,6 +275,57 @@ struct Stage<'a> {
     path_to_branch: FHashMap<Vec<u8>, FHashMap<Vec<u8>, Vec<usize>>>,
     has_partial_branches: bool,
     branch_path_commits: FHashMap<Vec<u8>, Vec<(usize, usize)>>,
+    /// Branches of the other outputs of a split conversion.
+    split_outputs: Vec<OutputBranches<'a>>,
+    repairs: Vec<Repair>,
+}
+
+/// Branches of an output of a split conversion. The branches of the
+/// output being imported are swapped into [`Stage`].
+struct OutputBranches<'a> {
+    options: &'a Options,
+    unbranched_rev_data: Vec<UnbranchedRevData>,
+    branch_data: Vec<BranchData>,
+    branch_rev_data: Vec<BranchRevData>,
+    head_branch: Option<Head>,
+    live_branches: FHashMap<Vec<u8>, usize>,
+    path_to_branch: FHashMap<Vec<u8>, FHashMap<Vec<u8>, Vec<usize>>>,
+    has_partial_branches: bool,
+    branch_path_commits: FHashMap<Vec<u8>, Vec<(usize, usize)>>,
+}
+
+impl<'a> OutputBranches<'a> {
+    fn new(options: &'a Options) -> Self {
+        Self {
+            options,
+            unbranched_rev_data: Vec::new(),
+            branch_data: Vec::new(),
+            branch_rev_data: Vec::new(),
+            head_branch: None,
+            live_branches: FHashMap::default(),
+            path_to_branch: FHashMap::default(),
+            has_partial_branches: false,
+            branch_path_commits: FHashMap::default(),
+        }
+    }
+}
+
+/// A recoverable anomaly of the SVN dump, repaired in lenient mode.
+struct Repair {
+    svn_rev: u32,
+    path: Option<Vec<u8>>,
+    problem: String,
+    recovery: &'static str,
+}
+
+impl std::fmt::Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SVN revision {}", self.svn_rev)?;
+        if let Some(ref path) = self.path {
+            write!(f, ", \"{}\"", path.escape_ascii())?;
+        }
+        write!(f, ": {}; {}", self.problem, self.recovery)
+    }
 }
 
 pub(super) struct RootCommitData {
@@ -171,6 +349,26 @@ pub(super) struct BranchData {
     pub(super) last_root_rev: usize,
     /// maps `root_commit <-> branch_commit`
     pub(super) rev_map: Vec<(usize, usize)>,
+    /// Previous paths of a branch moved by an `svn mv` of its root, along
+    /// with the root commit where it was moved away from each of them.
+    pub(super) prev_svn_paths: Vec<(usize, Vec<u8>)>,
+}
+
+impl BranchData {
+    /// Returns the path of the branch at `root_rev`.
+    pub(super) fn svn_path_at(&self, root_rev: usize) -> &[u8] {
+        self.prev_svn_paths
+            .iter()
+            .find(|&&(moved_at, _)| root_rev < moved_at)
+            .map_or(&self.svn_path, |(_, path)| path)
+    }
+
+    /// Returns the root commit where the branch got its current path.
+    pub(super) fn svn_path_root_rev(&self) -> usize {
+        self.prev_svn_paths
+            .last()
+            .map_or(self.first_root_rev, |&(moved_at, _)| moved_at)
+    }
 }
 
 pub(super) struct BranchRevData {
@@ -193,10 +391,111 @@ enum SpecialHandling {
     CustomReplace,
 }
 
-impl Stage<'_> {
-    fn run(mut self) -> Result<Output, ConvertError> {
+impl Stage<'_, '_> {
+    fn load_prev_output(&mut self, prev_output: Output) {
+        self.svn_rev_map = prev_output
+            .root_rev_data
+            .iter()
+            .enumerate()
+            .map(|(i, rev_data)| (rev_data.svn_rev, i))
+            .collect();
+        self.last_checkpoint.1 = prev_output.root_rev_data.len();
+
+        self.put_output(prev_output);
+    }
+
+    fn put_output(&mut self, output: Output) {
+        self.dump_uuid = output.state.dump_uuid;
+        self.svn_uuid = output.svn_uuid;
+        self.root_rev_data = output.root_rev_data;
+        self.tree_map = output.state.tree_map;
+        self.unbranched_rev_data = output.unbranched_rev_data;
+        self.branch_data = output.branch_data;
+        self.branch_rev_data = output.branch_rev_data;
+        self.head_branch = Some(output.head_branch);
+        self.live_branches = output.state.live_branches;
+        self.path_to_branch = output.state.path_to_branch;
+        self.has_partial_branches = output.state.has_partial_branches;
+        self.branch_path_commits = output.state.branch_path_commits;
+    }
+
+    fn take_output(&mut self, head_branch: Head) -> Output {
+        Output {
+            svn_uuid: self.svn_uuid,
+            root_rev_data: std::mem::take(&mut self.root_rev_data),
+            unbranched_rev_data: std::mem::take(&mut self.unbranched_rev_data),
+            branch_data: std::mem::take(&mut self.branch_data),
+            branch_rev_data: std::mem::take(&mut self.branch_rev_data),
+            head_branch,
+            merge_origin: None,
+            state: State {
+                dump_uuid: self.dump_uuid,
+                tree_map: std::mem::take(&mut self.tree_map),
+                live_branches: std::mem::take(&mut self.live_branches),
+                path_to_branch: std::mem::take(&mut self.path_to_branch),
+                has_partial_branches: self.has_partial_branches,
+                branch_path_commits: std::mem::take(&mut self.branch_path_commits),
+            },
+        }
+    }
+
+    /// Exchanges the branches of the output being imported with those of
+    /// the `i`-th other output of a split conversion.
+    fn swap_output(&mut self, i: usize) {
+        let other = &mut self.split_outputs[i];
+        std::mem::swap(&mut self.options, &mut other.options);
+        std::mem::swap(
+            &mut self.unbranched_rev_data,
+            &mut other.unbranched_rev_data,
+        );
+        std::mem::swap(&mut self.branch_data, &mut other.branch_data);
+        std::mem::swap(&mut self.branch_rev_data, &mut other.branch_rev_data);
+        std::mem::swap(&mut self.head_branch, &mut other.head_branch);
+        std::mem::swap(&mut self.live_branches, &mut other.live_branches);
+        std::mem::swap(&mut self.path_to_branch, &mut other.path_to_branch);
+        std::mem::swap(
+            &mut self.has_partial_branches,
+            &mut other.has_partial_branches,
+        );
+        std::mem::swap(
+            &mut self.branch_path_commits,
+            &mut other.branch_path_commits,
+        );
+    }
+
+    /// Runs `f` with the branches of the `i`-th other output of a split
+    /// conversion, or with the current ones if `i` is `None`.
+    fn with_output<R>(&mut self, i: Option<usize>, f: impl FnOnce(&mut Self) -> R) -> R {
+        let Some(i) = i else {
+            return f(self);
+        };
+        self.swap_output(i);
+        let r = f(self);
+        self.swap_output(i);
+        r
+    }
+
+    fn run(mut self) -> Result<Vec<Output>, ConvertError> {
         self.run_inner()?;
 
+        if !self.repairs.is_empty() {
+            tracing::warn!("summary of SVN dump repairs ({}):", self.repairs.len());
+            for repair in self.repairs.iter() {
+                tracing::warn!("  {repair}");
+            }
+        }
+
+        let mut outputs = Vec::with_capacity(self.split_outputs.len() + 1);
+        for i in std::iter::once(None).chain((0..self.split_outputs.len()).map(Some)) {
+            outputs.push(self.with_output(i, |stage| {
+                let head_branch = stage.check_head()?;
+                Ok(stage.take_output(head_branch))
+            })?);
+        }
+        Ok(outputs)
+    }
+
+    fn check_head(&self) -> Result<Head, ConvertError> {
         let head_branch = self.head_branch.ok_or_else(|| {
             tracing::error!(
                 "head \"{}\" not found",
@@ -223,14 +522,45 @@ impl Stage<'_> {
             }
         }
 
-        Ok(Output {
-            svn_uuid: self.svn_uuid,
-            root_rev_data: self.root_rev_data,
-            unbranched_rev_data: self.unbranched_rev_data,
-            branch_data: self.branch_data,
-            branch_rev_data: self.branch_rev_data,
-            head_branch,
-        })
+        Ok(head_branch)
+    }
+
+    /// Called before importing each revision. Saves a checkpoint if the
+    /// interval has elapsed or the conversion has been interrupted.
+    fn on_rev_boundary(&mut self) -> Result<(), ConvertError> {
+        let interrupted = super::is_interrupted();
+        if let Some(ref checkpoints) = self.checkpoints {
+            let (last_time, last_num_revs) = self.last_checkpoint;
+            if self.root_rev_data.len() != last_num_revs
+                && (interrupted || last_time.elapsed() >= checkpoints.interval)
+            {
+                self.checkpoint()?;
+            }
+        }
+
+        super::check_interrupted()
+    }
+
+    fn checkpoint(&mut self) -> Result<(), ConvertError> {
+        let Some(head_branch) = self.head_branch else {
+            return Ok(());
+        };
+        let Some(last_svn_rev) = self.root_rev_data.last().map(|rev_data| rev_data.svn_rev) else {
+            return Ok(());
+        };
+
+        tracing::info!("saving checkpoint at SVN revision {last_svn_rev}");
+        self.progress_print
+            .set_progress(format!("saving checkpoint at SVN revision {last_svn_rev}"));
+
+        let output = self.take_output(head_branch);
+        let checkpoints = self.checkpoints.as_mut().unwrap();
+        let r = (checkpoints.save)(self.git_import, &output);
+        self.put_output(output);
+        r?;
+
+        self.last_checkpoint = (std::time::Instant::now(), self.root_rev_data.len());
+        Ok(())
     }
 
     fn parse_svn_path(&self, path: &[u8]) -> Result<Vec<u8>, ConvertError> {
@@ -285,20 +615,98 @@ impl Stage<'_> {
     }
 
     fn run_inner(&mut self) -> Result<(), ConvertError> {
+        let mut seen_uuid = false;
+        // Each concatenated dump can repeat the UUID record before its
+        // first revision, but a dump cannot have more than one.
+        let mut seen_dump_uuid = false;
+        let mut last_skipped_rev = None;
+        // Last revision number found in the dump, used to check that
+        // concatenated dumps continue each other.
+        let mut last_rev_no = None;
+        let mut new_dump = false;
         let mut next_record = self.get_next_svn_dump_record()?;
         while let Some(record) = next_record {
+            if let svn::dump::Record::Rev(ref rev_record) = record {
+                if new_dump {
+                    if let Some(last_rev_no) = last_rev_no {
+                        if rev_record.rev_no != last_rev_no + 1 {
+                            tracing::error!(
+                                "SVN dump starting at revision {} does not continue the previous dump, which ends at revision {last_rev_no}",
+                                rev_record.rev_no,
+                            );
+                            return Err(ConvertError);
+                        }
+                    }
+                    new_dump = false;
+                }
+                last_rev_no = Some(rev_record.rev_no);
+            }
+
             match record {
+                svn::dump::Record::NewDump => {
+                    new_dump = true;
+                    seen_dump_uuid = false;
+                    next_record = self.get_next_svn_dump_record()?;
+                }
                 svn::dump::Record::Uuid(uuid) => {
-                    if self.svn_uuid.is_some() {
+                    if seen_dump_uuid || (seen_uuid && !new_dump) {
                         tracing::error!("more than one UUID record in SVN dump");
                         return Err(ConvertError);
+                    } else if seen_uuid {
+                        if self.dump_uuid != Some(uuid) {
+                            tracing::error!(
+                                "SVN dump UUID {uuid} does not match the previous UUID record",
+                            );
+                            return Err(ConvertError);
+                        }
+                    } else if self.resume_rev.is_some() {
+                        if self.dump_uuid != Some(uuid) {
+                            tracing::error!(
+                                "SVN repository UUID {uuid} does not match the UUID of the previous conversion",
+                            );
+                            return Err(ConvertError);
+                        }
+                    } else {
+                        tracing::info!("SVN repository UUID: {uuid}");
+                        self.dump_uuid = Some(uuid);
+                        self.svn_uuid = Some(uuid);
                     }
-                    tracing::info!("SVN repository UUID: {uuid}");
-                    self.svn_uuid = Some(uuid);
+                    seen_uuid = true;
+                    seen_dump_uuid = true;
 
                     next_record = self.get_next_svn_dump_record()?;
                 }
+                svn::dump::Record::Rev(rev_record)
+                    if self
+                        .resume_rev
+                        .is_some_and(|resume_rev| rev_record.rev_no <= resume_rev) =>
+                {
+                    self.on_rev_boundary()?;
+
+                    tracing::debug!(
+                        "skipping already imported SVN revision {}",
+                        rev_record.rev_no
+                    );
+                    last_skipped_rev = Some(rev_record.rev_no);
+                    next_record = self.skip_svn_rev()?;
+                }
+                svn::dump::Record::Rev(rev_record)
+                    if self
+                        .options
+                        .last_rev
+                        .is_some_and(|last_rev| rev_record.rev_no > last_rev) =>
+                {
+                    self.on_rev_boundary()?;
+
+                    tracing::debug!(
+                        "skipping SVN revision {} after the last revision to convert",
+                        rev_record.rev_no
+                    );
+                    next_record = self.skip_svn_rev()?;
+                }
                 svn::dump::Record::Rev(rev_record) => {
+                    self.on_rev_boundary()?;
+
                     // If the SVN repository is a mirror, pick the UUID of
                     // the original repository, which is present as a property
                     // of revision 0.
@@ -308,18 +716,23 @@ impl Stage<'_> {
                         .filter(|_| rev_record.rev_no == 0)
                         .and_then(|props| props.get(b"svn:sync-from-uuid".as_slice()))
                     {
-                        let Some(uuid) = std::str::from_utf8(raw_uuid)
+                        if let Some(uuid) = std::str::from_utf8(raw_uuid)
                             .ok()
                             .and_then(|raw_uuid| uuid::Uuid::parse_str(raw_uuid).ok())
-                        else {
-                            tracing::error!(
-                                "invalid UUID in svn:sync-from-uuid property: \"{}\"",
-                                raw_uuid.escape_ascii()
-                            );
-                            return Err(ConvertError);
-                        };
-                        tracing::info!("original SVN repository UUID: {uuid}");
-                        self.svn_uuid = Some(uuid);
+                        {
+                            tracing::info!("original SVN repository UUID: {uuid}");
+                            self.svn_uuid = Some(uuid);
+                        } else {
+                            self.repair(
+                                rev_record.rev_no,
+                                None,
+                                format!(
+                                    "invalid UUID in svn:sync-from-uuid property: \"{}\"",
+                                    raw_uuid.escape_ascii(),
+                                ),
+                                "using the UUID of the dump",
+                            )?;
+                        }
                     }
                     next_record = self.handle_svn_rev(rev_record)?;
                 }
@@ -330,9 +743,31 @@ impl Stage<'_> {
             }
         }
 
+        if let Some(resume_rev) = self.resume_rev {
+            if last_skipped_rev != Some(resume_rev) {
+                tracing::error!(
+                    "SVN dump does not contain revision {resume_rev}, which was the last imported revision",
+                );
+                return Err(ConvertError);
+            }
+        }
+
         Ok(())
     }
 
+    fn skip_svn_rev(&mut self) -> Result<Option<svn::dump::Record>, ConvertError> {
+        while let Some(record) = self.get_next_svn_dump_record()? {
+            if !matches!(record, svn::dump::Record::Node(_)) {
+                return Ok(Some(record));
+            }
+            self.svn_dump_reader.skip_text().map_err(|e| {
+                tracing::error!("failed to read SVN dump: {e}");
+                ConvertError
+            })?;
+        }
+        Ok(None)
+    }
+
     fn handle_svn_rev(
         &mut self,
         rev_record: svn::dump::RevRecord,
@@ -357,7 +792,16 @@ impl Stage<'_> {
         self.progress_print.set_progress(format!(
             "importing SVN revision {svn_rev} - splitting branches",
         ));
-        let (unbranched_ops, branches_ops) = self.split_branches(&root_node_ops)?;
+        // Each output of a split conversion splits the revision with its
+        // own specs, before the revision is added to the root commits.
+        let outputs = std::iter::once(None)
+            .chain((0..self.split_outputs.len()).map(Some))
+            .collect::<Vec<_>>();
+        let mut outputs_ops = Vec::with_capacity(outputs.len());
+        for &i in outputs.iter() {
+            outputs_ops
+                .push(self.with_output(i, |stage| stage.split_branches(svn_rev, &root_node_ops))?);
+        }
 
         let root_commit = self.root_rev_data.len();
         self.svn_rev_map.insert(svn_rev, root_commit);
@@ -367,10 +811,26 @@ impl Stage<'_> {
             svn_tree_oid: root_svn_tree_oid,
         });
 
+        for (&i, (unbranched_ops, branches_ops)) in outputs.iter().zip(outputs_ops.iter()) {
+            self.with_output(i, |stage| {
+                stage.make_rev_data(svn_rev, unbranched_ops, branches_ops)
+            })?;
+        }
+
+        Ok(next_record)
+    }
+
+    fn make_rev_data(
+        &mut self,
+        svn_rev: u32,
+        unbranched_ops: &[UnbranchedNodeOp],
+        branches_ops: &BTreeMap<Vec<u8>, BranchOps>,
+    ) -> Result<(), ConvertError> {
         if !unbranched_ops.is_empty() {
-            self.make_unbranched_tree(svn_rev, &unbranched_ops)?;
+            self.make_unbranched_tree(svn_rev, unbranched_ops)?;
         }
 
+        let branch_moves = self.find_branch_moves(svn_rev, branches_ops);
         for (i, (branch_path, branch_ops)) in branches_ops.iter().enumerate() {
             self.progress_print.set_progress(format!(
                 "importing SVN revision {svn_rev} - preparing branch {} / {}",
@@ -378,10 +838,10 @@ impl Stage<'_> {
                 branches_ops.len(),
             ));
             assert_ne!(branch_path, b"");
-            self.make_branch_rev_data(branch_path, branch_ops)?;
+            self.make_branch_rev_data(branch_path, branch_ops, &branch_moves)?;
         }
 
-        Ok(next_record)
+        Ok(())
     }
 
     fn read_svn_rev_tree(
@@ -424,23 +884,53 @@ impl Stage<'_> {
             ));
 
             let node_path = self.parse_svn_path(&node_record.path)?;
-            let node_action = node_record.action;
-            let node_kind = node_record.kind;
-            let mut copy_from = node_record
+            if self.options.is_path_excluded(&node_path) {
+                tracing::trace!(
+                    "skipping excluded SVN dump node record: path=\"{}\"",
+                    node_path.escape_ascii(),
+                );
+                self.svn_dump_reader.skip_text().map_err(|e| {
+                    tracing::error!("failed to read SVN dump: {e}");
+                    ConvertError
+                })?;
+                continue;
+            }
+
+            let mut node_action = node_record.action;
+            let mut node_kind = node_record.kind;
+            let copy_source_md5 = node_record
                 .copy_from
                 .as_ref()
-                .map(|copy_from| {
-                    let rev = *self.svn_rev_map.get(&copy_from.rev).ok_or_else(|| {
-                        tracing::error!(
+                .and_then(|copy_from| copy_from.source_md5);
+            let mut copy_from = None;
+            if let Some(ref node_copy_from) = node_record.copy_from {
+                if let Some(&rev) = self.svn_rev_map.get(&node_copy_from.rev) {
+                    let copy_from_path = self.parse_svn_path(&node_copy_from.path)?;
+                    if self.options.is_path_excluded(&copy_from_path) {
+                        self.repair(
+                            svn_rev,
+                            Some(&node_path),
+                            format!(
+                                "attempted to copy from excluded path \"{}\"",
+                                copy_from_path.escape_ascii(),
+                            ),
+                            "treating it as an add without copy-from",
+                        )?;
+                    } else {
+                        copy_from = Some((rev, copy_from_path));
+                    }
+                } else {
+                    self.repair(
+                        svn_rev,
+                        Some(&node_path),
+                        format!(
                             "attempted to copy from non-existent SVN rev {}",
-                            copy_from.rev
-                        );
-                        ConvertError
-                    })?;
-                    let path = self.parse_svn_path(&copy_from.path)?;
-                    Ok((rev, path))
-                })
-                .transpose()?;
+                            node_copy_from.rev,
+                        ),
+                        "treating it as an add without copy-from",
+                    )?;
+                }
+            }
 
             tracing::trace!(
                 "SVN dump node record: path=\"{}\", kind={node_kind:?}, action={node_action:?}",
@@ -449,39 +939,9 @@ impl Stage<'_> {
 
             let mut props = node_record.properties.as_ref();
 
+            let mut replaced_entry = None;
             if node_action == svn::dump::NodeAction::Replace {
-                let prev_entry =
-                    tree_builder
-                        .rm(&node_path, self.git_import)?
-                        .ok_or_else(|| {
-                            tracing::error!(
-                                "attempted to replace non-existent path \"{}\"",
-                                node_path.escape_ascii(),
-                            );
-                            ConvertError
-                        })?;
-                node_ops.push(RootNodeOp {
-                    path: node_path.clone(),
-                    action: if let svn_tree::NodeEntry::Dir(rm_tree_oid) = prev_entry {
-                        RootNodeAction::DelDir(rm_tree_oid)
-                    } else {
-                        RootNodeAction::DelFile
-                    },
-                });
-            }
-
-            match node_action {
-                svn::dump::NodeAction::Delete => {
-                    let prev_entry =
-                        tree_builder
-                            .rm(&node_path, self.git_import)?
-                            .ok_or_else(|| {
-                                tracing::error!(
-                                    "attempted to delete non-existent path \"{}\"",
-                                    node_path.escape_ascii(),
-                                );
-                                ConvertError
-                            })?;
+                if let Some(prev_entry) = tree_builder.rm(&node_path, self.git_import)? {
                     node_ops.push(RootNodeOp {
                         path: node_path.clone(),
                         action: if let svn_tree::NodeEntry::Dir(rm_tree_oid) = prev_entry {
@@ -490,14 +950,70 @@ impl Stage<'_> {
                             RootNodeAction::DelFile
                         },
                     });
+                    replaced_entry = Some(prev_entry);
+                } else {
+                    self.repair(
+                        svn_rev,
+                        Some(&node_path),
+                        format!(
+                            "attempted to replace non-existent path \"{}\"",
+                            node_path.escape_ascii(),
+                        ),
+                        "treating it as an add",
+                    )?;
+                    node_action = svn::dump::NodeAction::Add;
+                }
+            }
+
+            if node_kind.is_none() && node_action != svn::dump::NodeAction::Delete {
+                self.repair(
+                    svn_rev,
+                    Some(&node_path),
+                    "missing Node-kind in SVN dump node record".into(),
+                    "inferring it from the tree",
+                )?;
+                node_kind = Some(self.infer_node_kind(
+                    &mut tree_builder,
+                    &node_path,
+                    node_action,
+                    replaced_entry,
+                    copy_from.as_ref(),
+                    node_record.text.is_some(),
+                )?);
+            }
+
+            match node_action {
+                svn::dump::NodeAction::Delete => {
+                    if let Some(prev_entry) = tree_builder.rm(&node_path, self.git_import)? {
+                        node_ops.push(RootNodeOp {
+                            path: node_path.clone(),
+                            action: if let svn_tree::NodeEntry::Dir(rm_tree_oid) = prev_entry {
+                                RootNodeAction::DelDir(rm_tree_oid)
+                            } else {
+                                RootNodeAction::DelFile
+                            },
+                        });
+                    } else {
+                        self.repair(
+                            svn_rev,
+                            Some(&node_path),
+                            format!(
+                                "attempted to delete non-existent path \"{}\"",
+                                node_path.escape_ascii(),
+                            ),
+                            "skipping the node",
+                        )?;
+                        self.svn_dump_reader.skip_text().map_err(|e| {
+                            tracing::error!("failed to read SVN dump: {e}");
+                            ConvertError
+                        })?;
+                        continue;
+                    }
                 }
                 svn::dump::NodeAction::Change
                 | svn::dump::NodeAction::Add
                 | svn::dump::NodeAction::Replace => match node_kind {
-                    None => {
-                        tracing::error!("missing Node-kind in SVN dump node record");
-                        return Err(ConvertError);
-                    }
+                    None => unreachable!(),
                     Some(svn::dump::NodeKind::File) => {
                         let mut orig_entry = None;
 
@@ -507,54 +1023,69 @@ impl Stage<'_> {
                                 return Err(ConvertError);
                             }
 
-                            let entry = self
-                                .svn_tree_ls(
-                                    self.root_rev_data[copy_from_rev].svn_tree_oid,
-                                    &copy_from_path,
-                                )?
-                                .ok_or_else(|| {
-                                    tracing::error!(
-                                        "attempted to copy from non-existent path \"{}\" at rev {}",
-                                        copy_from_path.escape_ascii(),
-                                        self.root_rev_data[copy_from_rev].svn_rev,
-                                    );
-                                    ConvertError
-                                })?;
+                            let entry = self.svn_tree_ls(
+                                self.root_rev_data[copy_from_rev].svn_tree_oid,
+                                &copy_from_path,
+                            )?;
                             match entry {
-                                svn_tree::NodeEntry::Dir(_) => {
+                                None => {
+                                    self.repair(
+                                        svn_rev,
+                                        Some(&node_path),
+                                        format!(
+                                            "attempted to copy from non-existent path \"{}\" at rev {}",
+                                            copy_from_path.escape_ascii(),
+                                            self.root_rev_data[copy_from_rev].svn_rev,
+                                        ),
+                                        "treating it as an add without copy-from",
+                                    )?;
+                                }
+                                Some(svn_tree::NodeEntry::Dir(_)) => {
                                     tracing::error!(
                                         "attempted to copy directory as file at \"{}\" in SVN dump",
                                         node_path.escape_ascii(),
                                     );
                                     return Err(ConvertError);
                                 }
-                                svn_tree::NodeEntry::File {
+                                Some(svn_tree::NodeEntry::File {
                                     special,
                                     executable,
                                     oid,
-                                } => {
+                                }) => {
+                                    if let Some(expected_md5) = copy_source_md5 {
+                                        self.verify_checksum(
+                                            svn_rev,
+                                            &node_path,
+                                            "copy source MD5",
+                                            &expected_md5,
+                                            &self.svn_file_md5(special, oid)?,
+                                        )?;
+                                    }
                                     orig_entry = Some((special, executable, oid));
                                 }
                             }
                         } else if node_action == svn::dump::NodeAction::Change {
-                            let entry = tree_builder
-                                .ls_file(&node_path, self.git_import)?
-                                .ok_or_else(|| {
-                                    tracing::error!(
-                                        "attempted to change directory or non-existent path \"{}\"",
-                                        node_path.escape_ascii(),
-                                    );
-                                    ConvertError
-                                })?;
-                            let svn_tree::NodeEntry::File {
-                                special,
-                                executable,
-                                oid,
-                            } = entry
-                            else {
-                                unreachable!();
-                            };
-                            orig_entry = Some((special, executable, oid));
+                            match tree_builder.ls_file(&node_path, self.git_import)? {
+                                Some(svn_tree::NodeEntry::File {
+                                    special,
+                                    executable,
+                                    oid,
+                                }) => {
+                                    orig_entry = Some((special, executable, oid));
+                                }
+                                Some(svn_tree::NodeEntry::Dir(_)) => unreachable!(),
+                                None => {
+                                    self.repair(
+                                        svn_rev,
+                                        Some(&node_path),
+                                        format!(
+                                            "attempted to change directory or non-existent path \"{}\"",
+                                            node_path.escape_ascii(),
+                                        ),
+                                        "treating it as an add",
+                                    )?;
+                                }
+                            }
                         }
 
                         let mut props_special = None;
@@ -580,7 +1111,42 @@ impl Stage<'_> {
                             .unwrap_or(false);
 
                         if let Some(node_text) = node_record.text.take() {
-                            if node_text.is_delta {
+                            let text_len = self.svn_dump_reader.remaining_text_len();
+                            let large_source = if node_text.is_delta {
+                                match orig_entry {
+                                    Some((None, _, orig_oid)) => {
+                                        self.git_import.get_blob_size(orig_oid)?
+                                            > self.options.stream_file_size
+                                    }
+                                    _ => false,
+                                }
+                            } else {
+                                false
+                            };
+
+                            // Special files are small and need to be
+                            // decoded entirely.
+                            if !new_special
+                                && (text_len > self.options.stream_file_size || large_source)
+                            {
+                                let oid = self.put_streamed_text(
+                                    svn_rev,
+                                    &node_path,
+                                    &node_text,
+                                    orig_entry.map(|(orig_special, _, orig_oid)| {
+                                        (orig_special, orig_oid)
+                                    }),
+                                )?;
+                                tree_builder.mod_entry(
+                                    &node_path,
+                                    svn_tree::NodeEntry::File {
+                                        special: None,
+                                        executable: new_executable,
+                                        oid,
+                                    },
+                                    self.git_import,
+                                )?;
+                            } else if node_text.is_delta {
                                 let source = if let Some((orig_special, _, orig_oid)) = orig_entry {
                                     let mut source = self.git_import.get_blob(orig_oid)?;
                                     if let Some(orig_special) = orig_special {
@@ -591,24 +1157,38 @@ impl Stage<'_> {
                                     Vec::new()
                                 };
 
-                                let delta_len =
-                                    usize::try_from(self.svn_dump_reader.remaining_text_len())
-                                        .unwrap();
-                                let mut delta = vec![0; delta_len];
-                                self.svn_dump_reader.read_text(&mut delta).map_err(|e| {
-                                    tracing::error!("failed to read SVN node text: {e}");
-                                    ConvertError
-                                })?;
+                                if let Some(expected_md5) = node_text.delta_base_md5 {
+                                    self.verify_checksum(
+                                        svn_rev,
+                                        &node_path,
+                                        "delta base MD5",
+                                        &expected_md5,
+                                        &md5::Md5::digest(&source),
+                                    )?;
+                                }
+
+                                let delta =
+                                    self.svn_dump_reader.read_text_to_end().map_err(|e| {
+                                        tracing::error!("failed to read SVN node text: {e}");
+                                        ConvertError
+                                    })?;
 
                                 let mut result_data = Vec::new();
                                 if let Err(e) = svn::diff::apply(
                                     delta.as_slice(),
                                     source.as_slice(),
                                     &mut result_data,
+                                    &self.options.delta_limits,
                                 ) {
                                     tracing::error!("failed to apply SVN delta: {e}");
                                     return Err(ConvertError);
                                 };
+                                self.verify_text_checksums(
+                                    svn_rev,
+                                    &node_path,
+                                    &node_text,
+                                    &result_data,
+                                )?;
 
                                 let mut blob_data = result_data;
                                 let new_special = if new_special {
@@ -626,16 +1206,14 @@ impl Stage<'_> {
                                     self.git_import,
                                 )?;
                             } else {
-                                let data_len =
-                                    usize::try_from(self.svn_dump_reader.remaining_text_len())
-                                        .unwrap();
-                                let mut blob_data = vec![0; data_len];
-                                self.svn_dump_reader
-                                    .read_text(&mut blob_data)
-                                    .map_err(|e| {
+                                let mut blob_data =
+                                    self.svn_dump_reader.read_text_to_end().map_err(|e| {
                                         tracing::error!("failed to read SVN node text: {e}");
                                         ConvertError
                                     })?;
+                                self.verify_text_checksums(
+                                    svn_rev, &node_path, &node_text, &blob_data,
+                                )?;
 
                                 let new_special = if new_special {
                                     Some(self.decode_svn_special(&mut blob_data, &node_path)?)
@@ -677,8 +1255,20 @@ impl Stage<'_> {
                                 self.git_import,
                             )?;
                         } else {
-                            tracing::error!("missing file content in SVN dump node");
-                            return Err(ConvertError);
+                            self.repair(
+                                svn_rev,
+                                Some(&node_path),
+                                "missing file content in SVN dump node".into(),
+                                "adding an empty file",
+                            )?;
+                            tree_builder.mod_inline(
+                                &node_path,
+                                None,
+                                new_executable,
+                                Vec::new(),
+                                None,
+                                self.git_import,
+                            )?;
                         }
 
                         node_ops.push(RootNodeOp {
@@ -687,6 +1277,50 @@ impl Stage<'_> {
                         });
                     }
                     Some(svn::dump::NodeKind::Dir) => {
+                        // Only checked in lenient mode, since otherwise the
+                        // lookups below fail with the same errors.
+                        if self.options.lenient
+                            && node_action == svn::dump::NodeAction::Change
+                            && props.is_some()
+                            && tree_builder
+                                .ls_metadata(&node_path, self.git_import)?
+                                .is_none()
+                        {
+                            self.repair(
+                                svn_rev,
+                                Some(&node_path),
+                                format!(
+                                    "attempted to change non-existent directory \"{}\"",
+                                    node_path.escape_ascii(),
+                                ),
+                                "treating it as an add",
+                            )?;
+                            node_action = svn::dump::NodeAction::Add;
+                        }
+                        if let Some((copy_from_rev, ref copy_from_path)) = copy_from {
+                            if self.options.lenient
+                                && node_action != svn::dump::NodeAction::Change
+                                && self
+                                    .svn_tree_ls(
+                                        self.root_rev_data[copy_from_rev].svn_tree_oid,
+                                        copy_from_path,
+                                    )?
+                                    .is_none()
+                            {
+                                self.repair(
+                                    svn_rev,
+                                    Some(&node_path),
+                                    format!(
+                                        "attempted to copy from non-existent directory \"{}\" at rev {}",
+                                        copy_from_path.escape_ascii(),
+                                        self.root_rev_data[copy_from_rev].svn_rev,
+                                    ),
+                                    "treating it as an add without copy-from",
+                                )?;
+                                copy_from = None;
+                            }
+                        }
+
                         let mut metadata_oid = None;
                         let props = props.take();
                         if let Some(props) = props {
@@ -817,16 +1451,32 @@ impl Stage<'_> {
             }
 
             if node_record.text.is_some() {
-                tracing::error!("SVN dump node record has unused text content");
-                return Err(ConvertError);
+                self.repair(
+                    svn_rev,
+                    Some(&node_path),
+                    "SVN dump node record has unused text content".into(),
+                    "ignoring it",
+                )?;
+                self.svn_dump_reader.skip_text().map_err(|e| {
+                    tracing::error!("failed to read SVN dump: {e}");
+                    ConvertError
+                })?;
             }
             if copy_from.is_some() {
-                tracing::error!("SVN dump node record has unused copy-from");
-                return Err(ConvertError);
+                self.repair(
+                    svn_rev,
+                    Some(&node_path),
+                    "SVN dump node record has unused copy-from".into(),
+                    "ignoring it",
+                )?;
             }
             if props.is_some() {
-                tracing::error!("SVN dump node record has unused properties content");
-                return Err(ConvertError);
+                self.repair(
+                    svn_rev,
+                    Some(&node_path),
+                    "SVN dump node record has unused properties content".into(),
+                    "ignoring it",
+                )?;
             }
         }
 
@@ -868,6 +1518,226 @@ impl Stage<'_> {
         }
     }
 
+    /// Imports a node text without keeping it entirely in memory. If it
+    /// is a delta, it is applied to `orig_file`.
+    fn put_streamed_text(
+        &mut self,
+        svn_rev: u32,
+        node_path: &[u8],
+        node_text: &svn::dump::NodeText,
+        orig_file: Option<(Option<svn_tree::FileSpecial>, gix_hash::ObjectId)>,
+    ) -> Result<gix_hash::ObjectId, ConvertError> {
+        let mut hasher = TextHasher::new(node_text, self.git_import.blob_writer()?);
+
+        if node_text.is_delta {
+            let mut orig_data = Vec::new();
+            let mut orig_reader = None;
+            if let Some((orig_special, orig_oid)) = orig_file {
+                let orig_size = self.git_import.get_blob_size(orig_oid)?;
+                if orig_special.is_some() || orig_size <= self.options.stream_file_size {
+                    orig_data = self.git_import.get_blob(orig_oid)?;
+                    if let Some(orig_special) = orig_special {
+                        self.encode_svn_special(&mut orig_data, orig_special);
+                    }
+                } else {
+                    orig_reader = Some((self.git_import.get_blob_reader(orig_oid)?, orig_size));
+                }
+            }
+
+            let result = if let Some((mut orig_reader, orig_size)) = orig_reader {
+                if let Some(expected_md5) = node_text.delta_base_md5 {
+                    let md5 = stream_md5(&mut orig_reader).map_err(|e| {
+                        tracing::error!("failed to read SVN delta base: {e}");
+                        ConvertError
+                    })?;
+                    orig_reader.rewind();
+                    self.verify_checksum(
+                        svn_rev,
+                        node_path,
+                        "delta base MD5",
+                        &expected_md5,
+                        &md5,
+                    )?;
+                }
+
+                let mut source = svn::diff::StreamSource::new(
+                    orig_reader,
+                    orig_size,
+                    |orig_reader: &mut crate::git::BlobReader<'_>| {
+                        orig_reader.rewind();
+                        Ok(())
+                    },
+                );
+                svn::diff::apply_stream(
+                    &mut self.svn_dump_reader.text_reader(),
+                    &mut source,
+                    &mut hasher,
+                    &self.options.delta_limits,
+                )
+            } else {
+                if let Some(expected_md5) = node_text.delta_base_md5 {
+                    self.verify_checksum(
+                        svn_rev,
+                        node_path,
+                        "delta base MD5",
+                        &expected_md5,
+                        &md5::Md5::digest(&orig_data),
+                    )?;
+                }
+
+                svn::diff::apply_stream(
+                    &mut self.svn_dump_reader.text_reader(),
+                    &mut orig_data.as_slice(),
+                    &mut hasher,
+                    &self.options.delta_limits,
+                )
+            };
+            if let Err(e) = result {
+                tracing::error!("failed to apply SVN delta: {e}");
+                return Err(ConvertError);
+            }
+        } else {
+            std::io::copy(&mut self.svn_dump_reader.text_reader(), &mut hasher).map_err(|e| {
+                tracing::error!("failed to import SVN node text: {e}");
+                ConvertError
+            })?;
+        }
+
+        let blob_writer = self.verify_text_hasher(svn_rev, node_path, node_text, hasher)?;
+        self.git_import.put_blob_stream(blob_writer)
+    }
+
+    /// Computes the MD5 of a file as stored by SVN, streaming it if it is
+    /// large.
+    fn svn_file_md5(
+        &self,
+        special: Option<svn_tree::FileSpecial>,
+        oid: gix_hash::ObjectId,
+    ) -> Result<[u8; 16], ConvertError> {
+        if special.is_none() && self.git_import.get_blob_size(oid)? > self.options.stream_file_size
+        {
+            let mut reader = self.git_import.get_blob_reader(oid)?;
+            stream_md5(&mut reader).map_err(|e| {
+                tracing::error!("failed to read object {oid}: {e}");
+                ConvertError
+            })
+        } else {
+            let mut data = self.git_import.get_blob(oid)?;
+            if let Some(special) = special {
+                self.encode_svn_special(&mut data, special);
+            }
+            Ok(md5::Md5::digest(&data).into())
+        }
+    }
+
+    fn verify_text_checksums(
+        &self,
+        svn_rev: u32,
+        node_path: &[u8],
+        node_text: &svn::dump::NodeText,
+        text: &[u8],
+    ) -> Result<(), ConvertError> {
+        let mut hasher = TextHasher::new(node_text, std::io::sink());
+        hasher.update(text);
+        self.verify_text_hasher(svn_rev, node_path, node_text, hasher)?;
+        Ok(())
+    }
+
+    /// Verifies the checksums computed by `hasher` and returns its inner
+    /// writer.
+    fn verify_text_hasher<W>(
+        &self,
+        svn_rev: u32,
+        node_path: &[u8],
+        node_text: &svn::dump::NodeText,
+        hasher: TextHasher<W>,
+    ) -> Result<W, ConvertError> {
+        if let (Some(expected_md5), Some(md5)) = (node_text.md5, hasher.md5) {
+            self.verify_checksum(
+                svn_rev,
+                node_path,
+                "text MD5",
+                &expected_md5,
+                &md5.finalize(),
+            )?;
+        }
+        if let (Some(expected_sha1), Some(sha1)) = (node_text.sha1, hasher.sha1) {
+            self.verify_checksum(
+                svn_rev,
+                node_path,
+                "text SHA-1",
+                &expected_sha1,
+                &sha1.finalize(),
+            )?;
+        }
+        Ok(hasher.inner)
+    }
+
+    /// Compares a checksum from the SVN dump with the one of the imported
+    /// data. A mismatch is an error unless the options allow it.
+    fn verify_checksum(
+        &self,
+        svn_rev: u32,
+        node_path: &[u8],
+        what: &str,
+        expected: &[u8],
+        actual: &[u8],
+    ) -> Result<(), ConvertError> {
+        if expected == actual {
+            return Ok(());
+        }
+
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+        if self.options.allow_checksum_mismatch {
+            tracing::warn!(
+                "{what} mismatch for \"{}\" at SVN revision {svn_rev}: expected {}, found {}",
+                node_path.escape_ascii(),
+                hex(expected),
+                hex(actual),
+            );
+            Ok(())
+        } else {
+            tracing::error!(
+                "{what} mismatch for \"{}\" at SVN revision {svn_rev}: expected {}, found {}",
+                node_path.escape_ascii(),
+                hex(expected),
+                hex(actual),
+            );
+            Err(ConvertError)
+        }
+    }
+
+    /// Reports a recoverable anomaly of the SVN dump. It is an error unless
+    /// lenient mode is enabled, in which case it is logged as a warning,
+    /// recorded for the final summary and the caller applies `recovery`.
+    fn repair(
+        &mut self,
+        svn_rev: u32,
+        path: Option<&[u8]>,
+        problem: String,
+        recovery: &'static str,
+    ) -> Result<(), ConvertError> {
+        if !self.options.lenient {
+            tracing::error!("{problem}");
+            return Err(ConvertError);
+        }
+
+        let repair = Repair {
+            svn_rev,
+            path: path.map(<[u8]>::to_vec),
+            problem,
+            recovery,
+        };
+        tracing::warn!("{repair}");
+        self.repairs.push(repair);
+        Ok(())
+    }
+
     fn encode_svn_special(&self, blob_data: &mut Vec<u8>, special: svn_tree::FileSpecial) {
         match special {
             svn_tree::FileSpecial::Link => {
@@ -986,8 +1856,13 @@ impl Stage<'_> {
 
     fn split_branches(
         &mut self,
+        svn_rev: u32,
         node_ops: &[RootNodeOp],
     ) -> Result<(Vec<UnbranchedNodeOp>, BTreeMap<Vec<u8>, BranchOps>), ConvertError> {
+        // Deleted paths are classified with the layout they were in before
+        // this revision, which differs if the layout changes in it.
+        let prev_svn_rev = svn_rev.saturating_sub(1);
+
         let mut pending: VecDeque<_> = node_ops.iter().cloned().collect();
 
         let mut branches_ops = BTreeMap::<Vec<u8>, BranchOps>::new();
@@ -997,7 +1872,7 @@ impl Stage<'_> {
             match node_op.action {
                 RootNodeAction::DelFile => {
                     let dir_path = get_path_base_dir(&node_op.path);
-                    match self.options.classify_dir(dir_path) {
+                    match self.options.classify_dir(dir_path, prev_svn_rev) {
                         DirClass::Unbranched | DirClass::BranchParent => {
                             unbranched_ops.push(UnbranchedNodeOp {
                                 path: node_op.path,
@@ -1013,7 +1888,7 @@ impl Stage<'_> {
                 }
                 RootNodeAction::ModFile => {
                     let dir_path = get_path_base_dir(&node_op.path);
-                    match self.options.classify_dir(dir_path) {
+                    match self.options.classify_dir(dir_path, svn_rev) {
                         DirClass::Unbranched | DirClass::BranchParent => {
                             unbranched_ops.push(UnbranchedNodeOp {
                                 path: node_op.path,
@@ -1032,7 +1907,7 @@ impl Stage<'_> {
                     }
                 }
                 RootNodeAction::DelDir(tree_oid) => {
-                    match self.options.classify_dir(&node_op.path) {
+                    match self.options.classify_dir(&node_op.path, prev_svn_rev) {
                         DirClass::Unbranched => {
                             unbranched_ops.push(UnbranchedNodeOp {
                                 path: node_op.path,
@@ -1082,7 +1957,7 @@ impl Stage<'_> {
                         }
                     }
                 }
-                RootNodeAction::AddDir => match self.options.classify_dir(&node_op.path) {
+                RootNodeAction::AddDir => match self.options.classify_dir(&node_op.path, svn_rev) {
                     DirClass::Unbranched | DirClass::BranchParent => {
                         unbranched_ops.push(UnbranchedNodeOp {
                             path: node_op.path,
@@ -1102,9 +1977,13 @@ impl Stage<'_> {
                     }
                 },
                 RootNodeAction::CopyDir(has_metadata, copy_from_rev, copy_from_path) => {
-                    match self.options.classify_dir(&node_op.path) {
+                    let copy_from_svn_rev = self.root_rev_data[copy_from_rev].svn_rev;
+                    match self.options.classify_dir(&node_op.path, svn_rev) {
                         DirClass::Unbranched => {
-                            match self.options.classify_dir(&copy_from_path) {
+                            match self
+                                .options
+                                .classify_dir(&copy_from_path, copy_from_svn_rev)
+                            {
                                 DirClass::Branch(copy_from_branch, _, b"") => {
                                     tracing::warn!(
                                         "copying branch \"{}\" to non-branch/tag \"{}\"",
@@ -1146,8 +2025,9 @@ impl Stage<'_> {
                             }
                         }
                         DirClass::BranchParent => {
-                            if let DirClass::Branch(copy_from_branch, _, b"") =
-                                self.options.classify_dir(&copy_from_path)
+                            if let DirClass::Branch(copy_from_branch, _, b"") = self
+                                .options
+                                .classify_dir(&copy_from_path, copy_from_svn_rev)
                             {
                                 tracing::warn!(
                                     "copying branch \"{}\" to non-branch \"{}\"",
@@ -1209,7 +2089,7 @@ impl Stage<'_> {
                     }
                 }
                 RootNodeAction::ModDir(has_metadata) => {
-                    match self.options.classify_dir(&node_op.path) {
+                    match self.options.classify_dir(&node_op.path, svn_rev) {
                         DirClass::Unbranched => {
                             unbranched_ops.push(UnbranchedNodeOp {
                                 path: node_op.path,
@@ -1397,14 +2277,77 @@ impl Stage<'_> {
         Ok(())
     }
 
+    /// Finds the branches and tags whose root is moved, i.e., deleted and
+    /// copied from its last commit to a new branch or tag of the same kind
+    /// in the same revision. These are continued at the new path instead of
+    /// being deleted and created again.
+    ///
+    /// Unless `detect-branch-renames` is enabled, only moves done because
+    /// the layout changes (the old path is not a branch or tag in the layout
+    /// of the revision) are considered.
+    ///
+    /// Returns a map from new paths to old paths.
+    fn find_branch_moves(
+        &self,
+        svn_rev: u32,
+        branches_ops: &BTreeMap<Vec<u8>, BranchOps>,
+    ) -> FHashMap<Vec<u8>, Vec<u8>> {
+        let mut branch_moves = FHashMap::default();
+        let mut moved_from = FHashSet::default();
+        for (branch_path, branch_ops) in branches_ops.iter() {
+            if branch_ops.delete {
+                continue;
+            }
+            let Some(BranchCreateOp {
+                is_tag,
+                from: Some((from_rev, from_path)),
+            }) = &branch_ops.create
+            else {
+                continue;
+            };
+            if moved_from.contains(from_path)
+                || !branches_ops
+                    .get(from_path)
+                    .is_some_and(|from_ops| from_ops.delete && from_ops.create.is_none())
+                || (!self.options.detect_branch_renames
+                    && matches!(
+                        self.options.classify_dir(from_path, svn_rev),
+                        DirClass::Branch(_, _, b""),
+                    ))
+            {
+                continue;
+            }
+            let Some(&from_branch) = self.live_branches.get(from_path) else {
+                continue;
+            };
+            if *from_rev < self.branch_data[from_branch].last_root_rev
+                || *is_tag != self.branch_data[from_branch].is_tag
+            {
+                // not copied from the last commit of the branch, or moved
+                // between a branch and a tag
+                continue;
+            }
+
+            moved_from.insert(from_path.clone());
+            branch_moves.insert(branch_path.clone(), from_path.clone());
+        }
+        branch_moves
+    }
+
     fn make_branch_rev_data(
         &mut self,
         branch_path: &[u8],
         branch_ops: &BranchOps,
+        branch_moves: &FHashMap<Vec<u8>, Vec<u8>>,
     ) -> Result<(), ConvertError> {
         let root_commit = self.root_rev_data.len() - 1;
 
-        if branch_ops.delete {
+        // The branch of a moved root continues at the new path.
+        let moved_away = branch_moves
+            .values()
+            .any(|from_path| from_path == branch_path);
+
+        if branch_ops.delete && !moved_away {
             if branch_ops.create.is_some() {
                 tracing::warn!(
                     "branch/tag \"{}\" is deleted and re-created in the same commit",
@@ -1425,6 +2368,38 @@ impl Stage<'_> {
                     branch_path.escape_ascii(),
                 );
                 return Err(ConvertError);
+            } else if let Some(from_path) = branch_moves.get(branch_path) {
+                tracing::debug!(
+                    "moving branch/tag \"{}\" to \"{}\"",
+                    from_path.escape_ascii(),
+                    branch_path.escape_ascii(),
+                );
+                let moved_branch = self.live_branches.remove(from_path.as_slice()).unwrap();
+                let branch_data = &mut self.branch_data[moved_branch];
+                let prev_svn_path =
+                    std::mem::replace(&mut branch_data.svn_path, branch_path.to_vec());
+                branch_data
+                    .prev_svn_paths
+                    .push((root_commit, prev_svn_path));
+                self.live_branches
+                    .insert(branch_path.to_vec(), moved_branch);
+                self.path_to_branch
+                    .entry(branch_data.partial_sub_path.clone())
+                    .or_default()
+                    .entry(branch_path.to_vec())
+                    .or_default()
+                    .push(moved_branch);
+
+                if branch_data.is_tag && !branch_ops.modify {
+                    // A tag keeps its only commit.
+                    let tip_commit = branch_data.tip_commit.unwrap();
+                    self.branch_path_commits
+                        .entry(branch_path.to_vec())
+                        .or_default()
+                        .push((root_commit, tip_commit));
+                    return Ok(());
+                }
+                branch = Some(moved_branch);
             } else {
                 let mut is_tag = create_op.is_tag;
                 let mut tip_commit = None;
@@ -1432,8 +2407,9 @@ impl Stage<'_> {
                 if let Some((from_rev, ref from_path)) = create_op.from {
                     let mut parent_is_branch = None;
                     if from_path != b"" {
-                        if let DirClass::Branch(parent_branch_path, _, sub_path) =
-                            self.options.classify_dir(from_path)
+                        if let DirClass::Branch(parent_branch_path, _, sub_path) = self
+                            .options
+                            .classify_dir(from_path, self.root_rev_data[from_rev].svn_rev)
                         {
                             let parent_branch_commits =
                                 &self.branch_path_commits[parent_branch_path];
@@ -1512,6 +2488,7 @@ impl Stage<'_> {
                     first_root_rev: root_commit,
                     last_root_rev: root_commit,
                     rev_map: Vec::new(),
+                    prev_svn_paths: Vec::new(),
                 });
                 self.live_branches.insert(branch_path.to_vec(), new_branch);
                 self.has_partial_branches |= !partial_sub_path.is_empty();
@@ -1586,20 +2563,21 @@ impl Stage<'_> {
                 };
                 let git_tree_oid = self.tree_map[&svn_tree_oid];
 
-                let branch_data = &mut self.branch_data[branch];
-                if branch_data.partial_sub_path.is_empty() {
-                    git_tree_oid.unwrap_or_else(|| self.git_import.empty_tree_oid())
-                } else {
+                // Trees are re-rooted at the subdirectory, if set, while the
+                // sub-path of a partial branch is relative to the whole branch.
+                let branch_data = &self.branch_data[branch];
+                let subdirectory = &self.options.subdirectory;
+                if let Some(sub_path) = path_in_dir(subdirectory, &branch_data.partial_sub_path) {
+                    self.git_sub_tree(git_tree_oid, sub_path)?
+                } else if let Some(change_path) =
+                    path_in_dir(&branch_data.partial_sub_path, subdirectory)
+                {
                     let parent_tree_oid = self.branch_rev_data[parent_commit.unwrap()].tree_oid;
                     let mut change_set = crate::git::ChangeSet::new(Some(parent_tree_oid));
                     if let Some(git_tree_oid) = git_tree_oid {
-                        change_set.change(
-                            &branch_data.partial_sub_path,
-                            EntryKind::Tree,
-                            git_tree_oid,
-                        );
+                        change_set.change(change_path, EntryKind::Tree, git_tree_oid);
                     } else {
-                        change_set.remove(&branch_data.partial_sub_path);
+                        change_set.remove(change_path);
                     }
                     change_set
                         .apply(self.git_import.inner())
@@ -1608,6 +2586,9 @@ impl Stage<'_> {
                             ConvertError
                         })?
                         .unwrap_or_else(|| self.git_import.empty_tree_oid())
+                } else {
+                    // The partial branch is outside of the subdirectory.
+                    self.branch_rev_data[parent_commit.unwrap()].tree_oid
                 }
             } else {
                 tracing::error!(
@@ -1616,6 +2597,7 @@ impl Stage<'_> {
                 );
                 return Err(ConvertError);
             };
+            let tree_oid = self.rewrite_paths(tree_oid, branch_path, root_commit)?;
 
             self.branch_rev_data.push(BranchRevData {
                 branch,
@@ -1643,6 +2625,46 @@ impl Stage<'_> {
         Ok(())
     }
 
+    /// Applies the path rewrites to the tree of a branch.
+    fn rewrite_paths(
+        &mut self,
+        tree_oid: gix_hash::ObjectId,
+        branch_path: &[u8],
+        root_commit: usize,
+    ) -> Result<gix_hash::ObjectId, ConvertError> {
+        if self.options.path_rewrites.is_empty() {
+            return Ok(tree_oid);
+        }
+        if let Some(&rewritten_tree_oid) = self.rewritten_trees.get(&tree_oid) {
+            return Ok(rewritten_tree_oid);
+        }
+        let rewritten_tree_oid = self.options.path_rewrites.apply(
+            self.git_import,
+            tree_oid,
+            branch_path,
+            self.root_rev_data[root_commit].svn_rev,
+        )?;
+        self.rewritten_trees.insert(tree_oid, rewritten_tree_oid);
+        Ok(rewritten_tree_oid)
+    }
+
+    /// Returns the Git tree at `path` inside `tree_oid`, or the empty tree
+    /// if there is none.
+    fn git_sub_tree(
+        &self,
+        tree_oid: Option<gix_hash::ObjectId>,
+        path: &[u8],
+    ) -> Result<gix_hash::ObjectId, ConvertError> {
+        let entry = match tree_oid {
+            Some(tree_oid) => self.git_import.ls(tree_oid, path)?,
+            None => None,
+        };
+        match entry {
+            Some((EntryKind::Tree, sub_tree_oid)) => Ok(sub_tree_oid),
+            _ => Ok(self.git_import.empty_tree_oid()),
+        }
+    }
+
     fn gather_svn_merges(
         &mut self,
         branch: usize,
@@ -1883,6 +2905,44 @@ impl Stage<'_> {
         })
     }
 
+    /// Guesses the kind of a node record without Node-kind from the entry it
+    /// copies, changes or replaces, or else from whether it has text.
+    fn infer_node_kind(
+        &mut self,
+        tree_builder: &mut tree_builder::TreeBuilder,
+        node_path: &[u8],
+        node_action: svn::dump::NodeAction,
+        replaced_entry: Option<svn_tree::NodeEntry>,
+        copy_from: Option<&(usize, Vec<u8>)>,
+        has_text: bool,
+    ) -> Result<svn::dump::NodeKind, ConvertError> {
+        let entry = if let Some((copy_from_rev, copy_from_path)) = copy_from {
+            self.svn_tree_ls(
+                self.root_rev_data[*copy_from_rev].svn_tree_oid,
+                copy_from_path,
+            )?
+        } else if node_action == svn::dump::NodeAction::Change {
+            if tree_builder.ls_file(node_path, self.git_import)?.is_some() {
+                return Ok(svn::dump::NodeKind::File);
+            } else if tree_builder
+                .ls_metadata(node_path, self.git_import)?
+                .is_some()
+            {
+                return Ok(svn::dump::NodeKind::Dir);
+            }
+            None
+        } else {
+            replaced_entry
+        };
+
+        Ok(match entry {
+            Some(svn_tree::NodeEntry::Dir(_)) => svn::dump::NodeKind::Dir,
+            Some(svn_tree::NodeEntry::File { .. }) => svn::dump::NodeKind::File,
+            None if has_text => svn::dump::NodeKind::File,
+            None => svn::dump::NodeKind::Dir,
+        })
+    }
+
     fn svn_tree_ls(
         &self,
         root_tree_oid: gix_hash::ObjectId,
@@ -1953,6 +3013,51 @@ impl Stage<'_> {
     }
 }
 
+/// Computes the checksums of a node text listed in the SVN dump while
+/// the text is written to `inner`.
+struct TextHasher<W> {
+    inner: W,
+    md5: Option<md5::Md5>,
+    sha1: Option<sha1::Sha1>,
+}
+
+impl<W> TextHasher<W> {
+    fn new(node_text: &svn::dump::NodeText, inner: W) -> Self {
+        Self {
+            inner,
+            md5: node_text.md5.map(|_| md5::Md5::new()),
+            sha1: node_text.sha1.map(|_| sha1::Sha1::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        if let Some(ref mut md5) = self.md5 {
+            md5.update(data);
+        }
+        if let Some(ref mut sha1) = self.sha1 {
+            sha1.update(data);
+        }
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for TextHasher<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn stream_md5(reader: &mut dyn std::io::Read) -> std::io::Result<[u8; 16]> {
+    let mut md5 = md5::Md5::new();
+    std::io::copy(reader, &mut md5)?;
+    Ok(md5.finalize().into())
+}
+
 pub(crate) fn concat_path(a: &[u8], b: &[u8]) -> Vec<u8> {
     assert!(!a.ends_with(b"/"));
     assert!(!a.starts_with(b"/"));
@@ -1972,6 +3077,20 @@ pub(crate) fn concat_path(a: &[u8], b: &[u8]) -> Vec<u8> {
     }
 }
 
+/// Returns the rest of `path` inside `dir`, or `None` if it is not inside
+/// it. Every path is inside the empty directory.
+fn path_in_dir<'a>(path: &'a [u8], dir: &[u8]) -> Option<&'a [u8]> {
+    if dir.is_empty() {
+        return Some(path);
+    }
+    let rest = path.strip_prefix(dir)?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix(b"/")
+    }
+}
+
 fn get_path_base_dir(path: &[u8]) -> &[u8] {
     if let Some(sep_pos) = path.iter().rposition(|&c| c == b'/') {
         &path[..sep_pos]
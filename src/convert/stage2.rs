@@ -1,41 +1,77 @@
-use std::collections::{BTreeSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use super::options::Options;
 use super::{ConvertError, GitMetaMaker, git_wrap, stage1};
 use crate::term_out::ProgressPrint;
-use crate::{FHashMap, git};
+use crate::{FHashMap, FHashSet, git};
 
+/// Runs the stage.
+///
+/// When `prev_state` is provided, commits that were already emitted by a
+/// previous run are not emitted again and existing ref names are kept.
 pub(super) fn run(
     progress_print: &ProgressPrint,
     options: &Options,
     metadata_maker: &dyn GitMetaMaker,
     git_import: &mut git_wrap::Importer,
     stage1_out: &stage1::Output,
-) -> Result<(), ConvertError> {
+    prev_state: Option<State>,
+) -> Result<State, ConvertError> {
     tracing::info!("Stage 2: emit commits");
 
-    let reachable_revs = Stage::gather_reached_revs(progress_print, options, stage1_out);
+    let history = History {
+        stage1_out,
+        window_start: options.first_rev.map_or(0, |first_rev| {
+            stage1_out
+                .root_rev_data
+                .partition_point(|rev_data| rev_data.svn_rev < first_rev)
+        }),
+    };
+    if let Some(first_rev) = options.first_rev {
+        tracing::info!("squashing history before SVN revision {first_rev}");
+    }
+
+    let reachable_revs = Stage::gather_reached_revs(progress_print, options, history);
     let (unbranched_name, refs_names) =
-        Stage::calculate_git_names(progress_print, options, stage1_out);
+        Stage::calculate_git_names(progress_print, options, stage1_out, prev_state.as_ref())?;
 
-    Stage {
+    let mut stage = Stage {
         progress_print,
         options,
         metadata_maker,
         git_import,
         stage1_out,
+        history,
         unbranched_name,
         refs_names,
+        num_unbranched_commits: 0,
         last_unbranched_commit: None,
         branch_rev_git_data: FHashMap::default(),
+    };
+    if let Some(prev_state) = prev_state {
+        stage.load_prev_state(prev_state);
     }
-    .run(&reachable_revs)
+    stage.run(&reachable_revs)
+}
+
+/// State of the stage, needed to emit commits on top of a previous run.
+pub(super) struct State {
+    pub(super) unbranched_name: Option<String>,
+    pub(super) refs_names: FHashMap<usize, String>,
+    /// Branches that were already deleted when they were named.
+    pub(super) deleted_named: FHashSet<usize>,
+    /// Number of times the branches that had been moved when they were
+    /// named had been moved.
+    pub(super) moves_named: FHashMap<usize, usize>,
+    pub(super) num_unbranched_commits: usize,
+    pub(super) last_unbranched_commit: Option<gix_hash::ObjectId>,
+    pub(super) branch_rev_git_data: FHashMap<usize, BranchRevGitData>,
 }
 
-struct BranchRevGitData {
-    git_commit_oid: gix_hash::ObjectId,
-    merges: BTreeSet<usize>,
-    cherrypicks: BTreeSet<usize>,
+pub(super) struct BranchRevGitData {
+    pub(super) git_commit_oid: gix_hash::ObjectId,
+    pub(super) merges: BTreeSet<usize>,
+    pub(super) cherrypicks: BTreeSet<usize>,
 }
 
 struct Stage<'a> {
@@ -44,42 +80,116 @@ struct Stage<'a> {
     metadata_maker: &'a dyn GitMetaMaker,
     git_import: &'a mut git_wrap::Importer,
     stage1_out: &'a stage1::Output,
+    history: History<'a>,
     unbranched_name: Option<String>,
     refs_names: FHashMap<usize, String>,
+    num_unbranched_commits: usize,
     last_unbranched_commit: Option<gix_hash::ObjectId>,
     branch_rev_git_data: FHashMap<usize, BranchRevGitData>,
 }
 
 impl Stage<'_> {
-    fn run(mut self, reachable_revs: &BTreeSet<usize>) -> Result<(), ConvertError> {
-        self.run_inner(reachable_revs)
+    fn load_prev_state(&mut self, prev_state: State) {
+        // Rename or remove the refs of branches whose name has changed
+        // (e.g., because they have been deleted or moved). Tag objects hold
+        // their name, so renamed tags are emitted again.
+        let mut renamed_refs = Vec::new();
+        let mut renamed_tags = Vec::new();
+        for (branch, prev_name) in prev_state.refs_names.iter() {
+            if self.refs_names.get(branch) != Some(prev_name) {
+                if let Some(ref_oid) = self.git_import.remove_ref(prev_name) {
+                    let branch_data = &self.stage1_out.branch_data[*branch];
+                    if branch_data.is_tag {
+                        renamed_tags.push(branch_data.rev_map[0].1);
+                    } else if let Some(new_name) = self.refs_names.get(branch) {
+                        renamed_refs.push((new_name.as_str(), ref_oid));
+                    }
+                }
+            }
+        }
+        for (new_name, ref_oid) in renamed_refs {
+            tracing::debug!("renaming ref to \"{}\"", new_name.escape_default());
+            self.git_import.set_ref(new_name, ref_oid);
+        }
+
+        self.num_unbranched_commits = prev_state.num_unbranched_commits;
+        self.last_unbranched_commit = prev_state.last_unbranched_commit;
+        self.branch_rev_git_data = prev_state.branch_rev_git_data;
+        for tag_rev in renamed_tags {
+            self.branch_rev_git_data.remove(&tag_rev);
+        }
+    }
+
+    fn run(mut self, reachable_revs: &BTreeSet<usize>) -> Result<State, ConvertError> {
+        self.run_inner(reachable_revs)?;
+
+        let deleted_named = self
+            .refs_names
+            .keys()
+            .copied()
+            .filter(|&branch| self.stage1_out.branch_data[branch].deleted)
+            .collect();
+        let moves_named = self
+            .refs_names
+            .keys()
+            .map(|&branch| {
+                (
+                    branch,
+                    self.stage1_out.branch_data[branch].prev_svn_paths.len(),
+                )
+            })
+            .filter(|&(_, num_moves)| num_moves != 0)
+            .collect();
+
+        Ok(State {
+            unbranched_name: self.unbranched_name,
+            refs_names: self.refs_names,
+            deleted_named,
+            moves_named,
+            num_unbranched_commits: self.num_unbranched_commits,
+            last_unbranched_commit: self.last_unbranched_commit,
+            branch_rev_git_data: self.branch_rev_git_data,
+        })
     }
 
     fn gather_reached_revs(
         progress_print: &ProgressPrint,
         options: &Options,
-        stage1_out: &stage1::Output,
+        history: History<'_>,
     ) -> BTreeSet<usize> {
         progress_print.set_progress("gathering reachable commits".into());
 
+        let stage1_out = history.stage1_out;
         let mut reached_revs = BTreeSet::new();
 
         let mut queue = VecDeque::new();
         queue.extend(stage1_out.branch_data.iter().filter_map(|b| {
-            b.tip_commit.filter(|_| {
-                !b.deleted
-                    || if b.is_tag {
-                        options.keep_deleted_tags
-                    } else {
-                        options.keep_deleted_branches
-                    }
+            b.tip_commit.filter(|&tip| {
+                if history.is_before_window(tip) {
+                    // Branches deleted before the window are not kept
+                    !b.deleted
+                } else {
+                    !b.deleted
+                        || if b.is_tag {
+                            options.keep_deleted_tags
+                        } else {
+                            options.keep_deleted_branches
+                        }
+                }
             })
         }));
 
         while let Some(rev) = queue.pop_front() {
             if reached_revs.insert(rev) {
-                queue.extend(stage1_out.branch_rev_data[rev].parent);
-                queue.extend(&stage1_out.branch_rev_data[rev].added_svn_merges);
+                queue.extend(history.git_parent(rev));
+                if !history.is_before_window(rev) {
+                    queue.extend(
+                        stage1_out.branch_rev_data[rev]
+                            .added_svn_merges
+                            .iter()
+                            .filter(|&&merge| !history.is_before_window(merge)),
+                    );
+                }
             }
         }
 
@@ -90,14 +200,81 @@ impl Stage<'_> {
         progress_print: &ProgressPrint,
         options: &Options,
         stage1_out: &stage1::Output,
-    ) -> (Option<String>, FHashMap<usize, String>) {
+        prev_state: Option<&State>,
+    ) -> Result<(Option<String>, FHashMap<usize, String>), ConvertError> {
         tracing::info!("naming branches");
         progress_print.set_progress("naming branches".into());
 
-        let mut ref_name_map = Vec::new();
-        if !stage1_out.unbranched_rev_data.is_empty() {
+        // Names given in a previous run are kept as long as the deletion
+        // status of the branch has not changed and it has not been moved.
+        // They are sorted so names are inserted in the same order in every
+        // run.
+        let mut fixed_names = BTreeMap::new();
+        if let Some(prev_state) = prev_state {
+            for (&branch_i, prev_name) in prev_state.refs_names.iter() {
+                let branch_data = &stage1_out.branch_data[branch_i];
+                if branch_data.deleted == prev_state.deleted_named.contains(&branch_i)
+                    && branch_data.prev_svn_paths.len()
+                        == prev_state.moves_named.get(&branch_i).copied().unwrap_or(0)
+                {
+                    fixed_names.insert(Some(branch_i), prev_name.as_str());
+                }
+            }
+            if let Some(ref prev_unbranched_name) = prev_state.unbranched_name {
+                fixed_names.insert(None, prev_unbranched_name.as_str());
+            }
+        }
+
+        // The names come from the state of the previous run, which might
+        // have been written with other rename rules, so they are checked
+        // instead of trusted.
+        let mut ref_name_map = Vec::<(String, Option<usize>)>::new();
+        for (&branch_i, fixed_name) in fixed_names.iter() {
+            let is_tag = branch_i.is_some_and(|branch_i| stage1_out.branch_data[branch_i].is_tag);
+            let git_name = fixed_name
+                .strip_prefix(if is_tag { "refs/tags/" } else { "refs/heads/" })
+                .filter(|git_name| !git_name.is_empty());
+            let Some(git_name) = git_name else {
+                tracing::error!(
+                    "invalid {} name \"{}\" in previous state",
+                    if is_tag { "tag" } else { "branch" },
+                    fixed_name.escape_default(),
+                );
+                return Err(ConvertError);
+            };
+            let Err(i) = ref_name_map.binary_search_by_key(&git_name, |(n, _)| n) else {
+                tracing::error!(
+                    "duplicate ref name \"{}\" in previous state",
+                    fixed_name.escape_default(),
+                );
+                return Err(ConvertError);
+            };
+            ref_name_map.insert(i, (git_name.to_owned(), branch_i));
+        }
+
+        if !fixed_names.contains_key(&None) && !stage1_out.unbranched_rev_data.is_empty() {
             if let Some(ref unbranched_name) = options.unbranched_name {
-                ref_name_map.push((unbranched_name.clone(), None));
+                // Branches and tags named in a previous run keep their
+                // names, so the unbranched name might be taken.
+                let mut git_name = unbranched_name.clone();
+                let mut tries = 0;
+                let i = loop {
+                    match ref_name_map.binary_search_by_key(&git_name.as_str(), |(n, _)| n) {
+                        Ok(_) => {
+                            tries += 1;
+                            git_name = format!("{unbranched_name}_{tries}");
+                        }
+                        Err(i) => break i,
+                    }
+                };
+                if git_name != *unbranched_name {
+                    tracing::warn!(
+                        "using unbranched name \"{}\" instead of \"{}\" to avoid repetition",
+                        git_name.escape_default(),
+                        unbranched_name.escape_default(),
+                    );
+                }
+                ref_name_map.insert(i, (git_name, None));
             }
         }
 
@@ -109,11 +286,16 @@ impl Stage<'_> {
                 continue;
             }
 
-            let renamer = if branch_data.is_tag {
-                &options.rename_tags
-            } else {
-                &options.rename_branches
-            };
+            if fixed_names.contains_key(&Some(branch_i)) {
+                continue;
+            }
+
+            // The renames are those of the layout in which the branch got
+            // its current path.
+            let renamer = options.branch_renamer(
+                branch_data.is_tag,
+                stage1_out.root_rev_data[branch_data.svn_path_root_rev()].svn_rev,
+            );
 
             let pre_git_name = renamer.rename(&branch_data.svn_path);
 
@@ -166,13 +348,22 @@ impl Stage<'_> {
             let git_name = &ref_name_map[i].0;
             let mut new_git_name = git_name.clone();
 
+            let is_fixed = fixed_names.contains_key(&ref_name_map[i].1);
+
             let mut tries = 0;
             loop {
                 let mut ok = true;
-                for (j, (check_name, _)) in ref_name_map.iter().enumerate() {
+                for (j, (check_name, check_branch)) in ref_name_map.iter().enumerate() {
                     if i != j
-                        && strip_path_prefix(check_name.as_bytes(), new_git_name.as_bytes())
+                        && !is_fixed
+                        && (strip_path_prefix(check_name.as_bytes(), new_git_name.as_bytes())
                             .is_some()
+                            || (fixed_names.contains_key(check_branch)
+                                && strip_path_prefix(
+                                    new_git_name.as_bytes(),
+                                    check_name.as_bytes(),
+                                )
+                                .is_some()))
                     {
                         ok = false;
                         break;
@@ -211,14 +402,28 @@ impl Stage<'_> {
             }
         }
 
-        (final_unbranched_name, final_branch_name_map)
+        Ok((final_unbranched_name, final_branch_name_map))
     }
 
     fn run_inner(&mut self, reachable_revs: &BTreeSet<usize>) -> Result<(), ConvertError> {
         tracing::info!("Emitting unbranched commits");
 
         if self.unbranched_name.is_some() {
-            for i in 0..self.stage1_out.unbranched_rev_data.len() {
+            for i in self.num_unbranched_commits..self.stage1_out.unbranched_rev_data.len() {
+                super::check_interrupted()?;
+
+                // Only the last commit before the window is emitted, as the
+                // root commit holding the history before the window.
+                if self
+                    .stage1_out
+                    .unbranched_rev_data
+                    .get(i + 1)
+                    .is_some_and(|next| next.root_rev < self.history.window_start)
+                {
+                    self.num_unbranched_commits += 1;
+                    continue;
+                }
+
                 let svn_rev = self.stage1_out.root_rev_data
                     [self.stage1_out.unbranched_rev_data[i].root_rev]
                     .svn_rev;
@@ -237,6 +442,13 @@ impl Stage<'_> {
 
         let mut prev_svn_rev = 0;
         for &i in reachable_revs.iter() {
+            if self.branch_rev_git_data.contains_key(&i) {
+                // Already emitted in a previous run
+                continue;
+            }
+
+            super::check_interrupted()?;
+
             let svn_rev =
                 self.stage1_out.root_rev_data[self.stage1_out.branch_rev_data[i].root_rev].svn_rev;
             if svn_rev != prev_svn_rev {
@@ -257,6 +469,13 @@ impl Stage<'_> {
             prev_svn_rev = svn_rev;
         }
 
+        if self.options.rename_aliases {
+            self.set_rename_aliases();
+        }
+        if let Some(ref rename_log_path) = self.options.rename_log {
+            self.write_rename_log(rename_log_path)?;
+        }
+
         match self.stage1_out.head_branch {
             stage1::Head::Branch(branch) => {
                 self.git_import.set_head(&self.refs_names[&branch]);
@@ -270,14 +489,140 @@ impl Stage<'_> {
         Ok(())
     }
 
+    /// Sets a ref with each previous name of the moved branches and tags,
+    /// pointing to their last commit before each move.
+    fn set_rename_aliases(&mut self) {
+        let mut taken_names: Vec<String> = self
+            .refs_names
+            .values()
+            .cloned()
+            .chain(self.unbranched_name.clone())
+            .collect();
+
+        for (branch, branch_data) in self.stage1_out.branch_data.iter().enumerate() {
+            if !self.refs_names.contains_key(&branch) {
+                continue;
+            }
+
+            for (i, (moved_at, prev_svn_path)) in branch_data.prev_svn_paths.iter().enumerate() {
+                let rev_map_i = branch_data
+                    .rev_map
+                    .partition_point(|&(root_rev, _)| root_rev < *moved_at);
+                let Some(git_data) = rev_map_i
+                    .checked_sub(1)
+                    .and_then(|i| self.branch_rev_git_data.get(&branch_data.rev_map[i].1))
+                else {
+                    continue;
+                };
+
+                // The renames are those of the layout in which the branch got
+                // that path.
+                let path_root_rev = if i == 0 {
+                    branch_data.first_root_rev
+                } else {
+                    branch_data.prev_svn_paths[i - 1].0
+                };
+                let renamer = self.options.branch_renamer(
+                    branch_data.is_tag,
+                    self.stage1_out.root_rev_data[path_root_rev].svn_rev,
+                );
+                let mut git_name = git::legalize_branch_name(&renamer.rename(prev_svn_path));
+                if branch_data.deleted {
+                    git_name.insert_str(0, "deleted/");
+                }
+                let alias_name = if branch_data.is_tag {
+                    format!("refs/tags/{git_name}")
+                } else {
+                    format!("refs/heads/{git_name}")
+                };
+
+                let collides = taken_names.iter().any(|taken_name| {
+                    strip_path_prefix(taken_name.as_bytes(), alias_name.as_bytes()).is_some()
+                        || strip_path_prefix(alias_name.as_bytes(), taken_name.as_bytes()).is_some()
+                });
+                if collides {
+                    tracing::warn!(
+                        "not creating alias \"{}\" of \"{}\" because it collides with another ref",
+                        alias_name.escape_default(),
+                        self.refs_names[&branch].escape_default(),
+                    );
+                    continue;
+                }
+
+                tracing::debug!(
+                    "creating alias \"{}\" of \"{}\"",
+                    alias_name.escape_default(),
+                    self.refs_names[&branch].escape_default(),
+                );
+                self.git_import
+                    .set_ref(&alias_name, git_data.git_commit_oid);
+                taken_names.push(alias_name);
+            }
+        }
+    }
+
+    /// Writes a line for each move of a branch or tag, with the SVN
+    /// revision, the old and new SVN paths and the Git ref, separated by
+    /// tabs.
+    fn write_rename_log(&self, path: &std::path::Path) -> Result<(), ConvertError> {
+        let mut rename_log = String::new();
+        for (branch, branch_data) in self.stage1_out.branch_data.iter().enumerate() {
+            for (i, (moved_at, prev_svn_path)) in branch_data.prev_svn_paths.iter().enumerate() {
+                let new_svn_path = branch_data
+                    .prev_svn_paths
+                    .get(i + 1)
+                    .map_or(&branch_data.svn_path, |(_, path)| path);
+                rename_log.push_str(&format!(
+                    "{}\t{}\t{}\t{}\n",
+                    self.stage1_out.root_rev_data[*moved_at].svn_rev,
+                    prev_svn_path.escape_ascii(),
+                    new_svn_path.escape_ascii(),
+                    self.refs_names.get(&branch).map_or("-", String::as_str),
+                ));
+            }
+        }
+
+        std::fs::write(path, rename_log).map_err(|e| {
+            tracing::error!("failed to write rename log {path:?}: {e}");
+            ConvertError
+        })
+    }
+
+    /// Returns the UUID and, in merged conversions, the name of the
+    /// repository the revision of a root commit comes from.
+    fn svn_origin(&self, root_commit: usize) -> (Option<&uuid::Uuid>, Option<&str>) {
+        match self.stage1_out.merge_origin {
+            Some(ref origin) => {
+                let (ref name, ref uuid) = origin.sources[origin.root_rev_sources[root_commit]];
+                (uuid.as_ref(), Some(name.as_str()))
+            }
+            None => (self.stage1_out.svn_uuid.as_ref(), None),
+        }
+    }
+
+    /// Returns the SVN path of the branch of a revision, in its source
+    /// repository in merged conversions.
+    fn branch_rev_svn_path(&self, branch_rev: usize) -> &[u8] {
+        match self.stage1_out.merge_origin {
+            Some(ref origin) => &origin.branch_rev_svn_paths[branch_rev],
+            None => {
+                let branch_rev_data = &self.stage1_out.branch_rev_data[branch_rev];
+                self.stage1_out.branch_data[branch_rev_data.branch]
+                    .svn_path_at(branch_rev_data.root_rev)
+            }
+        }
+    }
+
     fn make_unbranched_commit(&mut self, unbranched_rev: usize) -> Result<(), ConvertError> {
         let unbranch_rev_data = &self.stage1_out.unbranched_rev_data[unbranched_rev];
         let root_commit = unbranch_rev_data.root_rev;
+        let (svn_uuid, svn_source) = self.svn_origin(root_commit);
 
         let git_commit_meta = self
             .metadata_maker
             .make_git_commit_meta(
-                self.stage1_out.svn_uuid.as_ref(),
+                svn_uuid,
+                svn_source,
                 self.stage1_out.root_rev_data[root_commit].svn_rev,
                 None,
                 &self.stage1_out.root_rev_data[root_commit].svn_rev_props,
@@ -306,6 +651,7 @@ impl Stage<'_> {
         self.git_import
             .set_ref(self.unbranched_name.as_deref().unwrap(), git_commit_oid);
 
+        self.num_unbranched_commits += 1;
         self.last_unbranched_commit = Some(git_commit_oid);
 
         Ok(())
@@ -319,16 +665,16 @@ impl Stage<'_> {
         let branch_rev_data = &self.stage1_out.branch_rev_data[branch_rev];
         let branch = branch_rev_data.branch;
         let branch_data = &self.stage1_out.branch_data[branch];
-        let branch_path = &branch_data.svn_path;
         let root_commit = branch_rev_data.root_rev;
-        let parent_commit = branch_rev_data.parent;
+        let branch_path = branch_data.svn_path_at(root_commit);
+        let parent_commit = self.history.git_parent(branch_rev);
 
-        let (new_merges, new_cherrypicks) =
-            if self.options.enable_merges && branch_rev_data.parent.is_some() {
-                self.analyze_merges(reachable_revs, branch_rev)
-            } else {
-                (BTreeSet::new(), BTreeSet::new())
-            };
+        let (new_merges, new_cherrypicks) = if self.options.enable_merges && parent_commit.is_some()
+        {
+            self.analyze_merges(reachable_revs, branch_rev)
+        } else {
+            (BTreeSet::new(), BTreeSet::new())
+        };
 
         let mut git_merges = Vec::new();
         for &merge in new_merges.iter() {
@@ -367,37 +713,61 @@ impl Stage<'_> {
             }
         }
 
-        let git_commit_meta = self
-            .metadata_maker
-            .make_git_commit_meta(
-                self.stage1_out.svn_uuid.as_ref(),
-                self.stage1_out.root_rev_data[root_commit].svn_rev,
-                Some(branch_path),
-                &self.stage1_out.root_rev_data[root_commit].svn_rev_props,
-            )
-            .map_err(|e| {
-                tracing::error!("failed to make git commit metadata: {e}");
-                ConvertError
-            })?;
-
-        let mut parents = smallvec::SmallVec::new();
-        parents.extend(parent_commit.map(|c| self.branch_rev_git_data[&c].git_commit_oid));
-        parents.extend(git_merges);
-
-        let git_commit_oid = self.git_import.put(
-            gix_object::Commit {
-                tree: branch_rev_data.tree_oid,
-                parents,
-                author: git_commit_meta.author,
-                committer: git_commit_meta.committer,
-                encoding: None,
-                message: git_commit_meta.message.into(),
-                extra_headers: vec![],
-            },
-            None,
-        )?;
+        // With a subdirectory filter, commits that do not change it are
+        // dropped, unless they merge other commits.
+        let unchanged_parent = parent_commit.filter(|&parent| {
+            !self.options.subdirectory.is_empty()
+                && git_merges.is_empty()
+                && self.stage1_out.branch_rev_data[parent].tree_oid == branch_rev_data.tree_oid
+        });
+        let git_commit_oid = if let Some(parent) = unchanged_parent {
+            tracing::debug!(
+                "dropping commit on branch \"{}\" that does not change the subdirectory",
+                branch_path.escape_ascii(),
+            );
+            self.branch_rev_git_data[&parent].git_commit_oid
+        } else {
+            let (svn_uuid, svn_source) = self.svn_origin(root_commit);
+            let git_commit_meta = self
+                .metadata_maker
+                .make_git_commit_meta(
+                    svn_uuid,
+                    svn_source,
+                    self.stage1_out.root_rev_data[root_commit].svn_rev,
+                    Some(self.branch_rev_svn_path(branch_rev)),
+                    &self.stage1_out.root_rev_data[root_commit].svn_rev_props,
+                )
+                .map_err(|e| {
+                    tracing::error!("failed to make git commit metadata: {e}");
+                    ConvertError
+                })?;
+
+            let mut parents = smallvec::SmallVec::new();
+            parents.extend(parent_commit.map(|c| self.branch_rev_git_data[&c].git_commit_oid));
+            parents.extend(git_merges);
+
+            self.git_import.put(
+                gix_object::Commit {
+                    tree: branch_rev_data.tree_oid,
+                    parents,
+                    author: git_commit_meta.author,
+                    committer: git_commit_meta.committer,
+                    encoding: None,
+                    message: git_commit_meta.message.into(),
+                    extra_headers: vec![],
+                },
+                None,
+            )?
+        };
 
-        if !branch_data.deleted || self.options.keep_deleted_branches {
+        let set_ref = if self.history.is_before_window(branch_rev) {
+            // Revisions before the window are emitted as root commits of
+            // later ones, but only the tip of a live branch gets a ref.
+            !branch_data.deleted && branch_data.tip_commit == Some(branch_rev)
+        } else {
+            !branch_data.deleted || self.options.keep_deleted_branches
+        };
+        if set_ref {
             self.git_import
                 .set_ref(&self.refs_names[&branch], git_commit_oid);
         }
@@ -420,17 +790,19 @@ impl Stage<'_> {
         let branch_rev_data = &self.stage1_out.branch_rev_data[branch_rev];
         let branch = branch_rev_data.branch;
         let branch_data = &self.stage1_out.branch_data[branch];
-        let branch_path = &branch_data.svn_path;
         let root_commit = branch_rev_data.root_rev;
+        let branch_path = branch_data.svn_path_at(root_commit);
 
         assert_eq!(branch_data.rev_map.len(), 1);
 
+        let (svn_uuid, svn_source) = self.svn_origin(root_commit);
         let git_tag_meta = self
             .metadata_maker
             .make_git_tag_meta(
-                self.stage1_out.svn_uuid.as_ref(),
+                svn_uuid,
+                svn_source,
                 self.stage1_out.root_rev_data[root_commit].svn_rev,
-                branch_path,
+                self.branch_rev_svn_path(branch_rev),
                 &self.stage1_out.root_rev_data[root_commit].svn_rev_props,
             )
             .map_err(|e| {
@@ -438,7 +810,7 @@ impl Stage<'_> {
                 ConvertError
             })?;
 
-        let target_rev = branch_rev_data.parent.unwrap();
+        let target_rev = self.history.git_parent(branch_rev).unwrap();
         let target_commit_oid = self.branch_rev_git_data[&target_rev].git_commit_oid;
 
         let git_tag_oid = self.git_import.put(
@@ -456,7 +828,9 @@ impl Stage<'_> {
             None,
         )?;
 
-        if !branch_data.deleted || self.options.keep_deleted_tags {
+        if !branch_data.deleted
+            || (self.options.keep_deleted_tags && !self.history.is_before_window(branch_rev))
+        {
             self.git_import
                 .set_ref(&self.refs_names[&branch], git_tag_oid);
         }
@@ -489,9 +863,7 @@ impl Stage<'_> {
             return (BTreeSet::new(), BTreeSet::new());
         }
 
-        let parent_commit = self.stage1_out.branch_rev_data[branch_commit]
-            .parent
-            .unwrap();
+        let parent_commit = self.history.git_parent(branch_commit).unwrap();
 
         // Gather previous cherry-picks...
         let mut inh_cherrypicks = BTreeSet::new();
@@ -506,7 +878,7 @@ impl Stage<'_> {
                 inh_cherrypicks.extend(&self.branch_rev_git_data[&some_commit].cherrypicks);
                 visit_queue.extend(&self.branch_rev_git_data[&some_commit].merges);
 
-                some_commit = match self.stage1_out.branch_rev_data[some_commit].parent {
+                some_commit = match self.history.git_parent(some_commit) {
                     Some(c) => c,
                     None => break,
                 };
@@ -523,10 +895,15 @@ impl Stage<'_> {
                 reachable_revs,
                 some_commit,
             ) {
-                svn_merges.extend(&self.stage1_out.branch_rev_data[some_commit].added_svn_merges);
+                svn_merges.extend(
+                    self.stage1_out.branch_rev_data[some_commit]
+                        .added_svn_merges
+                        .iter()
+                        .filter(|&&merge| !self.history.is_before_window(merge)),
+                );
             }
 
-            history_commit = self.stage1_out.branch_rev_data[some_commit].parent;
+            history_commit = self.history.git_parent(some_commit);
         }
 
         let mut new_merges = BTreeSet::new();
@@ -548,7 +925,12 @@ impl Stage<'_> {
             } else {
                 let mut parent = svn_merge;
                 loop {
-                    parent = self.stage1_out.branch_rev_data[parent].parent.unwrap();
+                    parent = match self.history.git_parent(parent) {
+                        Some(c) => c,
+                        // History starts at the window, without reaching
+                        // the destination history
+                        None => break false,
+                    };
 
                     if merged_history.contains(&parent) {
                         // The parent of the cherry-pick is merged, so
@@ -590,7 +972,7 @@ impl Stage<'_> {
                 merged_history.insert(svn_merge);
                 inh_cherrypicks.extend(&self.branch_rev_git_data[&svn_merge].cherrypicks);
 
-                visit_queue.push_back(self.stage1_out.branch_rev_data[svn_merge].parent.unwrap());
+                visit_queue.extend(self.history.git_parent(svn_merge));
                 visit_queue.extend(&self.branch_rev_git_data[&svn_merge].merges);
 
                 while let Some(mut some_commit) = visit_queue.pop_front() {
@@ -599,7 +981,10 @@ impl Stage<'_> {
                         // Remove parent in favor of merging the child
                         new_merges.remove(&some_commit);
 
-                        some_commit = self.stage1_out.branch_rev_data[some_commit].parent.unwrap();
+                        some_commit = match self.history.git_parent(some_commit) {
+                            Some(c) => c,
+                            None => break,
+                        };
                     }
                     new_merges.remove(&some_commit);
                 }
@@ -637,6 +1022,45 @@ impl Stage<'_> {
     }
 }
 
+/// History of branch revisions as emitted to git.
+///
+/// It matches the SVN history, except when conversion starts at a
+/// revision window. In that case, each branch starts from a root commit
+/// with its last revision before the window, and copies from earlier
+/// revisions are treated as copies from that root commit. Tags still
+/// point to the exact copied revision.
+#[derive(Copy, Clone)]
+struct History<'a> {
+    stage1_out: &'a stage1::Output,
+    /// First root revision inside the window
+    window_start: usize,
+}
+
+impl History<'_> {
+    fn is_before_window(&self, branch_rev: usize) -> bool {
+        self.stage1_out.branch_rev_data[branch_rev].root_rev < self.window_start
+    }
+
+    fn git_parent(&self, branch_rev: usize) -> Option<usize> {
+        let branch_rev_data = &self.stage1_out.branch_rev_data[branch_rev];
+        let parent = branch_rev_data.parent?;
+        if self.stage1_out.branch_data[branch_rev_data.branch].is_tag {
+            Some(parent)
+        } else if self.is_before_window(branch_rev) {
+            None
+        } else if self.is_before_window(parent) {
+            let parent_branch =
+                &self.stage1_out.branch_data[self.stage1_out.branch_rev_data[parent].branch];
+            let i = parent_branch
+                .rev_map
+                .partition_point(|&(root_rev, _)| root_rev < self.window_start);
+            Some(parent_branch.rev_map[i - 1].1)
+        } else {
+            Some(parent)
+        }
+    }
+}
+
 fn strip_path_prefix<'a>(path: &'a [u8], prefix: &[u8]) -> Option<&'a [u8]> {
     if let Some(suffix) = path.strip_prefix(prefix) {
         if suffix.is_empty() {
@@ -0,0 +1,474 @@
+//! Persistence of the conversion state, used to continue a conversion in
+//! later runs.
+
+use std::collections::BTreeSet;
+use std::hash::Hash;
+
+use super::bin_ser_de::{self, DeserializeError};
+use super::{ConvertError, stage1, stage2};
+use crate::{FHashMap, FHashSet};
+
+const MAGIC: &[u8] = b"\0svn2git state\0";
+const FORMAT_VERSION: u32 = 3;
+
+pub(super) struct ConvState {
+    pub(super) stage1: stage1::Output,
+    /// Not available in checkpoints taken before stage 2 of the first
+    /// conversion.
+    pub(super) stage2: Option<stage2::State>,
+}
+
+pub(super) fn load(path: &std::path::Path) -> Result<ConvState, ConvertError> {
+    let raw = std::fs::read(path).map_err(|e| {
+        tracing::error!("failed to read conversion state {path:?}: {e}");
+        ConvertError
+    })?;
+
+    let Some(mut src) = raw.strip_prefix(MAGIC) else {
+        tracing::error!("invalid conversion state {path:?}");
+        return Err(ConvertError);
+    };
+    let version = bin_ser_de::deserialize_u32_from(&mut src).map_err(|_| {
+        tracing::error!("invalid conversion state {path:?}");
+        ConvertError
+    })?;
+    if version != FORMAT_VERSION {
+        tracing::error!("unsupported conversion state version {version} in {path:?}");
+        return Err(ConvertError);
+    }
+
+    let state = deserialize_state_from(&mut src)
+        .ok()
+        .filter(|_| src.is_empty())
+        .ok_or_else(|| {
+            tracing::error!("invalid conversion state {path:?}");
+            ConvertError
+        })?;
+
+    Ok(state)
+}
+
+pub(super) fn save(
+    path: &std::path::Path,
+    stage1_out: &stage1::Output,
+    stage2_state: Option<&stage2::State>,
+) -> Result<(), ConvertError> {
+    let mut raw = Vec::new();
+    raw.extend(MAGIC);
+    bin_ser_de::serialize_u32_into(FORMAT_VERSION, &mut raw);
+    serialize_stage1_into(stage1_out, &mut raw);
+    serialize_option_into(stage2_state, &mut raw, serialize_stage2_into);
+
+    // Write to a temporary file first, so a crash does not leave a
+    // half-written state.
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, raw).map_err(|e| {
+        tracing::error!("failed to write conversion state {tmp_path:?}: {e}");
+        ConvertError
+    })?;
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        tracing::error!("failed to rename {tmp_path:?} to {path:?}: {e}");
+        ConvertError
+    })?;
+
+    Ok(())
+}
+
+fn serialize_stage1_into(stage1_out: &stage1::Output, out: &mut Vec<u8>) {
+    serialize_uuid_into(stage1_out.svn_uuid.as_ref(), out);
+
+    serialize_seq_into(stage1_out.root_rev_data.iter(), out, |rev_data, out| {
+        bin_ser_de::serialize_u32_into(rev_data.svn_rev, out);
+        serialize_seq_into(rev_data.svn_rev_props.iter(), out, |(key, value), out| {
+            bin_ser_de::serialize_byte_slice_into(key, out);
+            bin_ser_de::serialize_byte_slice_into(value, out);
+        });
+        bin_ser_de::serialize_oid_into(&rev_data.svn_tree_oid, out);
+    });
+
+    serialize_seq_into(
+        stage1_out.unbranched_rev_data.iter(),
+        out,
+        |rev_data, out| {
+            bin_ser_de::serialize_usize_into(rev_data.root_rev, out);
+            bin_ser_de::serialize_oid_into(&rev_data.tree_oid, out);
+        },
+    );
+
+    serialize_seq_into(stage1_out.branch_data.iter(), out, |branch_data, out| {
+        bin_ser_de::serialize_byte_slice_into(&branch_data.svn_path, out);
+        bin_ser_de::serialize_bool_into(branch_data.is_tag, out);
+        bin_ser_de::serialize_byte_slice_into(&branch_data.partial_sub_path, out);
+        bin_ser_de::serialize_bool_into(branch_data.deleted, out);
+        serialize_option_into(
+            branch_data.tip_commit,
+            out,
+            bin_ser_de::serialize_usize_into,
+        );
+        bin_ser_de::serialize_usize_into(branch_data.first_root_rev, out);
+        bin_ser_de::serialize_usize_into(branch_data.last_root_rev, out);
+        serialize_seq_into(branch_data.rev_map.iter(), out, |&(a, b), out| {
+            bin_ser_de::serialize_usize_into(a, out);
+            bin_ser_de::serialize_usize_into(b, out);
+        });
+        serialize_seq_into(
+            branch_data.prev_svn_paths.iter(),
+            out,
+            |(moved_at, path), out| {
+                bin_ser_de::serialize_usize_into(*moved_at, out);
+                bin_ser_de::serialize_byte_slice_into(path, out);
+            },
+        );
+    });
+
+    serialize_seq_into(stage1_out.branch_rev_data.iter(), out, |rev_data, out| {
+        bin_ser_de::serialize_usize_into(rev_data.branch, out);
+        serialize_option_into(rev_data.parent, out, bin_ser_de::serialize_usize_into);
+        bin_ser_de::serialize_usize_into(rev_data.tail, out);
+        bin_ser_de::serialize_usize_into(rev_data.root_rev, out);
+        bin_ser_de::serialize_bool_into(rev_data.required_in_mergeinfo, out);
+        serialize_usize_set_into(&rev_data.added_svn_merges, out);
+        serialize_usize_set_into(&rev_data.removed_svn_merges, out);
+        bin_ser_de::serialize_bool_into(rev_data.ignore_merges, out);
+        serialize_usize_set_into(&rev_data.fully_reverted_merges_in, out);
+        bin_ser_de::serialize_oid_into(&rev_data.tree_oid, out);
+    });
+
+    match stage1_out.head_branch {
+        stage1::Head::Branch(branch) => {
+            out.push(0);
+            bin_ser_de::serialize_usize_into(branch, out);
+        }
+        stage1::Head::Unbranched => out.push(1),
+    }
+
+    let state = &stage1_out.state;
+    serialize_uuid_into(state.dump_uuid.as_ref(), out);
+    serialize_seq_into(state.tree_map.iter(), out, |(svn_oid, git_oid), out| {
+        bin_ser_de::serialize_oid_into(svn_oid, out);
+        serialize_option_into(git_oid.as_ref(), out, bin_ser_de::serialize_oid_into);
+    });
+    serialize_seq_into(state.live_branches.iter(), out, |(path, &branch), out| {
+        bin_ser_de::serialize_byte_slice_into(path, out);
+        bin_ser_de::serialize_usize_into(branch, out);
+    });
+    serialize_seq_into(state.path_to_branch.iter(), out, |(sub_path, map), out| {
+        bin_ser_de::serialize_byte_slice_into(sub_path, out);
+        serialize_seq_into(map.iter(), out, |(path, branches), out| {
+            bin_ser_de::serialize_byte_slice_into(path, out);
+            serialize_seq_into(
+                branches.iter().copied(),
+                out,
+                bin_ser_de::serialize_usize_into,
+            );
+        });
+    });
+    bin_ser_de::serialize_bool_into(state.has_partial_branches, out);
+    serialize_seq_into(
+        state.branch_path_commits.iter(),
+        out,
+        |(path, commits), out| {
+            bin_ser_de::serialize_byte_slice_into(path, out);
+            serialize_seq_into(commits.iter(), out, |&(a, b), out| {
+                bin_ser_de::serialize_usize_into(a, out);
+                bin_ser_de::serialize_usize_into(b, out);
+            });
+        },
+    );
+}
+
+fn serialize_stage2_into(state: &stage2::State, out: &mut Vec<u8>) {
+    serialize_option_into(state.unbranched_name.as_deref(), out, |name, out| {
+        bin_ser_de::serialize_byte_slice_into(name.as_bytes(), out);
+    });
+    serialize_seq_into(state.refs_names.iter(), out, |(&branch, name), out| {
+        bin_ser_de::serialize_usize_into(branch, out);
+        bin_ser_de::serialize_byte_slice_into(name.as_bytes(), out);
+    });
+    serialize_seq_into(
+        state.deleted_named.iter().copied(),
+        out,
+        bin_ser_de::serialize_usize_into,
+    );
+    serialize_seq_into(
+        state.moves_named.iter(),
+        out,
+        |(&branch, &num_moves), out| {
+            bin_ser_de::serialize_usize_into(branch, out);
+            bin_ser_de::serialize_usize_into(num_moves, out);
+        },
+    );
+    bin_ser_de::serialize_usize_into(state.num_unbranched_commits, out);
+    serialize_option_into(
+        state.last_unbranched_commit.as_ref(),
+        out,
+        bin_ser_de::serialize_oid_into,
+    );
+    serialize_seq_into(
+        state.branch_rev_git_data.iter(),
+        out,
+        |(&rev, data), out| {
+            bin_ser_de::serialize_usize_into(rev, out);
+            bin_ser_de::serialize_oid_into(&data.git_commit_oid, out);
+            serialize_usize_set_into(&data.merges, out);
+            serialize_usize_set_into(&data.cherrypicks, out);
+        },
+    );
+}
+
+fn deserialize_state_from(src: &mut &[u8]) -> Result<ConvState, DeserializeError> {
+    let stage1 = deserialize_stage1_from(src)?;
+    let stage2 = deserialize_option_from(src, deserialize_stage2_from)?;
+    Ok(ConvState { stage1, stage2 })
+}
+
+fn deserialize_stage1_from(src: &mut &[u8]) -> Result<stage1::Output, DeserializeError> {
+    let svn_uuid = deserialize_uuid_from(src)?;
+
+    let root_rev_data = deserialize_seq_from(src, |src| {
+        Ok(stage1::RootCommitData {
+            svn_rev: bin_ser_de::deserialize_u32_from(src)?,
+            svn_rev_props: deserialize_map_from(src, |src| {
+                Ok((
+                    bin_ser_de::deserialize_byte_slice_from(src)?,
+                    bin_ser_de::deserialize_byte_slice_from(src)?,
+                ))
+            })?,
+            svn_tree_oid: bin_ser_de::deserialize_oid_from(src)?,
+        })
+    })?;
+
+    let unbranched_rev_data = deserialize_seq_from(src, |src| {
+        Ok(stage1::UnbranchedRevData {
+            root_rev: bin_ser_de::deserialize_usize_from(src)?,
+            tree_oid: bin_ser_de::deserialize_oid_from(src)?,
+        })
+    })?;
+
+    let branch_data = deserialize_seq_from(src, |src| {
+        Ok(stage1::BranchData {
+            svn_path: bin_ser_de::deserialize_byte_slice_from(src)?,
+            is_tag: bin_ser_de::deserialize_bool_from(src)?,
+            partial_sub_path: bin_ser_de::deserialize_byte_slice_from(src)?,
+            deleted: bin_ser_de::deserialize_bool_from(src)?,
+            tip_commit: deserialize_option_from(src, bin_ser_de::deserialize_usize_from)?,
+            first_root_rev: bin_ser_de::deserialize_usize_from(src)?,
+            last_root_rev: bin_ser_de::deserialize_usize_from(src)?,
+            rev_map: deserialize_seq_from(src, deserialize_usize_pair_from)?,
+            prev_svn_paths: deserialize_seq_from(src, |src| {
+                Ok((
+                    bin_ser_de::deserialize_usize_from(src)?,
+                    bin_ser_de::deserialize_byte_slice_from(src)?,
+                ))
+            })?,
+        })
+    })?;
+
+    let branch_rev_data = deserialize_seq_from(src, |src| {
+        Ok(stage1::BranchRevData {
+            branch: bin_ser_de::deserialize_usize_from(src)?,
+            parent: deserialize_option_from(src, bin_ser_de::deserialize_usize_from)?,
+            tail: bin_ser_de::deserialize_usize_from(src)?,
+            root_rev: bin_ser_de::deserialize_usize_from(src)?,
+            required_in_mergeinfo: bin_ser_de::deserialize_bool_from(src)?,
+            added_svn_merges: deserialize_usize_set_from(src)?,
+            removed_svn_merges: deserialize_usize_set_from(src)?,
+            ignore_merges: bin_ser_de::deserialize_bool_from(src)?,
+            fully_reverted_merges_in: deserialize_usize_set_from(src)?,
+            tree_oid: bin_ser_de::deserialize_oid_from(src)?,
+        })
+    })?;
+
+    let head_branch = match bin_ser_de::deserialize_byte_from(src)? {
+        0 => stage1::Head::Branch(bin_ser_de::deserialize_usize_from(src)?),
+        1 => stage1::Head::Unbranched,
+        _ => return Err(DeserializeError),
+    };
+
+    let state = stage1::State {
+        dump_uuid: deserialize_uuid_from(src)?,
+        tree_map: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_oid_from(src)?,
+                deserialize_option_from(src, bin_ser_de::deserialize_oid_from)?,
+            ))
+        })?,
+        live_branches: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_byte_slice_from(src)?,
+                bin_ser_de::deserialize_usize_from(src)?,
+            ))
+        })?,
+        path_to_branch: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_byte_slice_from(src)?,
+                deserialize_map_from(src, |src| {
+                    Ok((
+                        bin_ser_de::deserialize_byte_slice_from(src)?,
+                        deserialize_seq_from(src, bin_ser_de::deserialize_usize_from)?,
+                    ))
+                })?,
+            ))
+        })?,
+        has_partial_branches: bin_ser_de::deserialize_bool_from(src)?,
+        branch_path_commits: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_byte_slice_from(src)?,
+                deserialize_seq_from(src, deserialize_usize_pair_from)?,
+            ))
+        })?,
+    };
+
+    // Check that indices are in bounds, so a corrupted state does not
+    // cause panics later.
+    let num_root_revs = root_rev_data.len();
+    let num_branches = branch_data.len();
+    let num_branch_revs = branch_rev_data.len();
+    let valid = unbranched_rev_data
+        .iter()
+        .all(|rev_data| rev_data.root_rev < num_root_revs)
+        && branch_data.iter().all(|branch_data| {
+            branch_data.tip_commit.is_none_or(|c| c < num_branch_revs)
+                && branch_data.first_root_rev < num_root_revs
+                && branch_data.last_root_rev < num_root_revs
+        })
+        && branch_rev_data.iter().all(|rev_data| {
+            rev_data.branch < num_branches
+                && rev_data.parent.is_none_or(|p| p < num_branch_revs)
+                && rev_data.root_rev < num_root_revs
+        })
+        && match head_branch {
+            stage1::Head::Branch(branch) => branch < num_branches,
+            stage1::Head::Unbranched => true,
+        };
+    if !valid {
+        return Err(DeserializeError);
+    }
+
+    Ok(stage1::Output {
+        svn_uuid,
+        root_rev_data,
+        unbranched_rev_data,
+        branch_data,
+        branch_rev_data,
+        head_branch,
+        merge_origin: None,
+        state,
+    })
+}
+
+fn deserialize_stage2_from(src: &mut &[u8]) -> Result<stage2::State, DeserializeError> {
+    let deserialize_string_from = |src: &mut &[u8]| {
+        String::from_utf8(bin_ser_de::deserialize_byte_slice_from(src)?)
+            .map_err(|_| DeserializeError)
+    };
+
+    Ok(stage2::State {
+        unbranched_name: deserialize_option_from(src, deserialize_string_from)?,
+        refs_names: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_usize_from(src)?,
+                deserialize_string_from(src)?,
+            ))
+        })?,
+        deleted_named: deserialize_seq_from(src, bin_ser_de::deserialize_usize_from)?
+            .into_iter()
+            .collect::<FHashSet<_>>(),
+        moves_named: deserialize_map_from(src, deserialize_usize_pair_from)?,
+        num_unbranched_commits: bin_ser_de::deserialize_usize_from(src)?,
+        last_unbranched_commit: deserialize_option_from(src, bin_ser_de::deserialize_oid_from)?,
+        branch_rev_git_data: deserialize_map_from(src, |src| {
+            Ok((
+                bin_ser_de::deserialize_usize_from(src)?,
+                stage2::BranchRevGitData {
+                    git_commit_oid: bin_ser_de::deserialize_oid_from(src)?,
+                    merges: deserialize_usize_set_from(src)?,
+                    cherrypicks: deserialize_usize_set_from(src)?,
+                },
+            ))
+        })?,
+    })
+}
+
+fn serialize_seq_into<T>(
+    items: impl ExactSizeIterator<Item = T>,
+    out: &mut Vec<u8>,
+    mut f: impl FnMut(T, &mut Vec<u8>),
+) {
+    bin_ser_de::serialize_usize_into(items.len(), out);
+    for item in items {
+        f(item, out);
+    }
+}
+
+fn serialize_option_into<T>(value: Option<T>, out: &mut Vec<u8>, f: impl FnOnce(T, &mut Vec<u8>)) {
+    if let Some(value) = value {
+        out.push(1);
+        f(value, out);
+    } else {
+        out.push(0);
+    }
+}
+
+fn serialize_uuid_into(uuid: Option<&uuid::Uuid>, out: &mut Vec<u8>) {
+    serialize_option_into(uuid, out, |uuid, out| out.extend(uuid.as_bytes()));
+}
+
+fn serialize_usize_set_into(set: &BTreeSet<usize>, out: &mut Vec<u8>) {
+    serialize_seq_into(set.iter().copied(), out, bin_ser_de::serialize_usize_into);
+}
+
+fn deserialize_seq_from<T>(
+    src: &mut &[u8],
+    mut f: impl FnMut(&mut &[u8]) -> Result<T, DeserializeError>,
+) -> Result<Vec<T>, DeserializeError> {
+    let len = bin_ser_de::deserialize_usize_from(src)?;
+    // Do not trust the length for preallocation, each item takes
+    // at least one byte.
+    let mut items = Vec::with_capacity(len.min(src.len()));
+    for _ in 0..len {
+        items.push(f(src)?);
+    }
+    Ok(items)
+}
+
+fn deserialize_map_from<K: Eq + Hash, V>(
+    src: &mut &[u8],
+    f: impl FnMut(&mut &[u8]) -> Result<(K, V), DeserializeError>,
+) -> Result<FHashMap<K, V>, DeserializeError> {
+    Ok(deserialize_seq_from(src, f)?.into_iter().collect())
+}
+
+fn deserialize_option_from<T>(
+    src: &mut &[u8],
+    f: impl FnOnce(&mut &[u8]) -> Result<T, DeserializeError>,
+) -> Result<Option<T>, DeserializeError> {
+    if bin_ser_de::deserialize_bool_from(src)? {
+        Ok(Some(f(src)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn deserialize_uuid_from(src: &mut &[u8]) -> Result<Option<uuid::Uuid>, DeserializeError> {
+    deserialize_option_from(src, |src| {
+        Ok(uuid::Uuid::from_bytes(
+            bin_ser_de::deserialize_byte_array_from(src)?,
+        ))
+    })
+}
+
+fn deserialize_usize_pair_from(src: &mut &[u8]) -> Result<(usize, usize), DeserializeError> {
+    Ok((
+        bin_ser_de::deserialize_usize_from(src)?,
+        bin_ser_de::deserialize_usize_from(src)?,
+    ))
+}
+
+fn deserialize_usize_set_from(src: &mut &[u8]) -> Result<BTreeSet<usize>, DeserializeError> {
+    Ok(
+        deserialize_seq_from(src, bin_ser_de::deserialize_usize_from)?
+            .into_iter()
+            .collect(),
+    )
+}
@@ -41,7 +41,7 @@ impl Node {
         }
 
         bin_ser_de::serialize_oid_into(&self.metadata, out);
-        out.extend(self.entries.len().to_ne_bytes());
+        bin_ser_de::serialize_usize_into(self.entries.len(), out);
         for (name, entry) in &self.entries {
             bin_ser_de::serialize_byte_slice_into(name, out);
             entry.serialize_into(out);
@@ -58,8 +58,9 @@ impl Node {
 
     fn deserialize_from(src: &mut &[u8]) -> Result<Self, DeserializeError> {
         let metadata = bin_ser_de::deserialize_oid_from(src)?;
-        let entries_len = usize::from_ne_bytes(bin_ser_de::deserialize_byte_array_from(src)?);
-        let mut entries = Vec::with_capacity(entries_len);
+        let entries_len = bin_ser_de::deserialize_usize_from(src)?;
+        // Each entry takes at least one byte.
+        let mut entries = Vec::with_capacity(entries_len.min(src.len()));
         for _ in 0..entries_len {
             let name = bin_ser_de::deserialize_byte_slice_from(src)?;
             let entry = NodeEntry::deserialize_from(src)?;
@@ -81,7 +82,7 @@ impl Node {
     ) -> Result<Option<NodeEntry>, DeserializeError> {
         let mut src = src;
         let _ = bin_ser_de::deserialize_oid_from(&mut src)?;
-        let entries_len = usize::from_ne_bytes(bin_ser_de::deserialize_byte_array_from(&mut src)?);
+        let entries_len = bin_ser_de::deserialize_usize_from(&mut src)?;
         for _ in 0..entries_len {
             let current_name = bin_ser_de::deserialize_byte_slice_from(&mut src)?;
             let current_entry = NodeEntry::deserialize_from(&mut src)?;
@@ -196,4 +197,26 @@ mod tests {
         let deserialized = Node::deserialize(&serialized).unwrap();
         assert_eq!(deserialized, tree);
     }
+
+    #[test]
+    fn serialize_fixed_width_little_endian() {
+        let hash_kind = gix_hash::Kind::Sha1;
+        let oid1 = gix_object::compute_hash(hash_kind, gix_object::Kind::Blob, b"obj 1").unwrap();
+        let oid2 = gix_object::compute_hash(hash_kind, gix_object::Kind::Blob, b"obj 2").unwrap();
+        let tree = Node {
+            metadata: oid1,
+            entries: [(b"dir".to_vec(), NodeEntry::Dir(oid2))]
+                .into_iter()
+                .collect(),
+        };
+
+        let mut expected = vec![0];
+        expected.extend(oid1.as_bytes());
+        expected.extend([1, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend([3, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend(b"dir");
+        expected.extend([0, 0]);
+        expected.extend(oid2.as_bytes());
+        assert_eq!(tree.serialize(), expected);
+    }
 }
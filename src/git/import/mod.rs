@@ -1,5 +1,5 @@
 use std::collections::{BTreeMap, VecDeque};
-use std::io::Write as _;
+use std::io::{Read as _, Seek as _, Write as _};
 
 use gix_hash::ObjectId;
 use gix_object::ObjectRef;
@@ -44,6 +44,13 @@ pub(crate) enum ImportError {
         dest_path: std::path::PathBuf,
         error: std::io::Error,
     },
+    OpenPackIndexError {
+        path: std::path::PathBuf,
+        error: gix_pack::index::init::Error,
+    },
+    InvalidPackedRefs {
+        path: std::path::PathBuf,
+    },
     Sha1Collision {
         hash: ObjectId,
     },
@@ -100,6 +107,15 @@ impl std::fmt::Display for ImportError {
                     "failed to rename {source_path:?} to {dest_path:?}: {error}"
                 )
             }
+            Self::OpenPackIndexError {
+                ref path,
+                ref error,
+            } => {
+                write!(f, "failed to open pack index {path:?}: {error}")
+            }
+            Self::InvalidPackedRefs { ref path } => {
+                write!(f, "invalid packed refs file {path:?}")
+            }
             Self::Sha1Collision { hash } => {
                 write!(f, "SHA-1 collision attack with hash {hash}")
             }
@@ -119,19 +135,37 @@ pub(crate) struct Importer {
     path: std::path::PathBuf,
     hash_kind: gix_hash::Kind,
     temp_storage: temp_storage_thread::TempStorageThread,
+    /// Directory where the temporary storage is kept after finishing, so
+    /// the import can be continued later with [`Importer::open`].
+    persist_dir: Option<std::path::PathBuf>,
+    /// Whether the persisted storage must be left as it was at the last
+    /// [`Importer::save_storage`] when finishing.
+    storage_frozen: bool,
+    /// Whether the repository existed before, see [`Importer::open`].
+    appending: bool,
+    /// Indices of the packs that already exist in the repository.
+    existing_packs: Vec<gix_pack::index::File>,
     empty_tree_oid: ObjectId,
     head_ref: String,
     refs: BTreeMap<String, ObjectId>,
 }
 
 impl Importer {
-    pub(crate) fn init(path: &std::path::Path, obj_cache_size: usize) -> Result<Self, ImportError> {
+    pub(crate) fn init(
+        path: &std::path::Path,
+        obj_cache_size: usize,
+        persist_dir: Option<&std::path::Path>,
+    ) -> Result<Self, ImportError> {
         init_repo(path)?;
 
         let hash_kind = gix_hash::Kind::Sha1;
 
-        let temp_storage =
-            temp_storage::TempStorage::create(&path.join("temp_storage"), obj_cache_size)?;
+        let temp_storage = if let Some(persist_dir) = persist_dir {
+            create_dir(persist_dir)?;
+            temp_storage::TempStorage::create(&persist_dir.join("objects"), obj_cache_size)?
+        } else {
+            temp_storage::TempStorage::create(&path.join("temp_storage"), obj_cache_size)?
+        };
         let temp_storage = temp_storage_thread::TempStorageThread::new(temp_storage);
 
         let empty_tree_oid =
@@ -141,16 +175,147 @@ impl Importer {
             path: path.to_path_buf(),
             hash_kind,
             temp_storage,
+            persist_dir: persist_dir.map(Into::into),
+            storage_frozen: false,
+            appending: false,
+            existing_packs: Vec::new(),
             empty_tree_oid,
             head_ref: "refs/heads/master".into(),
             refs: BTreeMap::new(),
         })
     }
 
+    /// Creates a new repository, starting with the objects of a storage
+    /// saved in `storage_dir` by another import.
+    ///
+    /// The storage is frozen (see [`Importer::freeze_storage`]), so it can
+    /// be reused again.
+    pub(crate) fn init_with_storage(
+        path: &std::path::Path,
+        obj_cache_size: usize,
+        storage_dir: &std::path::Path,
+    ) -> Result<Self, ImportError> {
+        let hash_kind = gix_hash::Kind::Sha1;
+
+        let temp_storage = temp_storage::TempStorage::open(
+            &storage_dir.join("objects"),
+            &storage_dir.join("objects.idx"),
+            obj_cache_size,
+        )?;
+
+        init_repo(path)?;
+
+        let temp_storage = temp_storage_thread::TempStorageThread::new(temp_storage);
+
+        let empty_tree_oid =
+            Self::put_inner(gix_object::Tree::empty(), hash_kind, None, &temp_storage)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            hash_kind,
+            temp_storage,
+            persist_dir: Some(storage_dir.into()),
+            storage_frozen: true,
+            appending: false,
+            existing_packs: Vec::new(),
+            empty_tree_oid,
+            head_ref: "refs/heads/master".into(),
+            refs: BTreeMap::new(),
+        })
+    }
+
+    /// Opens a repository previously created with [`Importer::init`] with
+    /// a `persist_dir`, to import more objects into it.
+    ///
+    /// The temporary storage is restored to the state of the last
+    /// [`Importer::save_storage`] or [`Importer::finish`]. New objects are
+    /// written to a new pack when finishing, and `HEAD` and `packed-refs`
+    /// are replaced.
+    pub(crate) fn open(
+        path: &std::path::Path,
+        obj_cache_size: usize,
+        persist_dir: &std::path::Path,
+    ) -> Result<Self, ImportError> {
+        let hash_kind = gix_hash::Kind::Sha1;
+
+        let temp_storage = temp_storage::TempStorage::open(
+            &persist_dir.join("objects"),
+            &persist_dir.join("objects.idx"),
+            obj_cache_size,
+        )?;
+        let temp_storage = temp_storage_thread::TempStorageThread::new(temp_storage);
+
+        let packs_dir = path.join("objects").join("pack");
+        let mut existing_packs = Vec::new();
+        for dir_entry in std::fs::read_dir(&packs_dir).map_err(|e| ImportError::ReadFileError {
+            path: packs_dir.clone(),
+            error: e,
+        })? {
+            let dir_entry = dir_entry.map_err(|e| ImportError::ReadFileError {
+                path: packs_dir.clone(),
+                error: e,
+            })?;
+            let entry_path = dir_entry.path();
+            if entry_path.extension().is_some_and(|ext| ext == "idx") {
+                let pack_index =
+                    gix_pack::index::File::at(&entry_path, hash_kind).map_err(|e| {
+                        ImportError::OpenPackIndexError {
+                            path: entry_path,
+                            error: e,
+                        }
+                    })?;
+                existing_packs.push(pack_index);
+            }
+        }
+
+        // `packed-refs` does not exist if the previous import did not finish.
+        let packed_refs_path = path.join("packed-refs");
+        let appending = packed_refs_path.exists();
+        let refs = if appending {
+            read_packed_refs(&packed_refs_path)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let empty_tree_oid =
+            Self::put_inner(gix_object::Tree::empty(), hash_kind, None, &temp_storage)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            hash_kind,
+            temp_storage,
+            persist_dir: Some(persist_dir.into()),
+            storage_frozen: false,
+            appending,
+            existing_packs,
+            empty_tree_oid,
+            head_ref: "refs/heads/master".into(),
+            refs,
+        })
+    }
+
     pub(crate) fn abort(self) {
         let _ = self.temp_storage.finish();
     }
 
+    /// Flushes the temporary storage to the persistence directory, so it
+    /// can be restored with [`Importer::open`].
+    pub(crate) fn save_storage(&self) -> Result<(), ImportError> {
+        let persist_dir = self
+            .persist_dir
+            .as_ref()
+            .expect("importer without persistence directory");
+        self.temp_storage
+            .save_index(&persist_dir.join("objects.idx"))
+    }
+
+    /// Makes [`Importer::finish`] leave the persisted storage as it was at
+    /// the last [`Importer::save_storage`]. Objects added afterwards are
+    /// discarded when the storage is opened again.
+    pub(crate) fn freeze_storage(&mut self) {
+        self.storage_frozen = true;
+    }
+
     pub(crate) fn finish(
         self,
         mut progress_cb: impl FnMut(ImportFinishProgress),
@@ -160,6 +325,11 @@ impl Importer {
         let seen_objects = gather_objects(
             self.refs.values().copied(),
             &tmp_storage,
+            |oid| {
+                self.existing_packs
+                    .iter()
+                    .any(|pack_index| pack_index.lookup(oid).is_some())
+            },
             self.hash_kind,
             &mut progress_cb,
         )?;
@@ -168,30 +338,46 @@ impl Importer {
         packs_dir.push("objects");
         packs_dir.push("pack");
 
-        let (pack_hash, pack_index_entires) = write_pack_data(
-            &packs_dir,
-            self.hash_kind,
-            seen_objects.iter().copied(),
-            &tmp_storage,
-            &mut progress_cb,
-        )?;
-
-        tmp_storage.remove()?;
+        let pack = if !self.appending || !seen_objects.is_empty() {
+            Some(write_pack_data(
+                &packs_dir,
+                self.hash_kind,
+                seen_objects.iter().copied(),
+                &tmp_storage,
+                &mut progress_cb,
+            )?)
+        } else {
+            None
+        };
 
-        progress_cb(ImportFinishProgress::MakeIndex);
+        if let Some(ref persist_dir) = self.persist_dir {
+            if !self.storage_frozen {
+                tmp_storage.save_index(&persist_dir.join("objects.idx"))?;
+            }
+        } else {
+            tmp_storage.remove()?;
+        }
 
-        write_pack_index(&packs_dir, pack_hash, pack_index_entires)?;
+        if let Some((pack_hash, pack_index_entires)) = pack {
+            progress_cb(ImportFinishProgress::MakeIndex);
 
-        let head_path = self.path.join("HEAD");
-        create_file_fmt(head_path, format_args!("ref: {}\n", self.head_ref))?;
+            write_pack_index(&packs_dir, pack_hash, pack_index_entires)?;
+        }
 
         let mut packed_refs_data = Vec::<u8>::new();
         for (ref_name, ref_oid) in self.refs {
             packed_refs_data.extend(format!("{ref_oid} {ref_name}\n").as_bytes());
         }
 
+        let head_path = self.path.join("HEAD");
         let packed_refs_path = self.path.join("packed-refs");
-        create_file(packed_refs_path, &packed_refs_data)?;
+        if !self.appending {
+            create_file_fmt(head_path, format_args!("ref: {}\n", self.head_ref))?;
+            create_file(packed_refs_path, &packed_refs_data)?;
+        } else {
+            replace_file(head_path, format!("ref: {}\n", self.head_ref).as_bytes())?;
+            replace_file(packed_refs_path, &packed_refs_data)?;
+        }
 
         Ok(())
     }
@@ -241,10 +427,181 @@ impl Importer {
         Ok(obj_id)
     }
 
+    /// Creates a writer for a blob that is too large to be kept in memory.
+    /// The blob is stored with [`Importer::put_blob_stream`].
+    pub(crate) fn blob_writer(&self) -> Result<BlobWriter, ImportError> {
+        let path = self.path.join("temp_blob");
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|e| ImportError::CreateFileError {
+                path: path.clone(),
+                error: e,
+            })?;
+
+        Ok(BlobWriter {
+            path,
+            compressor: lz4_flex::frame::FrameEncoder::new(std::io::BufWriter::new(file)),
+            size: 0,
+        })
+    }
+
+    pub(crate) fn put_blob_stream(&mut self, writer: BlobWriter) -> Result<ObjectId, ImportError> {
+        let BlobWriter {
+            path,
+            compressor,
+            size,
+        } = writer;
+
+        let mut file = compressor
+            .finish()
+            .map_err(|e| ImportError::WriteFileError {
+                path: path.clone(),
+                error: e.into(),
+            })?
+            .into_inner()
+            .map_err(|e| ImportError::WriteFileError {
+                path: path.clone(),
+                error: e.into_error(),
+            })?;
+
+        // The size is needed to hash the object, so the compressed data is
+        // read again to hash it.
+        file.seek(std::io::SeekFrom::Start(0))
+            .map_err(|e| ImportError::SeekFileError {
+                path: path.clone(),
+                error: e,
+            })?;
+        let mut hasher = gix_hash::hasher(self.hash_kind);
+        hasher.update(&gix_object::encode::loose_header(
+            gix_object::Kind::Blob,
+            size,
+        ));
+        let mut decompressor = lz4_flex::frame::FrameDecoder::new(std::io::BufReader::new(&file));
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read_len = decompressor
+                .read(&mut buf)
+                .map_err(|e| ImportError::ReadFileError {
+                    path: path.clone(),
+                    error: e,
+                })?;
+            if read_len == 0 {
+                break;
+            }
+            hasher.update(&buf[..read_len]);
+        }
+        let obj_id = hasher.try_finalize().map_err(convert_hash_error)?;
+
+        self.temp_storage.insert_compressed(
+            obj_id,
+            gix_object::Kind::Blob,
+            size,
+            &mut file,
+            &path,
+        )?;
+
+        drop(file);
+        std::fs::remove_file(&path).map_err(|e| ImportError::RemoveFileError { path, error: e })?;
+
+        Ok(obj_id)
+    }
+
+    /// Copies the tree `tree_oid`, with its subtrees and blobs, from the
+    /// storage of `src`. Objects in `copied` are skipped, and the copied
+    /// ones are added to it.
+    ///
+    /// Objects keep their delta bases if these have already been copied,
+    /// so trees should be copied in the order they were imported.
+    pub(crate) fn copy_tree_from(
+        &mut self,
+        src: &Self,
+        tree_oid: ObjectId,
+        copied: &mut FHashSet<ObjectId>,
+    ) -> Result<(), ImportError> {
+        // Trees are added to `copied` once they are inserted, so they can
+        // be used as delta bases.
+        let mut queued_trees = FHashSet::default();
+        let mut tree_queue = Vec::new();
+        if !copied.contains(&tree_oid) {
+            queued_trees.insert(tree_oid);
+            tree_queue.push(tree_oid);
+        }
+
+        while let Some(tree_oid) = tree_queue.pop() {
+            for (_, entry_kind, entry_oid) in src.ls_tree(tree_oid)? {
+                match entry_kind {
+                    EntryKind::Tree => {
+                        if !copied.contains(&entry_oid) && queued_trees.insert(entry_oid) {
+                            tree_queue.push(entry_oid);
+                        }
+                    }
+                    EntryKind::Blob | EntryKind::BlobExecutable | EntryKind::Link => {
+                        if !copied.contains(&entry_oid) {
+                            self.copy_object_from(src, gix_object::Kind::Blob, entry_oid, copied)?;
+                        }
+                    }
+                    EntryKind::Commit => {}
+                }
+            }
+            self.copy_object_from(src, gix_object::Kind::Tree, tree_oid, copied)?;
+        }
+
+        Ok(())
+    }
+
+    fn copy_object_from(
+        &mut self,
+        src: &Self,
+        obj_kind: gix_object::Kind,
+        obj_id: ObjectId,
+        copied: &mut FHashSet<ObjectId>,
+    ) -> Result<(), ImportError> {
+        let delta_base = src
+            .temp_storage
+            .get_delta_base(obj_id)?
+            .filter(|delta_base| copied.contains(delta_base));
+
+        // Large blobs are streamed, as when they were imported.
+        if obj_kind == gix_object::Kind::Blob
+            && delta_base.is_none()
+            && src.temp_storage.get_size(obj_id)? > temp_storage::LARGE_OBJECT_SIZE
+        {
+            let mut writer = self.blob_writer()?;
+            std::io::copy(&mut src.get_blob_reader(obj_id)?, &mut writer).map_err(|e| {
+                ImportError::WriteFileError {
+                    path: writer.path.clone(),
+                    error: e,
+                }
+            })?;
+            let copied_oid = self.put_blob_stream(writer)?;
+            assert_eq!(copied_oid, obj_id, "object {obj_id} changed when copied");
+        } else {
+            let (_, raw_obj) = src.get_raw(obj_id)?;
+            self.temp_storage
+                .insert_raw(obj_id, obj_kind, raw_obj, delta_base)?;
+        }
+
+        copied.insert(obj_id);
+        Ok(())
+    }
+
     pub(crate) fn get_raw(&self, id: ObjectId) -> Result<(gix_object::Kind, Vec<u8>), ImportError> {
         self.temp_storage.get_raw(id)
     }
 
+    pub(crate) fn get_blob_size(&self, id: ObjectId) -> Result<u64, ImportError> {
+        self.temp_storage.get_size(id)
+    }
+
+    /// Opens a blob to read it without loading it entirely in memory.
+    pub(crate) fn get_blob_reader(&self, id: ObjectId) -> Result<BlobReader<'_>, ImportError> {
+        self.temp_storage.get_reader(id).map(BlobReader)
+    }
+
     pub(crate) fn get_blob(&self, id: ObjectId) -> Result<Vec<u8>, ImportError> {
         let (obj_kind, raw_obj) = self.temp_storage.get_raw(id)?;
         assert_eq!(
@@ -300,6 +657,29 @@ impl Importer {
         Ok(Some((cur_kind, cur_oid)))
     }
 
+    /// Returns the entries of the tree `tree_oid`, as `(name, kind, oid)`.
+    pub(crate) fn ls_tree(
+        &self,
+        tree_oid: ObjectId,
+    ) -> Result<Vec<(Vec<u8>, EntryKind, ObjectId)>, ImportError> {
+        let (obj_kind, raw_obj) = self.get_raw(tree_oid)?;
+        assert_eq!(
+            obj_kind,
+            gix_object::Kind::Tree,
+            "unexpected object kind for {tree_oid}",
+        );
+
+        let tree = gix_object::TreeRef::from_bytes(&raw_obj, self.hash_kind).unwrap_or_else(|_| {
+            panic!("failed to parse object {tree_oid}");
+        });
+
+        Ok(tree
+            .entries
+            .iter()
+            .map(|entry| (entry.filename.to_vec(), entry.mode.kind(), entry.oid.into()))
+            .collect())
+    }
+
     pub(crate) fn set_head(&mut self, head_ref: &str) {
         self.head_ref = head_ref.into();
     }
@@ -307,6 +687,10 @@ impl Importer {
     pub(crate) fn set_ref(&mut self, ref_name: &str, commit_oid: ObjectId) {
         self.refs.insert(ref_name.into(), commit_oid);
     }
+
+    pub(crate) fn remove_ref(&mut self, ref_name: &str) -> Option<ObjectId> {
+        self.refs.remove(ref_name)
+    }
 }
 
 pub(crate) enum ImportFinishProgress {
@@ -359,24 +743,51 @@ fn init_repo(path: &std::path::Path) -> Result<(), ImportError> {
     Ok(())
 }
 
+fn read_packed_refs(path: &std::path::Path) -> Result<BTreeMap<String, ObjectId>, ImportError> {
+    let raw = std::fs::read(path).map_err(|e| ImportError::ReadFileError {
+        path: path.into(),
+        error: e,
+    })?;
+    let raw =
+        String::from_utf8(raw).map_err(|_| ImportError::InvalidPackedRefs { path: path.into() })?;
+
+    let mut refs = BTreeMap::new();
+    for line in raw.lines() {
+        // Skip comments and peeled tags
+        if line.starts_with('#') || line.starts_with('^') {
+            continue;
+        }
+        let (raw_oid, ref_name) = line
+            .split_once(' ')
+            .ok_or_else(|| ImportError::InvalidPackedRefs { path: path.into() })?;
+        let oid = ObjectId::from_hex(raw_oid.as_bytes())
+            .map_err(|_| ImportError::InvalidPackedRefs { path: path.into() })?;
+        refs.insert(ref_name.into(), oid);
+    }
+
+    Ok(refs)
+}
+
+/// Gathers the objects reachable from `initial_set`, stopping at objects
+/// for which `is_packed` returns `true`.
 fn gather_objects(
     initial_set: impl IntoIterator<Item = ObjectId>,
     tmp_storage: &temp_storage::TempStorage,
+    is_packed: impl Fn(ObjectId) -> bool,
     hash_kind: gix_hash::Kind,
     mut cb: impl FnMut(ImportFinishProgress),
 ) -> Result<Vec<ObjectId>, ImportError> {
     let mut seen_objects = FHashSet::default();
     let mut obj_queue = VecDeque::new();
 
-    fn see(
-        obj_id: ObjectId,
-        seen_objects: &mut FHashSet<ObjectId>,
-        obj_queue: &mut VecDeque<ObjectId>,
-    ) {
-        if seen_objects.insert(obj_id) {
+    let see = |obj_id: ObjectId,
+               seen_objects: &mut FHashSet<ObjectId>,
+               obj_queue: &mut VecDeque<ObjectId>| {
+        if !seen_objects.contains(&obj_id) && !is_packed(obj_id) {
+            seen_objects.insert(obj_id);
             obj_queue.push_back(obj_id);
         }
-    }
+    };
 
     for init_oid in initial_set {
         see(init_oid, &mut seen_objects, &mut obj_queue);
@@ -403,7 +814,10 @@ fn gather_objects(
                             see(entry.oid.to_owned(), &mut seen_objects, &mut obj_queue);
                         }
                         EntryKind::Blob | EntryKind::BlobExecutable | EntryKind::Link => {
-                            seen_objects.insert(entry.oid.to_owned());
+                            let blob_oid = entry.oid.to_owned();
+                            if !is_packed(blob_oid) {
+                                seen_objects.insert(blob_oid);
+                            }
                         }
                         EntryKind::Commit => {}
                     }
@@ -440,6 +854,41 @@ fn gather_objects(
     Ok(seen_objects)
 }
 
+/// See [`Importer::blob_writer`].
+pub(crate) struct BlobWriter {
+    path: std::path::PathBuf,
+    compressor: lz4_flex::frame::FrameEncoder<std::io::BufWriter<std::fs::File>>,
+    size: u64,
+}
+
+impl std::io::Write for BlobWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.compressor.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.compressor.flush()
+    }
+}
+
+/// See [`Importer::get_blob_reader`].
+pub(crate) struct BlobReader<'a>(temp_storage::ObjectReader<'a>);
+
+impl BlobReader<'_> {
+    /// Goes back to the start of the blob.
+    pub(crate) fn rewind(&mut self) {
+        self.0.rewind();
+    }
+}
+
+impl std::io::Read for BlobReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
 struct PackIndexEntry {
     oid: ObjectId,
     offset: u64,
@@ -482,6 +931,48 @@ fn write_pack_data(
     for (i, oid) in seen_objects.enumerate() {
         let entry_offset = pack_data_offset;
 
+        let (obj_kind, delta_base_oid, obj_size) = tmp_storage.get_info(oid);
+        if delta_base_oid.is_none() && obj_size > temp_storage::LARGE_OBJECT_SIZE {
+            let mut raw_header = Vec::new();
+            pack_entry_header(obj_kind)
+                .write_to(obj_size, &mut raw_header)
+                .unwrap();
+            file_write_all(&mut pack_data_file, &pack_data_tmp_path, &raw_header)?;
+
+            let mut entry_writer = PackEntryWriter {
+                inner: &mut pack_data_file,
+                len: u64::try_from(raw_header.len()).unwrap(),
+                crc32: gix_features::hash::crc32_update(0, &raw_header),
+            };
+            let mut compressor = gix_features::zlib::stream::deflate::Write::new(&mut entry_writer);
+            let mut obj_reader = tmp_storage.get_reader(oid)?;
+            let copied = std::io::copy(&mut obj_reader, &mut compressor).map_err(|e| {
+                ImportError::WriteFileError {
+                    path: pack_data_tmp_path.clone(),
+                    error: e,
+                }
+            })?;
+            assert_eq!(copied, obj_size, "unexpected size of object {oid}");
+            compressor
+                .flush()
+                .map_err(|e| ImportError::WriteFileError {
+                    path: pack_data_tmp_path.clone(),
+                    error: e,
+                })?;
+            drop(compressor);
+
+            pack_data_offset += entry_writer.len;
+            index_entries.push(PackIndexEntry {
+                oid,
+                offset: entry_offset,
+                crc32: entry_writer.crc32,
+            });
+            offset_map.insert(oid, entry_offset);
+
+            cb(ImportFinishProgress::Write(i + 1, num_objects));
+            continue;
+        }
+
         let (obj_kind, delta_base_oid, mut raw_obj) = tmp_storage.get_raw_maybe_delta(oid)?;
         let header;
         if let Some(base_offset) = delta_base_oid.and_then(|base_oid| offset_map.get(&base_oid)) {
@@ -492,12 +983,7 @@ fn write_pack_data(
             if delta_base_oid.is_some() {
                 (_, raw_obj) = tmp_storage.get_raw(oid)?;
             }
-            header = match obj_kind {
-                gix_object::Kind::Tree => gix_pack::data::entry::Header::Tree,
-                gix_object::Kind::Blob => gix_pack::data::entry::Header::Blob,
-                gix_object::Kind::Commit => gix_pack::data::entry::Header::Commit,
-                gix_object::Kind::Tag => gix_pack::data::entry::Header::Tag,
-            };
+            header = pack_entry_header(obj_kind);
         }
 
         let decompressed_size = u64::try_from(raw_obj.len()).unwrap();
@@ -545,6 +1031,36 @@ fn write_pack_data(
     Ok((pack_hash, index_entries))
 }
 
+fn pack_entry_header(obj_kind: gix_object::Kind) -> gix_pack::data::entry::Header {
+    match obj_kind {
+        gix_object::Kind::Tree => gix_pack::data::entry::Header::Tree,
+        gix_object::Kind::Blob => gix_pack::data::entry::Header::Blob,
+        gix_object::Kind::Commit => gix_pack::data::entry::Header::Commit,
+        gix_object::Kind::Tag => gix_pack::data::entry::Header::Tag,
+    }
+}
+
+/// Writes the compressed data of a pack entry, keeping track of its
+/// length and CRC32.
+struct PackEntryWriter<W> {
+    inner: W,
+    len: u64,
+    crc32: u32,
+}
+
+impl<W: std::io::Write> std::io::Write for PackEntryWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.len += written as u64;
+        self.crc32 = gix_features::hash::crc32_update(self.crc32, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 fn write_pack_index(
     packs_dir: &std::path::Path,
     pack_hash: ObjectId,
@@ -701,6 +1217,20 @@ where
         })
 }
 
+/// Replaces the contents of a file by writing a temporary file and renaming it
+/// over the original one.
+fn replace_file(path: std::path::PathBuf, data: &[u8]) -> Result<(), ImportError> {
+    let mut tmp_path = path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+
+    std::fs::write(&tmp_path, data).map_err(|e| ImportError::WriteFileError {
+        path: tmp_path.clone(),
+        error: e,
+    })?;
+    rename(tmp_path, path)
+}
+
 fn rename<P, Q>(from: P, to: Q) -> Result<(), ImportError>
 where
     P: AsRef<std::path::Path> + Into<std::path::PathBuf>,
@@ -7,6 +7,11 @@ use super::super::delta;
 use super::ImportError;
 use crate::FHashMap;
 
+/// Objects larger than this are never loaded entirely in memory by the
+/// storage: they are not used as delta bases and they are read as streams
+/// when writing the pack.
+pub(super) const LARGE_OBJECT_SIZE: u64 = 64 * 1024 * 1024;
+
 pub(super) struct TempStorage {
     path: std::path::PathBuf,
     file: Mutex<std::fs::File>,
@@ -40,6 +45,81 @@ impl TempStorage {
         })
     }
 
+    /// Opens a storage whose index was previously written with
+    /// [`TempStorage::save_index`].
+    ///
+    /// Data appended to the storage after the index was saved is discarded.
+    pub(super) fn open(
+        path: &std::path::Path,
+        index_path: &std::path::Path,
+        cache_size: usize,
+    ) -> Result<Self, ImportError> {
+        let raw_index = std::fs::read(index_path).map_err(|e| ImportError::ReadFileError {
+            path: index_path.into(),
+            error: e,
+        })?;
+        let (data_len, map) =
+            parse_index(&raw_index).ok_or_else(|| ImportError::ReadFileError {
+                path: index_path.into(),
+                error: std::io::Error::from(std::io::ErrorKind::InvalidData),
+            })?;
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| ImportError::ReadFileError {
+                path: path.into(),
+                error: e,
+            })?;
+        file.set_len(data_len)
+            .map_err(|e| ImportError::WriteFileError {
+                path: path.into(),
+                error: e,
+            })?;
+
+        Ok(Self {
+            path: path.into(),
+            file: Mutex::new(file),
+            info: ObjsInfo {
+                map: Mutex::new(map),
+                condvar: Condvar::new(),
+            },
+            cache: Cache::new(cache_size),
+        })
+    }
+
+    /// Writes the object index, so the storage can be opened again with
+    /// [`TempStorage::open`].
+    pub(super) fn save_index(&self, index_path: &std::path::Path) -> Result<(), ImportError> {
+        let file = self.file.lock().unwrap();
+        file.sync_data().map_err(|e| ImportError::WriteFileError {
+            path: self.path.clone(),
+            error: e,
+        })?;
+        let data_len = file
+            .metadata()
+            .map_err(|e| ImportError::ReadFileError {
+                path: self.path.clone(),
+                error: e,
+            })?
+            .len();
+        drop(file);
+
+        let raw_index = serialize_index(data_len, &self.info.map.lock().unwrap());
+
+        let tmp_index_path = index_path.with_extension("tmp");
+        std::fs::write(&tmp_index_path, raw_index).map_err(|e| ImportError::WriteFileError {
+            path: tmp_index_path.clone(),
+            error: e,
+        })?;
+        std::fs::rename(&tmp_index_path, index_path).map_err(|e| ImportError::RenameError {
+            source_path: tmp_index_path,
+            dest_path: index_path.into(),
+            error: e,
+        })
+    }
+
     pub(super) fn remove(self) -> Result<(), ImportError> {
         drop(self.file);
         std::fs::remove_file(&self.path).map_err(|e| ImportError::RemoveFileError {
@@ -79,7 +159,7 @@ impl TempStorage {
 
             assert_eq!(delta_base_info.kind, obj_kind, "invalid delta base kind");
 
-            if delta_base_info.delta_depth < 50 {
+            if delta_base_info.delta_depth < 50 && delta_base_info.size <= LARGE_OBJECT_SIZE {
                 let delta_base = self.get_raw_from_info(&delta_base_info)?;
 
                 let delta_window_shift = 4;
@@ -90,6 +170,7 @@ impl TempStorage {
             }
         }
 
+        let obj_size = obj_data.len() as u64;
         let (raw_data, delta_base, delta_depth) =
             if let Some((ref delta, delta_base, delta_depth)) = delta_data {
                 (delta.as_slice(), Some(delta_base), delta_depth)
@@ -100,12 +181,79 @@ impl TempStorage {
         let offset = write_compress(&self.file.lock().unwrap(), &self.path, raw_data)?;
 
         self.info
-            .finish_insert(obj_id, offset, delta_depth, delta_base);
+            .finish_insert(obj_id, offset, obj_size, delta_depth, delta_base);
         self.cache.insert(offset, obj_data);
 
         Ok(())
     }
 
+    /// Inserts an object whose data has already been compressed into `src`
+    /// (as a LZ4 frame), copying it without decompressing.
+    pub(super) fn insert_compressed(
+        &self,
+        obj_id: ObjectId,
+        obj_kind: gix_object::Kind,
+        obj_size: u64,
+        src: &mut std::fs::File,
+        src_path: &std::path::Path,
+    ) -> Result<(), ImportError> {
+        if self.info.pre_insert(obj_id, obj_kind).is_some() {
+            return Ok(());
+        }
+
+        src.seek(std::io::SeekFrom::Start(0))
+            .map_err(|e| ImportError::SeekFileError {
+                path: src_path.into(),
+                error: e,
+            })?;
+
+        let mut file = self.file.lock().unwrap();
+        let offset =
+            file.seek(std::io::SeekFrom::End(0))
+                .map_err(|e| ImportError::SeekFileError {
+                    path: self.path.clone(),
+                    error: e,
+                })?;
+        std::io::copy(src, &mut *file).map_err(|e| ImportError::WriteFileError {
+            path: self.path.clone(),
+            error: e,
+        })?;
+        drop(file);
+
+        self.info.finish_insert(obj_id, offset, obj_size, 0, None);
+
+        Ok(())
+    }
+
+    /// Returns the kind, the delta base and the size of an object.
+    pub(super) fn get_info(&self, obj_id: ObjectId) -> (gix_object::Kind, Option<ObjectId>, u64) {
+        let info = self.info.get(obj_id).unwrap_or_else(|| {
+            panic!("object {obj_id} not found");
+        });
+        (info.kind, info.delta_base, info.size)
+    }
+
+    /// Opens an object to read its data as a stream. Only objects that are
+    /// not stored as deltas are actually streamed from the file.
+    pub(super) fn get_reader(&self, obj_id: ObjectId) -> Result<ObjectReader<'_>, ImportError> {
+        let info = self.info.get(obj_id).unwrap_or_else(|| {
+            panic!("object {obj_id} not found");
+        });
+
+        if info.delta_base.is_some() {
+            let obj_data = self.get_raw_from_info(&info)?;
+            Ok(ObjectReader::Memory(std::io::Cursor::new(obj_data)))
+        } else {
+            Ok(ObjectReader::Stored {
+                offset: info.offset,
+                decompressor: lz4_flex::frame::FrameDecoder::new(FileReader {
+                    file: &self.file,
+                    pos: info.offset,
+                }),
+            })
+        }
+    }
+
     pub(super) fn get_raw(
         &self,
         obj_id: ObjectId,
@@ -201,6 +349,9 @@ struct ObjsInfo {
 struct ObjInfo {
     offset: u64,
     kind: gix_object::Kind,
+    /// Size of the object data, which is not the size of the delta if the
+    /// object is stored as one.
+    size: u64,
     delta_depth: u8,
     delta_base: Option<ObjectId>,
 }
@@ -224,6 +375,7 @@ impl ObjsInfo {
                 entry.insert(ObjInfo {
                     offset: u64::MAX,
                     kind,
+                    size: 0,
                     delta_depth: u8::MAX,
                     delta_base: None,
                 });
@@ -236,6 +388,7 @@ impl ObjsInfo {
         &self,
         obj_id: ObjectId,
         offset: u64,
+        size: u64,
         delta_depth: u8,
         delta_base: Option<ObjectId>,
     ) {
@@ -243,6 +396,7 @@ impl ObjsInfo {
         let info = map.get_mut(&obj_id).unwrap();
         assert_eq!(info.offset, u64::MAX);
         info.offset = offset;
+        info.size = size;
         info.delta_depth = delta_depth;
         info.delta_base = delta_base;
 
@@ -266,6 +420,137 @@ impl ObjsInfo {
     }
 }
 
+const INDEX_MAGIC: &[u8] = b"\0temp storage index\0";
+
+fn serialize_index(data_len: u64, map: &FHashMap<ObjectId, ObjInfo>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(INDEX_MAGIC);
+    out.extend(data_len.to_le_bytes());
+    out.extend(u64::try_from(map.len()).unwrap().to_le_bytes());
+    for (oid, info) in map.iter() {
+        assert_ne!(
+            info.offset,
+            u64::MAX,
+            "object {oid} has not been written yet"
+        );
+        out.extend(oid.as_bytes());
+        out.extend(info.offset.to_le_bytes());
+        out.extend(info.size.to_le_bytes());
+        out.push(match info.kind {
+            gix_object::Kind::Tree => 0,
+            gix_object::Kind::Blob => 1,
+            gix_object::Kind::Commit => 2,
+            gix_object::Kind::Tag => 3,
+        });
+        out.push(info.delta_depth);
+        if let Some(delta_base) = info.delta_base {
+            out.push(1);
+            out.extend(delta_base.as_bytes());
+        } else {
+            out.push(0);
+        }
+    }
+    out
+}
+
+fn parse_index(mut src: &[u8]) -> Option<(u64, FHashMap<ObjectId, ObjInfo>)> {
+    fn take<const N: usize>(src: &mut &[u8]) -> Option<[u8; N]> {
+        let array;
+        (array, *src) = src.split_first_chunk()?;
+        Some(*array)
+    }
+
+    src = src.strip_prefix(INDEX_MAGIC)?;
+    let data_len = u64::from_le_bytes(take(&mut src)?);
+    let num_objects = usize::try_from(u64::from_le_bytes(take(&mut src)?)).ok()?;
+
+    let mut map = FHashMap::default();
+    for _ in 0..num_objects {
+        let oid = ObjectId::Sha1(take(&mut src)?);
+        let offset = u64::from_le_bytes(take(&mut src)?);
+        let size = u64::from_le_bytes(take(&mut src)?);
+        let [kind, delta_depth, has_delta_base] = take(&mut src)?;
+        let kind = match kind {
+            0 => gix_object::Kind::Tree,
+            1 => gix_object::Kind::Blob,
+            2 => gix_object::Kind::Commit,
+            3 => gix_object::Kind::Tag,
+            _ => return None,
+        };
+        let delta_base = match has_delta_base {
+            0 => None,
+            1 => Some(ObjectId::Sha1(take(&mut src)?)),
+            _ => return None,
+        };
+        if offset >= data_len {
+            return None;
+        }
+        map.insert(
+            oid,
+            ObjInfo {
+                offset,
+                kind,
+                size,
+                delta_depth,
+                delta_base,
+            },
+        );
+    }
+
+    src.is_empty().then_some((data_len, map))
+}
+
+pub(super) enum ObjectReader<'a> {
+    Stored {
+        offset: u64,
+        decompressor: lz4_flex::frame::FrameDecoder<FileReader<'a>>,
+    },
+    Memory(std::io::Cursor<Vec<u8>>),
+}
+
+impl ObjectReader<'_> {
+    /// Goes back to the start of the object data.
+    pub(super) fn rewind(&mut self) {
+        match self {
+            Self::Stored {
+                offset,
+                decompressor,
+            } => {
+                let file = decompressor.get_ref().file;
+                *decompressor =
+                    lz4_flex::frame::FrameDecoder::new(FileReader { file, pos: *offset });
+            }
+            Self::Memory(cursor) => cursor.set_position(0),
+        }
+    }
+}
+
+impl std::io::Read for ObjectReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Stored { decompressor, .. } => decompressor.read(buf),
+            Self::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+/// Reads the storage file from a position, sharing it with other readers
+/// and writers.
+pub(super) struct FileReader<'a> {
+    file: &'a Mutex<std::fs::File>,
+    pos: u64,
+}
+
+impl std::io::Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut file = self.file.lock().unwrap();
+        file.seek(std::io::SeekFrom::Start(self.pos))?;
+        let read_len = file.read(buf)?;
+        self.pos += read_len as u64;
+        Ok(read_len)
+    }
+}
+
 struct Cache {
     cache: Mutex<lru_mem::LruCache<u64, Vec<u8>>>,
 }
@@ -4,7 +4,7 @@ use std::sync::{Arc, Condvar, Mutex};
 use gix_hash::ObjectId;
 
 use super::ImportError;
-use super::temp_storage::TempStorage;
+use super::temp_storage::{ObjectReader, TempStorage};
 use crate::FHashMap;
 
 pub(super) struct TempStorageThread {
@@ -16,10 +16,15 @@ pub(super) struct TempStorageThread {
 struct Data {
     storage: TempStorage,
     inner: Mutex<Inner>,
+    /// Notified when `Inner::unwritten` reaches zero or an error happens.
+    idle: Condvar,
 }
 
 struct Inner {
     pending: FHashMap<ObjectId, (gix_object::Kind, Vec<u8>, Option<ObjectId>)>,
+    /// Number of objects that have been sent to the thread and have not
+    /// been written yet.
+    unwritten: usize,
     error: Option<ImportError>,
 }
 
@@ -31,8 +36,10 @@ impl TempStorageThread {
             storage,
             inner: Mutex::new(Inner {
                 pending: FHashMap::default(),
+                unwritten: 0,
                 error: None,
             }),
+            idle: Condvar::new(),
         });
 
         let data_clone = data.clone();
@@ -76,6 +83,7 @@ impl TempStorageThread {
                 let obj_size = raw_obj.len();
 
                 entry.insert((obj_kind, raw_obj, delta_base));
+                inner.unwritten += 1;
                 drop(inner);
 
                 if !self.sender.send(obj_id, obj_size) {
@@ -103,18 +111,104 @@ impl TempStorageThread {
         }
     }
 
+    pub(crate) fn insert_compressed(
+        &self,
+        obj_id: ObjectId,
+        obj_kind: gix_object::Kind,
+        obj_size: u64,
+        src: &mut std::fs::File,
+        src_path: &std::path::Path,
+    ) -> Result<(), ImportError> {
+        let mut inner = self.data.inner.lock().unwrap();
+        if let Some(e) = inner.error.take() {
+            return Err(e);
+        }
+        if inner.pending.contains_key(&obj_id) {
+            return Ok(());
+        }
+        drop(inner);
+
+        self.data
+            .storage
+            .insert_compressed(obj_id, obj_kind, obj_size, src, src_path)
+    }
+
+    pub(crate) fn get_size(&self, obj_id: ObjectId) -> Result<u64, ImportError> {
+        let mut inner = self.data.inner.lock().unwrap();
+        if let Some((_, raw_obj, _)) = inner.pending.get(&obj_id) {
+            Ok(raw_obj.len() as u64)
+        } else {
+            if let Some(e) = inner.error.take() {
+                return Err(e);
+            }
+            drop(inner);
+            let (_, _, obj_size) = self.data.storage.get_info(obj_id);
+            Ok(obj_size)
+        }
+    }
+
+    /// Returns the object that `obj_id` is stored as a delta of, if any.
+    pub(crate) fn get_delta_base(&self, obj_id: ObjectId) -> Result<Option<ObjectId>, ImportError> {
+        let mut inner = self.data.inner.lock().unwrap();
+        if let Some(&(_, _, delta_base)) = inner.pending.get(&obj_id) {
+            Ok(delta_base)
+        } else {
+            if let Some(e) = inner.error.take() {
+                return Err(e);
+            }
+            drop(inner);
+            let (_, delta_base, _) = self.data.storage.get_info(obj_id);
+            Ok(delta_base)
+        }
+    }
+
+    pub(crate) fn get_reader(&self, obj_id: ObjectId) -> Result<ObjectReader<'_>, ImportError> {
+        let mut inner = self.data.inner.lock().unwrap();
+        if let Some((_, raw_obj, _)) = inner.pending.get(&obj_id) {
+            Ok(ObjectReader::Memory(std::io::Cursor::new(raw_obj.clone())))
+        } else {
+            if let Some(e) = inner.error.take() {
+                return Err(e);
+            }
+            drop(inner);
+            self.data.storage.get_reader(obj_id)
+        }
+    }
+
+    /// Waits until all the inserted objects have been written and saves
+    /// the storage index.
+    pub(crate) fn save_index(&self, index_path: &std::path::Path) -> Result<(), ImportError> {
+        let mut inner = self.data.inner.lock().unwrap();
+        while inner.unwritten != 0 && inner.error.is_none() {
+            inner = self.data.idle.wait(inner).unwrap();
+        }
+        if let Some(e) = inner.error.take() {
+            return Err(e);
+        }
+        drop(inner);
+
+        self.data.storage.save_index(index_path)
+    }
+
     fn thread_main(data: Arc<Data>, receiver: ChannelReceiver) {
         while let Some(obj_id) = receiver.recv() {
             let mut inner = data.inner.lock().unwrap();
 
             let (obj_kind, raw_obj, delta_base) = inner.pending.remove(&obj_id).unwrap();
-            if let Err(e) = data
+            let result = data
                 .storage
-                .insert_raw_with_oid(obj_id, obj_kind, raw_obj, delta_base, inner)
-            {
-                data.inner.lock().unwrap().error = Some(e);
+                .insert_raw_with_oid(obj_id, obj_kind, raw_obj, delta_base, inner);
+
+            let mut inner = data.inner.lock().unwrap();
+            inner.unwritten -= 1;
+            if let Err(e) = result {
+                inner.error = Some(e);
+                data.idle.notify_all();
                 break;
             }
+            if inner.unwritten == 0 {
+                data.idle.notify_all();
+            }
         }
     }
 }
@@ -1,7 +1,7 @@
 mod delta;
 mod import;
 
-pub(crate) use import::{ChangeSet, ImportFinishProgress, Importer};
+pub(crate) use import::{BlobReader, BlobWriter, ChangeSet, ImportFinishProgress, Importer};
 
 pub(crate) fn legalize_branch_name(raw_name: &[u8]) -> String {
     fn legalize_component(name: &mut String) {
@@ -8,6 +8,7 @@
 #![allow(
     clippy::derivable_impls,
     clippy::enum_variant_names,
+    clippy::too_many_arguments,
     clippy::type_complexity
 )]
 
@@ -50,6 +51,13 @@ fn main_inner() -> Result<(), RunError> {
         RunError::Usage
     })?;
 
+    if let (Some(first_rev), Some(last_rev)) = (args.first_rev, args.last_rev) {
+        if first_rev > last_rev {
+            eprintln!("--first-rev ({first_rev}) is greater than --last-rev ({last_rev})");
+            return Err(RunError::Usage);
+        }
+    }
+
     let term_out = term_out::init(start, !args.no_progress);
     let progress_print = term_out.get_progress_print();
 
@@ -94,83 +102,233 @@ fn main_inner() -> Result<(), RunError> {
                 RunError::Generic
             })?;
 
-    let mut options = convert::Options::new(convert::InitOptions {
+    let exclude_paths =
+        path_pattern::PathPattern::new(params.exclude_paths.iter().map(String::as_str), true)
+            .map_err(|(pat, e)| {
+                tracing::error!("invalid pattern {pat:?}: {e}");
+                RunError::Generic
+            })?;
+
+    if params.outputs.is_empty() && params.sources.is_empty() {
+        if params.head.is_none() {
+            tracing::error!("missing \"head\" in conversion parameters");
+            return Err(RunError::Generic);
+        }
+    } else {
+        if !params.outputs.is_empty() && !params.sources.is_empty() {
+            tracing::error!("\"outputs\" and \"sources\" cannot be combined");
+            return Err(RunError::Generic);
+        }
+        if params.head.is_some()
+            || !params.branches.is_empty()
+            || !params.tags.is_empty()
+            || !params.rename_branches.is_empty()
+            || !params.rename_tags.is_empty()
+            || !params.layouts.is_empty()
+        {
+            tracing::error!(
+                "top-level branches, tags, renames, layouts and head cannot be combined with \"outputs\" or \"sources\"",
+            );
+            return Err(RunError::Generic);
+        }
+        if args.incremental
+            || args.resume
+            || args.reuse_stage1.is_some()
+            || args.rename_log.is_some()
+        {
+            tracing::error!(
+                "--incremental, --resume, --reuse-stage1 and --rename-log cannot be used with \"outputs\" or \"sources\"",
+            );
+            return Err(RunError::Generic);
+        }
+    }
+    if !params.sources.is_empty() {
+        if args.first_rev.is_some() || args.export_dump.is_some() {
+            tracing::error!("--first-rev and --export-dump cannot be used with \"sources\"");
+            return Err(RunError::Generic);
+        }
+        if args.src.len() != params.sources.len() {
+            tracing::error!(
+                "{} source paths given for {} entries in \"sources\"",
+                args.src.len(),
+                params.sources.len(),
+            );
+            return Err(RunError::Generic);
+        }
+    }
+
+    if !params.rewrite_paths.is_empty()
+        && (!params.partial_branches.is_empty() || !params.partial_tags.is_empty())
+    {
+        // The tree of a partial branch is taken from the tree of its parent,
+        // which would already be rewritten.
+        tracing::error!("\"rewrite-paths\" cannot be combined with partial branches or tags");
+        return Err(RunError::Generic);
+    }
+
+    let default_dump_limits = svn::dump::Limits::default();
+    let default_delta_limits = svn::diff::Limits::default();
+    let init_options = convert::InitOptions {
         keep_deleted_branches: params.keep_deleted_branches,
         keep_deleted_tags: params.keep_deleted_tags,
-        head_path: params.head.into(),
-        unbranched_name: params.unbranched_name,
+        detect_branch_renames: params.detect_branch_renames,
+        rename_aliases: params.rename_aliases,
+        rename_log: args.rename_log.clone(),
+        head_path: params.head.clone().unwrap_or_default().into(),
+        unbranched_name: params.unbranched_name.clone(),
         enable_merges: params.enable_merges,
         merge_optional,
         avoid_fully_reverted_merges: params.avoid_fully_reverted_merges,
         generate_gitignore: params.generate_gitignore,
         delete_files,
+        exclude_paths,
+        subdirectory: params
+            .subdirectory
+            .as_deref()
+            .map_or_else(Vec::new, |dir| dir.trim_matches('/').into()),
         git_obj_cache_size: args.git_obj_cache_size.saturating_mul(1024 * 1024),
         git_repack: args.git_repack,
-    });
+        incremental: args.incremental,
+        // Checkpoints are kept in the destination, which is not reused
+        // along with the output of stage 1. Split and merged conversions
+        // cannot be resumed. A resumed conversion keeps saving checkpoints
+        // (every 5 minutes if no interval is given), so it can be resumed
+        // again.
+        checkpoint_interval: args
+            .checkpoint_interval
+            .or(args.resume.then_some(300))
+            .filter(|_| {
+                args.reuse_stage1.is_none()
+                    && params.outputs.is_empty()
+                    && params.sources.is_empty()
+            })
+            .map(std::time::Duration::from_secs),
+        resume: args.resume,
+        reuse_stage1: args.reuse_stage1.clone(),
+        first_rev: args.first_rev,
+        last_rev: args.last_rev,
+        allow_checksum_mismatch: args.allow_checksum_mismatch,
+        lenient: args.lenient,
+        dump_limits: svn::dump::Limits {
+            max_text_len: args.max_text_size.or(default_dump_limits.max_text_len),
+            max_props_len: args.max_props_size.or(default_dump_limits.max_props_len),
+            max_path_len: args.max_path_length.or(default_dump_limits.max_path_len),
+            max_path_depth: args.max_path_depth.or(default_dump_limits.max_path_depth),
+            max_nodes_per_rev: args
+                .max_nodes_per_rev
+                .or(default_dump_limits.max_nodes_per_rev),
+        },
+        delta_limits: svn::diff::Limits {
+            max_window_len: args
+                .max_delta_window
+                .or(default_delta_limits.max_window_len),
+            max_target_len: args
+                .max_delta_target_size
+                .or(default_delta_limits.max_target_len),
+        },
+        stream_file_size: args.stream_file_size.saturating_mul(1024 * 1024),
+        remote: svn::source::RemoteOptions {
+            chunk_size: args.remote_chunk_size,
+            retries: args.remote_retries,
+            cache_dir: args.remote_cache.clone(),
+            svnrdump_args: args.svnrdump_args.clone(),
+        },
+        svnadmin: svn::source::SvnadminOptions {
+            // The options of svnadmin would be ignored by the native
+            // reader, so they select svnadmin.
+            force: args.svnadmin || args.svnadmin_deltas || args.svnadmin_jobs.is_some(),
+            deltas: args.svnadmin_deltas,
+            jobs: args.svnadmin_jobs.map_or(1, usize::from),
+        },
+        decompress_threads: args.decompress_threads.into(),
+    };
 
-    for path in params.branches.iter() {
-        let path = path.as_bytes();
-        options.add_branch_dir(path, false).map_err(|e| {
-            if let Some(conflicting_path) = e {
-                tracing::error!(
-                    "cannot add \"{}\" as branch because it conflicts with branch/tag \"{}\"",
-                    path.escape_ascii(),
-                    conflicting_path.escape_ascii(),
-                );
-            } else {
-                tracing::error!("invalid branch path: \"{}\"", path.escape_ascii());
-            }
-            RunError::Generic
-        })?;
-    }
-    for path in params.tags.iter() {
-        let path = path.as_bytes();
-        options.add_branch_dir(path, true).map_err(|e| {
-            if let Some(conflicting_path) = e {
-                tracing::error!(
-                    "cannot add \"{}\" as tag because it conflicts with branch/tag \"{}\"",
-                    path.escape_ascii(),
-                    conflicting_path.escape_ascii(),
-                );
-            } else {
-                tracing::error!("invalid tag path: \"{}\"", path.escape_ascii());
-            }
-            RunError::Generic
-        })?;
-    }
+    let mut options = convert::Options::new(init_options.clone());
+    add_branch_layout(
+        options.default_layout_mut(),
+        &params.branches,
+        &params.tags,
+        &params.rename_branches,
+        &params.rename_tags,
+    )?;
+    add_branch_layouts(&mut options, &params.layouts)?;
+    add_branch_options(&mut options, &params)?;
+
+    let mut split_outputs = Vec::<(std::path::PathBuf, convert::Options)>::new();
+    for output_params in params.outputs.iter() {
+        if split_outputs
+            .iter()
+            .any(|(other_dest, _)| *other_dest == output_params.dest)
+        {
+            tracing::error!("duplicate output destination {:?}", output_params.dest);
+            return Err(RunError::Generic);
+        }
 
-    for (from, to) in params.rename_branches.iter() {
-        options
-            .add_branch_rename(from.as_bytes(), to.as_bytes())
-            .map_err(|_| {
-                tracing::error!("invalid branch rename: {from:?} -> {to:?}");
-                RunError::Generic
-            })?;
-    }
-    for (from, to) in params.rename_tags.iter() {
-        options
-            .add_tag_rename(from.as_bytes(), to.as_bytes())
-            .map_err(|_| {
-                tracing::error!("invalid tag rename: {from:?}\" -> {to:?}");
-                RunError::Generic
-            })?;
+        let mut output_options = convert::Options::new(convert::InitOptions {
+            head_path: output_params.head.clone().into(),
+            ..init_options.clone()
+        });
+        add_branch_layout(
+            output_options.default_layout_mut(),
+            &output_params.branches,
+            &output_params.tags,
+            &output_params.rename_branches,
+            &output_params.rename_tags,
+        )?;
+        add_branch_layouts(&mut output_options, &output_params.layouts)?;
+        add_branch_options(&mut output_options, &params)?;
+        split_outputs.push((output_params.dest.clone(), output_options));
     }
 
-    for name in params.partial_branches.iter() {
-        options.add_partial_branch(name.as_bytes()).map_err(|_| {
-            tracing::error!("invalid partial branch name: {name:?}");
-            RunError::Generic
-        })?;
-    }
-    for name in params.partial_tags.iter() {
-        options.add_partial_tag(name.as_bytes()).map_err(|_| {
-            tracing::error!("invalid partial tag name: {name:?}");
-            RunError::Generic
-        })?;
-    }
+    let mut merge_sources = Vec::<convert::MergeSource>::new();
+    for (source_params, src_path) in params.sources.iter().zip(args.src.iter()) {
+        let subdir = source_params.subdir.trim_matches('/');
+        if subdir.is_empty() {
+            tracing::error!("empty subdirectory for source {:?}", source_params.name);
+            return Err(RunError::Generic);
+        }
+        if let Some(other) = merge_sources
+            .iter()
+            .find(|other| other.name == source_params.name)
+        {
+            tracing::error!("duplicate source name {:?}", other.name);
+            return Err(RunError::Generic);
+        }
+        let is_within = |path: &[u8], dir: &[u8]| {
+            path.strip_prefix(dir)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"))
+        };
+        if let Some(other) = merge_sources.iter().find(|other| {
+            is_within(subdir.as_bytes(), &other.subdir)
+                || is_within(&other.subdir, subdir.as_bytes())
+        }) {
+            tracing::error!(
+                "subdirectories of sources {:?} and {:?} overlap",
+                other.name,
+                source_params.name,
+            );
+            return Err(RunError::Generic);
+        }
 
-    for ignored_merge in params.ignore_merges.iter() {
-        options.add_ignored_merge_at(ignored_merge.path.as_bytes(), ignored_merge.rev);
+        let mut source_options = convert::Options::new(convert::InitOptions {
+            head_path: source_params.head.clone().into(),
+            ..init_options.clone()
+        });
+        add_branch_layout(
+            source_options.default_layout_mut(),
+            &source_params.branches,
+            &source_params.tags,
+            &source_params.rename_branches,
+            &source_params.rename_tags,
+        )?;
+        add_branch_layouts(&mut source_options, &source_params.layouts)?;
+        add_branch_options(&mut source_options, &params)?;
+        merge_sources.push(convert::MergeSource {
+            name: source_params.name.clone(),
+            subdir: subdir.as_bytes().to_vec(),
+            src_paths: vec![src_path.clone()],
+            options: source_options,
+        });
     }
 
     let user_map = match params.user_map_file {
@@ -211,7 +369,8 @@ fn main_inner() -> Result<(), RunError> {
             {% if svn_log %}{{ svn_log }}
 
             {% endif %}[[SVN revision: {{ svn_rev }}]]{% if svn_path %}
-            [[SVN path: {{ svn_path }}]]{% endif %}
+            [[SVN path: {{ svn_path }}]]{% endif %}{% if svn_source %}
+            [[SVN source: {{ svn_source }}]]{% endif %}
         "#});
     let tag_msg_template = params
         .tag_msg_template
@@ -220,7 +379,8 @@ fn main_inner() -> Result<(), RunError> {
            {% if svn_log %}{{ svn_log }}
 
            {% endif %}[[SVN revision: {{ svn_rev }}]]
-           [[SVN path: {{ svn_path }}]]
+           [[SVN path: {{ svn_path }}]]{% if svn_source %}
+           [[SVN source: {{ svn_source }}]]{% endif %}
         "#});
 
     let metadata_maker = make_meta::GitMetadataMaker::new(
@@ -234,22 +394,203 @@ fn main_inner() -> Result<(), RunError> {
         RunError::Generic
     })?;
 
-    options.validate().map_err(|_| RunError::Generic)?;
+    if args.export_dump.is_none() {
+        if split_outputs.is_empty() && merge_sources.is_empty() {
+            options.validate().map_err(|_| RunError::Generic)?;
+        }
+        for (_, output_options) in split_outputs.iter() {
+            output_options.validate().map_err(|_| RunError::Generic)?;
+        }
+        for source in merge_sources.iter() {
+            source.options.validate().map_err(|_| RunError::Generic)?;
+        }
+    }
 
-    let r = convert::convert(
-        &progress_print,
-        &options,
-        &metadata_maker,
-        &args.src,
-        args.remote_svn,
-        &args.dest,
-    );
+    // The first Ctrl-C stops the conversion at the next revision boundary,
+    // after saving a checkpoint. The second one exits immediately.
+    let interrupted = std::sync::atomic::AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if interrupted.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        tracing::warn!("interrupt requested, stopping conversion");
+        convert::interrupt();
+    })
+    .map_err(|e| {
+        tracing::error!("failed to set interrupt handler: {e}");
+        RunError::Generic
+    })?;
+
+    let r = match (&args.export_dump, &args.dest) {
+        (Some(export_path), _) => convert::export(
+            &progress_print,
+            &options,
+            &args.src,
+            args.remote_svn,
+            export_path,
+            match args.export_dump_version.unwrap_or(cli::DumpVersion::Two) {
+                cli::DumpVersion::Two => svn::dump::DumpVersion::Two,
+                cli::DumpVersion::Three => svn::dump::DumpVersion::Three,
+            },
+        ),
+        (None, Some(dest)) if !split_outputs.is_empty() => convert::convert_split(
+            &progress_print,
+            &split_outputs,
+            &metadata_maker,
+            &args.src,
+            args.remote_svn,
+            dest,
+        ),
+        (None, Some(dest)) if !merge_sources.is_empty() => convert::convert_merge(
+            &progress_print,
+            &options,
+            &merge_sources,
+            &metadata_maker,
+            args.remote_svn,
+            dest,
+        ),
+        (None, Some(dest)) => convert::convert(
+            &progress_print,
+            &options,
+            &metadata_maker,
+            &args.src,
+            args.remote_svn,
+            dest,
+        ),
+        (None, None) => unreachable!("--dest is required without --export-dump"),
+    };
 
     term_out.finish();
 
     r.map_err(|_| RunError::Generic)
 }
 
+fn add_branch_layouts(
+    options: &mut convert::Options,
+    layouts: &[params_file::Layout],
+) -> Result<(), RunError> {
+    for layout_params in layouts.iter() {
+        let layout = options
+            .add_layout(layout_params.from_rev, layout_params.to_rev)
+            .map_err(|_| {
+                tracing::error!(
+                    "layout revision range {}:{} is empty or overlaps with another layout",
+                    layout_params.from_rev,
+                    layout_params
+                        .to_rev
+                        .map_or_else(|| "HEAD".into(), |rev| rev.to_string()),
+                );
+                RunError::Generic
+            })?;
+        add_branch_layout(
+            layout,
+            &layout_params.branches,
+            &layout_params.tags,
+            &layout_params.rename_branches,
+            &layout_params.rename_tags,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Adds the options about branches that are not part of the specs, which
+/// are the same for every output of a split conversion.
+fn add_branch_options(
+    options: &mut convert::Options,
+    params: &params_file::ConvParams,
+) -> Result<(), RunError> {
+    for name in params.partial_branches.iter() {
+        options.add_partial_branch(name.as_bytes()).map_err(|_| {
+            tracing::error!("invalid partial branch name: {name:?}");
+            RunError::Generic
+        })?;
+    }
+    for name in params.partial_tags.iter() {
+        options.add_partial_tag(name.as_bytes()).map_err(|_| {
+            tracing::error!("invalid partial tag name: {name:?}");
+            RunError::Generic
+        })?;
+    }
+
+    for rewrite in params.rewrite_paths.iter() {
+        options
+            .add_path_rewrite(rewrite.from.as_bytes(), rewrite.to.as_bytes())
+            .map_err(|_| {
+                tracing::error!(
+                    "invalid path rewrite: {:?} -> {:?}",
+                    rewrite.from,
+                    rewrite.to
+                );
+                RunError::Generic
+            })?;
+    }
+
+    for ignored_merge in params.ignore_merges.iter() {
+        options.add_ignored_merge_at(ignored_merge.path.as_bytes(), ignored_merge.rev);
+    }
+
+    Ok(())
+}
+
+fn add_branch_layout(
+    layout: &mut convert::BranchLayout,
+    branches: &[String],
+    tags: &[String],
+    rename_branches: &std::collections::HashMap<String, String>,
+    rename_tags: &std::collections::HashMap<String, String>,
+) -> Result<(), RunError> {
+    for path in branches.iter() {
+        let path = path.as_bytes();
+        layout.add_branch_dir(path, false).map_err(|e| {
+            if let Some(conflicting_path) = e {
+                tracing::error!(
+                    "cannot add \"{}\" as branch because it conflicts with branch/tag \"{}\"",
+                    path.escape_ascii(),
+                    conflicting_path.escape_ascii(),
+                );
+            } else {
+                tracing::error!("invalid branch path: \"{}\"", path.escape_ascii());
+            }
+            RunError::Generic
+        })?;
+    }
+    for path in tags.iter() {
+        let path = path.as_bytes();
+        layout.add_branch_dir(path, true).map_err(|e| {
+            if let Some(conflicting_path) = e {
+                tracing::error!(
+                    "cannot add \"{}\" as tag because it conflicts with branch/tag \"{}\"",
+                    path.escape_ascii(),
+                    conflicting_path.escape_ascii(),
+                );
+            } else {
+                tracing::error!("invalid tag path: \"{}\"", path.escape_ascii());
+            }
+            RunError::Generic
+        })?;
+    }
+
+    for (from, to) in rename_branches.iter() {
+        layout
+            .add_branch_rename(from.as_bytes(), to.as_bytes())
+            .map_err(|_| {
+                tracing::error!("invalid branch rename: {from:?} -> {to:?}");
+                RunError::Generic
+            })?;
+    }
+    for (from, to) in rename_tags.iter() {
+        layout
+            .add_tag_rename(from.as_bytes(), to.as_bytes())
+            .map_err(|_| {
+                tracing::error!("invalid tag rename: {from:?}\" -> {to:?}");
+                RunError::Generic
+            })?;
+    }
+
+    Ok(())
+}
+
 fn init_logger(
     stderr_level: Option<tracing::Level>,
     file_path: Option<&std::path::Path>,
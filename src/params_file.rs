@@ -20,7 +20,17 @@ pub(crate) struct ConvParams {
     pub(crate) keep_deleted_tags: bool,
     #[serde(rename = "partial-tags", default)]
     pub(crate) partial_tags: Vec<String>,
-    pub(crate) head: String,
+    #[serde(default)]
+    pub(crate) layouts: Vec<Layout>,
+    #[serde(rename = "detect-branch-renames", default = "false_")]
+    pub(crate) detect_branch_renames: bool,
+    #[serde(rename = "rename-aliases", default = "false_")]
+    pub(crate) rename_aliases: bool,
+    pub(crate) head: Option<String>,
+    #[serde(default)]
+    pub(crate) outputs: Vec<Output>,
+    #[serde(default)]
+    pub(crate) sources: Vec<Source>,
     #[serde(rename = "unbranched-name")]
     pub(crate) unbranched_name: Option<String>,
     #[serde(rename = "enable-merges", default = "true_")]
@@ -35,6 +45,11 @@ pub(crate) struct ConvParams {
     pub(crate) generate_gitignore: bool,
     #[serde(rename = "delete-files", default)]
     pub(crate) delete_files: Vec<String>,
+    #[serde(rename = "exclude-paths", default)]
+    pub(crate) exclude_paths: Vec<String>,
+    pub(crate) subdirectory: Option<String>,
+    #[serde(rename = "rewrite-paths", default)]
+    pub(crate) rewrite_paths: Vec<PathRewrite>,
     #[serde(rename = "user-map-file")]
     pub(crate) user_map_file: Option<PathBuf>,
     #[serde(rename = "user-fallback-template")]
@@ -45,6 +60,65 @@ pub(crate) struct ConvParams {
     pub(crate) tag_msg_template: Option<String>,
 }
 
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Layout {
+    #[serde(rename = "from-rev", default)]
+    pub(crate) from_rev: u32,
+    #[serde(rename = "to-rev")]
+    pub(crate) to_rev: Option<u32>,
+    #[serde(default)]
+    pub(crate) branches: Vec<String>,
+    #[serde(rename = "rename-branches", default)]
+    pub(crate) rename_branches: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(rename = "rename-tags", default)]
+    pub(crate) rename_tags: HashMap<String, String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Output {
+    pub(crate) dest: PathBuf,
+    #[serde(default)]
+    pub(crate) branches: Vec<String>,
+    #[serde(rename = "rename-branches", default)]
+    pub(crate) rename_branches: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(rename = "rename-tags", default)]
+    pub(crate) rename_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) layouts: Vec<Layout>,
+    pub(crate) head: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Source {
+    pub(crate) name: String,
+    pub(crate) subdir: String,
+    #[serde(default)]
+    pub(crate) branches: Vec<String>,
+    #[serde(rename = "rename-branches", default)]
+    pub(crate) rename_branches: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(rename = "rename-tags", default)]
+    pub(crate) rename_tags: HashMap<String, String>,
+    #[serde(default)]
+    pub(crate) layouts: Vec<Layout>,
+    pub(crate) head: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PathRewrite {
+    pub(crate) from: String,
+    pub(crate) to: String,
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct BranchRev {
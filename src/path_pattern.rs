@@ -119,6 +119,7 @@ fn pattern_to_hir(pattern: &str, full_path: bool) -> Result<regex_hir::Hir, Pars
     Ok(regex_hir::Hir::concat(hir))
 }
 
+#[derive(Clone)]
 pub(crate) struct PathPattern {
     regex: regex_automata::meta::Regex,
 }
@@ -16,6 +16,8 @@ struct Inner {
 
 struct Buffer {
     closed: bool,
+    /// Error that the reader gets instead of the end of the stream.
+    error: Option<std::io::Error>,
     buf: VecDeque<u8>,
 }
 
@@ -23,6 +25,7 @@ pub(crate) fn create() -> (PipeReader, PipeWriter) {
     let inner = Arc::new(Inner {
         buffer: Mutex::new(Buffer {
             closed: false,
+            error: None,
             buf: VecDeque::with_capacity(1024 * 1024),
         }),
         condvar: Condvar::new(),
@@ -53,7 +56,10 @@ impl std::io::Read for PipeReader {
         loop {
             if buffer.buf.is_empty() {
                 if buffer.closed {
-                    return Ok(0);
+                    return match buffer.error.take() {
+                        Some(e) => Err(e),
+                        None => Ok(0),
+                    };
                 }
                 buffer = self.inner.condvar.wait(buffer).unwrap();
             } else {
@@ -84,6 +90,17 @@ impl std::io::Read for PipeReader {
     }
 }
 
+impl PipeWriter {
+    /// Closes the pipe, making the reader get an error with the same kind
+    /// and message as `error` after the buffered data.
+    pub(crate) fn close_with_error(self, error: &std::io::Error) {
+        let mut buffer = self.inner.buffer.lock().unwrap();
+        if !buffer.closed {
+            buffer.error = Some(std::io::Error::new(error.kind(), error.to_string()));
+        }
+    }
+}
+
 impl Drop for PipeWriter {
     fn drop(&mut self) {
         let mut buffer = self.inner.buffer.lock().unwrap();
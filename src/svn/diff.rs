@@ -1,29 +1,79 @@
+use std::borrow::Cow;
+use std::io::Read as _;
+
 // svndiff format described in
 // https://svn.apache.org/repos/asf/subversion/trunk/notes/svndiff
 
+/// Maximum capacity reserved in advance for a target view, which is
+/// otherwise grown as it is written, since its length comes from the
+/// delta.
+const MAX_PREALLOC_LEN: usize = 1 << 20;
+
+/// Limits on the sizes found in a delta, to reject malformed or hostile
+/// input before allocating memory for it. `None` means unlimited.
+#[derive(Clone, Debug)]
+pub(crate) struct Limits {
+    /// Maximum length of the source view, target view and (decoded)
+    /// sections of a window.
+    pub(crate) max_window_len: Option<u64>,
+    /// Maximum length of the text resulting from applying a delta.
+    pub(crate) max_target_len: Option<u64>,
+}
+
+impl Default for Limits {
+    /// Windows written by Subversion are at most 100 KiB, while texts are
+    /// not limited, since large ones are streamed.
+    fn default() -> Self {
+        Self {
+            max_window_len: Some(64 * 1024 * 1024),
+            max_target_len: None,
+        }
+    }
+}
+
+impl Limits {
+    fn check(limit: Option<u64>, what: &'static str, value: u64) -> Result<(), ApplyError> {
+        match limit {
+            Some(limit) if value > limit => Err(ApplyError::LimitExceeded { what, value, limit }),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum ApplyError {
     InvalidDeltaHeader,
+    DeltaIo(std::io::Error),
+    SourceIo(std::io::Error),
     DestIo(std::io::Error),
     InvalidVarLenInt,
     OffsetTooLarge,
     LenTooLarge,
     SourceViewOutOfBounds {
-        source_len: usize,
-        view_offset: usize,
-        view_len: usize,
+        source_len: u64,
+        view_offset: u64,
+        view_len: u64,
     },
     TruncatedInstrs,
     TruncatedNewData,
+    InvalidCompressedSection,
     NotEnoughNewData,
     InvalidInstr,
+    CopyOutOfBounds,
     MismatchedTargetLen,
+    LimitExceeded {
+        what: &'static str,
+        value: u64,
+        limit: u64,
+    },
 }
 
 impl std::fmt::Display for ApplyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
             Self::InvalidDeltaHeader => write!(f, "invalid delta header"),
+            Self::DeltaIo(ref e) => write!(f, "delta I/O error: {e}"),
+            Self::SourceIo(ref e) => write!(f, "source I/O error: {e}"),
             Self::DestIo(ref e) => write!(f, "destination I/O error: {e}"),
             Self::InvalidVarLenInt => write!(f, "invalid variable-length integer"),
             Self::OffsetTooLarge => write!(f, "offset too large"),
@@ -38,10 +88,101 @@ impl std::fmt::Display for ApplyError {
             ),
             Self::TruncatedInstrs => write!(f, "truncated instructions"),
             Self::TruncatedNewData => write!(f, "truncated new data"),
+            Self::InvalidCompressedSection => write!(f, "invalid compressed section"),
             Self::NotEnoughNewData => write!(f, "not enough new data"),
             Self::InvalidInstr => write!(f, "invalid instruction"),
+            Self::CopyOutOfBounds => write!(f, "copy instruction out of bounds"),
             Self::MismatchedTargetLen => write!(f, "mismatched target length"),
+            Self::LimitExceeded { what, value, limit } => {
+                write!(f, "{what} {value} exceeds the limit of {limit}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+/// Data a delta is applied to.
+pub(crate) trait DeltaSource {
+    fn size(&self) -> u64;
+
+    /// Fills `buf` with the data at `offset`, which is always within
+    /// bounds.
+    fn read_view(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()>;
+}
+
+impl<S: DeltaSource + ?Sized> DeltaSource for &mut S {
+    fn size(&self) -> u64 {
+        (**self).size()
+    }
+
+    fn read_view(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        (**self).read_view(offset, buf)
+    }
+}
+
+impl<S: DeltaSource + ?Sized> DeltaSource for Box<S> {
+    fn size(&self) -> u64 {
+        (**self).size()
+    }
+
+    fn read_view(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        (**self).read_view(offset, buf)
+    }
+}
+
+impl DeltaSource for &[u8] {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_view(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        let offset = offset as usize;
+        buf.copy_from_slice(&self[offset..(offset + buf.len())]);
+        Ok(())
+    }
+}
+
+/// A [`DeltaSource`] backed by a stream. Source views of consecutive
+/// windows usually move forward, so the stream is only restarted (with
+/// `rewind`) when a view starts before the current position.
+pub(crate) struct StreamSource<R, F> {
+    reader: R,
+    rewind: F,
+    pos: u64,
+    size: u64,
+}
+
+impl<R: std::io::Read, F: FnMut(&mut R) -> std::io::Result<()>> StreamSource<R, F> {
+    pub(crate) fn new(reader: R, size: u64, rewind: F) -> Self {
+        Self {
+            reader,
+            rewind,
+            pos: 0,
+            size,
+        }
+    }
+}
+
+impl<R: std::io::Read, F: FnMut(&mut R) -> std::io::Result<()>> DeltaSource for StreamSource<R, F> {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_view(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        if offset < self.pos {
+            (self.rewind)(&mut self.reader)?;
+            self.pos = 0;
         }
+
+        let skip = offset - self.pos;
+        let skipped = std::io::copy(&mut (&mut self.reader).take(skip), &mut std::io::sink())?;
+        if skipped != skip {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.reader.read_exact(buf)?;
+        self.pos = offset + buf.len() as u64;
+        Ok(())
     }
 }
 
@@ -49,53 +190,156 @@ pub(crate) fn apply(
     delta: &[u8],
     source: &[u8],
     dest: &mut dyn std::io::Write,
+    limits: &Limits,
 ) -> Result<(), ApplyError> {
-    let mut rem_delta = delta;
-    // only support version 0
-    rem_delta = rem_delta
-        .strip_prefix(b"SVN\0")
-        .ok_or(ApplyError::InvalidDeltaHeader)?;
-
-    while !rem_delta.is_empty() {
-        let source_view_off = read_var_len_int(&mut rem_delta)?;
-        let source_view_len = read_var_len_int(&mut rem_delta)?;
-        let target_view_len = read_var_len_int(&mut rem_delta)?;
-        let instrs_len = read_var_len_int(&mut rem_delta)?;
-        let new_data_len = read_var_len_int(&mut rem_delta)?;
-
-        let source_view_off =
-            usize::try_from(source_view_off).map_err(|_| ApplyError::OffsetTooLarge)?;
-        let source_view_len =
-            usize::try_from(source_view_len).map_err(|_| ApplyError::LenTooLarge)?;
-        let source_view = source
-            .get(source_view_off..(source_view_off + source_view_len))
-            .ok_or(ApplyError::SourceViewOutOfBounds {
-                source_len: source.len(),
+    apply_stream(&mut { delta }, &mut { source }, dest, limits)
+}
+
+/// Applies a delta read from a stream, writing the result window by
+/// window, so only one window is kept in memory.
+pub(crate) fn apply_stream(
+    delta: &mut dyn std::io::Read,
+    source: &mut dyn DeltaSource,
+    dest: &mut dyn std::io::Write,
+    limits: &Limits,
+) -> Result<(), ApplyError> {
+    let mut reader = DeltaReader::new(delta, source, limits.clone())?;
+    while reader.next_window()? {
+        dest.write_all(&reader.target_buf)
+            .map_err(ApplyError::DestIo)?;
+    }
+    Ok(())
+}
+
+/// Reads the text resulting from applying a delta, which is applied one
+/// window at a time as the text is read, so it can be used as the source
+/// of another delta.
+///
+/// Errors from applying the delta are returned as [`std::io::Error`]s
+/// wrapping an [`ApplyError`].
+pub(crate) struct DeltaReader<D, S> {
+    delta: D,
+    source: S,
+    limits: Limits,
+    version: Version,
+    source_view: Vec<u8>,
+    instrs_section: Vec<u8>,
+    new_data_section: Vec<u8>,
+    total_target_len: u64,
+    target_buf: Vec<u8>,
+    target_pos: usize,
+}
+
+impl<D: std::io::Read, S: DeltaSource> DeltaReader<D, S> {
+    /// Reads the header of the delta.
+    pub(crate) fn new(mut delta: D, source: S, limits: Limits) -> Result<Self, ApplyError> {
+        let mut header = [0; 4];
+        delta.read_exact(&mut header).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                ApplyError::InvalidDeltaHeader
+            } else {
+                ApplyError::DeltaIo(e)
+            }
+        })?;
+        let version = match header {
+            [b'S', b'V', b'N', 0] => Version::Zero,
+            [b'S', b'V', b'N', 1] => Version::One,
+            [b'S', b'V', b'N', 2] => Version::Two,
+            _ => return Err(ApplyError::InvalidDeltaHeader),
+        };
+
+        Ok(Self {
+            delta,
+            source,
+            limits,
+            version,
+            source_view: Vec::new(),
+            instrs_section: Vec::new(),
+            new_data_section: Vec::new(),
+            total_target_len: 0,
+            target_buf: Vec::new(),
+            target_pos: 0,
+        })
+    }
+
+    /// Applies the next window into `self.target_buf`, or returns `false`
+    /// at the end of the delta.
+    fn next_window(&mut self) -> Result<bool, ApplyError> {
+        let delta: &mut dyn std::io::Read = &mut self.delta;
+        let source = &mut self.source;
+        let limits = &self.limits;
+        let version = self.version;
+        let source_view = &mut self.source_view;
+        let instrs_section = &mut self.instrs_section;
+        let new_data_section = &mut self.new_data_section;
+        let target_buf = &mut self.target_buf;
+        target_buf.clear();
+        self.target_pos = 0;
+
+        let Some(source_view_off) = read_window_start(delta)? else {
+            return Ok(false);
+        };
+        let source_view_len = read_stream_var_len_int(delta)?;
+        let target_view_len = read_stream_var_len_int(delta)?;
+        let instrs_len = read_stream_var_len_int(delta)?;
+        let new_data_len = read_stream_var_len_int(delta)?;
+
+        Limits::check(limits.max_window_len, "source view length", source_view_len)?;
+        Limits::check(limits.max_window_len, "target view length", target_view_len)?;
+        Limits::check(limits.max_window_len, "instructions length", instrs_len)?;
+        Limits::check(limits.max_window_len, "new data length", new_data_len)?;
+        self.total_target_len = self.total_target_len.saturating_add(target_view_len);
+        Limits::check(
+            limits.max_target_len,
+            "target length",
+            self.total_target_len,
+        )?;
+
+        if source_view_off
+            .checked_add(source_view_len)
+            .is_none_or(|view_end| view_end > source.size())
+        {
+            return Err(ApplyError::SourceViewOutOfBounds {
+                source_len: source.size(),
                 view_offset: source_view_off,
                 view_len: source_view_len,
-            })?;
-
-        let instrs_len = usize::try_from(instrs_len).map_err(|_| ApplyError::LenTooLarge)?;
-        if rem_delta.len() < instrs_len {
-            return Err(ApplyError::TruncatedInstrs);
+            });
         }
-        let mut instrs;
-        (instrs, rem_delta) = rem_delta.split_at(instrs_len);
+        let source_view_len =
+            usize::try_from(source_view_len).map_err(|_| ApplyError::LenTooLarge)?;
+        source_view.resize(source_view_len, 0);
+        source
+            .read_view(source_view_off, source_view)
+            .map_err(ApplyError::SourceIo)?;
 
-        let new_data_len = usize::try_from(new_data_len).map_err(|_| ApplyError::LenTooLarge)?;
-        if rem_delta.len() < new_data_len {
-            return Err(ApplyError::TruncatedNewData);
-        }
-        let mut new_data;
-        (new_data, rem_delta) = rem_delta.split_at(new_data_len);
+        read_section(
+            delta,
+            instrs_len,
+            instrs_section,
+            ApplyError::TruncatedInstrs,
+        )?;
+        read_section(
+            delta,
+            new_data_len,
+            new_data_section,
+            ApplyError::TruncatedNewData,
+        )?;
+
+        let instrs = decode_section(version, instrs_section, limits)?;
+        let mut instrs = &*instrs;
+        let new_data = decode_section(version, new_data_section, limits)?;
+        let mut new_data = &*new_data;
 
         let target_view_len =
             usize::try_from(target_view_len).map_err(|_| ApplyError::LenTooLarge)?;
-        let mut target_buf = Vec::with_capacity(target_view_len);
+        target_buf.reserve(target_view_len.min(MAX_PREALLOC_LEN));
 
         while !instrs.is_empty() {
             let (instr, copy_len) = read_instruction(&mut instrs)?;
             let copy_len = usize::try_from(copy_len).map_err(|_| ApplyError::LenTooLarge)?;
+            if copy_len > target_view_len - target_buf.len() {
+                return Err(ApplyError::MismatchedTargetLen);
+            }
 
             match instr {
                 0b00 => {
@@ -104,7 +348,11 @@ pub(crate) fn apply(
                     let copy_offset =
                         usize::try_from(copy_offset).map_err(|_| ApplyError::OffsetTooLarge)?;
 
-                    target_buf.extend(&source_view[copy_offset..(copy_offset + copy_len)]);
+                    let copy_data = copy_offset
+                        .checked_add(copy_len)
+                        .and_then(|copy_end| source_view.get(copy_offset..copy_end))
+                        .ok_or(ApplyError::CopyOutOfBounds)?;
+                    target_buf.extend(copy_data);
                 }
                 0b01 => {
                     // copy from target view
@@ -112,6 +360,11 @@ pub(crate) fn apply(
                     let copy_offset =
                         usize::try_from(copy_offset).map_err(|_| ApplyError::LenTooLarge)?;
 
+                    // The copied range can overlap the data being written,
+                    // but it has to start before it.
+                    if copy_offset >= target_buf.len() {
+                        return Err(ApplyError::CopyOutOfBounds);
+                    }
                     for i in 0..copy_len {
                         target_buf.push(target_buf[copy_offset + i]);
                     }
@@ -137,12 +390,193 @@ pub(crate) fn apply(
             return Err(ApplyError::MismatchedTargetLen);
         }
 
-        dest.write_all(&target_buf).map_err(ApplyError::DestIo)?;
+        Ok(true)
+    }
+}
+
+impl<D: std::io::Read, S: DeltaSource> std::io::Read for DeltaReader<D, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.target_pos == self.target_buf.len()
+            && !self.next_window().map_err(std::io::Error::other)?
+        {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.target_buf.len() - self.target_pos);
+        buf[..len].copy_from_slice(&self.target_buf[self.target_pos..(self.target_pos + len)]);
+        self.target_pos += len;
+        Ok(len)
+    }
+}
+
+/// Returns the length of the text resulting from applying a delta, read
+/// from the window headers without applying it.
+pub(crate) fn target_len(
+    delta: &mut dyn std::io::Read,
+    limits: &Limits,
+) -> Result<u64, ApplyError> {
+    let mut header = [0; 4];
+    delta.read_exact(&mut header).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            ApplyError::InvalidDeltaHeader
+        } else {
+            ApplyError::DeltaIo(e)
+        }
+    })?;
+    if !matches!(header, [b'S', b'V', b'N', 0..=2]) {
+        return Err(ApplyError::InvalidDeltaHeader);
     }
 
+    let mut total_target_len = 0u64;
+    while read_window_start(delta)?.is_some() {
+        read_stream_var_len_int(delta)?;
+        let target_view_len = read_stream_var_len_int(delta)?;
+        let instrs_len = read_stream_var_len_int(delta)?;
+        let new_data_len = read_stream_var_len_int(delta)?;
+
+        total_target_len = total_target_len.saturating_add(target_view_len);
+        Limits::check(limits.max_target_len, "target length", total_target_len)?;
+
+        for (len, truncated_error) in [
+            (instrs_len, ApplyError::TruncatedInstrs),
+            (new_data_len, ApplyError::TruncatedNewData),
+        ] {
+            let skipped = std::io::copy(&mut delta.take(len), &mut std::io::sink())
+                .map_err(ApplyError::DeltaIo)?;
+            if skipped != len {
+                return Err(truncated_error);
+            }
+        }
+    }
+    Ok(total_target_len)
+}
+
+/// Decompresses data compressed by Subversion with zlib outside deltas
+/// (such as packed revision properties), which uses the same encoding as
+/// svndiff1 sections.
+pub(crate) fn decompress_zlib(data: &[u8]) -> Result<Cow<'_, [u8]>, ApplyError> {
+    decode_section(Version::One, data, &Limits::default())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Version {
+    /// Uncompressed sections
+    Zero,
+    /// zlib compressed sections
+    One,
+    /// LZ4 compressed sections
+    Two,
+}
+
+/// Decodes the instructions or new data section of a window.
+///
+/// In versions 1 and 2, sections start with their original length. When
+/// compression does not make them smaller, they are stored uncompressed.
+fn decode_section<'a>(
+    version: Version,
+    mut section: &'a [u8],
+    limits: &Limits,
+) -> Result<Cow<'a, [u8]>, ApplyError> {
+    if version == Version::Zero {
+        return Ok(Cow::Borrowed(section));
+    }
+
+    let orig_len = read_var_len_int(&mut section)?;
+    Limits::check(limits.max_window_len, "decoded section length", orig_len)?;
+    let orig_len = usize::try_from(orig_len).map_err(|_| ApplyError::LenTooLarge)?;
+    if orig_len == section.len() {
+        return Ok(Cow::Borrowed(section));
+    }
+
+    let decoded = match version {
+        Version::Zero => unreachable!(),
+        Version::One => {
+            let mut decoded = Vec::new();
+            flate2::read::ZlibDecoder::new(section)
+                .take(orig_len as u64 + 1)
+                .read_to_end(&mut decoded)
+                .map_err(|_| ApplyError::InvalidCompressedSection)?;
+            decoded
+        }
+        Version::Two => {
+            // LZ4 cannot compress more than 255:1, which also avoids
+            // allocating huge buffers for corrupted lengths.
+            if orig_len > section.len().saturating_mul(255).saturating_add(16) {
+                return Err(ApplyError::InvalidCompressedSection);
+            }
+            lz4_flex::block::decompress(section, orig_len)
+                .map_err(|_| ApplyError::InvalidCompressedSection)?
+        }
+    };
+    if decoded.len() != orig_len {
+        return Err(ApplyError::InvalidCompressedSection);
+    }
+    Ok(Cow::Owned(decoded))
+}
+
+/// Reads a section of `len` bytes from the delta stream into `buf`.
+fn read_section(
+    delta: &mut dyn std::io::Read,
+    len: u64,
+    buf: &mut Vec<u8>,
+    truncated_error: ApplyError,
+) -> Result<(), ApplyError> {
+    buf.clear();
+    let read_len = delta
+        .take(len)
+        .read_to_end(buf)
+        .map_err(ApplyError::DeltaIo)?;
+    if read_len as u64 != len {
+        return Err(truncated_error);
+    }
     Ok(())
 }
 
+/// Reads the source view offset of the next window, or returns `None` at
+/// the end of the delta.
+fn read_window_start(delta: &mut dyn std::io::Read) -> Result<Option<u64>, ApplyError> {
+    let mut first_byte = [0];
+    loop {
+        match delta.read(&mut first_byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(ApplyError::DeltaIo(e)),
+        }
+    }
+    read_var_len_int_from(first_byte[0], delta).map(Some)
+}
+
+fn read_stream_var_len_int(delta: &mut dyn std::io::Read) -> Result<u64, ApplyError> {
+    let mut first_byte = [0];
+    read_stream_byte(delta, &mut first_byte)?;
+    read_var_len_int_from(first_byte[0], delta)
+}
+
+fn read_var_len_int_from(first_byte: u8, delta: &mut dyn std::io::Read) -> Result<u64, ApplyError> {
+    let mut value = u64::from(first_byte & 0x7F);
+    let mut byte = [first_byte];
+    while (byte[0] & 0x80) != 0 {
+        read_stream_byte(delta, &mut byte)?;
+
+        if value > (u64::MAX >> 7) {
+            return Err(ApplyError::InvalidVarLenInt);
+        }
+
+        value = (value << 7) | u64::from(byte[0] & 0x7F);
+    }
+    Ok(value)
+}
+
+fn read_stream_byte(delta: &mut dyn std::io::Read, byte: &mut [u8; 1]) -> Result<(), ApplyError> {
+    delta.read_exact(byte).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            ApplyError::InvalidVarLenInt
+        } else {
+            ApplyError::DeltaIo(e)
+        }
+    })
+}
+
 fn read_var_len_int(src: &mut &[u8]) -> Result<u64, ApplyError> {
     let mut value = 0;
     loop {
@@ -177,7 +611,9 @@ fn read_instruction(src: &mut &[u8]) -> Result<(u8, u64), ApplyError> {
 
 #[cfg(test)]
 mod tests {
-    use super::apply;
+    use std::io::Write as _;
+
+    use super::{ApplyError, DeltaReader, Limits, StreamSource, apply, apply_stream, target_len};
 
     #[test]
     fn test_apply() {
@@ -199,8 +635,315 @@ mod tests {
         let expected_target = b"aaaaccccdddddddd";
 
         let mut target = Vec::new();
-        apply(delta, source, &mut target).unwrap();
+        apply(delta, source, &mut target, &Limits::default()).unwrap();
 
         assert_eq!(target, expected_target);
     }
+
+    /// Makes a delta with a single window that copies the first 4 bytes
+    /// of the source and then 100 bytes of new data (`x`).
+    fn make_repetitive_delta(version: u8, compress: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let instrs = [
+            0x04, 0x00, // source, length 4, offset 0
+            0x80, 0x64, // new, length 100
+        ];
+        let new_data = [b'x'; 100];
+
+        // instructions are stored uncompressed, since they are too short
+        let instrs_section = [&[0x04][..], &instrs].concat();
+        let new_data_section = [&[0x64][..], &compress(&new_data)].concat();
+        assert!(new_data_section.len() < new_data.len());
+
+        let mut delta = vec![b'S', b'V', b'N', version];
+        delta.extend([
+            0x00, // source view offset 0
+            0x0C, // source view length 12
+            0x68, // target view length 104
+        ]);
+        delta.push(u8::try_from(instrs_section.len()).unwrap());
+        delta.push(u8::try_from(new_data_section.len()).unwrap());
+        delta.extend(instrs_section);
+        delta.extend(new_data_section);
+        delta
+    }
+
+    #[test]
+    fn test_apply_svndiff1() {
+        let delta = make_repetitive_delta(1, |data| {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        });
+
+        let mut target = Vec::new();
+        apply(&delta, b"aaaabbbbcccc", &mut target, &Limits::default()).unwrap();
+
+        assert_eq!(target, [b"aaaa".as_slice(), &[b'x'; 100]].concat());
+    }
+
+    #[test]
+    fn test_apply_svndiff2() {
+        let delta = make_repetitive_delta(2, lz4_flex::block::compress);
+
+        let mut target = Vec::new();
+        apply(&delta, b"aaaabbbbcccc", &mut target, &Limits::default()).unwrap();
+
+        assert_eq!(target, [b"aaaa".as_slice(), &[b'x'; 100]].concat());
+    }
+
+    #[test]
+    fn test_apply_corrupted_compressed() {
+        let mut delta = make_repetitive_delta(2, lz4_flex::block::compress);
+        // Claim a larger original length of the new data
+        let new_data_start = delta.len() - 1 - lz4_flex::block::compress(&[b'x'; 100]).len();
+        delta[new_data_start] = 0x65;
+
+        let r = apply(&delta, b"aaaabbbbcccc", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::InvalidCompressedSection)));
+    }
+
+    #[test]
+    fn test_apply_stream() {
+        let source = b"aaaabbbbcccc";
+        let delta = &[
+            b'S', b'V', b'N', 0, // header
+            // window 1
+            0x08, // source view offset 8
+            0x04, // source view length 4
+            0x04, // target view length 4
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x04, 0x00, // source, length 4, offset 0
+            // window 2
+            0x00, // source view offset 0
+            0x04, // source view length 4
+            0x05, // target view length 5
+            0x03, // instructions length 3
+            0x01, // new data length 1
+            0x04, 0x00, // source, length 4, offset 0
+            0x81, // new, length 1
+            b'd', // new data 'd'
+        ];
+
+        let mut rewinds = 0;
+        let mut source_stream = StreamSource::new(
+            source.as_slice(),
+            source.len() as u64,
+            |reader: &mut &[u8]| {
+                rewinds += 1;
+                *reader = source.as_slice();
+                Ok(())
+            },
+        );
+        let mut target = Vec::new();
+        apply_stream(
+            &mut delta.as_slice(),
+            &mut source_stream,
+            &mut target,
+            &Limits::default(),
+        )
+        .unwrap();
+
+        assert_eq!(target, b"ccccaaaad");
+        // The second window starts before the end of the first one.
+        assert_eq!(rewinds, 1);
+    }
+
+    #[test]
+    fn test_delta_reader_chain() {
+        let source = b"aaaabbbbcccc";
+        // Produces "ccccaaaad", with a second window that goes backwards.
+        let first_delta = &[
+            b'S', b'V', b'N', 0, // header
+            // window 1
+            0x08, // source view offset 8
+            0x04, // source view length 4
+            0x04, // target view length 4
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x04, 0x00, // source, length 4, offset 0
+            // window 2
+            0x00, // source view offset 0
+            0x04, // source view length 4
+            0x05, // target view length 5
+            0x03, // instructions length 3
+            0x01, // new data length 1
+            0x04, 0x00, // source, length 4, offset 0
+            0x81, // new, length 1
+            b'd', // new data 'd'
+        ];
+        // Applied to the result of the first one, produces "aaaadcccc".
+        let second_delta = &[
+            b'S', b'V', b'N', 0, // header
+            // window 1
+            0x04, // source view offset 4
+            0x05, // source view length 5
+            0x05, // target view length 5
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x05, 0x00, // source, length 5, offset 0
+            // window 2
+            0x00, // source view offset 0
+            0x04, // source view length 4
+            0x04, // target view length 4
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x04, 0x00, // source, length 4, offset 0
+        ];
+
+        assert_eq!(
+            target_len(&mut first_delta.as_slice(), &Limits::default()).unwrap(),
+            9,
+        );
+        assert_eq!(
+            target_len(&mut second_delta.as_slice(), &Limits::default()).unwrap(),
+            9,
+        );
+
+        let open_first = || {
+            DeltaReader::new(first_delta.as_slice(), source.as_slice(), Limits::default()).unwrap()
+        };
+        let mut rewinds = 0;
+        let first_stream = StreamSource::new(open_first(), 9, |reader: &mut DeltaReader<_, _>| {
+            rewinds += 1;
+            *reader = open_first();
+            Ok(())
+        });
+        let mut second_reader =
+            DeltaReader::new(second_delta.as_slice(), first_stream, Limits::default()).unwrap();
+        let mut target = Vec::new();
+        std::io::Read::read_to_end(&mut second_reader, &mut target).unwrap();
+        drop(second_reader);
+
+        assert_eq!(target, b"aaaadcccc");
+        assert_eq!(rewinds, 1);
+    }
+
+    #[test]
+    fn test_delta_reader_error() {
+        let delta = &[
+            b'S', b'V', b'N', 0,    // header
+            0x00, // source view offset 0
+            0x00, // source view length 0
+            0x01, // target view length 1
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x01, 0x00, // source, length 1, offset 0
+        ];
+        let mut reader =
+            DeltaReader::new(delta.as_slice(), [].as_slice(), Limits::default()).unwrap();
+        let error = std::io::Read::read_to_end(&mut reader, &mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            error
+                .into_inner()
+                .unwrap()
+                .downcast::<ApplyError>()
+                .as_deref(),
+            Ok(ApplyError::CopyOutOfBounds),
+        ));
+    }
+
+    #[test]
+    fn test_apply_truncated() {
+        let delta = &[
+            b'S', b'V', b'N', 0,    // header
+            0x00, // source view offset 0
+            0x00, // source view length 0
+            0x02, // target view length 2
+            0x01, // instructions length 1
+            0x02, // new data length 2
+            0x82, // new, length 2
+            b'd', // new data 'd' (truncated)
+        ];
+
+        let r = apply(delta, b"", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::TruncatedNewData)));
+
+        let r = apply(&delta[..6], b"", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::InvalidVarLenInt)));
+    }
+
+    #[test]
+    fn test_apply_limits() {
+        let delta = make_repetitive_delta(1, |data| {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        });
+
+        let limits = Limits {
+            max_window_len: Some(104),
+            max_target_len: Some(104),
+        };
+        apply(&delta, b"aaaabbbbcccc", &mut Vec::new(), &limits).unwrap();
+
+        let limits = Limits {
+            max_window_len: Some(100),
+            max_target_len: None,
+        };
+        let r = apply(&delta, b"aaaabbbbcccc", &mut Vec::new(), &limits);
+        assert!(matches!(
+            r,
+            Err(ApplyError::LimitExceeded {
+                what: "target view length",
+                value: 104,
+                limit: 100,
+            }),
+        ));
+
+        let limits = Limits {
+            max_window_len: None,
+            max_target_len: Some(103),
+        };
+        let r = apply(&delta, b"aaaabbbbcccc", &mut Vec::new(), &limits);
+        assert!(matches!(
+            r,
+            Err(ApplyError::LimitExceeded {
+                what: "target length",
+                value: 104,
+                limit: 103,
+            }),
+        ));
+    }
+
+    #[test]
+    fn test_apply_out_of_bounds() {
+        let delta = &[
+            b'S', b'V', b'N', 0,    // header
+            0x00, // source view offset 0
+            0x04, // source view length 4
+            0x04, // target view length 4
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x04, 0x02, // source, length 4, offset 2
+        ];
+        let r = apply(delta, b"aaaa", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::CopyOutOfBounds)));
+
+        let delta = &[
+            b'S', b'V', b'N', 0,    // header
+            0x00, // source view offset 0
+            0x00, // source view length 0
+            0x04, // target view length 4
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x44, 0x00, // target, length 4, offset 0
+        ];
+        let r = apply(delta, b"", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::CopyOutOfBounds)));
+
+        let delta = &[
+            b'S', b'V', b'N', 0,    // header
+            0x00, // source view offset 0
+            0x04, // source view length 4
+            0x02, // target view length 2
+            0x02, // instructions length 2
+            0x00, // new data length 0
+            0x04, 0x00, // source, length 4, offset 0
+        ];
+        let r = apply(delta, b"aaaa", &mut Vec::new(), &Limits::default());
+        assert!(matches!(r, Err(ApplyError::MismatchedTargetLen)));
+    }
 }
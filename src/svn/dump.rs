@@ -7,13 +7,22 @@ use crate::FHashMap;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum DumpVersion {
+    One,
     Two,
     Three,
 }
 
 impl DumpVersion {
+    pub(crate) fn has_deltas(self) -> bool {
+        match self {
+            Self::One | Self::Two => false,
+            Self::Three => true,
+        }
+    }
+
     fn parse(s: &[u8]) -> Option<Self> {
         match s {
+            b"1" => Some(Self::One),
             b"2" => Some(Self::Two),
             b"3" => Some(Self::Three),
             _ => None,
@@ -21,7 +30,54 @@ impl DumpVersion {
     }
 }
 
+/// Limits on the sizes found in a dump, to reject malformed or hostile
+/// input before allocating memory for it. `None` means unlimited.
+#[derive(Clone, Debug)]
+pub(crate) struct Limits {
+    /// Maximum length of a node text (`Text-content-length`).
+    pub(crate) max_text_len: Option<u64>,
+    /// Maximum length of the properties of a record (`Prop-content-length`).
+    pub(crate) max_props_len: Option<u64>,
+    /// Maximum length of a node path or copy source path.
+    pub(crate) max_path_len: Option<u64>,
+    /// Maximum number of components of a node path or copy source path.
+    pub(crate) max_path_depth: Option<u64>,
+    /// Maximum number of node records in a revision.
+    pub(crate) max_nodes_per_rev: Option<u64>,
+}
+
+impl Default for Limits {
+    /// Texts are not limited, since large ones are streamed instead of
+    /// being kept in memory.
+    fn default() -> Self {
+        Self {
+            max_text_len: None,
+            max_props_len: Some(256 * 1024 * 1024),
+            max_path_len: Some(64 * 1024),
+            max_path_depth: Some(4096),
+            max_nodes_per_rev: Some(16 * 1024 * 1024),
+        }
+    }
+}
+
+impl Limits {
+    fn check(limit: Option<u64>, what: &'static str, value: u64) -> Result<(), ReadError> {
+        match limit {
+            Some(limit) if value > limit => Err(ReadError::LimitExceeded { what, value, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_path(&self, path: &[u8]) -> Result<(), ReadError> {
+        Self::check(self.max_path_len, "path length", path.len() as u64)?;
+        let depth = path.split(|&c| c == b'/').filter(|c| !c.is_empty()).count();
+        Self::check(self.max_path_depth, "path depth", depth as u64)
+    }
+}
+
 pub(crate) enum Record {
+    /// Header of another dump concatenated to the previous one.
+    NewDump,
     Uuid(uuid::Uuid),
     Rev(RevRecord),
     Node(NodeRecord),
@@ -81,6 +137,8 @@ impl NodeKind {
 pub(crate) struct NodeCopyFrom {
     pub(crate) rev: u32,
     pub(crate) path: Vec<u8>,
+    /// MD5 of the text of the copy source (`Text-copy-source-md5`)
+    pub(crate) source_md5: Option<[u8; 16]>,
 }
 
 #[derive(Debug)]
@@ -91,6 +149,38 @@ pub(crate) struct NodeProperties {
 
 pub(crate) struct NodeText {
     pub(crate) is_delta: bool,
+    /// MD5 of the full text (`Text-content-md5`)
+    pub(crate) md5: Option<[u8; 16]>,
+    /// SHA-1 of the full text (`Text-content-sha1`)
+    pub(crate) sha1: Option<[u8; 20]>,
+    /// MD5 of the text the delta applies to (`Text-delta-base-md5`)
+    pub(crate) delta_base_md5: Option<[u8; 16]>,
+}
+
+fn parse_hex<const N: usize>(s: &[u8]) -> Option<[u8; N]> {
+    if s.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, hex) in bytes.iter_mut().zip(s.chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn parse_checksum_entry<const N: usize>(
+    header: &RecordHeader,
+    key: &[u8],
+) -> Result<Option<[u8; N]>, ReadError> {
+    header
+        .get(key)
+        .map(|raw| {
+            parse_hex(raw).ok_or_else(|| ReadError::InvalidHeaderEntry {
+                key: key.to_vec(),
+                value: raw.clone(),
+            })
+        })
+        .transpose()
 }
 
 fn parse_bool(s: &[u8]) -> Option<bool> {
@@ -105,13 +195,27 @@ fn parse_bool(s: &[u8]) -> Option<bool> {
 pub(crate) enum ReadError {
     Io(std::io::Error),
     BrokenHeader,
-    InvalidVersion { version: Vec<u8> },
-    MissingHeaderEntry { key: Vec<u8> },
-    UnexpectedHeaderEntry { key: Vec<u8> },
-    InvalidHeaderEntry { key: Vec<u8>, value: Vec<u8> },
+    InvalidVersion {
+        version: Vec<u8>,
+    },
+    MissingHeaderEntry {
+        key: Vec<u8>,
+    },
+    UnexpectedHeaderEntry {
+        key: Vec<u8>,
+    },
+    InvalidHeaderEntry {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
     UnknownRecordType,
     MismatchedContentLen,
     BrokenProperties,
+    LimitExceeded {
+        what: &'static str,
+        value: u64,
+        limit: u64,
+    },
 }
 
 impl From<std::io::Error> for ReadError {
@@ -144,28 +248,37 @@ impl std::fmt::Display for ReadError {
             Self::UnknownRecordType => write!(f, "unknown record type"),
             Self::MismatchedContentLen => write!(f, "mismatched content length"),
             Self::BrokenProperties => write!(f, "broken properties"),
+            Self::LimitExceeded { what, value, limit } => {
+                write!(f, "{what} {value} exceeds the limit of {limit}")
+            }
         }
     }
 }
 
+const VERSION_KEY: &[u8] = b"SVN-fs-dump-format-version";
+
 pub(crate) struct DumpReader<'a> {
     source: &'a mut dyn std::io::BufRead,
     version: DumpVersion,
+    limits: Limits,
     rem_text_len: u64,
+    /// Number of node records since the last revision record.
+    rev_nodes: u64,
 }
 
 impl<'a> DumpReader<'a> {
-    pub(crate) fn new(source: &'a mut dyn std::io::BufRead) -> Result<Self, ReadError> {
+    pub(crate) fn new(
+        source: &'a mut dyn std::io::BufRead,
+        limits: Limits,
+    ) -> Result<Self, ReadError> {
         let header = parse_header(source)?
             .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
 
-        let version_key = b"SVN-fs-dump-format-version";
-        let raw_version =
-            header
-                .get(version_key.as_slice())
-                .ok_or_else(|| ReadError::MissingHeaderEntry {
-                    key: version_key.to_vec(),
-                })?;
+        let raw_version = header
+            .get(VERSION_KEY)
+            .ok_or_else(|| ReadError::MissingHeaderEntry {
+                key: VERSION_KEY.to_vec(),
+            })?;
         let version = DumpVersion::parse(raw_version).ok_or_else(|| ReadError::InvalidVersion {
             version: raw_version.clone(),
         })?;
@@ -173,17 +286,12 @@ impl<'a> DumpReader<'a> {
         Ok(Self {
             source,
             version,
+            limits,
             rem_text_len: 0,
+            rev_nodes: 0,
         })
     }
 
-    fn has_deltas(&self) -> bool {
-        match self.version {
-            DumpVersion::Two => false,
-            DumpVersion::Three => true,
-        }
-    }
-
     pub(crate) fn next_record(&mut self) -> Result<Option<Record>, ReadError> {
         assert_eq!(
             self.rem_text_len, 0,
@@ -194,6 +302,17 @@ impl<'a> DumpReader<'a> {
             return Ok(None);
         };
 
+        if let Some(raw_version) = header.get(VERSION_KEY) {
+            if header.len() != 1 {
+                return Err(ReadError::UnknownRecordType);
+            }
+            self.version =
+                DumpVersion::parse(raw_version).ok_or_else(|| ReadError::InvalidVersion {
+                    version: raw_version.clone(),
+                })?;
+            return Ok(Some(Record::NewDump));
+        }
+
         let uuid_key = b"UUID";
         let rev_no_key = b"Revision-number";
         let node_path_key = b"Node-path";
@@ -276,30 +395,34 @@ impl<'a> DumpReader<'a> {
             if prop_content_len.unwrap_or(0) != content_len.unwrap_or(0) {
                 return Err(ReadError::MismatchedContentLen);
             }
+            Limits::check(
+                self.limits.max_props_len,
+                "properties length",
+                prop_content_len.unwrap_or(0),
+            )?;
 
+            self.rev_nodes = 0;
             let properties = prop_content_len
                 .map(|prop_content_len| {
-                    let mut prop_stream = std::io::Read::take(&mut self.source, prop_content_len);
-                    match parse_properties(&mut prop_stream, false) {
-                        Ok(props) => {
-                            if prop_stream.limit() != 0 {
-                                Err(ReadError::BrokenProperties)
-                            } else {
-                                Ok(props.into_iter().map(|(k, v)| (k, v.unwrap())).collect())
-                            }
-                        }
-                        Err(e) => match e.kind() {
-                            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
-                                Err(ReadError::BrokenProperties)
-                            }
-                            _ => Err(ReadError::Io(e)),
-                        },
+                    let (props, rem_len) = self.read_properties(prop_content_len, false)?;
+                    if rem_len != 0 {
+                        Err(ReadError::BrokenProperties)
+                    } else {
+                        Ok(props.into_iter().map(|(k, v)| (k, v.unwrap())).collect())
                     }
                 })
                 .transpose()?;
 
             Ok(Some(Record::Rev(RevRecord { rev_no, properties })))
         } else if let Some(raw_node_path) = raw_node_path {
+            self.rev_nodes += 1;
+            Limits::check(
+                self.limits.max_nodes_per_rev,
+                "number of nodes in revision",
+                self.rev_nodes,
+            )?;
+            self.limits.check_path(raw_node_path)?;
+
             let kind_key = b"Node-kind";
             let kind = header
                 .get(kind_key.as_slice())
@@ -339,9 +462,11 @@ impl<'a> DumpReader<'a> {
                             key: copy_from_rev_key.to_vec(),
                             value: raw_copy_from_rev.clone(),
                         })?;
+                    self.limits.check_path(raw_copy_from_path)?;
                     Some(NodeCopyFrom {
                         rev: copy_from_rev,
                         path: raw_copy_from_path.clone(),
+                        source_md5: parse_checksum_entry(&header, b"Text-copy-source-md5")?,
                     })
                 }
                 (Some(_), None) => {
@@ -369,7 +494,7 @@ impl<'a> DumpReader<'a> {
                 })
                 .transpose()?;
 
-            let text_content_len = header
+            let mut text_content_len = header
                 .get(text_content_len_key.as_slice())
                 .map(|raw| {
                     std::str::from_utf8(raw)
@@ -395,6 +520,17 @@ impl<'a> DumpReader<'a> {
                 })
                 .transpose()?;
 
+            let can_have_deltas = self.version.has_deltas();
+
+            // Old version 1 dumps might only have Content-length, which is
+            // then the length of the text, as in the loader of Subversion.
+            if self.version == DumpVersion::One
+                && prop_content_len.is_none()
+                && text_content_len.is_none()
+            {
+                text_content_len = content_len;
+            }
+
             let expected_content_len = prop_content_len
                 .unwrap_or(0)
                 .checked_add(text_content_len.unwrap_or(0))
@@ -402,8 +538,17 @@ impl<'a> DumpReader<'a> {
             if content_len.unwrap_or(0) != expected_content_len {
                 return Err(ReadError::MismatchedContentLen);
             }
+            Limits::check(
+                self.limits.max_props_len,
+                "properties length",
+                prop_content_len.unwrap_or(0),
+            )?;
+            Limits::check(
+                self.limits.max_text_len,
+                "text length",
+                text_content_len.unwrap_or(0),
+            )?;
 
-            let can_have_deltas = self.has_deltas();
             let properties = prop_content_len
                 .map(|prop_content_len| {
                     let prop_delta_key = b"Prop-delta";
@@ -428,24 +573,14 @@ impl<'a> DumpReader<'a> {
                         }
                     }
 
-                    let mut prop_stream = (&mut self.source).take(prop_content_len);
-                    match parse_properties(&mut prop_stream, has_deltas) {
-                        Ok(props) => {
-                            if prop_stream.limit() != 0 {
-                                Err(ReadError::BrokenProperties)
-                            } else {
-                                Ok(NodeProperties {
-                                    is_delta: has_deltas,
-                                    properties: props,
-                                })
-                            }
-                        }
-                        Err(e) => match e.kind() {
-                            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
-                                Err(ReadError::BrokenProperties)
-                            }
-                            _ => Err(ReadError::Io(e)),
-                        },
+                    let (props, rem_len) = self.read_properties(prop_content_len, has_deltas)?;
+                    if rem_len != 0 {
+                        Err(ReadError::BrokenProperties)
+                    } else {
+                        Ok(NodeProperties {
+                            is_delta: has_deltas,
+                            properties: props,
+                        })
                     }
                 })
                 .transpose()?;
@@ -474,9 +609,16 @@ impl<'a> DumpReader<'a> {
                         }
                     }
 
+                    let md5 = parse_checksum_entry(&header, b"Text-content-md5")?;
+                    let sha1 = parse_checksum_entry(&header, b"Text-content-sha1")?;
+                    let delta_base_md5 = parse_checksum_entry(&header, b"Text-delta-base-md5")?;
+
                     self.rem_text_len = text_content_len;
                     Ok(NodeText {
                         is_delta: has_deltas,
+                        md5,
+                        sha1,
+                        delta_base_md5,
                     })
                 })
                 .transpose()?;
@@ -494,20 +636,248 @@ impl<'a> DumpReader<'a> {
         }
     }
 
+    /// Reads a property block from the next `len` bytes, returning the
+    /// number of bytes left after it.
+    fn read_properties(
+        &mut self,
+        len: u64,
+        is_delta: bool,
+    ) -> Result<(Properties, u64), ReadError> {
+        let mut prop_stream = (&mut self.source).take(len);
+        match parse_properties(&mut prop_stream, len, is_delta) {
+            Ok(props) => Ok((props, prop_stream.limit())),
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                    Err(ReadError::BrokenProperties)
+                }
+                _ => Err(ReadError::Io(e)),
+            },
+        }
+    }
+
     #[inline]
     pub(crate) fn remaining_text_len(&self) -> u64 {
         self.rem_text_len
     }
 
-    pub(crate) fn read_text(&mut self, buf: &mut [u8]) -> Result<(), std::io::Error> {
-        let len_u64 = u64::try_from(buf.len())
-            .ok()
-            .filter(|&l| l <= self.rem_text_len)
-            .expect("buffer too large");
-        self.source.read_exact(buf)?;
-        self.rem_text_len -= len_u64;
+    pub(crate) fn skip_text(&mut self) -> Result<(), std::io::Error> {
+        let skipped = std::io::copy(
+            &mut std::io::Read::take(&mut self.source, self.rem_text_len),
+            &mut std::io::sink(),
+        )?;
+        if skipped != self.rem_text_len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.rem_text_len = 0;
         Ok(())
     }
+
+    /// Reads the remaining text. The buffer grows as the text is read, so
+    /// a corrupted length does not allocate more memory than the dump
+    /// contains.
+    pub(crate) fn read_text_to_end(&mut self) -> Result<Vec<u8>, std::io::Error> {
+        let mut buf = Vec::new();
+        self.text_reader().read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Returns a reader of the remaining text, to consume it without
+    /// keeping it entirely in memory.
+    pub(crate) fn text_reader(&mut self) -> TextReader<'_, 'a> {
+        TextReader { dump_reader: self }
+    }
+}
+
+pub(crate) struct TextReader<'r, 'a> {
+    dump_reader: &'r mut DumpReader<'a>,
+}
+
+impl std::io::Read for TextReader<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let rem_text_len = self.dump_reader.rem_text_len;
+        if rem_text_len == 0 {
+            return Ok(0);
+        }
+
+        let max_len = usize::try_from(rem_text_len).unwrap_or(usize::MAX);
+        let len = buf.len().min(max_len);
+        let read_len = self.dump_reader.source.read(&mut buf[..len])?;
+        if read_len == 0 && len != 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.dump_reader.rem_text_len -= read_len as u64;
+        Ok(read_len)
+    }
+}
+
+/// Writes a dump, in the format read by [`DumpReader`].
+pub(crate) struct DumpWriter<W: std::io::Write> {
+    dest: W,
+    version: DumpVersion,
+}
+
+impl<W: std::io::Write> DumpWriter<W> {
+    /// Creates a writer of a dump of the given version, which cannot be 1,
+    /// and writes its header.
+    pub(crate) fn new(mut dest: W, version: DumpVersion) -> Result<Self, std::io::Error> {
+        let raw_version = match version {
+            DumpVersion::One => panic!("cannot write version 1 dumps"),
+            DumpVersion::Two => "2",
+            DumpVersion::Three => "3",
+        };
+        writeln!(dest, "SVN-fs-dump-format-version: {raw_version}\n")?;
+        Ok(Self { dest, version })
+    }
+
+    #[inline]
+    pub(crate) fn version(&self) -> DumpVersion {
+        self.version
+    }
+
+    pub(crate) fn write_uuid(&mut self, uuid: &uuid::Uuid) -> Result<(), std::io::Error> {
+        writeln!(self.dest, "UUID: {uuid}\n")
+    }
+
+    pub(crate) fn write_rev(&mut self, rev_record: &RevRecord) -> Result<(), std::io::Error> {
+        writeln!(self.dest, "Revision-number: {}", rev_record.rev_no)?;
+        if let Some(ref properties) = rev_record.properties {
+            let raw_props = serialize_properties(
+                properties
+                    .iter()
+                    .map(|(k, v)| (k.as_slice(), Some(v.as_slice()))),
+            );
+            writeln!(self.dest, "Prop-content-length: {}", raw_props.len())?;
+            writeln!(self.dest, "Content-length: {}\n", raw_props.len())?;
+            self.dest.write_all(&raw_props)?;
+        }
+        self.dest.write_all(b"\n")
+    }
+
+    /// Writes a node record. If the node has a text, its `text_len` bytes
+    /// are copied from `text_src`.
+    ///
+    /// Property and text deltas can only be written to version 3 dumps.
+    pub(crate) fn write_node(
+        &mut self,
+        node_record: &NodeRecord,
+        text_len: u64,
+        text_src: &mut dyn std::io::Read,
+    ) -> Result<(), std::io::Error> {
+        let has_deltas = self.version.has_deltas();
+        assert!(
+            has_deltas
+                || !(node_record.properties.as_ref().is_some_and(|p| p.is_delta)
+                    || node_record.text.as_ref().is_some_and(|t| t.is_delta)),
+            "deltas can only be written to version 3 dumps",
+        );
+
+        let w = &mut self.dest;
+        w.write_all(b"Node-path: ")?;
+        w.write_all(&node_record.path)?;
+        w.write_all(b"\n")?;
+        match node_record.kind {
+            None => {}
+            Some(NodeKind::File) => w.write_all(b"Node-kind: file\n")?,
+            Some(NodeKind::Dir) => w.write_all(b"Node-kind: dir\n")?,
+        }
+        w.write_all(match node_record.action {
+            NodeAction::Change => b"Node-action: change\n",
+            NodeAction::Add => b"Node-action: add\n",
+            NodeAction::Delete => b"Node-action: delete\n",
+            NodeAction::Replace => b"Node-action: replace\n",
+        })?;
+        if let Some(ref copy_from) = node_record.copy_from {
+            writeln!(w, "Node-copyfrom-rev: {}", copy_from.rev)?;
+            w.write_all(b"Node-copyfrom-path: ")?;
+            w.write_all(&copy_from.path)?;
+            w.write_all(b"\n")?;
+            if let Some(ref md5) = copy_from.source_md5 {
+                writeln!(w, "Text-copy-source-md5: {}", hex_string(md5))?;
+            }
+        }
+
+        let raw_props = node_record.properties.as_ref().map(|props| {
+            serialize_properties(
+                props
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.as_slice(), v.as_deref())),
+            )
+        });
+        if node_record.properties.as_ref().is_some_and(|p| p.is_delta) {
+            w.write_all(b"Prop-delta: true\n")?;
+        }
+        if let Some(ref text) = node_record.text {
+            if text.is_delta {
+                w.write_all(b"Text-delta: true\n")?;
+                if let Some(ref md5) = text.delta_base_md5 {
+                    writeln!(w, "Text-delta-base-md5: {}", hex_string(md5))?;
+                }
+            }
+            if let Some(ref md5) = text.md5 {
+                writeln!(w, "Text-content-md5: {}", hex_string(md5))?;
+            }
+            if let Some(ref sha1) = text.sha1 {
+                writeln!(w, "Text-content-sha1: {}", hex_string(sha1))?;
+            }
+        }
+
+        let props_len = raw_props.as_ref().map_or(0, |p| p.len() as u64);
+        if let Some(ref raw_props) = raw_props {
+            writeln!(w, "Prop-content-length: {}", raw_props.len())?;
+        }
+        if node_record.text.is_some() {
+            writeln!(w, "Text-content-length: {text_len}")?;
+        } else {
+            assert_eq!(text_len, 0, "text length of node without text");
+        }
+        if raw_props.is_some() || node_record.text.is_some() {
+            writeln!(w, "Content-length: {}", props_len + text_len)?;
+        }
+        w.write_all(b"\n")?;
+
+        if let Some(ref raw_props) = raw_props {
+            w.write_all(raw_props)?;
+        }
+        let copied = std::io::copy(&mut std::io::Read::take(text_src, text_len), w)?;
+        if copied != text_len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        w.write_all(b"\n\n")
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W, std::io::Error> {
+        self.dest.flush()?;
+        Ok(self.dest)
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Serializes a property block. Properties are sorted by key, so the output
+/// does not depend on the order of the map they come from. `None` values
+/// are deletions, which only appear in property deltas.
+fn serialize_properties<'a>(props: impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)>) -> Vec<u8> {
+    let mut props: Vec<_> = props.collect();
+    props.sort_unstable_by_key(|&(k, _)| k);
+
+    let mut out = Vec::new();
+    for (k, v) in props {
+        if let Some(v) = v {
+            out.extend(format!("K {}\n", k.len()).as_bytes());
+            out.extend(k);
+            out.extend(format!("\nV {}\n", v.len()).as_bytes());
+            out.extend(v);
+        } else {
+            out.extend(format!("D {}\n", k.len()).as_bytes());
+            out.extend(k);
+        }
+        out.push(b'\n');
+    }
+    out.extend(b"PROPS-END\n");
+    out
 }
 
 type RecordHeader = FHashMap<Vec<u8>, Vec<u8>>;
@@ -541,8 +911,12 @@ fn parse_header(r: &mut dyn std::io::BufRead) -> Result<Option<RecordHeader>, Re
 
 type Properties = FHashMap<Vec<u8>, Option<Vec<u8>>>;
 
+/// Parses properties from `r`, which holds `len` bytes. Keys and values
+/// cannot be longer than that, so larger lengths are rejected before
+/// allocating memory for them.
 fn parse_properties(
     r: &mut dyn std::io::BufRead,
+    len: u64,
     is_delta: bool,
 ) -> Result<Properties, std::io::Error> {
     let mut buf = Vec::new();
@@ -562,6 +936,7 @@ fn parse_properties(
             let key_len = std::str::from_utf8(line_rem)
                 .ok()
                 .and_then(|s| s.parse::<usize>().ok())
+                .filter(|&l| l as u64 <= len)
                 .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
 
             let mut key = vec![0; key_len];
@@ -583,6 +958,7 @@ fn parse_properties(
                 .strip_prefix(b"V ")
                 .and_then(|s| std::str::from_utf8(s).ok())
                 .and_then(|s| s.parse::<usize>().ok())
+                .filter(|&l| l as u64 <= len)
                 .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
 
             let mut value = vec![0; value_len];
@@ -602,6 +978,7 @@ fn parse_properties(
             let key_len = std::str::from_utf8(line_rem)
                 .ok()
                 .and_then(|s| s.parse::<usize>().ok())
+                .filter(|&l| l as u64 <= len)
                 .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
 
             let mut key = vec![0; key_len];
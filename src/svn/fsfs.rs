@@ -0,0 +1,1537 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead as _, Read as _, Seek as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{diff, dump};
+use crate::FHashMap;
+
+// FSFS format described in
+// https://svn.apache.org/repos/asf/subversion/trunk/subversion/libsvn_fs_fs/structure
+
+const MIN_FORMAT: u32 = 4;
+const MAX_FORMAT: u32 = 8;
+/// First format that supports logical addressing.
+const MIN_LOG_ADDRESSING_FORMAT: u32 = 7;
+/// First format that supports packed revision properties.
+const MIN_PACKED_REVPROPS_FORMAT: u32 = 6;
+
+/// Item index of the changed paths list with logical addressing.
+const CHANGES_ITEM_INDEX: u64 = 1;
+
+/// Maximum number of representations in a delta chain, which protects
+/// against cycles in corrupted repositories. Subversion keeps its chains
+/// much shorter than this with skip-deltas.
+const MAX_DELTA_CHAIN_LEN: usize = 4096;
+/// Representations longer than this are streamed instead of being read
+/// into memory, and are not cached.
+const MAX_CACHED_REP_LEN: u64 = 1024 * 1024;
+/// Maximum total length of the cached representations.
+const REP_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub(crate) enum ReadError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        what: &'static str,
+    },
+    Delta {
+        path: PathBuf,
+        error: diff::ApplyError,
+    },
+    NoSuchRevision {
+        rev: u32,
+        youngest_rev: u32,
+    },
+    LimitExceeded {
+        what: &'static str,
+        value: u64,
+        limit: u64,
+    },
+    WriteDump(std::io::Error),
+}
+
+impl std::error::Error for ReadError {}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "failed to read {path:?}: {error}"),
+            Self::Invalid { path, what } => write!(f, "invalid {what} in {path:?}"),
+            Self::Delta { path, error } => {
+                write!(f, "failed to apply delta from {path:?}: {error}")
+            }
+            Self::NoSuchRevision { rev, youngest_rev } => write!(
+                f,
+                "revision {rev} does not exist, the youngest revision is {youngest_rev}",
+            ),
+            Self::LimitExceeded { what, value, limit } => {
+                write!(f, "{what} {value} exceeds the limit of {limit}")
+            }
+            Self::WriteDump(e) => write!(f, "failed to write dump: {e}"),
+        }
+    }
+}
+
+/// A FSFS repository, read without Subversion libraries.
+pub(crate) struct Repo {
+    db_path: PathBuf,
+    format: u32,
+    /// Number of revisions per shard, `None` with the linear layout.
+    shard_size: Option<u32>,
+    logical_addressing: bool,
+    /// Revisions before this one are packed.
+    min_unpacked_rev: u32,
+    uuid: Option<Vec<u8>>,
+    youngest_rev: u32,
+    manifests: FHashMap<u32, Vec<u64>>,
+    l2p_indices: FHashMap<PathBuf, L2pIndex>,
+    rep_cache: RepCache,
+    limits: dump::Limits,
+    delta_limits: diff::Limits,
+}
+
+/// A representation (the stored text of a file, the entries of a directory
+/// or the properties of a node).
+#[derive(Clone, Debug)]
+struct RepRef {
+    rev: u32,
+    /// Offset in the revision with physical addressing, or item index with
+    /// logical addressing.
+    item: u64,
+    len: u64,
+    /// Length of the contents, 0 if it is the same as `len`.
+    expanded_len: u64,
+    md5: Option<[u8; 16]>,
+    sha1: Option<[u8; 20]>,
+}
+
+/// A level of the delta chain of a representation.
+enum RepLevel {
+    /// Data stored in a revision or pack file, either as is or as a delta
+    /// against the next level (or against an empty text if it is the last
+    /// one).
+    Stored {
+        path: PathBuf,
+        /// Offset of the data, after the header.
+        offset: u64,
+        len: u64,
+        is_delta: bool,
+        expanded_len: u64,
+    },
+    Cached(Arc<[u8]>),
+}
+
+impl RepLevel {
+    fn expanded_len(&self) -> u64 {
+        match *self {
+            Self::Stored { expanded_len, .. } => expanded_len,
+            Self::Cached(ref data) => data.len() as u64,
+        }
+    }
+}
+
+/// Expanded contents of a representation.
+enum RepContents {
+    Memory(Arc<[u8]>),
+    /// Contents too long to be kept in memory, which are reconstructed one
+    /// delta window at a time as they are read.
+    Stream {
+        reader: Box<dyn std::io::Read>,
+        len: u64,
+        path: PathBuf,
+    },
+}
+
+/// Recently read representations, by revision and item. Deltas of a file
+/// are usually against one of its recent versions, so the bases of the
+/// following deltas are often found here, which ends their chains early.
+#[derive(Default)]
+struct RepCache {
+    entries: FHashMap<(u32, u64), Arc<[u8]>>,
+    /// Keys in insertion order, the oldest ones are evicted first.
+    order: VecDeque<(u32, u64)>,
+    total_len: usize,
+}
+
+impl RepCache {
+    fn get(&self, rev: u32, item: u64) -> Option<Arc<[u8]>> {
+        self.entries.get(&(rev, item)).cloned()
+    }
+
+    fn insert(&mut self, rev: u32, item: u64, data: Arc<[u8]>) {
+        if data.len() as u64 > MAX_CACHED_REP_LEN || self.entries.contains_key(&(rev, item)) {
+            return;
+        }
+        while self.total_len + data.len() > REP_CACHE_CAPACITY {
+            let Some(key) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&key) {
+                self.total_len -= evicted.len();
+            }
+        }
+        self.total_len += data.len();
+        self.order.push_back((rev, item));
+        self.entries.insert((rev, item), data);
+    }
+}
+
+/// Location of a node revision.
+#[derive(Copy, Clone, Debug)]
+struct NodeRevId {
+    rev: u32,
+    item: u64,
+}
+
+struct NodeRev {
+    is_dir: bool,
+    text: Option<RepRef>,
+    props: Option<RepRef>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ChangeAction {
+    Add,
+    Delete,
+    Replace,
+    Modify,
+    Reset,
+}
+
+struct Change {
+    path: Vec<u8>,
+    node_rev_id: Option<NodeRevId>,
+    action: ChangeAction,
+    is_dir: Option<bool>,
+    text_mod: bool,
+    prop_mod: bool,
+    copy_from: Option<(u32, Vec<u8>)>,
+}
+
+/// Log-to-phys index of a revision or pack file, mapping item indices to
+/// offsets.
+struct L2pIndex {
+    first_rev: u32,
+    /// Offsets of the items of each revision, `None` for unused items.
+    offsets: Vec<Vec<Option<u64>>>,
+}
+
+impl Repo {
+    /// Opens the repository at `path`. Returns `None` if the repository
+    /// does not use FSFS or its format is not supported.
+    ///
+    /// The sizes of the texts, properties and changed paths lists read from
+    /// the repository are checked against `limits`, and the deltas they are
+    /// stored as against `delta_limits`.
+    pub(crate) fn open(
+        path: &Path,
+        limits: &dump::Limits,
+        delta_limits: &diff::Limits,
+    ) -> Result<Option<Self>, ReadError> {
+        let db_path = path.join("db");
+
+        // Repositories without this file use Berkeley DB.
+        let fs_type_path = db_path.join("fs-type");
+        if !fs_type_path.exists() {
+            return Ok(None);
+        }
+        let fs_type = read_file(&fs_type_path)?;
+        if fs_type.trim_ascii() != b"fsfs" {
+            return Ok(None);
+        }
+
+        let format_path = db_path.join("format");
+        let raw_format = read_file(&format_path)?;
+        let invalid_format = || ReadError::Invalid {
+            path: format_path.clone(),
+            what: "format",
+        };
+        let mut format_lines = raw_format.split(|&c| c == b'\n');
+        let format = format_lines
+            .next()
+            .and_then(parse_number::<u32>)
+            .ok_or_else(invalid_format)?;
+        if !(MIN_FORMAT..=MAX_FORMAT).contains(&format) {
+            return Ok(None);
+        }
+
+        let mut shard_size = None;
+        let mut logical_addressing = false;
+        for line in format_lines {
+            if let Some(layout) = line.strip_prefix(b"layout ") {
+                if let Some(size) = layout.strip_prefix(b"sharded ") {
+                    shard_size = Some(
+                        parse_number::<u32>(size)
+                            .filter(|&size| size != 0)
+                            .ok_or_else(invalid_format)?,
+                    );
+                } else if layout != b"linear" {
+                    return Err(invalid_format());
+                }
+            } else if let Some(addressing) = line.strip_prefix(b"addressing ") {
+                logical_addressing = match addressing {
+                    b"logical" if format >= MIN_LOG_ADDRESSING_FORMAT => true,
+                    b"physical" => false,
+                    _ => return Err(invalid_format()),
+                };
+            }
+        }
+
+        let current_path = db_path.join("current");
+        let youngest_rev = read_file(&current_path)?
+            .split(|&c| c == b' ' || c == b'\n')
+            .next()
+            .and_then(parse_number::<u32>)
+            .ok_or_else(|| ReadError::Invalid {
+                path: current_path.clone(),
+                what: "current revision",
+            })?;
+
+        let uuid_path = db_path.join("uuid");
+        let uuid = if uuid_path.exists() {
+            let raw_uuid = read_file(&uuid_path)?;
+            raw_uuid
+                .split(|&c| c == b'\n')
+                .next()
+                .filter(|uuid| !uuid.is_empty())
+                .map(<[u8]>::to_vec)
+        } else {
+            None
+        };
+
+        let min_unpacked_rev_path = db_path.join("min-unpacked-rev");
+        let min_unpacked_rev = if shard_size.is_some() && min_unpacked_rev_path.exists() {
+            parse_number::<u32>(read_file(&min_unpacked_rev_path)?.trim_ascii()).ok_or_else(
+                || ReadError::Invalid {
+                    path: min_unpacked_rev_path.clone(),
+                    what: "minimum unpacked revision",
+                },
+            )?
+        } else {
+            0
+        };
+
+        Ok(Some(Self {
+            db_path,
+            format,
+            shard_size,
+            logical_addressing,
+            min_unpacked_rev,
+            uuid,
+            youngest_rev,
+            manifests: FHashMap::default(),
+            l2p_indices: FHashMap::default(),
+            rep_cache: RepCache::default(),
+            limits: limits.clone(),
+            delta_limits: delta_limits.clone(),
+        }))
+    }
+
+    #[inline]
+    pub(crate) fn youngest_rev(&self) -> u32 {
+        self.youngest_rev
+    }
+
+    /// Writes a dump (in version 2 format, without deltas) of revisions
+    /// `start_rev` to `end_rev`. As with `svnadmin dump --incremental`, the
+    /// first revision only contains its changes.
+    pub(crate) fn write_dump(
+        &mut self,
+        start_rev: u32,
+        end_rev: u32,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), ReadError> {
+        for rev in [start_rev, end_rev] {
+            if rev > self.youngest_rev {
+                return Err(ReadError::NoSuchRevision {
+                    rev,
+                    youngest_rev: self.youngest_rev,
+                });
+            }
+        }
+
+        let mut out = std::io::BufWriter::new(out);
+        out.write_all(b"SVN-fs-dump-format-version: 2\n\n")
+            .map_err(ReadError::WriteDump)?;
+        if let Some(ref uuid) = self.uuid {
+            out.write_all(b"UUID: ")
+                .and_then(|()| out.write_all(uuid))
+                .and_then(|()| out.write_all(b"\n\n"))
+                .map_err(ReadError::WriteDump)?;
+        }
+
+        for rev in start_rev..=end_rev {
+            tracing::debug!("reading SVN revision {rev} from repository");
+            self.write_rev_dump(rev, &mut out)?;
+        }
+
+        out.flush().map_err(ReadError::WriteDump)
+    }
+
+    fn write_rev_dump(&mut self, rev: u32, out: &mut dyn std::io::Write) -> Result<(), ReadError> {
+        let rev_props = self.read_rev_props(rev)?;
+        let mut raw_rev_props = Vec::new();
+        serialize_props(&rev_props, &mut raw_rev_props);
+
+        let mut record = Vec::new();
+        write!(
+            record,
+            "Revision-number: {rev}\nProp-content-length: {0}\nContent-length: {0}\n\n",
+            raw_rev_props.len(),
+        )
+        .unwrap();
+        record.extend(raw_rev_props);
+        record.push(b'\n');
+        out.write_all(&record).map_err(ReadError::WriteDump)?;
+
+        if rev == 0 {
+            return Ok(());
+        }
+
+        let mut changes = self.read_changes(rev)?;
+        // Parents must come before their children.
+        changes.sort_by_cached_key(|change| {
+            change
+                .path
+                .iter()
+                .map(|&c| if c == b'/' { 0 } else { c })
+                .collect::<Vec<_>>()
+        });
+
+        for change in changes {
+            self.write_change_dump(rev, &change, out)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_change_dump(
+        &mut self,
+        rev: u32,
+        change: &Change,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), ReadError> {
+        let mut record = Vec::new();
+        record.extend(b"Node-path: ");
+        record.extend(change.path.strip_prefix(b"/").unwrap_or(&change.path));
+        record.push(b'\n');
+
+        let action = match change.action {
+            ChangeAction::Add => "add",
+            ChangeAction::Delete => {
+                record.extend(b"Node-action: delete\n\n");
+                return out.write_all(&record).map_err(ReadError::WriteDump);
+            }
+            ChangeAction::Replace => "replace",
+            ChangeAction::Modify => "change",
+            ChangeAction::Reset => return Ok(()),
+        };
+
+        let rev_path = self.rev_file_path(rev);
+        let node_rev_id = change.node_rev_id.ok_or_else(|| ReadError::Invalid {
+            path: rev_path.clone(),
+            what: "changed path",
+        })?;
+        let node_rev = self.read_node_rev(node_rev_id)?;
+        if change
+            .is_dir
+            .is_some_and(|is_dir| is_dir != node_rev.is_dir)
+        {
+            return Err(ReadError::Invalid {
+                path: rev_path,
+                what: "node kind",
+            });
+        }
+
+        writeln!(
+            record,
+            "Node-kind: {}\nNode-action: {action}",
+            if node_rev.is_dir { "dir" } else { "file" },
+        )
+        .unwrap();
+        if let Some((copy_from_rev, ref copy_from_path)) = change.copy_from {
+            writeln!(record, "Node-copyfrom-rev: {copy_from_rev}").unwrap();
+            record.extend(b"Node-copyfrom-path: ");
+            record.extend(copy_from_path.strip_prefix(b"/").unwrap_or(copy_from_path));
+            record.push(b'\n');
+        }
+
+        // New nodes that are not copies are dumped entirely.
+        let is_new = change.action != ChangeAction::Modify && change.copy_from.is_none();
+
+        let mut raw_props = None;
+        if change.prop_mod || is_new {
+            let props = if let Some(ref props_rep) = node_rev.props {
+                let raw_props =
+                    self.read_rep(props_rep, self.limits.max_props_len, "properties length")?;
+                parse_hash(&raw_props).ok_or_else(|| ReadError::Invalid {
+                    path: self.rev_file_path(props_rep.rev),
+                    what: "properties",
+                })?
+            } else {
+                BTreeMap::new()
+            };
+            let mut raw = Vec::new();
+            serialize_props(&props, &mut raw);
+            writeln!(record, "Prop-content-length: {}", raw.len()).unwrap();
+            raw_props = Some(raw);
+        }
+
+        let mut text = None;
+        let mut text_len = 0;
+        if !node_rev.is_dir && (change.text_mod || is_new) {
+            let (contents, md5, sha1) = if let Some(ref text_rep) = node_rev.text {
+                let contents = self.open_rep(text_rep, self.limits.max_text_len, "text length")?;
+                (contents, text_rep.md5, text_rep.sha1)
+            } else {
+                (RepContents::Memory(Arc::new([])), None, None)
+            };
+            text_len = match contents {
+                RepContents::Memory(ref data) => data.len() as u64,
+                RepContents::Stream { len, .. } => len,
+            };
+            writeln!(record, "Text-content-length: {text_len}").unwrap();
+            if let Some(md5) = md5 {
+                writeln!(record, "Text-content-md5: {}", hex(&md5)).unwrap();
+            }
+            if let Some(sha1) = sha1 {
+                writeln!(record, "Text-content-sha1: {}", hex(&sha1)).unwrap();
+            }
+            text = Some(contents);
+        }
+
+        let content_len = raw_props.as_ref().map_or(0, Vec::len) as u64 + text_len;
+        writeln!(record, "Content-length: {content_len}\n").unwrap();
+        if let Some(raw_props) = raw_props {
+            record.extend(raw_props);
+        }
+        out.write_all(&record).map_err(ReadError::WriteDump)?;
+        match text {
+            None => {}
+            Some(RepContents::Memory(data)) => {
+                out.write_all(&data).map_err(ReadError::WriteDump)?;
+            }
+            Some(RepContents::Stream { reader, len, path }) => {
+                copy_rep_stream(reader, len, &path, out)?;
+            }
+        }
+        out.write_all(b"\n\n").map_err(ReadError::WriteDump)
+    }
+
+    fn shard_path(&self, dir: &str, rev: u32) -> PathBuf {
+        let mut path = self.db_path.join(dir);
+        if let Some(shard_size) = self.shard_size {
+            path.push((rev / shard_size).to_string());
+        }
+        path
+    }
+
+    fn is_packed(&self, rev: u32) -> bool {
+        rev < self.min_unpacked_rev
+    }
+
+    /// Path of the file that contains the revision, which is a pack file
+    /// if the revision is packed.
+    fn rev_file_path(&self, rev: u32) -> PathBuf {
+        if self.is_packed(rev) {
+            let shard_size = self.shard_size.unwrap();
+            self.db_path
+                .join("revs")
+                .join(format!("{}.pack", rev / shard_size))
+                .join("pack")
+        } else {
+            self.shard_path("revs", rev).join(rev.to_string())
+        }
+    }
+
+    /// Returns the file and the offset of an item of a revision.
+    fn locate(&mut self, rev: u32, item: u64) -> Result<(PathBuf, u64), ReadError> {
+        let path = self.rev_file_path(rev);
+        if self.logical_addressing {
+            if !self.l2p_indices.contains_key(&path) {
+                let index = read_l2p_index(&path)?;
+                // Avoid keeping too many indices of pack files.
+                if self.l2p_indices.len() >= 16 {
+                    self.l2p_indices.clear();
+                }
+                self.l2p_indices.insert(path.clone(), index);
+            }
+            let index = &self.l2p_indices[&path];
+            let offset = rev
+                .checked_sub(index.first_rev)
+                .and_then(|rev_i| index.offsets.get(rev_i as usize))
+                .and_then(|rev_offsets| rev_offsets.get(usize::try_from(item).ok()?))
+                .copied()
+                .flatten()
+                .ok_or_else(|| ReadError::Invalid {
+                    path: path.clone(),
+                    what: "item index",
+                })?;
+            Ok((path, offset))
+        } else {
+            let (start, _) = self.packed_rev_range(rev)?;
+            Ok((path, start + item))
+        }
+    }
+
+    /// Returns the range of bytes of a revision in its file with physical
+    /// addressing. The end is `None` if it is the end of the file.
+    fn packed_rev_range(&mut self, rev: u32) -> Result<(u64, Option<u64>), ReadError> {
+        if !self.is_packed(rev) {
+            return Ok((0, None));
+        }
+
+        let shard_size = self.shard_size.unwrap();
+        let shard = rev / shard_size;
+        if !self.manifests.contains_key(&shard) {
+            let manifest_path = self
+                .db_path
+                .join("revs")
+                .join(format!("{shard}.pack"))
+                .join("manifest");
+            let manifest = read_file(&manifest_path)?
+                .split(|&c| c == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(parse_number::<u64>)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| ReadError::Invalid {
+                    path: manifest_path.clone(),
+                    what: "manifest",
+                })?;
+            self.manifests.insert(shard, manifest);
+        }
+
+        let manifest = &self.manifests[&shard];
+        let i = (rev % shard_size) as usize;
+        let start = *manifest.get(i).ok_or_else(|| ReadError::Invalid {
+            path: self.rev_file_path(rev),
+            what: "manifest",
+        })?;
+        Ok((start, manifest.get(i + 1).copied()))
+    }
+
+    fn read_changes(&mut self, rev: u32) -> Result<Vec<Change>, ReadError> {
+        let raw_changes = if self.logical_addressing {
+            let (path, offset) = self.locate(rev, CHANGES_ITEM_INDEX)?;
+            let mut reader = open_at(&path, offset)?;
+            // The list is terminated by an empty line.
+            let mut raw_changes = Vec::new();
+            loop {
+                let line_start = raw_changes.len();
+                reader
+                    .read_until(b'\n', &mut raw_changes)
+                    .map_err(|e| ReadError::Io {
+                        path: path.clone(),
+                        error: e,
+                    })?;
+                if raw_changes.len() - line_start <= 1 {
+                    break;
+                }
+                reader
+                    .read_until(b'\n', &mut raw_changes)
+                    .map_err(|e| ReadError::Io {
+                        path: path.clone(),
+                        error: e,
+                    })?;
+            }
+            raw_changes
+        } else {
+            let path = self.rev_file_path(rev);
+            let (start, end) = self.packed_rev_range(rev)?;
+            let end = match end {
+                Some(end) => end,
+                None => std::fs::metadata(&path)
+                    .map_err(|e| ReadError::Io {
+                        path: path.clone(),
+                        error: e,
+                    })?
+                    .len(),
+            };
+
+            // The revision ends with a line that contains the offsets of
+            // the root node and the changed paths list.
+            let tail_len = (end - start).min(64);
+            let tail = read_at(&path, end - tail_len, tail_len)?;
+            let invalid_trailer = || ReadError::Invalid {
+                path: path.clone(),
+                what: "revision trailer",
+            };
+            let tail = tail.strip_suffix(b"\n").ok_or_else(invalid_trailer)?;
+            let trailer_start = tail
+                .iter()
+                .rposition(|&c| c == b'\n')
+                .ok_or_else(invalid_trailer)?
+                + 1;
+            let changes_offset = tail[trailer_start..]
+                .split(|&c| c == b' ')
+                .nth(1)
+                .and_then(parse_number::<u64>)
+                .ok_or_else(invalid_trailer)?;
+            let changes_start = start + changes_offset;
+            let changes_end = end - tail_len + trailer_start as u64;
+            if changes_start > changes_end {
+                return Err(invalid_trailer());
+            }
+            read_at(&path, changes_start, changes_end - changes_start)?
+        };
+
+        let changes =
+            parse_changes(&raw_changes, self.format).ok_or_else(|| ReadError::Invalid {
+                path: self.rev_file_path(rev),
+                what: "changed paths",
+            })?;
+        check_limit(
+            self.limits.max_nodes_per_rev,
+            "number of changed paths",
+            changes.len() as u64,
+        )?;
+        Ok(changes)
+    }
+
+    fn read_node_rev(&mut self, id: NodeRevId) -> Result<NodeRev, ReadError> {
+        let (path, offset) = self.locate(id.rev, id.item)?;
+        let mut reader = open_at(&path, offset)?;
+        let invalid = |path: &Path| ReadError::Invalid {
+            path: path.to_path_buf(),
+            what: "node revision",
+        };
+
+        let mut is_dir = None;
+        let mut text = None;
+        let mut props = None;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            reader
+                .read_until(b'\n', &mut line)
+                .map_err(|e| ReadError::Io {
+                    path: path.clone(),
+                    error: e,
+                })?;
+            let line = line.strip_suffix(b"\n").ok_or_else(|| invalid(&path))?;
+            if line.is_empty() {
+                break;
+            }
+
+            let sep_pos = line
+                .windows(2)
+                .position(|s| s == b": ")
+                .ok_or_else(|| invalid(&path))?;
+            let (key, value) = (&line[..sep_pos], &line[(sep_pos + 2)..]);
+            match key {
+                b"type" => {
+                    is_dir = Some(match value {
+                        b"file" => false,
+                        b"dir" => true,
+                        _ => return Err(invalid(&path)),
+                    });
+                }
+                b"text" => text = Some(parse_rep_ref(value).ok_or_else(|| invalid(&path))?),
+                b"props" => props = Some(parse_rep_ref(value).ok_or_else(|| invalid(&path))?),
+                _ => {}
+            }
+        }
+
+        Ok(NodeRev {
+            is_dir: is_dir.ok_or_else(|| invalid(&path))?,
+            text,
+            props,
+        })
+    }
+
+    /// Reads the expanded contents of a representation, which cannot be
+    /// longer than `max_len`.
+    fn read_rep(
+        &mut self,
+        rep: &RepRef,
+        max_len: Option<u64>,
+        what: &'static str,
+    ) -> Result<Arc<[u8]>, ReadError> {
+        match self.open_rep(rep, max_len, what)? {
+            RepContents::Memory(data) => Ok(data),
+            RepContents::Stream {
+                mut reader,
+                len,
+                path,
+            } => {
+                let mut data = Vec::new();
+                reader
+                    .read_to_end(&mut data)
+                    .map_err(|e| rep_read_error(&path, e))?;
+                if data.len() as u64 != len {
+                    return Err(ReadError::Invalid {
+                        path,
+                        what: "representation length",
+                    });
+                }
+                Ok(data.into())
+            }
+        }
+    }
+
+    /// Opens the expanded contents of a representation, which cannot be
+    /// longer than `max_len`. Short contents are read (and cached) at once,
+    /// while longer ones are streamed.
+    fn open_rep(
+        &mut self,
+        rep: &RepRef,
+        max_len: Option<u64>,
+        what: &'static str,
+    ) -> Result<RepContents, ReadError> {
+        if let Some(data) = self.rep_cache.get(rep.rev, rep.item) {
+            check_limit(max_len, what, data.len() as u64)?;
+            return Ok(RepContents::Memory(data));
+        }
+
+        let expanded_len = if rep.expanded_len == 0 {
+            rep.len
+        } else {
+            rep.expanded_len
+        };
+        check_limit(max_len, what, expanded_len)?;
+
+        let chain = self.read_rep_chain(rep.rev, rep.item, rep.len, max_len, what)?;
+        let path = self.rev_file_path(rep.rev);
+        // Some versions of Subversion leave the expanded length at 0 for
+        // deltas, so it is only checked when it is set.
+        let len = chain[0].expanded_len();
+        if rep.expanded_len != 0 && len != rep.expanded_len {
+            return Err(ReadError::Invalid {
+                path,
+                what: "representation length",
+            });
+        }
+
+        let delta_limits = diff::Limits {
+            max_window_len: self.delta_limits.max_window_len,
+            max_target_len: match (self.delta_limits.max_target_len, max_len) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        };
+        let mut reader = open_rep_level(&chain.into(), 0, &delta_limits)?;
+        if len > MAX_CACHED_REP_LEN {
+            return Ok(RepContents::Stream { reader, len, path });
+        }
+
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| rep_read_error(&path, e))?;
+        if data.len() as u64 != len {
+            return Err(ReadError::Invalid {
+                path,
+                what: "representation length",
+            });
+        }
+        let data = Arc::<[u8]>::from(data);
+        self.rep_cache.insert(rep.rev, rep.item, data.clone());
+        Ok(RepContents::Memory(data))
+    }
+
+    /// Locates the levels of the delta chain of a representation, down to
+    /// a plain or cached one. Neither the stored data nor the contents of
+    /// any level can be longer than `max_len`.
+    fn read_rep_chain(
+        &mut self,
+        mut rev: u32,
+        mut item: u64,
+        mut len: u64,
+        max_len: Option<u64>,
+        what: &'static str,
+    ) -> Result<Vec<RepLevel>, ReadError> {
+        let mut chain = Vec::new();
+        loop {
+            if let Some(data) = self.rep_cache.get(rev, item) {
+                chain.push(RepLevel::Cached(data));
+                return Ok(chain);
+            }
+
+            let (path, offset) = self.locate(rev, item)?;
+            let invalid = |path: &Path| ReadError::Invalid {
+                path: path.to_path_buf(),
+                what: "representation",
+            };
+            if chain.len() == MAX_DELTA_CHAIN_LEN {
+                return Err(invalid(&path));
+            }
+            check_limit(max_len, what, len)?;
+            let mut reader = open_at(&path, offset)?;
+
+            let mut header = Vec::new();
+            reader
+                .read_until(b'\n', &mut header)
+                .map_err(|e| ReadError::Io {
+                    path: path.clone(),
+                    error: e,
+                })?;
+            let data_offset = offset + header.len() as u64;
+            let header = header.strip_suffix(b"\n").ok_or_else(|| invalid(&path))?;
+
+            let base = if header == b"PLAIN" {
+                chain.push(RepLevel::Stored {
+                    path,
+                    offset: data_offset,
+                    len,
+                    is_delta: false,
+                    expanded_len: len,
+                });
+                return Ok(chain);
+            } else if header == b"DELTA" {
+                None
+            } else if let Some(base) = header.strip_prefix(b"DELTA ") {
+                let mut base = base.split(|&c| c == b' ');
+                let base_rev = base.next().and_then(parse_number::<u32>);
+                let base_item = base.next().and_then(parse_number::<u64>);
+                let base_len = base.next().and_then(parse_number::<u64>);
+                let (Some(base_rev), Some(base_item), Some(base_len)) =
+                    (base_rev, base_item, base_len)
+                else {
+                    return Err(invalid(&path));
+                };
+                Some((base_rev, base_item, base_len))
+            } else {
+                return Err(invalid(&path));
+            };
+
+            // The expanded length in the node revision cannot be trusted
+            // for the bases of the delta, so the length of each level is
+            // read from the headers of its windows.
+            let expanded_len = diff::target_len(
+                &mut reader.take(len),
+                &diff::Limits {
+                    max_window_len: None,
+                    max_target_len: max_len,
+                },
+            )
+            .map_err(|e| ReadError::Delta {
+                path: path.clone(),
+                error: e,
+            })?;
+            chain.push(RepLevel::Stored {
+                path,
+                offset: data_offset,
+                len,
+                is_delta: true,
+                expanded_len,
+            });
+
+            let Some(base) = base else {
+                return Ok(chain);
+            };
+            (rev, item, len) = base;
+        }
+    }
+
+    fn read_rev_props(&self, rev: u32) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, ReadError> {
+        let path = self.shard_path("revprops", rev).join(rev.to_string());
+        // Properties of revision 0 are never packed.
+        let raw_props =
+            if self.format >= MIN_PACKED_REVPROPS_FORMAT && self.is_packed(rev) && !path.exists() {
+                self.read_packed_rev_props(rev)?
+            } else {
+                read_file(&path)?
+            };
+
+        parse_hash(&raw_props).ok_or(ReadError::Invalid {
+            path,
+            what: "revision properties",
+        })
+    }
+
+    fn read_packed_rev_props(&self, rev: u32) -> Result<Vec<u8>, ReadError> {
+        let shard_size = self.shard_size.unwrap();
+        let shard_start = rev - rev % shard_size;
+        let pack_dir = self
+            .db_path
+            .join("revprops")
+            .join(format!("{}.pack", rev / shard_size));
+
+        // The manifest lists the pack file of each revision of the shard,
+        // except revision 0, whose properties are not packed.
+        let manifest_path = pack_dir.join("manifest");
+        let manifest = read_file(&manifest_path)?;
+        let pack_names = manifest
+            .split(|&c| c == b'\n')
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        let first_rev = if shard_start == 0 && pack_names.len() as u64 == u64::from(shard_size) - 1
+        {
+            1
+        } else {
+            shard_start
+        };
+        let pack_name = rev
+            .checked_sub(first_rev)
+            .and_then(|i| pack_names.get(i as usize))
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| ReadError::Invalid {
+                path: manifest_path.clone(),
+                what: "manifest",
+            })?;
+
+        let pack_path = pack_dir.join(pack_name);
+        let invalid = || ReadError::Invalid {
+            path: pack_path.clone(),
+            what: "revision properties pack",
+        };
+        let raw_pack = read_file(&pack_path)?;
+        let pack = diff::decompress_zlib(&raw_pack).map_err(|_| invalid())?;
+
+        // The pack starts with the first revision, the number of revisions
+        // and the size of the properties of each one, followed by an empty
+        // line and the properties.
+        let mut rem = &*pack;
+        let mut next_number = || -> Option<u64> {
+            let line_end = rem.iter().position(|&c| c == b'\n')?;
+            let number = parse_number(&rem[..line_end]);
+            rem = &rem[(line_end + 1)..];
+            number
+        };
+        let pack_first_rev = next_number().ok_or_else(invalid)?;
+        let count = next_number().ok_or_else(invalid)?;
+        let sizes = (0..count)
+            .map(|_| next_number())
+            .collect::<Option<Vec<u64>>>()
+            .ok_or_else(invalid)?;
+        rem = rem.strip_prefix(b"\n").ok_or_else(invalid)?;
+
+        let i = u64::from(rev)
+            .checked_sub(pack_first_rev)
+            .filter(|&i| i < count)
+            .ok_or_else(invalid)? as usize;
+        let start = sizes[..i].iter().sum::<u64>() as usize;
+        let end = start + sizes[i] as usize;
+        rem.get(start..end).map(<[u8]>::to_vec).ok_or_else(invalid)
+    }
+}
+
+/// Opens a reader of the contents of a level of a delta chain, which
+/// applies its delta one window at a time.
+fn open_rep_level(
+    chain: &Arc<[RepLevel]>,
+    index: usize,
+    delta_limits: &diff::Limits,
+) -> Result<Box<dyn std::io::Read>, ReadError> {
+    let (path, offset, len, is_delta) = match chain[index] {
+        RepLevel::Stored {
+            ref path,
+            offset,
+            len,
+            is_delta,
+            ..
+        } => (path, offset, len, is_delta),
+        RepLevel::Cached(ref data) => return Ok(Box::new(std::io::Cursor::new(data.clone()))),
+    };
+    let data = open_at(path, offset)?.take(len);
+    if !is_delta {
+        return Ok(Box::new(data));
+    }
+
+    let source: Box<dyn diff::DeltaSource> = if let Some(base) = chain.get(index + 1) {
+        let base_reader = open_rep_level(chain, index + 1, delta_limits)?;
+        let chain = chain.clone();
+        let delta_limits = delta_limits.clone();
+        Box::new(diff::StreamSource::new(
+            base_reader,
+            base.expanded_len(),
+            move |reader: &mut Box<dyn std::io::Read>| {
+                *reader = open_rep_level(&chain, index + 1, &delta_limits)
+                    .map_err(std::io::Error::other)?;
+                Ok(())
+            },
+        ))
+    } else {
+        Box::new(&[][..])
+    };
+    let reader = diff::DeltaReader::new(data, source, delta_limits.clone()).map_err(|e| {
+        ReadError::Delta {
+            path: path.clone(),
+            error: e,
+        }
+    })?;
+    Ok(Box::new(reader))
+}
+
+/// Converts an error from reading the contents of a representation, which
+/// can come from applying a delta.
+fn rep_read_error(path: &Path, error: std::io::Error) -> ReadError {
+    if error
+        .get_ref()
+        .is_some_and(|e| e.is::<diff::ApplyError>() || e.is::<ReadError>())
+    {
+        let inner = error.into_inner().unwrap();
+        match inner.downcast::<diff::ApplyError>() {
+            Ok(e) => ReadError::Delta {
+                path: path.to_path_buf(),
+                error: *e,
+            },
+            Err(inner) => *inner.downcast::<ReadError>().unwrap(),
+        }
+    } else {
+        ReadError::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+}
+
+/// Writes `len` bytes of the contents of a representation to `out`.
+fn copy_rep_stream(
+    mut reader: Box<dyn std::io::Read>,
+    len: u64,
+    path: &Path,
+    out: &mut dyn std::io::Write,
+) -> Result<(), ReadError> {
+    let mut buf = vec![0; 64 * 1024];
+    let mut remaining = len;
+    while remaining != 0 {
+        let chunk_len = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let read_len = match reader.read(&mut buf[..chunk_len]) {
+            Ok(0) => {
+                return Err(ReadError::Invalid {
+                    path: path.to_path_buf(),
+                    what: "representation length",
+                });
+            }
+            Ok(read_len) => read_len,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(rep_read_error(path, e)),
+        };
+        out.write_all(&buf[..read_len])
+            .map_err(ReadError::WriteDump)?;
+        remaining -= read_len as u64;
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ReadError> {
+    std::fs::read(path).map_err(|e| ReadError::Io {
+        path: path.to_path_buf(),
+        error: e,
+    })
+}
+
+fn open_at(path: &Path, offset: u64) -> Result<std::io::BufReader<std::fs::File>, ReadError> {
+    let map_err = |e| ReadError::Io {
+        path: path.to_path_buf(),
+        error: e,
+    };
+    let mut file = std::fs::File::open(path).map_err(map_err)?;
+    file.seek(std::io::SeekFrom::Start(offset))
+        .map_err(map_err)?;
+    Ok(std::io::BufReader::new(file))
+}
+
+fn read_at(path: &Path, offset: u64, len: u64) -> Result<Vec<u8>, ReadError> {
+    read_exact_len(&mut open_at(path, offset)?, path, len)
+}
+
+/// Reads `len` bytes from `reader`. The buffer grows as data is read, so a
+/// corrupted length does not allocate more memory than the file contains.
+fn read_exact_len(
+    reader: &mut dyn std::io::Read,
+    path: &Path,
+    len: u64,
+) -> Result<Vec<u8>, ReadError> {
+    let map_err = |e| ReadError::Io {
+        path: path.to_path_buf(),
+        error: e,
+    };
+    let mut data = Vec::new();
+    reader.take(len).read_to_end(&mut data).map_err(map_err)?;
+    if data.len() as u64 != len {
+        return Err(map_err(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(data)
+}
+
+fn check_limit(limit: Option<u64>, what: &'static str, value: u64) -> Result<(), ReadError> {
+    match limit {
+        Some(limit) if value > limit => Err(ReadError::LimitExceeded { what, value, limit }),
+        _ => Ok(()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &[u8]) -> Option<T> {
+    std::str::from_utf8(s).ok()?.parse().ok()
+}
+
+fn parse_hex<const N: usize>(s: &[u8]) -> Option<[u8; N]> {
+    if s.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, hex) in bytes.iter_mut().zip(s.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Parses a representation reference of a node revision:
+/// `<rev> <item> <length> <expanded size> <md5> [<sha1> <uniquifier>]`.
+fn parse_rep_ref(s: &[u8]) -> Option<RepRef> {
+    let mut fields = s.split(|&c| c == b' ');
+    let rev = parse_number(fields.next()?)?;
+    let item = parse_number(fields.next()?)?;
+    let len = parse_number(fields.next()?)?;
+    let expanded_len = parse_number(fields.next()?)?;
+    let md5 = fields.next().and_then(parse_hex);
+    let sha1 = fields.next().and_then(parse_hex);
+    Some(RepRef {
+        rev,
+        item,
+        len,
+        expanded_len,
+        md5,
+        sha1,
+    })
+}
+
+/// Parses a node revision ID (`<node id>.<copy id>.r<rev>/<item>`).
+fn parse_node_rev_id(s: &[u8]) -> Option<NodeRevId> {
+    let rev_pos = s.windows(2).rposition(|w| w == b".r")?;
+    let (rev, item) =
+        s[(rev_pos + 2)..].split_at(s[(rev_pos + 2)..].iter().position(|&c| c == b'/')?);
+    Some(NodeRevId {
+        rev: parse_number(rev)?,
+        item: parse_number(&item[1..])?,
+    })
+}
+
+/// Parses a changed paths list. Each change is made of two lines:
+///
+/// ```text
+/// <id> <action>[-<kind>] <text-mod> <prop-mod> [<mergeinfo-mod>] <path>
+/// [<copy-from-rev> <copy-from-path>]
+/// ```
+fn parse_changes(mut src: &[u8], format: u32) -> Option<Vec<Change>> {
+    let mut changes = Vec::new();
+    loop {
+        let Some(line_end) = src.iter().position(|&c| c == b'\n') else {
+            // Without terminator, the list ends with the data
+            if src.is_empty() {
+                break;
+            }
+            return None;
+        };
+        let line = &src[..line_end];
+        src = &src[(line_end + 1)..];
+        if line.is_empty() {
+            break;
+        }
+
+        let mut fields = line.splitn(if format >= 7 { 6 } else { 5 }, |&c| c == b' ');
+        let node_rev_id = parse_node_rev_id(fields.next()?);
+        let raw_action = fields.next()?;
+        let (raw_action, is_dir) = match raw_action.iter().position(|&c| c == b'-') {
+            Some(sep_pos) => (
+                &raw_action[..sep_pos],
+                match &raw_action[(sep_pos + 1)..] {
+                    b"file" => Some(false),
+                    b"dir" => Some(true),
+                    _ => return None,
+                },
+            ),
+            None => (raw_action, None),
+        };
+        let action = match raw_action {
+            b"add" => ChangeAction::Add,
+            b"delete" => ChangeAction::Delete,
+            b"replace" => ChangeAction::Replace,
+            b"modify" => ChangeAction::Modify,
+            b"reset" => ChangeAction::Reset,
+            _ => return None,
+        };
+        let parse_bool = |s: &[u8]| match s {
+            b"true" => Some(true),
+            b"false" => Some(false),
+            _ => None,
+        };
+        let text_mod = parse_bool(fields.next()?)?;
+        let prop_mod = parse_bool(fields.next()?)?;
+        let mut path = fields.next()?;
+        if format >= 7 && !path.starts_with(b"/") {
+            // mergeinfo modification flag
+            parse_bool(path)?;
+            path = fields.next()?;
+        }
+
+        let line_end = src.iter().position(|&c| c == b'\n')?;
+        let copy_from_line = &src[..line_end];
+        src = &src[(line_end + 1)..];
+        let copy_from = if copy_from_line.is_empty() {
+            None
+        } else {
+            let sep_pos = copy_from_line.iter().position(|&c| c == b' ')?;
+            Some((
+                parse_number(&copy_from_line[..sep_pos])?,
+                copy_from_line[(sep_pos + 1)..].to_vec(),
+            ))
+        };
+
+        changes.push(Change {
+            path: path.to_vec(),
+            node_rev_id,
+            action,
+            is_dir,
+            text_mod,
+            prop_mod,
+            copy_from,
+        });
+    }
+    Some(changes)
+}
+
+/// Parses a hash (used for properties), made of `K <len>\n<key>\nV <len>\n<value>\n`
+/// entries and terminated by `END\n`.
+fn parse_hash(mut src: &[u8]) -> Option<BTreeMap<Vec<u8>, Vec<u8>>> {
+    fn take_sized<'a>(src: &mut &'a [u8], prefix: &[u8]) -> Option<&'a [u8]> {
+        let line_end = src.iter().position(|&c| c == b'\n')?;
+        let len = parse_number::<usize>(src[..line_end].strip_prefix(prefix)?)?;
+        let rem = &src[(line_end + 1)..];
+        let data = rem.get(..len)?;
+        *src = rem.get(len..)?.strip_prefix(b"\n")?;
+        Some(data)
+    }
+
+    let mut map = BTreeMap::new();
+    while !src.starts_with(b"END\n") {
+        let key = take_sized(&mut src, b"K ")?;
+        let value = take_sized(&mut src, b"V ")?;
+        map.insert(key.to_vec(), value.to_vec());
+    }
+    Some(map)
+}
+
+fn serialize_props(props: &BTreeMap<Vec<u8>, Vec<u8>>, out: &mut Vec<u8>) {
+    for (key, value) in props.iter() {
+        writeln!(out, "K {}", key.len()).unwrap();
+        out.extend(key);
+        writeln!(out, "\nV {}", value.len()).unwrap();
+        out.extend(value);
+        out.push(b'\n');
+    }
+    out.extend(b"PROPS-END\n");
+}
+
+/// Reads the log-to-phys index of a revision or pack file, which is found
+/// through the footer at the end of the file.
+fn read_l2p_index(path: &Path) -> Result<L2pIndex, ReadError> {
+    let invalid = || ReadError::Invalid {
+        path: path.to_path_buf(),
+        what: "log-to-phys index",
+    };
+
+    let file_len = std::fs::metadata(path)
+        .map_err(|e| ReadError::Io {
+            path: path.to_path_buf(),
+            error: e,
+        })?
+        .len();
+    if file_len == 0 {
+        return Err(invalid());
+    }
+
+    // The last byte is the length of the footer, which contains the
+    // offsets and checksums of both indices:
+    // `<l2p offset> <l2p md5> <p2l offset> <p2l md5>`
+    let footer_len = u64::from(read_at(path, file_len - 1, 1)?[0]);
+    let footer_start = file_len.checked_sub(footer_len + 1).ok_or_else(invalid)?;
+    let footer = read_at(path, footer_start, footer_len)?;
+    let mut footer_fields = footer.split(|&c| c == b' ');
+    let l2p_offset = footer_fields
+        .next()
+        .and_then(parse_number::<u64>)
+        .ok_or_else(invalid)?;
+    let p2l_offset = footer_fields
+        .nth(1)
+        .and_then(parse_number::<u64>)
+        .ok_or_else(invalid)?;
+    if l2p_offset > p2l_offset || p2l_offset > footer_start {
+        return Err(invalid());
+    }
+
+    let raw_index = read_at(path, l2p_offset, p2l_offset - l2p_offset)?;
+    parse_l2p_index(&raw_index).ok_or_else(invalid)
+}
+
+fn parse_l2p_index(src: &[u8]) -> Option<L2pIndex> {
+    struct NumberStream<'a> {
+        src: &'a [u8],
+        pos: usize,
+    }
+
+    impl NumberStream<'_> {
+        /// Reads a 7b/8b encoded number, least significant group first.
+        fn next(&mut self) -> Option<u64> {
+            let mut value = 0u64;
+            let mut shift = 0;
+            loop {
+                let byte = *self.src.get(self.pos)?;
+                self.pos += 1;
+                if shift > 63 {
+                    return None;
+                }
+                value |= u64::from(byte & 0x7F) << shift;
+                if byte & 0x80 == 0 {
+                    return Some(value);
+                }
+                shift += 7;
+            }
+        }
+    }
+
+    let src = src.strip_prefix(b"L2P-INDEX\n").unwrap_or(src);
+    let mut stream = NumberStream { src, pos: 0 };
+
+    let first_rev = u32::try_from(stream.next()?).ok()?;
+    let page_size = stream.next()?;
+    let rev_count = usize::try_from(stream.next()?).ok()?;
+    let page_count = usize::try_from(stream.next()?).ok()?;
+    if page_size == 0 || rev_count > src.len() || page_count > src.len() {
+        return None;
+    }
+
+    let rev_page_counts = (0..rev_count)
+        .map(|_| stream.next().and_then(|n| usize::try_from(n).ok()))
+        .collect::<Option<Vec<_>>>()?;
+    let page_entry_counts = (0..page_count)
+        .map(|_| {
+            let _page_len = stream.next()?;
+            usize::try_from(stream.next()?).ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // Pages follow the tables, and each one contains the offsets (plus
+    // one, 0 for unused items) as differences from the previous entry.
+    let mut page_entry_counts = page_entry_counts.into_iter();
+    let mut offsets = Vec::with_capacity(rev_count);
+    for rev_page_count in rev_page_counts {
+        let mut rev_offsets = Vec::new();
+        for _ in 0..rev_page_count {
+            let entry_count = page_entry_counts.next()?;
+            let mut last_value = 0i64;
+            for _ in 0..entry_count {
+                let value = stream.next()?;
+                let diff = if value & 1 != 0 {
+                    -1 - (value >> 1) as i64
+                } else {
+                    (value >> 1) as i64
+                };
+                last_value = last_value.checked_add(diff)?;
+                rev_offsets.push(u64::try_from(last_value - 1).ok());
+            }
+        }
+        offsets.push(rev_offsets);
+    }
+
+    Some(L2pIndex { first_rev, offsets })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        ChangeAction, MAX_CACHED_REP_LEN, REP_CACHE_CAPACITY, RepCache, parse_changes, parse_hash,
+        parse_l2p_index,
+    };
+
+    #[test]
+    fn test_rep_cache() {
+        let mut cache = RepCache::default();
+        let data = Arc::<[u8]>::from(vec![0; MAX_CACHED_REP_LEN as usize]);
+        let num_entries = REP_CACHE_CAPACITY / data.len();
+        for item in 0..num_entries {
+            cache.insert(1, item as u64, data.clone());
+        }
+        assert!(cache.get(1, 0).is_some());
+
+        // The oldest entry is evicted to make room.
+        cache.insert(2, 0, Arc::from(vec![0; 1]));
+        assert!(cache.get(1, 0).is_none());
+        assert!(cache.get(1, 1).is_some());
+        assert!(cache.get(2, 0).is_some());
+
+        // Long representations are not cached.
+        cache.insert(3, 0, Arc::from(vec![0; MAX_CACHED_REP_LEN as usize + 1]));
+        assert!(cache.get(3, 0).is_none());
+        assert!(cache.get(1, 1).is_some());
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let changes = parse_changes(
+            b"0-1.0.r1/3 add-dir false false true /trunk dir\n\n\
+              _1.0.r1/4 add-file true false false /trunk dir/a\n2 /a\n\
+              \n",
+            7,
+        )
+        .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, b"/trunk dir");
+        assert_eq!(changes[0].action, ChangeAction::Add);
+        assert_eq!(changes[0].is_dir, Some(true));
+        assert!(changes[0].copy_from.is_none());
+        assert_eq!(changes[1].path, b"/trunk dir/a");
+        assert!(changes[1].text_mod);
+        assert_eq!(changes[1].node_rev_id.unwrap().item, 4);
+        assert_eq!(changes[1].copy_from, Some((2, b"/a".to_vec())));
+
+        let changes = parse_changes(b"0.0.r2/10 modify true true /a b\n\n", 4).unwrap();
+        assert_eq!(changes[0].path, b"/a b");
+        assert_eq!(changes[0].action, ChangeAction::Modify);
+        assert_eq!(changes[0].is_dir, None);
+
+        // Written by old servers for paths whose changes were undone in
+        // the transaction, which are not dumped.
+        let changes = parse_changes(b"_0.0.t1-1 reset false false /a\n\n", 4).unwrap();
+        assert_eq!(changes[0].action, ChangeAction::Reset);
+    }
+
+    #[test]
+    fn test_parse_hash() {
+        let props = parse_hash(b"K 3\nabc\nV 4\n1\n23\nK 1\nx\nV 0\n\nEND\n").unwrap();
+        assert_eq!(props.len(), 2);
+        assert_eq!(props[b"abc".as_slice()], b"1\n23");
+        assert_eq!(props[b"x".as_slice()], b"");
+
+        assert!(parse_hash(b"K 3\nabc\nV 5\n1\n23\nEND\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_l2p_index() {
+        let index = parse_l2p_index(&[
+            5, // first revision
+            2, // page size
+            2, // revision count
+            3, // page count
+            2, // pages of revision 5
+            1, // pages of revision 6
+            2, 2, // size and entries of page 1
+            2, 1, // size and entries of page 2
+            1, 1, // size and entries of page 3
+            0x00, 0xA0, 0x01, // 0 (unused), 80 (offset 79)
+            0x80, 0x01, // 64 (offset 63), each page starts at 0
+            0x04, // 2 (offset 1)
+        ])
+        .unwrap();
+        assert_eq!(index.first_rev, 5);
+        assert_eq!(
+            index.offsets,
+            [vec![None, Some(79), Some(63)], vec![Some(1)]]
+        );
+    }
+}
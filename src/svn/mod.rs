@@ -1,3 +1,6 @@
 pub(crate) mod diff;
 pub(crate) mod dump;
+pub(crate) mod fsfs;
+mod parallel_decode;
+pub(crate) mod ra_svn;
 pub(crate) mod source;
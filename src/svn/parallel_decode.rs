@@ -0,0 +1,435 @@
+//! Parallel decompression of compressed streams made of independent parts,
+//! such as multi-frame zstd or concatenated bzip2 streams (as written by
+//! pbzip2).
+//!
+//! The input is split into batches of complete parts, which are
+//! decompressed by worker threads and written to the output in order.
+
+use std::io::Read;
+use std::sync::mpsc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Format {
+    Zstd,
+    Bzip2,
+}
+
+/// Minimum size (compressed) of each batch given to a worker.
+const BATCH_SIZE: usize = 4 * 1024 * 1024;
+
+/// Size of the pieces of output when decompressing sequentially.
+const SEQUENTIAL_OUT_SIZE: usize = 1024 * 1024;
+
+const ZSTD_FRAME_MAGIC: u32 = 0xFD2F_B528;
+const ZSTD_SKIPPABLE_MAGIC: std::ops::RangeInclusive<u32> = 0x184D_2A50..=0x184D_2A5F;
+
+/// Beginning of a bzip2 stream: `BZh`, the block size and the magic of the
+/// first block.
+const BZIP2_STREAM_START: [u8; 10] = *b"BZh9\x31\x41\x59\x26\x53\x59";
+
+/// Decompresses `src` with `threads` worker threads.
+pub(super) fn copy_decode(
+    format: Format,
+    src: impl Read + Send,
+    dest: &mut dyn std::io::Write,
+    threads: usize,
+) -> Result<(), std::io::Error> {
+    copy_decode_with(format, src, dest, threads, BATCH_SIZE)
+}
+
+fn copy_decode_with(
+    format: Format,
+    mut src: impl Read + Send,
+    dest: &mut dyn std::io::Write,
+    threads: usize,
+    batch_size: usize,
+) -> Result<(), std::io::Error> {
+    // Limits the number of batches that are being read, decompressed or
+    // waiting to be written, so memory usage is bounded.
+    let max_in_flight = threads * 2;
+    let (credit_tx, credit_rx) = mpsc::sync_channel(max_in_flight);
+    for _ in 0..max_in_flight {
+        credit_tx.send(()).unwrap();
+    }
+    let (job_tx, job_rx) = mpsc::sync_channel::<(u64, Vec<u8>)>(threads);
+    // Owned by the workers, so the splitter stops if all of them stop.
+    let job_rx = std::sync::Arc::new(std::sync::Mutex::new(job_rx));
+    let (result_tx, result_rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let job_rx = job_rx.clone();
+            let result_tx = result_tx.clone();
+            std::thread::Builder::new()
+                .name("decompress".into())
+                .spawn_scoped(scope, move || {
+                    loop {
+                        let job = job_rx.lock().unwrap().recv();
+                        let Ok((seq, batch)) = job else {
+                            break;
+                        };
+                        if result_tx.send((seq, decode(format, &batch))).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("failed to spawn thread");
+        }
+        drop(job_rx);
+
+        let splitter = std::thread::Builder::new()
+            .name("decompress split".into())
+            .spawn_scoped(scope, move || {
+                let mut dispatcher = Dispatcher {
+                    next_seq: 0,
+                    credits: credit_rx,
+                    jobs: job_tx,
+                    results: result_tx,
+                };
+                match format {
+                    Format::Zstd => split_zstd(&mut src, &mut dispatcher, batch_size),
+                    Format::Bzip2 => split_bzip2(&mut src, &mut dispatcher, batch_size),
+                }
+            })
+            .expect("failed to spawn thread");
+
+        // The channels are closed when this returns, which stops the other
+        // threads if it has failed.
+        let r = write_in_order(result_rx, credit_tx, dest);
+
+        match splitter.join() {
+            Ok(Ok(())) => r,
+            Ok(Err(e)) => Err(e),
+            Err(e) => std::panic::resume_unwind(e),
+        }
+    })
+}
+
+/// Writes the decompressed batches in order, until every thread has
+/// finished.
+fn write_in_order(
+    results: mpsc::Receiver<(u64, Result<Vec<u8>, std::io::Error>)>,
+    credits: mpsc::SyncSender<()>,
+    dest: &mut dyn std::io::Write,
+) -> Result<(), std::io::Error> {
+    let mut next_seq = 0;
+    let mut pending = crate::FHashMap::default();
+    for (seq, result) in results {
+        pending.insert(seq, result);
+        while let Some(result) = pending.remove(&next_seq) {
+            dest.write_all(&result?)?;
+            next_seq += 1;
+            // The splitter may have already finished.
+            let _ = credits.send(());
+        }
+    }
+    Ok(())
+}
+
+struct Dispatcher {
+    next_seq: u64,
+    credits: mpsc::Receiver<()>,
+    jobs: mpsc::SyncSender<(u64, Vec<u8>)>,
+    results: mpsc::Sender<(u64, Result<Vec<u8>, std::io::Error>)>,
+}
+
+impl Dispatcher {
+    /// Sends a batch to the workers. Returns `false` if the output has
+    /// stopped.
+    fn send_batch(&mut self, batch: Vec<u8>) -> bool {
+        if self.credits.recv().is_err() {
+            return false;
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.jobs.send((seq, batch)).is_ok()
+    }
+
+    /// Sends already decompressed data directly to the output. Returns
+    /// `false` if the output has stopped.
+    fn send_decoded(&mut self, data: Vec<u8>) -> bool {
+        if self.credits.recv().is_err() {
+            return false;
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.results.send((seq, Ok(data))).is_ok()
+    }
+
+    /// Decompresses `src` in this thread, which is used when it cannot be
+    /// split.
+    fn send_sequential(&mut self, format: Format, src: impl Read) -> Result<(), std::io::Error> {
+        let mut decoder: Box<dyn Read> = match format {
+            Format::Zstd => Box::new(zstd::stream::read::Decoder::new(src)?),
+            Format::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(src)),
+        };
+        loop {
+            let mut data = Vec::with_capacity(SEQUENTIAL_OUT_SIZE);
+            decoder
+                .by_ref()
+                .take(SEQUENTIAL_OUT_SIZE as u64)
+                .read_to_end(&mut data)?;
+            if data.is_empty() || !self.send_decoded(data) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn decode(format: Format, batch: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    match format {
+        Format::Zstd => zstd::stream::decode_all(batch),
+        Format::Bzip2 => {
+            let mut data = Vec::new();
+            bzip2::read::MultiBzDecoder::new(batch).read_to_end(&mut data)?;
+            Ok(data)
+        }
+    }
+}
+
+/// Splits a zstd stream at frame boundaries, which are found by walking
+/// the frame and block headers.
+///
+/// Frames larger than 16 batches (for example, written by `zstd -T`, which
+/// produces a single frame) are decompressed sequentially.
+fn split_zstd(
+    src: &mut impl Read,
+    dispatcher: &mut Dispatcher,
+    batch_size: usize,
+) -> Result<(), std::io::Error> {
+    let mut batch = Vec::new();
+    loop {
+        let frame_start = batch.len();
+        if !read_exact_or_eof(src, &mut batch, 4)? {
+            break;
+        }
+        let magic = u32::from_le_bytes(batch[frame_start..].try_into().unwrap());
+
+        if ZSTD_SKIPPABLE_MAGIC.contains(&magic) {
+            read_exact(src, &mut batch, 4)?;
+            let size = u32::from_le_bytes(batch[(frame_start + 4)..].try_into().unwrap());
+            read_exact(src, &mut batch, size as usize)?;
+        } else if magic == ZSTD_FRAME_MAGIC {
+            read_exact(src, &mut batch, 1)?;
+            let descriptor = batch[frame_start + 4];
+            let fcs_flag = descriptor >> 6;
+            let single_segment = descriptor & 0x20 != 0;
+            let has_checksum = descriptor & 0x04 != 0;
+            let dict_id_size = [0, 1, 2, 4][usize::from(descriptor & 0x03)];
+            let fcs_size = match fcs_flag {
+                0 => usize::from(single_segment),
+                1 => 2,
+                2 => 4,
+                _ => 8,
+            };
+            let window_size = usize::from(!single_segment);
+            read_exact(src, &mut batch, window_size + dict_id_size + fcs_size)?;
+
+            loop {
+                let header_start = batch.len();
+                read_exact(src, &mut batch, 3)?;
+                let header = u32::from(batch[header_start])
+                    | (u32::from(batch[header_start + 1]) << 8)
+                    | (u32::from(batch[header_start + 2]) << 16);
+                let last = header & 1 != 0;
+                let size = (header >> 3) as usize;
+                match (header >> 1) & 3 {
+                    // Raw or compressed
+                    0 | 2 => read_exact(src, &mut batch, size)?,
+                    // RLE
+                    1 => read_exact(src, &mut batch, 1)?,
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "invalid zstd block type",
+                        ));
+                    }
+                }
+                if last {
+                    break;
+                }
+                if batch.len() - frame_start > batch_size * 16 {
+                    let frame = batch.split_off(frame_start);
+                    if !batch.is_empty() && !dispatcher.send_batch(batch) {
+                        return Ok(());
+                    }
+                    return dispatcher
+                        .send_sequential(Format::Zstd, std::io::Cursor::new(frame).chain(src));
+                }
+            }
+            if has_checksum {
+                read_exact(src, &mut batch, 4)?;
+            }
+        } else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid zstd frame",
+            ));
+        }
+
+        if batch.len() >= batch_size && !dispatcher.send_batch(std::mem::take(&mut batch)) {
+            return Ok(());
+        }
+    }
+    if !batch.is_empty() {
+        dispatcher.send_batch(batch);
+    }
+    Ok(())
+}
+
+/// Splits concatenated bzip2 streams at the beginning of each stream, which
+/// is found by its signature.
+///
+/// The signature may appear by chance inside a stream, in which case
+/// decompression fails, but it is unlikely enough to be ignored. If no
+/// signature is found after 16 batches (for example, in a single stream
+/// written by `bzip2`), the rest is decompressed sequentially.
+fn split_bzip2(
+    src: &mut impl Read,
+    dispatcher: &mut Dispatcher,
+    batch_size: usize,
+) -> Result<(), std::io::Error> {
+    let mut batch = Vec::new();
+    // Position below which there is no stream beginning in `batch`.
+    let mut searched = 0;
+    loop {
+        let read_size = batch_size.clamp(1, SEQUENTIAL_OUT_SIZE);
+        let n = src
+            .by_ref()
+            .take(read_size as u64)
+            .read_to_end(&mut batch)?;
+        if n == 0 {
+            break;
+        }
+
+        loop {
+            let search_start = searched.max(batch_size).max(1);
+            let Some(pos) = batch.get(search_start..).and_then(find_bzip2_stream_start) else {
+                searched = batch.len().saturating_sub(BZIP2_STREAM_START.len() - 1);
+                break;
+            };
+            let rem = batch.split_off(search_start + pos);
+            if !dispatcher.send_batch(std::mem::replace(&mut batch, rem)) {
+                return Ok(());
+            }
+            searched = 0;
+        }
+
+        if batch.len() > batch_size * 16 {
+            return dispatcher
+                .send_sequential(Format::Bzip2, std::io::Cursor::new(batch).chain(src));
+        }
+    }
+    if !batch.is_empty() {
+        dispatcher.send_batch(batch);
+    }
+    Ok(())
+}
+
+fn find_bzip2_stream_start(data: &[u8]) -> Option<usize> {
+    data.windows(BZIP2_STREAM_START.len()).position(|window| {
+        window[..3] == BZIP2_STREAM_START[..3]
+            && (b'1'..=b'9').contains(&window[3])
+            && window[4..] == BZIP2_STREAM_START[4..]
+    })
+}
+
+/// Reads exactly `len` bytes from `src` and appends them to `out`.
+fn read_exact(src: &mut impl Read, out: &mut Vec<u8>, len: usize) -> Result<(), std::io::Error> {
+    let n = src.by_ref().take(len as u64).read_to_end(out)?;
+    if n != len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "unexpected end of compressed data",
+        ));
+    }
+    Ok(())
+}
+
+/// Like `read_exact`, but returns `false` if `src` is already at its end.
+fn read_exact_or_eof(
+    src: &mut impl Read,
+    out: &mut Vec<u8>,
+    len: usize,
+) -> Result<bool, std::io::Error> {
+    let n = src.by_ref().take(len as u64).read_to_end(out)?;
+    if n == 0 {
+        Ok(false)
+    } else if n != len {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "unexpected end of compressed data",
+        ))
+    } else {
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use super::{Format, copy_decode_with};
+
+    fn test_data(len: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len);
+        let mut x = 1u32;
+        while data.len() < len {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            data.extend(format!("line {}\n", x >> 20).as_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+
+    fn check(format: Format, compressed: &[u8], expected: &[u8]) {
+        for threads in [1, 3] {
+            for batch_size in [1, 1000, 1_000_000] {
+                let mut out = Vec::new();
+                copy_decode_with(format, compressed, &mut out, threads, batch_size).unwrap();
+                assert!(out == expected, "threads={threads} batch_size={batch_size}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_zstd() {
+        let data = test_data(200_000);
+        let mut compressed = Vec::new();
+        for (i, part) in data.chunks(30_000).enumerate() {
+            let mut encoder = zstd::stream::write::Encoder::new(&mut compressed, 3).unwrap();
+            encoder.include_checksum(i % 2 == 0).unwrap();
+            encoder.write_all(part).unwrap();
+            encoder.finish().unwrap();
+            // Skippable frame
+            compressed.extend(0x184D_2A50u32.to_le_bytes());
+            compressed.extend(3u32.to_le_bytes());
+            compressed.extend(b"abc");
+        }
+        check(Format::Zstd, &compressed, &data);
+
+        // Truncated
+        let mut out = Vec::new();
+        let r = copy_decode_with(
+            Format::Zstd,
+            &compressed[..(compressed.len() - 5)],
+            &mut out,
+            2,
+            100,
+        );
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_bzip2() {
+        let data = test_data(200_000);
+        let mut compressed = Vec::new();
+        for part in data.chunks(30_000) {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(&mut compressed, bzip2::Compression::fast());
+            encoder.write_all(part).unwrap();
+            encoder.finish().unwrap();
+        }
+        check(Format::Bzip2, &compressed, &data);
+    }
+}
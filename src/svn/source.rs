@@ -1,7 +1,9 @@
 use std::ffi::OsString;
-use std::io::{Read as _, Seek as _};
-use std::path::PathBuf;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
 
+use super::{diff, dump, fsfs, parallel_decode, ra_svn};
+use crate::path_pattern::{self, PathPattern};
 use crate::pipe;
 
 #[derive(Debug)]
@@ -14,18 +16,34 @@ pub(crate) enum OpenError {
         path: PathBuf,
         error: std::io::Error,
     },
-    FileReadError {
+    ReadDirError {
         path: PathBuf,
         error: std::io::Error,
     },
-    FileSeekError {
+    InvalidFilePattern {
+        path: PathBuf,
+        error: Option<path_pattern::ParseError>,
+    },
+    NoMatchingFiles {
         path: PathBuf,
-        error: std::io::Error,
     },
+    NoDumpFiles,
+    MultipleRepositories,
+    StdinWithOtherSources,
     SpawnProcessError {
         arg0: OsString,
         error: std::io::Error,
     },
+    ProcessFailed {
+        arg0: OsString,
+        message: String,
+    },
+    RepositoryReadError(fsfs::ReadError),
+    RemoteError(ra_svn::Error),
+    InvalidRevRange {
+        start_rev: u32,
+        end_rev: u32,
+    },
 }
 
 impl std::fmt::Display for OpenError {
@@ -37,15 +55,38 @@ impl std::fmt::Display for OpenError {
             Self::FileOpenError { path, error } => {
                 write!(f, "failed to open file {path:?}: {error}")
             }
-            Self::FileReadError { path, error } => {
-                write!(f, "failed to read file {path:?}: {error}")
+            Self::ReadDirError { path, error } => {
+                write!(f, "failed to read directory {path:?}: {error}")
+            }
+            Self::InvalidFilePattern { path, error } => {
+                write!(f, "invalid file pattern {path:?}")?;
+                if let Some(error) = error {
+                    write!(f, ": {error}")?;
+                }
+                Ok(())
             }
-            Self::FileSeekError { path, error } => {
-                write!(f, "failed to seek file {path:?}: {error}")
+            Self::NoMatchingFiles { path } => {
+                write!(f, "no files match {path:?}")
+            }
+            Self::NoDumpFiles => write!(f, "no dump files found"),
+            Self::MultipleRepositories => {
+                write!(f, "a repository cannot be combined with other sources")
+            }
+            Self::StdinWithOtherSources => {
+                write!(f, "standard input cannot be combined with other sources")
             }
             Self::SpawnProcessError { arg0, error } => {
                 write!(f, "failed to spawn process {arg0:?}: {error}")
             }
+            Self::ProcessFailed { arg0, message } => {
+                write!(f, "process {arg0:?} failed: {message}")
+            }
+            Self::RepositoryReadError(e) => write!(f, "failed to read repository: {e}"),
+            Self::RemoteError(e) => write!(f, "failed to access remote repository: {e}"),
+            Self::InvalidRevRange { start_rev, end_rev } => write!(
+                f,
+                "start revision {start_rev} is after end revision {end_rev}",
+            ),
         }
     }
 }
@@ -62,111 +103,438 @@ pub(crate) enum DumpSource {
 }
 
 impl DumpSource {
-    pub(crate) fn open(path: &std::path::Path, is_remote: bool) -> Result<Self, OpenError> {
+    /// Opens a dump source.
+    ///
+    /// `paths` can contain a single repository or any number of dump files,
+    /// directories of dump files or file name patterns, which are read as
+    /// a sequence of dumps (usually incremental) in natural order of their
+    /// file names. A path of `-` means the standard input, which must be
+    /// the only source.
+    ///
+    /// If `start_rev` is provided and the source is a repository, the dump
+    /// starts at that revision (as an incremental dump). Likewise, if
+    /// `end_rev` is provided, the dump of a repository ends at that revision.
+    /// Dump files are always read completely.
+    ///
+    /// A remote repository is fetched according to `remote_options`, and a
+    /// local repository that needs svnadmin (or if it is forced) is dumped
+    /// according to `svnadmin_options`. Compressed dump files are decompressed with up to
+    /// `decompress_threads` threads.
+    ///
+    /// FSFS repositories and svn:// servers, which are read natively, are
+    /// checked against `limits` and `delta_limits` before allocating memory
+    /// for what they contain. Dumps are checked later by their reader.
+    pub(crate) fn open(
+        paths: &[PathBuf],
+        is_remote: bool,
+        remote_options: &RemoteOptions,
+        svnadmin_options: &SvnadminOptions,
+        decompress_threads: usize,
+        limits: &dump::Limits,
+        delta_limits: &diff::Limits,
+        start_rev: Option<u32>,
+        end_rev: Option<u32>,
+    ) -> Result<Self, OpenError> {
+        let mut rev_args = Vec::new();
+        if start_rev.is_some() || end_rev.is_some() {
+            let end = end_rev.map_or_else(|| "HEAD".into(), |end_rev| end_rev.to_string());
+            rev_args.push(format!("-r{}:{end}", start_rev.unwrap_or(0)));
+        }
+        if start_rev.is_some() {
+            rev_args.push("--incremental".into());
+        }
+
         if is_remote {
-            let cmd = "svnrdump";
-            let mut child = std::process::Command::new(cmd)
-                .arg("dump")
-                .arg(path)
-                .arg("-q")
-                .stdin(std::process::Stdio::null())
-                .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::inherit())
-                .spawn()
-                .map_err(|e| OpenError::SpawnProcessError {
-                    arg0: cmd.into(),
+            check_rev_range(start_rev, end_rev)?;
+            let [path] = paths else {
+                return Err(OpenError::MultipleRepositories);
+            };
+
+            // svn:// URLs are handled natively, other protocols need
+            // svnrdump.
+            let remote = if let Some(url) = path.to_str().filter(|url| url.starts_with("svn://")) {
+                tracing::debug!("connecting to {url}");
+                let session = ra_svn::Session::connect(url, limits, delta_limits)
+                    .map_err(OpenError::RemoteError)?;
+                Remote::Native {
+                    url: url.into(),
+                    limits: limits.clone(),
+                    delta_limits: delta_limits.clone(),
+                    session: Some(Box::new(session)),
+                }
+            } else {
+                Remote::Svnrdump {
+                    url: path.into(),
+                    args: remote_options.svnrdump_args.clone(),
+                }
+            };
+
+            if let Some(chunk_size) = remote_options.chunk_size {
+                return Ok(Self::spawn_chunked_reader(
+                    remote,
+                    remote_options,
+                    chunk_size,
+                    start_rev,
+                    end_rev,
+                ));
+            }
+
+            return match remote {
+                Remote::Native { session, .. } => Ok(Self::spawn_remote_reader(
+                    *session.unwrap(),
+                    start_rev,
+                    end_rev,
+                )),
+                Remote::Svnrdump { url, args } => {
+                    let cmd = "svnrdump";
+                    let mut child = std::process::Command::new(cmd)
+                        .arg("dump")
+                        .arg(url)
+                        .arg("-q")
+                        .args(&rev_args)
+                        .args(args)
+                        .stdin(std::process::Stdio::null())
+                        .stdout(std::process::Stdio::piped())
+                        .stderr(std::process::Stdio::piped())
+                        .isolate_from_interrupt()
+                        .spawn()
+                        .map_err(|e| OpenError::SpawnProcessError {
+                            arg0: cmd.into(),
+                            error: e,
+                        })?;
+                    log_stderr(&mut child, cmd);
+                    let stdout = child.stdout.take().unwrap();
+                    Ok(Self::Command(child, std::io::BufReader::new(stdout)))
+                }
+            };
+        }
+
+        if paths.iter().any(|path| path.as_os_str() == "-") {
+            if paths.len() != 1 {
+                return Err(OpenError::StdinWithOtherSources);
+            }
+            tracing::debug!("reading SVN dump from standard input");
+            return Ok(Self::spawn_reader(
+                vec![DumpInput::Stdin],
+                decompress_threads,
+            ));
+        }
+
+        let mut repo_path = None;
+        let mut file_paths = Vec::new();
+        for path in paths.iter() {
+            match std::fs::metadata(path) {
+                Ok(path_meta) if path_meta.file_type().is_dir() => {
+                    if is_repository(path) {
+                        repo_path = Some(path);
+                    } else {
+                        file_paths.extend(list_dir_files(path, None)?);
+                    }
+                }
+                Ok(_) => file_paths.push(path.clone()),
+                Err(e) => {
+                    let file_name = path.file_name().unwrap_or_default();
+                    let is_pattern = file_name
+                        .as_encoded_bytes()
+                        .iter()
+                        .any(|&c| c == b'*' || c == b'?');
+                    if e.kind() == std::io::ErrorKind::NotFound && is_pattern {
+                        let file_name =
+                            file_name
+                                .to_str()
+                                .ok_or_else(|| OpenError::InvalidFilePattern {
+                                    path: path.clone(),
+                                    error: None,
+                                })?;
+                        let pattern = PathPattern::new([file_name], false).map_err(|(_, e)| {
+                            OpenError::InvalidFilePattern {
+                                path: path.clone(),
+                                error: Some(e),
+                            }
+                        })?;
+                        let dir_path = path
+                            .parent()
+                            .filter(|parent| !parent.as_os_str().is_empty())
+                            .unwrap_or(Path::new("."));
+                        let matched = list_dir_files(dir_path, Some(&pattern))?;
+                        if matched.is_empty() {
+                            return Err(OpenError::NoMatchingFiles { path: path.clone() });
+                        }
+                        file_paths.extend(matched);
+                    } else {
+                        return Err(OpenError::MetadataFetchError {
+                            path: path.clone(),
+                            error: e,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(repo_path) = repo_path {
+            if paths.len() != 1 {
+                return Err(OpenError::MultipleRepositories);
+            }
+            check_rev_range(start_rev, end_rev)?;
+
+            // FSFS repositories are read natively, other ones (such as
+            // Berkeley DB or newer formats) need svnadmin.
+            if svnadmin_options.force {
+                tracing::debug!("dumping {repo_path:?} with svnadmin");
+            } else if let Some(repo) = fsfs::Repo::open(repo_path, limits, delta_limits)
+                .map_err(OpenError::RepositoryReadError)?
+            {
+                tracing::debug!("reading FSFS repository {repo_path:?}");
+                return Ok(Self::spawn_repo_reader(repo, start_rev, end_rev));
+            } else {
+                tracing::debug!("{repo_path:?} is not a supported FSFS repository, using svnadmin");
+            }
+
+            if svnadmin_options.jobs > 1 {
+                let end_rev = match end_rev {
+                    Some(end_rev) => end_rev,
+                    None => svnlook_youngest(repo_path)?,
+                };
+                return Self::spawn_parallel_svnadmin(
+                    repo_path,
+                    svnadmin_options,
+                    start_rev,
+                    end_rev,
+                );
+            }
+
+            let mut child = spawn_svnadmin(repo_path, &rev_args, svnadmin_options, None)?;
+            let stdout = child.stdout.take().unwrap();
+            return Ok(Self::Command(child, std::io::BufReader::new(stdout)));
+        }
+
+        file_paths.sort_by(|a, b| natural_cmp(a.as_os_str(), b.as_os_str()));
+        file_paths.dedup();
+        if file_paths.is_empty() {
+            return Err(OpenError::NoDumpFiles);
+        }
+
+        let mut inputs = Vec::with_capacity(file_paths.len());
+        for path in file_paths {
+            let is_file = std::fs::metadata(&path)
+                .map_err(|e| OpenError::MetadataFetchError {
+                    path: path.clone(),
+                    error: e,
+                })?
+                .is_file();
+            if is_file {
+                let file = std::fs::File::open(&path).map_err(|e| OpenError::FileOpenError {
+                    path: path.clone(),
                     error: e,
                 })?;
-            let stdout = child.stdout.take().unwrap();
-            Ok(Self::Command(child, std::io::BufReader::new(stdout)))
-        } else {
-            let path_meta = std::fs::metadata(path).map_err(|e| OpenError::MetadataFetchError {
-                path: path.to_path_buf(),
-                error: e,
-            })?;
-            let cmd = "svnadmin";
-            if path_meta.file_type().is_dir() {
-                let mut child = std::process::Command::new(cmd)
-                    .arg("dump")
-                    .arg(path)
-                    .arg("-q")
-                    .stdin(std::process::Stdio::null())
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::inherit())
-                    .spawn()
-                    .map_err(|e| OpenError::SpawnProcessError {
-                        arg0: cmd.into(),
-                        error: e,
-                    })?;
-                let stdout = child.stdout.take().unwrap();
-                Ok(Self::Command(child, std::io::BufReader::new(stdout)))
+                inputs.push(DumpInput::File(path, file));
             } else {
-                let mut file = std::fs::OpenOptions::new()
-                    .read(true)
-                    .open(path)
-                    .map_err(|e| OpenError::FileOpenError {
-                        path: path.to_path_buf(),
-                        error: e,
-                    })?;
-
-                const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
-                const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
-                const BZIP2_MAGIC: &[u8] = b"BZh";
-                const XZ_MAGIC: &[u8] = &[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
-                const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4D, 0x18];
-
-                const HEADER_SIZE: usize = 6;
-
-                let mut header = Vec::<u8>::with_capacity(HEADER_SIZE);
-                while header.len() < HEADER_SIZE {
-                    let mut buf = [0; HEADER_SIZE];
-                    match file.read(&mut buf[..(HEADER_SIZE - header.len())]) {
-                        Ok(0) => break,
-                        Ok(n) => header.extend(&buf[..n]),
-                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
-                        Err(e) => {
-                            return Err(OpenError::FileReadError {
-                                path: path.to_path_buf(),
-                                error: e,
-                            });
-                        }
+                inputs.push(DumpInput::Special(path));
+            }
+        }
+
+        Ok(Self::spawn_reader(inputs, decompress_threads))
+    }
+
+    /// Spawns a thread that decompresses and concatenates `inputs`.
+    fn spawn_reader(inputs: Vec<DumpInput>, decompress_threads: usize) -> Self {
+        Self::spawn_thread_pipe(move |pipe_write| {
+            for input in inputs {
+                match input {
+                    DumpInput::File(path, file) => {
+                        tracing::debug!("reading SVN dump file {path:?}");
+                        copy_decompressed(file, pipe_write, decompress_threads)?;
+                    }
+                    DumpInput::Special(path) => {
+                        tracing::debug!("reading SVN dump file {path:?}");
+                        let file = std::fs::File::open(&path).map_err(|e| {
+                            std::io::Error::new(
+                                e.kind(),
+                                format!("failed to open file {path:?}: {e}"),
+                            )
+                        })?;
+                        copy_decompressed(file, pipe_write, decompress_threads)?;
+                    }
+                    DumpInput::Stdin => {
+                        copy_decompressed(std::io::stdin(), pipe_write, decompress_threads)?;
                     }
                 }
+            }
+            Ok(())
+        })
+    }
 
-                file.seek(std::io::SeekFrom::Start(0))
-                    .map_err(|e| OpenError::FileSeekError {
-                        path: path.to_path_buf(),
-                        error: e,
-                    })?;
-
-                let (pipe_read, mut pipe_write) = pipe::create();
-
-                let joiner = std::thread::Builder::new()
-                    .name("svn source".into())
-                    .spawn(move || {
-                        if header.starts_with(ZSTD_MAGIC) {
-                            zstd::stream::copy_decode(&file, &mut pipe_write)?;
-                        } else if header.starts_with(GZIP_MAGIC) {
-                            let mut decoder = flate2::read::GzDecoder::new(&file);
-                            std::io::copy(&mut decoder, &mut pipe_write)?;
-                        } else if header.starts_with(BZIP2_MAGIC) {
-                            let mut decoder = bzip2::read::BzDecoder::new(&file);
-                            std::io::copy(&mut decoder, &mut pipe_write)?;
-                        } else if header.starts_with(XZ_MAGIC) {
-                            liblzma::copy_decode(&file, &mut pipe_write)?;
-                        } else if header.starts_with(LZ4_MAGIC) {
-                            let mut decoder = lz4_flex::frame::FrameDecoder::new(&file);
-                            std::io::copy(&mut decoder, &mut pipe_write)?;
-                        } else {
-                            std::io::copy(&mut file, &mut pipe_write)?;
-                        }
-                        Ok(())
-                    })
-                    .expect("failed to spawn thread");
+    /// Spawns a thread that dumps a FSFS repository.
+    fn spawn_repo_reader(
+        mut repo: fsfs::Repo,
+        start_rev: Option<u32>,
+        end_rev: Option<u32>,
+    ) -> Self {
+        Self::spawn_thread_pipe(move |pipe_write| {
+            let end_rev = end_rev.unwrap_or(repo.youngest_rev());
+            repo.write_dump(start_rev.unwrap_or(0), end_rev, pipe_write)
+                .map_err(std::io::Error::other)
+        })
+    }
+
+    /// Spawns a thread that dumps a remote repository through the svn://
+    /// protocol.
+    fn spawn_remote_reader(
+        mut session: ra_svn::Session,
+        start_rev: Option<u32>,
+        end_rev: Option<u32>,
+    ) -> Self {
+        Self::spawn_thread_pipe(move |pipe_write| {
+            let end_rev = match end_rev {
+                Some(end_rev) => end_rev,
+                None => session.latest_rev().map_err(std::io::Error::other)?,
+            };
+            session
+                .write_dump(start_rev.unwrap_or(0), end_rev, pipe_write)
+                .map_err(std::io::Error::other)
+        })
+    }
 
-                Ok(Self::ThreadPipe(joiner, std::io::BufReader::new(pipe_read)))
+    /// Spawns one svnadmin process for each of `options.jobs` disjoint
+    /// revision ranges, and a thread that concatenates their dumps in order.
+    /// The dumps of all but the first range are written to temporary files,
+    /// so their processes do not wait until the previous ranges are read.
+    fn spawn_parallel_svnadmin(
+        repo_path: &Path,
+        options: &SvnadminOptions,
+        start_rev: Option<u32>,
+        end_rev: u32,
+    ) -> Result<Self, OpenError> {
+        // The end might be the youngest revision, which is only known now.
+        check_rev_range(start_rev, Some(end_rev))?;
+        let ranges = split_rev_range(start_rev.unwrap_or(0), end_rev, options.jobs);
+        let mut children = Vec::with_capacity(ranges.len());
+        for (i, &(range_start, range_end)) in ranges.iter().enumerate() {
+            let mut rev_args = vec![format!("-r{range_start}:{range_end}")];
+            if i != 0 || start_rev.is_some() {
+                rev_args.push("--incremental".into());
+            }
+            let temp_path = (i != 0).then(|| {
+                std::env::temp_dir().join(format!(
+                    "svn2git-{}-svnadmin-r{range_start}-{range_end}.dump",
+                    std::process::id(),
+                ))
+            });
+            tracing::debug!("dumping SVN revisions {range_start}-{range_end} with svnadmin");
+            match spawn_svnadmin(repo_path, &rev_args, options, temp_path.as_deref()) {
+                Ok(child) => children.push((child, temp_path)),
+                Err(e) => {
+                    for (mut child, temp_path) in children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        if let Some(temp_path) = temp_path {
+                            let _ = std::fs::remove_file(temp_path);
+                        }
+                    }
+                    return Err(e);
+                }
             }
         }
+
+        Ok(Self::spawn_thread_pipe(move |pipe_write| {
+            let mut r = Ok(());
+            for (mut child, temp_path) in children {
+                if r.is_ok() {
+                    r = copy_svnadmin_output(&mut child, temp_path.as_deref(), pipe_write);
+                } else {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                if let Some(temp_path) = temp_path {
+                    let _ = std::fs::remove_file(temp_path);
+                }
+            }
+            r
+        }))
+    }
+
+    /// Spawns a thread that fetches a remote repository in chunks of
+    /// `chunk_size` revisions and concatenates them. Each chunk is
+    /// completely fetched (to a file) before it is passed to the reader,
+    /// so it can be fetched again if it fails.
+    fn spawn_chunked_reader(
+        mut remote: Remote,
+        options: &RemoteOptions,
+        chunk_size: u32,
+        start_rev: Option<u32>,
+        end_rev: Option<u32>,
+    ) -> Self {
+        let retries = options.retries;
+        let cache_dir = options.cache_dir.clone();
+        Self::spawn_thread_pipe(move |pipe_write| {
+            if let Some(ref cache_dir) = cache_dir {
+                std::fs::create_dir_all(cache_dir).map_err(|e| {
+                    std::io::Error::new(
+                        e.kind(),
+                        format!("failed to create directory {cache_dir:?}: {e}"),
+                    )
+                })?;
+            }
+
+            let end_rev = match end_rev {
+                Some(end_rev) => end_rev,
+                None => with_retries("fetch the latest SVN revision", retries, || {
+                    remote.latest_rev()
+                })?,
+            };
+
+            let mut chunk_start = start_rev.unwrap_or(0);
+            while chunk_start <= end_rev {
+                // Chunks are aligned to multiples of `chunk_size`, so
+                // cached chunks can be reused when resuming from another
+                // revision.
+                let chunk_end = (chunk_start / chunk_size + 1)
+                    .saturating_mul(chunk_size)
+                    .saturating_sub(1)
+                    .min(end_rev);
+                let (chunk_path, mut chunk_file) = fetch_chunk(
+                    &mut remote,
+                    chunk_start,
+                    chunk_end,
+                    retries,
+                    cache_dir.as_deref(),
+                )?;
+                let r = std::io::copy(&mut chunk_file, pipe_write);
+                if cache_dir.is_none() {
+                    drop(chunk_file);
+                    let _ = std::fs::remove_file(&chunk_path);
+                }
+                r?;
+
+                let Some(next_start) = chunk_end.checked_add(1) else {
+                    break;
+                };
+                chunk_start = next_start;
+            }
+            Ok(())
+        })
+    }
+
+    /// Spawns a thread that writes the dump with `write`. If it fails, the
+    /// error is also passed to the reader, so it is not mistaken for the
+    /// end of the dump.
+    fn spawn_thread_pipe(
+        write: impl FnOnce(&mut pipe::PipeWriter) -> Result<(), std::io::Error> + Send + 'static,
+    ) -> Self {
+        let (pipe_read, mut pipe_write) = pipe::create();
+
+        let joiner = std::thread::Builder::new()
+            .name("svn source".into())
+            .spawn(move || {
+                let r = write(&mut pipe_write);
+                if let Err(ref e) = r {
+                    pipe_write.close_with_error(e);
+                }
+                r
+            })
+            .expect("failed to spawn thread");
+
+        Self::ThreadPipe(joiner, std::io::BufReader::new(pipe_read))
     }
 
     pub(crate) fn close(self) -> Result<(), std::io::Error> {
@@ -201,3 +569,701 @@ impl DumpSource {
         }
     }
 }
+
+/// How a local repository is dumped with svnadmin.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SvnadminOptions {
+    /// Use svnadmin even for repositories that can be read natively.
+    pub(crate) force: bool,
+    /// Pass `--deltas` to svnadmin.
+    pub(crate) deltas: bool,
+    /// Number of svnadmin processes that dump disjoint revision ranges in
+    /// parallel.
+    pub(crate) jobs: usize,
+}
+
+/// Spawns `svnadmin dump`, writing to `output` if provided or to a pipe
+/// otherwise.
+fn spawn_svnadmin(
+    repo_path: &Path,
+    rev_args: &[String],
+    options: &SvnadminOptions,
+    output: Option<&Path>,
+) -> Result<std::process::Child, OpenError> {
+    let stdout = match output {
+        Some(output) => std::fs::File::create(output)
+            .map_err(|e| OpenError::FileOpenError {
+                path: output.to_path_buf(),
+                error: e,
+            })?
+            .into(),
+        None => std::process::Stdio::piped(),
+    };
+
+    let cmd = "svnadmin";
+    let mut command = std::process::Command::new(cmd);
+    command.arg("dump").arg(repo_path).arg("-q").args(rev_args);
+    if options.deltas {
+        command.arg("--deltas");
+    }
+    let mut child = command
+        .stdin(std::process::Stdio::null())
+        .stdout(stdout)
+        .stderr(std::process::Stdio::piped())
+        .isolate_from_interrupt()
+        .spawn()
+        .map_err(|e| OpenError::SpawnProcessError {
+            arg0: cmd.into(),
+            error: e,
+        })?;
+    log_stderr(&mut child, cmd);
+    Ok(child)
+}
+
+/// Copies the dump of an svnadmin process (from its pipe, or from
+/// `temp_path` once it has finished) to `dest`.
+fn copy_svnadmin_output(
+    child: &mut std::process::Child,
+    temp_path: Option<&Path>,
+    dest: &mut pipe::PipeWriter,
+) -> Result<(), std::io::Error> {
+    let copy_result = match child.stdout.take() {
+        Some(mut stdout) => std::io::copy(&mut stdout, dest).map(|_| ()),
+        None => Ok(()),
+    };
+    let exit_code = child.wait()?;
+    copy_result?;
+    if !exit_code.success() {
+        return Err(std::io::Error::other(format!(
+            "svnadmin finished with {exit_code}"
+        )));
+    }
+    if let Some(temp_path) = temp_path {
+        let mut file = std::fs::File::open(temp_path)?;
+        std::io::copy(&mut file, dest)?;
+    }
+    Ok(())
+}
+
+/// Splits `start_rev..=end_rev` into up to `num` ranges of (almost) the same
+/// number of revisions, or none if the range is empty.
+fn split_rev_range(start_rev: u32, end_rev: u32, num: usize) -> Vec<(u32, u32)> {
+    let Some(len) = end_rev.checked_sub(start_rev) else {
+        return Vec::new();
+    };
+    let count = u64::from(len) + 1;
+    let num = (num as u64).clamp(1, count);
+    (0..num)
+        .map(|i| {
+            let range_start = u64::from(start_rev) + count * i / num;
+            let range_end = u64::from(start_rev) + count * (i + 1) / num - 1;
+            (range_start as u32, range_end as u32)
+        })
+        .collect()
+}
+
+/// Checks that a repository can be dumped from `start_rev` to `end_rev`,
+/// which is not the case when an incremental run has already imported
+/// revisions after the requested end.
+fn check_rev_range(start_rev: Option<u32>, end_rev: Option<u32>) -> Result<(), OpenError> {
+    match (start_rev, end_rev) {
+        (Some(start_rev), Some(end_rev)) if start_rev > end_rev => {
+            Err(OpenError::InvalidRevRange { start_rev, end_rev })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Gets the youngest revision of a local repository with svnlook.
+fn svnlook_youngest(repo_path: &Path) -> Result<u32, OpenError> {
+    let cmd = "svnlook";
+    let output = std::process::Command::new(cmd)
+        .arg("youngest")
+        .arg(repo_path)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|e| OpenError::SpawnProcessError {
+            arg0: cmd.into(),
+            error: e,
+        })?;
+    let youngest = std::str::from_utf8(&output.stdout)
+        .ok()
+        .and_then(|stdout| stdout.trim().parse().ok());
+    match youngest {
+        Some(youngest) if output.status.success() => Ok(youngest),
+        _ => Err(OpenError::ProcessFailed {
+            arg0: cmd.into(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        }),
+    }
+}
+
+/// Spawns a thread that logs the standard error of `child` line by line,
+/// as warnings.
+fn log_stderr(child: &mut std::process::Child, name: &'static str) {
+    let stderr = child.stderr.take().unwrap();
+    std::thread::Builder::new()
+        .name(format!("{name} stderr"))
+        .spawn(move || {
+            let stderr = std::io::BufReader::new(stderr);
+            for line in std::io::BufRead::split(stderr, b'\n') {
+                let Ok(line) = line else {
+                    break;
+                };
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end();
+                if !line.is_empty() {
+                    tracing::warn!("{name}: {line}");
+                }
+            }
+        })
+        .expect("failed to spawn thread");
+}
+
+/// How a remote repository is fetched.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RemoteOptions {
+    /// If set, revisions are fetched in chunks of this many revisions,
+    /// which are fetched again when they fail.
+    pub(crate) chunk_size: Option<u32>,
+    /// Number of times a failed chunk is fetched again.
+    pub(crate) retries: u32,
+    /// Directory where fetched chunks are kept, so later runs do not fetch
+    /// them again.
+    pub(crate) cache_dir: Option<PathBuf>,
+    /// Additional arguments for svnrdump.
+    pub(crate) svnrdump_args: Vec<OsString>,
+}
+
+/// Delay before fetching a failed chunk again for the first time. It is
+/// doubled after each failure, up to `MAX_RETRY_DELAY`.
+const INITIAL_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
+
+enum Remote {
+    Native {
+        url: String,
+        limits: dump::Limits,
+        delta_limits: diff::Limits,
+        /// The session is dropped when an operation fails, and a new one
+        /// is opened for the next operation.
+        session: Option<Box<ra_svn::Session>>,
+    },
+    Svnrdump {
+        url: OsString,
+        args: Vec<OsString>,
+    },
+}
+
+impl Remote {
+    fn latest_rev(&mut self) -> Result<u32, std::io::Error> {
+        match self {
+            Self::Native {
+                url,
+                limits,
+                delta_limits,
+                session,
+            } => with_session(
+                url,
+                limits,
+                delta_limits,
+                session,
+                ra_svn::Session::latest_rev,
+            ),
+            Self::Svnrdump { url, args } => svnrdump_latest_rev(url, args),
+        }
+    }
+
+    /// Writes an incremental dump of revisions `start_rev` to `end_rev`.
+    fn write_dump(
+        &mut self,
+        start_rev: u32,
+        end_rev: u32,
+        out: &mut dyn std::io::Write,
+    ) -> Result<(), std::io::Error> {
+        match self {
+            Self::Native {
+                url,
+                limits,
+                delta_limits,
+                session,
+            } => with_session(url, limits, delta_limits, session, |session| {
+                session.write_dump(start_rev, end_rev, out)
+            }),
+            Self::Svnrdump { url, args } => {
+                let mut child = spawn_svnrdump(url, &format!("-r{start_rev}:{end_rev}"), args)?;
+                let mut stdout = child.stdout.take().unwrap();
+                let r = std::io::copy(&mut stdout, out);
+                drop(stdout);
+                let exit_code = child.wait()?;
+                r?;
+                if exit_code.success() {
+                    Ok(())
+                } else {
+                    Err(std::io::Error::other(format!(
+                        "svnrdump finished with {exit_code}"
+                    )))
+                }
+            }
+        }
+    }
+}
+
+fn with_session<R>(
+    url: &str,
+    limits: &dump::Limits,
+    delta_limits: &diff::Limits,
+    session: &mut Option<Box<ra_svn::Session>>,
+    f: impl FnOnce(&mut ra_svn::Session) -> Result<R, ra_svn::Error>,
+) -> Result<R, std::io::Error> {
+    if session.is_none() {
+        tracing::debug!("connecting to {url}");
+        *session = Some(Box::new(
+            ra_svn::Session::connect(url, limits, delta_limits).map_err(std::io::Error::other)?,
+        ));
+    }
+    let r = f(session.as_mut().unwrap());
+    if r.is_err() {
+        // The state of the connection is unknown after an error.
+        *session = None;
+    }
+    r.map_err(std::io::Error::other)
+}
+
+fn spawn_svnrdump(
+    url: &OsString,
+    rev_arg: &str,
+    args: &[OsString],
+) -> Result<std::process::Child, std::io::Error> {
+    let mut child = std::process::Command::new("svnrdump")
+        .arg("dump")
+        .arg(url)
+        .arg("-q")
+        .arg(rev_arg)
+        .arg("--incremental")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .isolate_from_interrupt()
+        .spawn()
+        .map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("failed to spawn process \"svnrdump\": {e}"),
+            )
+        })?;
+    log_stderr(&mut child, "svnrdump");
+    Ok(child)
+}
+
+/// Finds the latest revision of a remote repository by reading the
+/// beginning of the dump of `HEAD`, without waiting for the whole revision.
+fn svnrdump_latest_rev(url: &OsString, args: &[OsString]) -> Result<u32, std::io::Error> {
+    let mut child = spawn_svnrdump(url, "-rHEAD", args)?;
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    let mut latest_rev = None;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if std::io::BufRead::read_until(&mut stdout, b'\n', &mut line)? == 0 {
+            break;
+        }
+        if let Some(rev) = line.strip_prefix(b"Revision-number: ") {
+            latest_rev = std::str::from_utf8(rev)
+                .ok()
+                .and_then(|rev| rev.trim_end().parse().ok());
+            break;
+        }
+    }
+    drop(stdout);
+    // It may have already finished.
+    let _ = child.kill();
+    child.wait()?;
+    latest_rev.ok_or_else(|| std::io::Error::other("svnrdump did not output the latest revision"))
+}
+
+/// Calls `f` until it succeeds or it has failed `retries + 1` times,
+/// waiting longer after each failure.
+fn with_retries<R>(
+    what: &str,
+    retries: u32,
+    mut f: impl FnMut() -> Result<R, std::io::Error>,
+) -> Result<R, std::io::Error> {
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut failures = 0;
+    loop {
+        match f() {
+            Ok(r) => return Ok(r),
+            Err(e) if failures < retries => {
+                failures += 1;
+                tracing::warn!(
+                    "failed to {what} (attempt {failures} of {}): {e}; retrying in {} s",
+                    retries + 1,
+                    delay.as_secs(),
+                );
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            Err(e) => {
+                return Err(std::io::Error::new(
+                    e.kind(),
+                    format!("failed to {what}: {e}"),
+                ));
+            }
+        }
+    }
+}
+
+/// Fetches revisions `start_rev` to `end_rev` into a file, or takes them
+/// from `cache_dir` if they were already fetched. Returns the file, ready
+/// to be read from its beginning.
+fn fetch_chunk(
+    remote: &mut Remote,
+    start_rev: u32,
+    end_rev: u32,
+    retries: u32,
+    cache_dir: Option<&Path>,
+) -> Result<(PathBuf, std::fs::File), std::io::Error> {
+    let file_name = format!("r{start_rev}-{end_rev}.dump");
+    let path = match cache_dir {
+        Some(cache_dir) => {
+            let path = cache_dir.join(&file_name);
+            if path.is_file() {
+                tracing::debug!("using cached SVN revisions {start_rev}-{end_rev}");
+                let file = std::fs::File::open(&path).map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("failed to open file {path:?}: {e}"))
+                })?;
+                return Ok((path, file));
+            }
+            path
+        }
+        None => std::env::temp_dir().join(format!("svn2git-{}-{file_name}", std::process::id())),
+    };
+
+    // A chunk is only put in the cache once it is complete.
+    let temp_path = path.with_extension("dump.tmp");
+    let r = with_retries(
+        &format!("fetch SVN revisions {start_rev}-{end_rev}"),
+        retries,
+        || {
+            tracing::debug!("fetching SVN revisions {start_rev}-{end_rev}");
+            let file = std::fs::File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&temp_path)
+                .map_err(|e| {
+                    std::io::Error::new(
+                        e.kind(),
+                        format!("failed to create file {temp_path:?}: {e}"),
+                    )
+                })?;
+            let mut writer = std::io::BufWriter::new(file);
+            remote.write_dump(start_rev, end_rev, &mut writer)?;
+            writer
+                .into_inner()
+                .map_err(std::io::IntoInnerError::into_error)
+        },
+    );
+    let mut file = match r {
+        Ok(file) => file,
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    };
+    std::fs::rename(&temp_path, &path)?;
+    std::io::Seek::rewind(&mut file)?;
+    Ok((path, file))
+}
+
+/// Whether `path` (a directory) looks like a Subversion repository.
+fn is_repository(path: &Path) -> bool {
+    path.join("format").is_file() && path.join("db").is_dir()
+}
+
+fn list_dir_files(
+    dir_path: &Path,
+    pattern: Option<&PathPattern>,
+) -> Result<Vec<PathBuf>, OpenError> {
+    let map_err = |e| OpenError::ReadDirError {
+        path: dir_path.to_path_buf(),
+        error: e,
+    };
+
+    let mut file_paths = Vec::new();
+    for dir_entry in std::fs::read_dir(dir_path).map_err(map_err)? {
+        let dir_entry = dir_entry.map_err(map_err)?;
+        let file_name = dir_entry.file_name();
+        if file_name.as_encoded_bytes().starts_with(b".")
+            || pattern.is_some_and(|pattern| !pattern.is_match(file_name.as_encoded_bytes()))
+        {
+            continue;
+        }
+        let entry_path = dir_entry.path();
+        if std::fs::metadata(&entry_path)
+            .map_err(|e| OpenError::MetadataFetchError {
+                path: entry_path.clone(),
+                error: e,
+            })?
+            .is_file()
+        {
+            file_paths.push(entry_path);
+        }
+    }
+    Ok(file_paths)
+}
+
+/// An element of a sequence of dumps.
+enum DumpInput {
+    /// A regular file, opened in advance.
+    File(PathBuf, std::fs::File),
+    /// A FIFO or character device. It is opened when it is reached, since
+    /// opening a FIFO blocks until the other end is opened.
+    Special(PathBuf),
+    Stdin,
+}
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
+const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4D, 0x18];
+
+const HEADER_SIZE: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Compression {
+    None,
+    Zstd,
+    Gzip,
+    Bzip2,
+    Xz,
+    Lz4,
+}
+
+impl Compression {
+    fn detect(header: &[u8]) -> Self {
+        if header.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else if header.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if header.starts_with(BZIP2_MAGIC) {
+            Self::Bzip2
+        } else if header.starts_with(XZ_MAGIC) {
+            Self::Xz
+        } else if header.starts_with(LZ4_MAGIC) {
+            Self::Lz4
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// Reads up to `HEADER_SIZE` bytes, used to detect the compression.
+fn read_header(src: &mut impl std::io::Read) -> Result<Vec<u8>, std::io::Error> {
+    let mut header = Vec::<u8>::with_capacity(HEADER_SIZE);
+    while header.len() < HEADER_SIZE {
+        let mut buf = [0; HEADER_SIZE];
+        match src.read(&mut buf[..(HEADER_SIZE - header.len())]) {
+            Ok(0) => break,
+            Ok(n) => header.extend(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(header)
+}
+
+/// Decompresses `src` (if it is compressed) into `dest`. If `threads` is
+/// greater than one, multi-block XZ, multi-frame zstd and concatenated bzip2
+/// streams are decompressed in parallel.
+fn copy_decompressed(
+    mut src: impl std::io::Read + Send,
+    dest: &mut pipe::PipeWriter,
+    threads: usize,
+) -> Result<(), std::io::Error> {
+    // The header is put back in front of the stream instead of seeking,
+    // so pipes and character devices are supported.
+    let header = read_header(&mut src)?;
+    let compression = Compression::detect(&header);
+    let mut src = std::io::Cursor::new(header).chain(src);
+
+    match compression {
+        Compression::Zstd if threads > 1 => {
+            parallel_decode::copy_decode(parallel_decode::Format::Zstd, src, dest, threads)?;
+        }
+        Compression::Bzip2 if threads > 1 => {
+            parallel_decode::copy_decode(parallel_decode::Format::Bzip2, src, dest, threads)?;
+        }
+        Compression::Xz if threads > 1 => {
+            // liblzma decompresses the blocks in parallel if their sizes
+            // are stored in their headers, as done by `xz -T`.
+            let stream = liblzma::stream::MtStreamBuilder::new()
+                .threads(u32::try_from(threads).unwrap_or(u32::MAX))
+                .memlimit_stop(u64::MAX)
+                .decoder()?;
+            let mut decoder = liblzma::read::XzDecoder::new_stream(src, stream);
+            std::io::copy(&mut decoder, dest)?;
+        }
+        Compression::Zstd => zstd::stream::copy_decode(src, dest)?,
+        Compression::Gzip => {
+            let mut decoder = flate2::read::GzDecoder::new(src);
+            std::io::copy(&mut decoder, dest)?;
+        }
+        Compression::Bzip2 => {
+            // pbzip2 and `cat a.bz2 b.bz2` produce several streams.
+            let mut decoder = bzip2::read::MultiBzDecoder::new(src);
+            std::io::copy(&mut decoder, dest)?;
+        }
+        Compression::Xz => liblzma::copy_decode(src, dest)?,
+        Compression::Lz4 => {
+            let mut decoder = lz4_flex::frame::FrameDecoder::new(src);
+            std::io::copy(&mut decoder, dest)?;
+        }
+        Compression::None => {
+            std::io::copy(&mut src, dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Compares file names so numbers are ordered by value, e.g.,
+/// `9-10.dump` < `10-20.dump`.
+fn natural_cmp(a: &std::ffi::OsStr, b: &std::ffi::OsStr) -> std::cmp::Ordering {
+    let mut a = a.as_encoded_bytes();
+    let mut b = b.as_encoded_bytes();
+
+    fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+        let n = s.iter().take_while(|c| c.is_ascii_digit()).count();
+        s.split_at(n)
+    }
+
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let (num_a, rem_a) = split_digits(a);
+                let (num_b, rem_b) = split_digits(b);
+                let trimmed_a = &num_a[num_a.iter().take_while(|&&c| c == b'0').count()..];
+                let trimmed_b = &num_b[num_b.iter().take_while(|&&c| c == b'0').count()..];
+                let ord = trimmed_a
+                    .len()
+                    .cmp(&trimmed_b.len())
+                    .then_with(|| trimmed_a.cmp(trimmed_b))
+                    .then_with(|| num_a.len().cmp(&num_b.len()));
+                if ord.is_ne() {
+                    return ord;
+                }
+                a = rem_a;
+                b = rem_b;
+            }
+            (Some(ca), Some(cb)) => {
+                let ord = ca.cmp(cb);
+                if ord.is_ne() {
+                    return ord;
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+trait CommandExt {
+    /// Prevents the process from receiving the Ctrl-C of the terminal, so
+    /// the dump is not cut while the conversion stops at a revision boundary.
+    fn isolate_from_interrupt(&mut self) -> &mut Self;
+}
+
+impl CommandExt for std::process::Command {
+    #[cfg(unix)]
+    fn isolate_from_interrupt(&mut self) -> &mut Self {
+        std::os::unix::process::CommandExt::process_group(self, 0)
+    }
+
+    #[cfg(not(unix))]
+    fn isolate_from_interrupt(&mut self) -> &mut Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::ffi::OsStr;
+
+    use super::{Compression, check_rev_range, natural_cmp, read_header, split_rev_range};
+
+    #[test]
+    fn test_natural_cmp() {
+        let cmp = |a: &str, b: &str| natural_cmp(OsStr::new(a), OsStr::new(b));
+
+        assert_eq!(cmp("a", "a"), Ordering::Equal);
+        assert_eq!(cmp("a", "b"), Ordering::Less);
+        assert_eq!(cmp("a", "ab"), Ordering::Less);
+        assert_eq!(cmp("9.dump", "10.dump"), Ordering::Less);
+        assert_eq!(cmp("0-9999.dump", "10000-19999.dump"), Ordering::Less);
+        assert_eq!(
+            cmp("90000-99999.dump", "100000-109999.dump"),
+            Ordering::Less
+        );
+        assert_eq!(cmp("r10.dump", "r9.dump"), Ordering::Greater);
+        assert_eq!(cmp("01.dump", "1.dump"), Ordering::Greater);
+        assert_eq!(cmp("01.dump", "2.dump"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_read_header() {
+        // Reads one byte at a time, like a pipe may do
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.len().min(buf.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        let mut src = Trickle(b"\xFD7zXZ\x00rest");
+        let header = read_header(&mut src).unwrap();
+        assert_eq!(header, b"\xFD7zXZ\x00");
+        assert_eq!(Compression::detect(&header), Compression::Xz);
+        assert_eq!(src.0, b"rest");
+
+        let header = read_header(&mut Trickle(b"SVN")).unwrap();
+        assert_eq!(header, b"SVN");
+        assert_eq!(Compression::detect(&header), Compression::None);
+
+        let header = read_header(&mut Trickle(b"\x28\xB5\x2F\xFDxx")).unwrap();
+        assert_eq!(Compression::detect(&header), Compression::Zstd);
+    }
+
+    #[test]
+    fn test_split_rev_range() {
+        assert_eq!(split_rev_range(0, 9, 1), [(0, 9)]);
+        assert_eq!(split_rev_range(0, 9, 3), [(0, 2), (3, 5), (6, 9)]);
+        assert_eq!(split_rev_range(5, 6, 4), [(5, 5), (6, 6)]);
+        assert_eq!(split_rev_range(7, 7, 2), [(7, 7)]);
+        assert_eq!(split_rev_range(8, 7, 2), []);
+        assert_eq!(
+            split_rev_range(0, u32::MAX, 2),
+            [(0, u32::MAX / 2), (u32::MAX / 2 + 1, u32::MAX)],
+        );
+    }
+
+    #[test]
+    fn test_check_rev_range() {
+        assert!(check_rev_range(None, None).is_ok());
+        assert!(check_rev_range(Some(5), None).is_ok());
+        assert!(check_rev_range(None, Some(0)).is_ok());
+        assert!(check_rev_range(Some(5), Some(5)).is_ok());
+        assert!(check_rev_range(Some(6), Some(5)).is_err());
+    }
+}